@@ -0,0 +1,39 @@
+/* C API for rust_dmx, built with the `ffi` feature. */
+
+#ifndef RUST_DMX_H
+#define RUST_DMX_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct DmxPortList DmxPortList;
+typedef struct DmxPortHandle DmxPortHandle;
+
+/* Describe the last failure on this thread, or NULL if nothing has failed.
+ * Valid until the next failing call on this thread. */
+const char *dmx_last_error(void);
+
+/* List the available ports.  Returns NULL on failure. */
+DmxPortList *dmx_available_ports(void);
+size_t dmx_port_list_len(const DmxPortList *list);
+/* Returns NULL if index is out of range.  Valid until the list is freed. */
+const char *dmx_port_list_name(const DmxPortList *list, size_t index);
+void dmx_port_list_free(DmxPortList *list);
+
+/* Open a port by name, or the first port whose name or description contains
+ * the text.  Returns NULL on failure. */
+DmxPortHandle *dmx_open(const char *name);
+/* Write len channel levels.  Returns 0 on success and -1 on failure. */
+int dmx_write(DmxPortHandle *port, const uint8_t *frame, size_t len);
+/* Close the port and free it. */
+void dmx_close(DmxPortHandle *port);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,174 @@
+//! Names operators give their interfaces.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+#[cfg(any(feature = "enttec", feature = "udmx", feature = "ftdi"))]
+use std::fmt;
+use std::sync::{Mutex, PoisonError};
+
+use crate::{HardwareInfo, PortInfo};
+
+static ALIASES: Mutex<PortAliases> = Mutex::new(PortAliases::new());
+
+/// Labels for interfaces, such as "FOH rig" or "Stage left truss", since names like
+/// `/dev/ttyUSB1` mean nothing to operators.  Labels are keyed by serial number, so they follow
+/// an interface to whichever port it is plugged into.  Aliases serialize with any serde format,
+/// to be saved and loaded alongside a rig.
+///
+/// Once installed with `set_port_aliases`, listed ports carry the alias of their interface, and
+/// `open_port_by_name` and `open_port_spec` find ports by alias too.  Listed and opened ports
+/// of interfaces with an alias display it ahead of their own description.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PortAliases {
+    aliases: BTreeMap<String, String>,
+}
+
+impl PortAliases {
+    /// Create an empty set of aliases.
+    pub const fn new() -> Self {
+        Self {
+            aliases: BTreeMap::new(),
+        }
+    }
+
+    /// Label the interface with the provided serial number, returning its previous alias.
+    pub fn set(
+        &mut self,
+        serial_number: impl Into<String>,
+        alias: impl Into<String>,
+    ) -> Option<String> {
+        self.aliases.insert(serial_number.into(), alias.into())
+    }
+
+    /// Remove the alias of an interface, returning it.
+    pub fn remove(&mut self, serial_number: &str) -> Option<String> {
+        self.aliases.remove(serial_number)
+    }
+
+    /// Return the alias of the interface with the provided serial number.
+    pub fn get(&self, serial_number: &str) -> Option<&str> {
+        self.aliases.get(serial_number).map(String::as_str)
+    }
+
+    /// Return the alias of the interface described, looking up its USB serial number and then
+    /// the one the device reports itself.
+    pub fn alias_of(&self, hardware: &HardwareInfo) -> Option<&str> {
+        [&hardware.serial_number, &hardware.device_serial_number]
+            .iter()
+            .find_map(|serial_number| self.get(serial_number.as_deref()?))
+    }
+
+    /// Set the alias of each listed port from its interface's serial number.
+    pub fn apply(&self, ports: &mut [PortInfo]) {
+        for port in ports {
+            port.alias = self.alias_of(&port.hardware).map(str::to_string);
+        }
+    }
+
+    /// Iterate over the serial numbers and aliases, in order of serial number.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.aliases
+            .iter()
+            .map(|(sn, alias)| (sn.as_str(), alias.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+}
+
+/// Use the provided aliases for the ports listed from now on.
+pub fn set_port_aliases(aliases: PortAliases) {
+    *ALIASES.lock().unwrap_or_else(PoisonError::into_inner) = aliases;
+}
+
+/// Return the aliases in use.
+pub fn port_aliases() -> PortAliases {
+    ALIASES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+/// Display a port as listed ports do: the alias of its interface, if it has one, followed by
+/// its own description in parentheses.  The alternate flag, `{:#}`, leaves the alias out.
+#[cfg(any(feature = "enttec", feature = "udmx", feature = "ftdi"))]
+pub(crate) fn write_aliased(
+    f: &mut fmt::Formatter<'_>,
+    hardware: &HardwareInfo,
+    describe: impl FnOnce(&mut fmt::Formatter<'_>) -> fmt::Result,
+) -> fmt::Result {
+    let alias = match f.alternate() {
+        true => None,
+        false => ALIASES
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .alias_of(hardware)
+            .map(str::to_string),
+    };
+    match alias {
+        Some(alias) => {
+            write!(f, "{} (", alias)?;
+            describe(f)?;
+            write!(f, ")")
+        }
+        None => describe(f),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+
+    #[test]
+    fn test_apply() {
+        let mut aliases = PortAliases::new();
+        assert_eq!(aliases.set("EN123456", "FOH"), None);
+        assert_eq!(aliases.set("EN123456", "FOH rig"), Some("FOH".to_string()));
+        aliases.set("0042", "Stage left truss");
+        let mut ports = vec![
+            PortInfo::new(Box::new(MockDmxPort::new())),
+            PortInfo::new(Box::new(MockDmxPort::new())),
+            PortInfo::new(Box::new(MockDmxPort::new())),
+        ];
+        ports[0].hardware.serial_number = Some("EN123456".to_string());
+        ports[1].hardware.device_serial_number = Some("0042".to_string());
+        aliases.apply(&mut ports);
+        assert_eq!(ports[0].alias.as_deref(), Some("FOH rig"));
+        assert_eq!(ports[0].to_string(), "FOH rig (mock)");
+        assert_eq!(ports[1].alias.as_deref(), Some("Stage left truss"));
+        assert_eq!(ports[2].alias, None);
+        assert_eq!(ports[2].to_string(), "mock");
+        assert_eq!(aliases.remove("0042").as_deref(), Some("Stage left truss"));
+        assert_eq!(
+            aliases.iter().collect::<Vec<_>>(),
+            vec![("EN123456", "FOH rig")]
+        );
+    }
+
+    #[cfg(any(feature = "enttec", feature = "udmx", feature = "ftdi"))]
+    #[test]
+    fn test_write_aliased() {
+        struct Described(HardwareInfo);
+
+        impl fmt::Display for Described {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write_aliased(f, &self.0, |f| write!(f, "widget"))
+            }
+        }
+
+        let mut aliases = PortAliases::new();
+        aliases.set("ALIASED-0001", "Stage left truss");
+        set_port_aliases(aliases);
+        let port = Described(HardwareInfo {
+            serial_number: Some("ALIASED-0001".to_string()),
+            ..HardwareInfo::default()
+        });
+        assert_eq!(port.to_string(), "Stage left truss (widget)");
+        assert_eq!(format!("{:#}", port), "widget");
+        assert_eq!(Described(HardwareInfo::default()).to_string(), "widget");
+        set_port_aliases(PortAliases::new());
+    }
+}
@@ -0,0 +1,1034 @@
+//! Implementation of Art-Net output and input over UDP.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::fmt;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::pcap::capture;
+use crate::target::deserialize_target;
+use crate::{
+    DmxFrame, DmxInputPort, DmxPort, Error, FrameShape, InputPortListing, NetworkTarget,
+    PcapWriter, PortKind, PortListing, PortProvider, PortStatus, ReceivedFrame, StatusTracker,
+};
+
+/// The UDP port Art-Net nodes listen on.
+pub const ARTNET_PORT: u16 = 6454;
+
+// Art-Net packet framing.
+const ARTNET_ID: &[u8; 8] = b"Art-Net\0";
+const PROTOCOL_VERSION: u16 = 14;
+const OP_POLL: u16 = 0x2000;
+const OP_POLL_REPLY: u16 = 0x2100;
+const OP_DMX: u16 = 0x5000;
+const OP_NZS: u16 = 0x5100;
+const OP_SYNC: u16 = 0x5200;
+
+// ArtDmx data length constraints.
+const ART_DMX_HEADER_LENGTH: usize = 18;
+const MIN_DATA_LENGTH: usize = 2;
+const MAX_DATA_LENGTH: usize = 512;
+
+/// Port-addresses are 15 bits wide: a 7-bit net, a 4-bit sub-net and a 4-bit universe.
+const PORT_ADDRESS_MASK: u16 = 0x7FFF;
+
+// ArtPollReply layout.
+const MIN_POLL_REPLY_LENGTH: usize = 194;
+const MAX_PORTS: usize = 4;
+/// PortTypes flag marking a port that can output DMX.
+const PORT_OUTPUT: u8 = 0x80;
+
+/// How long `available_ports` waits for nodes to answer an ArtPoll.
+const AVAILABLE_PORTS_DISCOVERY_TIMEOUT: Duration = Duration::from_millis(300);
+
+/// An Art-Net node that answered an ArtPoll.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArtNetNode {
+    /// The address the node reported.
+    pub address: Ipv4Addr,
+    pub short_name: String,
+    pub long_name: String,
+    /// The port-addresses of the node's DMX outputs.
+    pub outputs: Vec<u16>,
+}
+
+impl ArtNetNode {
+    /// Return a port for each of the node's DMX outputs.  The ports are not opened yet.
+    pub fn ports(&self) -> Vec<ArtNetDmxPort> {
+        self.outputs
+            .iter()
+            .map(|&port_address| ArtNetDmxPort::new(self.address.into(), port_address))
+            .collect()
+    }
+}
+
+/// Read a nul-padded name field.
+fn read_name(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// Parse an ArtPollReply packet, returning None for anything else.
+fn parse_poll_reply(packet: &[u8]) -> Option<ArtNetNode> {
+    if packet.len() < MIN_POLL_REPLY_LENGTH
+        || &packet[0..8] != ARTNET_ID
+        || u16::from_le_bytes([packet[8], packet[9]]) != OP_POLL_REPLY
+    {
+        return None;
+    }
+    let net = u16::from(packet[18] & 0x7F);
+    let sub_net = u16::from(packet[19] & 0x0F);
+    let num_ports = usize::from(u16::from_be_bytes([packet[172], packet[173]])).min(MAX_PORTS);
+    let outputs = (0..num_ports)
+        .filter(|&i| packet[174 + i] & PORT_OUTPUT != 0)
+        .map(|i| net << 8 | sub_net << 4 | u16::from(packet[190 + i] & 0x0F))
+        .collect();
+    Some(ArtNetNode {
+        address: Ipv4Addr::new(packet[10], packet[11], packet[12], packet[13]),
+        short_name: read_name(&packet[26..44]),
+        long_name: read_name(&packet[44..108]),
+        outputs,
+    })
+}
+
+/// Broadcast an ArtPoll and collect the nodes that reply within the timeout.
+///
+/// Nodes reply to the Art-Net port, so this listens on it if it's free; if another
+/// application has it, only nodes that reply to the sending port are found.
+pub fn discover_artnet_nodes(timeout: Duration) -> Result<Vec<ArtNetNode>, Error> {
+    let socket = match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, ARTNET_PORT)) {
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+            UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?
+        }
+        socket => socket?,
+    };
+    socket.set_broadcast(true)?;
+    let mut poll = Vec::with_capacity(14);
+    poll.extend_from_slice(ARTNET_ID);
+    poll.extend_from_slice(&OP_POLL.to_le_bytes());
+    poll.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    poll.push(0); // flags: reply to this poll only
+    poll.push(0); // lowest diagnostic priority
+    socket.send_to(&poll, (Ipv4Addr::BROADCAST, ARTNET_PORT))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut nodes: Vec<ArtNetNode> = Vec::new();
+    let mut buf = [0; 1024];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(nodes);
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        match socket.recv_from(&mut buf) {
+            Ok((len, _)) => {
+                if let Some(node) = parse_poll_reply(&buf[..len]) {
+                    if !nodes.contains(&node) {
+                        nodes.push(node);
+                    }
+                }
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Ok(nodes)
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// The START code of RDM packets, which Art-Net carries in its own packets, not ArtNzs.
+const RDM_START_CODE: u8 = 0xCC;
+
+/// Format a DMX frame as an ArtDmx packet into the provided buffer.
+/// The frame is truncated to 512 slots, and padded with zeros to an even length of at least 2.
+pub(crate) fn write_art_dmx(sequence: u8, port_address: u16, frame: &[u8], buf: &mut Vec<u8>) {
+    // The physical input port is informational only.
+    write_art_data(OP_DMX, sequence, 0, port_address, frame, buf);
+}
+
+/// Format data with an alternate START code as an ArtNzs packet into the provided buffer.
+/// The data is truncated and padded as ArtDmx frames are.
+fn write_art_nzs(sequence: u8, start_code: u8, port_address: u16, data: &[u8], buf: &mut Vec<u8>) {
+    write_art_data(OP_NZS, sequence, start_code, port_address, data, buf);
+}
+
+/// Format an ArtDmx or ArtNzs packet, which differ only in the opcode and the meaning of the
+/// byte after the sequence number.
+fn write_art_data(
+    op_code: u16,
+    sequence: u8,
+    field: u8,
+    port_address: u16,
+    frame: &[u8],
+    buf: &mut Vec<u8>,
+) {
+    let data = &frame[0..min(frame.len(), MAX_DATA_LENGTH)];
+    let length = (data.len() + data.len() % 2).max(MIN_DATA_LENGTH);
+    buf.clear();
+    buf.extend_from_slice(ARTNET_ID);
+    buf.extend_from_slice(&op_code.to_le_bytes());
+    buf.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+    buf.push(sequence);
+    buf.push(field);
+    buf.push(port_address as u8); // SubUni
+    buf.push((port_address >> 8) as u8); // Net
+    buf.extend_from_slice(&(length as u16).to_be_bytes());
+    buf.extend_from_slice(data);
+    buf.resize(buf.len() + length - data.len(), 0);
+}
+
+/// Parse an ArtDmx packet into its port-address and data, returning None for anything else.
+fn parse_art_dmx(packet: &[u8]) -> Option<(u16, &[u8])> {
+    if packet.len() < ART_DMX_HEADER_LENGTH || &packet[0..8] != ARTNET_ID {
+        return None;
+    }
+    if u16::from_le_bytes([packet[8], packet[9]]) != OP_DMX {
+        return None;
+    }
+    let port_address = u16::from_le_bytes([packet[14], packet[15]]) & PORT_ADDRESS_MASK;
+    let length = usize::from(u16::from_be_bytes([packet[16], packet[17]])).min(MAX_DATA_LENGTH);
+    let data = packet.get(ART_DMX_HEADER_LENGTH..ART_DMX_HEADER_LENGTH + length)?;
+    Some((port_address, data))
+}
+
+/// Return the address a received packet was most likely sent to, for captures.  A socket
+/// bound to every interface can't tell which of its addresses a packet arrived on, so the
+/// packet is recorded as broadcast, as Art-Net usually is.
+fn received_destination(local: SocketAddr) -> SocketAddr {
+    match local.ip().is_unspecified() {
+        true => (Ipv4Addr::BROADCAST, local.port()).into(),
+        false => local,
+    }
+}
+
+/// An ArtDmx packet waiting for its sync group to be flushed.
+struct PendingDmx {
+    destination: (IpAddr, u16),
+    port_address: u16,
+    packet: Vec<u8>,
+    /// The capture of the port that wrote the packet, to record it in once it is sent.
+    capture: Option<PcapWriter>,
+}
+
+struct SyncState {
+    socket: UdpSocket,
+    sync_target: (IpAddr, u16),
+    pending: Vec<PendingDmx>,
+}
+
+/// Sends the ArtDmx packets of several ports together, followed by an ArtSync, so nodes in
+/// synchronous mode update all of their outputs at once instead of tearing across universes.
+///
+/// Writes to the group's ports are held until `flush` is called; only the latest frame written
+/// to each port is sent.  Clones share the same group.
+#[derive(Clone)]
+pub struct ArtNetSyncGroup {
+    state: Arc<Mutex<SyncState>>,
+}
+
+impl ArtNetSyncGroup {
+    /// Create a group that broadcasts its ArtSync packets.
+    pub fn new() -> Result<Self, Error> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        Ok(Self {
+            state: Arc::new(Mutex::new(SyncState {
+                socket,
+                sync_target: (Ipv4Addr::BROADCAST.into(), ARTNET_PORT),
+                pending: Vec::new(),
+            })),
+        })
+    }
+
+    /// Send ArtSync packets to the provided address instead of broadcasting them, such as when
+    /// all of the group's ports send to one node.
+    pub fn with_sync_target(self, target: IpAddr) -> Self {
+        self.lock().sync_target.0 = target;
+        self
+    }
+
+    /// Send the group's packets from the provided local address, so they leave through the
+    /// network interface that has it.  Fails if the address can't be bound.
+    pub fn with_local_address(self, local_address: IpAddr) -> Result<Self, Error> {
+        let socket = UdpSocket::bind((local_address, 0))?;
+        socket.set_broadcast(true)?;
+        self.lock().socket = socket;
+        Ok(self)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SyncState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Create a port in this group that sends to the provided node and port-address.
+    /// The port is not opened yet.
+    pub fn port(&self, target: IpAddr, port_address: u16) -> ArtNetDmxPort {
+        let mut port = ArtNetDmxPort::new(target, port_address);
+        port.sync = Some(self.clone());
+        port
+    }
+
+    /// Hold a packet until the next flush, replacing any older packet for the same output.
+    fn queue(
+        &self,
+        destination: (IpAddr, u16),
+        port_address: u16,
+        packet: &[u8],
+        capture: &Option<PcapWriter>,
+    ) {
+        let mut state = self.lock();
+        let pending = &mut state.pending;
+        match pending
+            .iter_mut()
+            .find(|p| p.destination == destination && p.port_address == port_address)
+        {
+            Some(p) => {
+                p.packet.clear();
+                p.packet.extend_from_slice(packet);
+                p.capture.clone_from(capture);
+            }
+            None => pending.push(PendingDmx {
+                destination,
+                port_address,
+                packet: packet.to_vec(),
+                capture: capture.clone(),
+            }),
+        }
+    }
+
+    /// Send the held packets, then an ArtSync.  Packets that fail to send are dropped, and the
+    /// first error is returned after the rest have been sent.  Sent packets are recorded to
+    /// the captures of the ports that wrote them.
+    pub fn flush(&self) -> Result<(), Error> {
+        let mut state = self.lock();
+        let mut result = Ok(());
+        for pending in std::mem::take(&mut state.pending) {
+            let sent = state
+                .socket
+                .send_to(&pending.packet, pending.destination)
+                .and_then(|_| state.socket.local_addr());
+            match sent {
+                Ok(source) => capture(
+                    &pending.capture,
+                    source,
+                    pending.destination.into(),
+                    &pending.packet,
+                ),
+                Err(e) if result.is_ok() => result = Err(e.into()),
+                Err(_) => {}
+            }
+        }
+        let mut sync = Vec::with_capacity(14);
+        sync.extend_from_slice(ARTNET_ID);
+        sync.extend_from_slice(&OP_SYNC.to_le_bytes());
+        sync.extend_from_slice(&PROTOCOL_VERSION.to_be_bytes());
+        sync.extend_from_slice(&[0, 0]); // aux
+        state.socket.send_to(&sync, state.sync_target)?;
+        result
+    }
+}
+
+/// How an `ArtNetDmxPort` numbers the ArtDmx packets it sends, which receivers use to put
+/// packets that arrive out of order back in order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArtNetSequencing {
+    /// Number packets from 1 each time the port is opened.
+    #[default]
+    ResetOnOpen,
+    /// Carry on numbering from the last packet sent when the port is reopened, for receivers
+    /// that would drop packets numbered lower than the last they saw.
+    Continue,
+    /// Send every packet with sequence number 0, which turns reordering off on the receiver,
+    /// for nodes that drop packets after a restarted source starts numbering again.
+    Disabled,
+}
+
+/// A DMX port that sends ArtDmx packets to a single Art-Net port-address.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ArtNetDmxPort {
+    name: String,
+    /// Where packets are sent: the receiving nodes, or broadcast.
+    #[serde(deserialize_with = "deserialize_target")]
+    target: NetworkTarget,
+    /// UDP port the node listens on; this is almost always the Art-Net default.
+    target_port: u16,
+    /// 15-bit Art-Net port-address to send to.
+    port_address: u16,
+    /// The local address to send from, selecting the network interface; if absent, the OS
+    /// picks one.
+    #[serde(default)]
+    local_address: Option<IpAddr>,
+    #[serde(default)]
+    sequencing: ArtNetSequencing,
+    #[serde(default)]
+    shape: FrameShape,
+    #[serde(skip)]
+    socket: Option<Arc<UdpSocket>>,
+    #[serde(skip)]
+    sequence: u8,
+    #[serde(skip)]
+    buf: Vec<u8>,
+    /// The group holding this port's packets; deserialized ports send immediately.
+    #[serde(skip)]
+    sync: Option<ArtNetSyncGroup>,
+    #[serde(skip)]
+    capture: Option<PcapWriter>,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+impl ArtNetDmxPort {
+    /// Create an Art-Net port that sends to the provided node and port-address.
+    /// Only the low 15 bits of the port-address are used.
+    /// The port is not opened yet.
+    pub fn new(target: IpAddr, port_address: u16) -> Self {
+        Self::with_target(target.into(), port_address)
+    }
+
+    /// Create an Art-Net port that sends to the provided port-address at each of the target's
+    /// addresses.  Only the low 15 bits of the port-address are used.
+    /// The port is not opened yet.
+    pub fn with_target(target: NetworkTarget, port_address: u16) -> Self {
+        let port_address = port_address & PORT_ADDRESS_MASK;
+        Self {
+            name: port_name(&target, port_address),
+            target,
+            target_port: ARTNET_PORT,
+            port_address,
+            local_address: None,
+            sequencing: ArtNetSequencing::default(),
+            shape: FrameShape::default(),
+            socket: None,
+            sequence: 0,
+            buf: Vec::new(),
+            sync: None,
+            capture: None,
+            last_frame: None,
+            status: StatusTracker::new(),
+        }
+    }
+
+    /// Create an Art-Net port that broadcasts to the provided port-address.
+    /// The port is not opened yet.
+    pub fn broadcast(port_address: u16) -> Self {
+        Self::with_target(NetworkTarget::Broadcast, port_address)
+    }
+
+    /// Create an Art-Net port and open it.
+    pub fn opened(target: IpAddr, port_address: u16) -> Result<Self, Error> {
+        let mut port = Self::new(target, port_address);
+        port.open()?;
+        Ok(port)
+    }
+
+    /// Return the port-address this port sends to.
+    pub fn port_address(&self) -> u16 {
+        self.port_address
+    }
+
+    /// Return where packets are sent.
+    pub fn target(&self) -> &NetworkTarget {
+        &self.target
+    }
+
+    /// Send from the provided local address, so packets leave through the network interface
+    /// that has it.  Lighting networks are usually on a secondary interface, which the OS
+    /// won't pick for broadcasts by itself.
+    pub fn with_local_address(mut self, local_address: IpAddr) -> Self {
+        self.local_address = Some(local_address);
+        self
+    }
+
+    /// Return the local address packets are sent from, if one was chosen.
+    pub fn local_address(&self) -> Option<IpAddr> {
+        self.local_address
+    }
+
+    /// Check the target and bind a socket to send to it from.
+    fn bind(&self) -> Result<UdpSocket, Error> {
+        if self.target.is_multicast() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Art-Net has no multicast groups",
+            )
+            .into());
+        }
+        self.target.check()?;
+        let local_address = self.local_address.unwrap_or(Ipv4Addr::UNSPECIFIED.into());
+        let socket = UdpSocket::bind((local_address, 0))?;
+        socket.set_broadcast(true)?;
+        Ok(socket)
+    }
+
+    /// Record the packets sent to a capture.  Packets held by a sync group are recorded when
+    /// the group sends them.
+    pub fn with_capture(mut self, capture: PcapWriter) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Choose how packets are numbered.  By default they are numbered from 1 each time the
+    /// port is opened.
+    pub fn with_sequencing(mut self, sequencing: ArtNetSequencing) -> Self {
+        self.sequencing = sequencing;
+        self
+    }
+
+    /// Return how packets are numbered.
+    pub fn sequencing(&self) -> ArtNetSequencing {
+        self.sequencing
+    }
+
+    /// Set how each frame is shaped before it is transmitted: how many of its slots are sent,
+    /// and how shorter frames are padded.  By default frames are transmitted at the length
+    /// they're written, padded with zeros only as far as the protocol requires.
+    pub fn with_shape(mut self, shape: FrameShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Return how each frame is shaped before it is transmitted.
+    pub fn shape(&self) -> &FrameShape {
+        &self.shape
+    }
+
+    /// Advance the sequence number, skipping 0 which disables sequencing on the receiver.
+    fn next_sequence(&mut self) -> u8 {
+        if self.sequencing == ArtNetSequencing::Disabled {
+            return 0;
+        }
+        self.sequence = self.sequence.checked_add(1).unwrap_or(1);
+        self.sequence
+    }
+
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), Error> {
+        if self.socket.is_none() {
+            return Err(Error::NotOpen);
+        }
+        let sequence = self.next_sequence();
+        let sent = self
+            .shape
+            .apply(frame, |slots| (slots + slots % 2).max(MIN_DATA_LENGTH));
+        write_art_dmx(sequence, self.port_address, sent, &mut self.buf);
+        for address in self.target.addresses(None) {
+            let destination = (address, self.target_port);
+            if let Some(sync) = &self.sync {
+                sync.queue(destination, self.port_address, &self.buf, &self.capture);
+            } else {
+                let socket = self.socket.as_ref().ok_or(Error::NotOpen)?;
+                socket.send_to(&self.buf, destination)?;
+                if self.capture.is_some() {
+                    let source = socket.local_addr()?;
+                    capture(&self.capture, source, destination.into(), &self.buf);
+                }
+            }
+        }
+        self.last_frame = Some(DmxFrame::from(frame));
+        Ok(())
+    }
+
+    /// Send data with an alternate START code as an ArtNzs packet.  ArtNzs packets are sent
+    /// straight away, even by ports in a sync group, which only holds ArtDmx frames.
+    fn send_nzs(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        if start_code == RDM_START_CODE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Art-Net carries RDM in ArtRdm packets, not ArtNzs",
+            )
+            .into());
+        }
+        let socket = self.socket.clone().ok_or(Error::NotOpen)?;
+        let sequence = self.next_sequence();
+        write_art_nzs(sequence, start_code, self.port_address, data, &mut self.buf);
+        for address in self.target.addresses(None) {
+            let destination = (address, self.target_port);
+            socket.send_to(&self.buf, destination)?;
+            if self.capture.is_some() {
+                let source = socket.local_addr()?;
+                capture(&self.capture, source, destination.into(), &self.buf);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Return the name of an Art-Net port.  Broadcast ports are named by the broadcast address,
+/// as they were before targets could be configured, so saved names keep finding them.
+fn port_name(target: &NetworkTarget, port_address: u16) -> String {
+    match target {
+        NetworkTarget::Broadcast => format!("artnet:{}/{}", Ipv4Addr::BROADCAST, port_address),
+        _ => format!("artnet:{}/{}", target, port_address),
+    }
+}
+
+/// Lists a port for each output of the Art-Net nodes that answer an ArtPoll within a short
+/// timeout.  Without a network to broadcast on there are none to list.
+///
+/// Discovery broadcasts on the network, listens on the Art-Net port, which a receiver may
+/// want, and takes the whole timeout, so this provider isn't among the built-in ones.
+/// Register it with `register_provider` to list Art-Net nodes with the other ports.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ArtNetProvider;
+
+impl PortProvider for ArtNetProvider {
+    fn name(&self) -> &str {
+        "artnet"
+    }
+
+    fn available_ports(&self) -> Result<PortListing, Error> {
+        let nodes = match discover_artnet_nodes(AVAILABLE_PORTS_DISCOVERY_TIMEOUT) {
+            Ok(nodes) => nodes,
+            Err(Error::IO(_)) => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut ports: PortListing = Vec::new();
+        for node in nodes {
+            for port in node.ports() {
+                if !ports.iter().any(|p| p.name() == port.name()) {
+                    ports.push(Box::new(port));
+                }
+            }
+        }
+        Ok(ports)
+    }
+
+    fn kinds(&self) -> &[PortKind] {
+        &[PortKind::ArtNet]
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for ArtNetDmxPort {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Open the port.
+    fn open(&mut self) -> Result<(), Error> {
+        if self.socket.is_some() {
+            return Ok(());
+        }
+        let result = self.bind().map(|socket| {
+            self.socket = Some(Arc::new(socket));
+            if self.sequencing == ArtNetSequencing::ResetOnOpen {
+                self.sequence = 0;
+            }
+        });
+        self.status.record(result)
+    }
+
+    fn close(&mut self) {
+        self.status.clear();
+        self.socket = None;
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let result = self.send_frame(frame);
+        self.status.record(result)
+    }
+
+    /// Write data with an alternate START code, sent as an ArtNzs packet.  RDM's START code
+    /// isn't allowed.
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        let result = match start_code {
+            0 => self.send_frame(data),
+            _ => self.send_nzs(start_code, data),
+        };
+        self.status.record(result)
+    }
+
+    fn kind(&self) -> PortKind {
+        PortKind::ArtNet
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(self.socket.is_some())
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+}
+
+impl fmt::Display for ArtNetDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Art-Net {} net {} sub-net {} universe {}",
+            self.target,
+            self.port_address >> 8,
+            (self.port_address >> 4) & 0xF,
+            self.port_address & 0xF
+        )
+    }
+}
+
+/// Receives ArtDmx packets sent to any of a set of port-addresses, for acting as the
+/// receiving end of an Art-Net link, such as a bridge from the network to a serial interface.
+pub struct ArtNetReceiver {
+    name: String,
+    bind_address: SocketAddr,
+    port_addresses: Vec<u16>,
+    socket: Option<UdpSocket>,
+    buf: Vec<u8>,
+    capture: Option<PcapWriter>,
+}
+
+impl ArtNetReceiver {
+    /// Create a receiver for the provided port-addresses, listening on the Art-Net port of
+    /// every interface.  Only the low 15 bits of each port-address are used, and each is
+    /// received once however many times it is listed.  The receiver is not opened yet.
+    pub fn new(port_addresses: impl IntoIterator<Item = u16>) -> Self {
+        let mut masked: Vec<u16> = Vec::new();
+        for port_address in port_addresses {
+            let port_address = port_address & PORT_ADDRESS_MASK;
+            if !masked.contains(&port_address) {
+                masked.push(port_address);
+            }
+        }
+        let port_addresses = masked;
+        let names: Vec<_> = port_addresses.iter().map(u16::to_string).collect();
+        Self {
+            name: format!("artnet-in:{}", names.join(",")),
+            bind_address: (Ipv4Addr::UNSPECIFIED, ARTNET_PORT).into(),
+            port_addresses,
+            socket: None,
+            buf: vec![0; ART_DMX_HEADER_LENGTH + MAX_DATA_LENGTH],
+            capture: None,
+        }
+    }
+
+    /// Listen on the provided address instead, such as the Art-Net port of one interface.
+    pub fn with_bind_address(mut self, bind_address: SocketAddr) -> Self {
+        self.bind_address = bind_address;
+        self
+    }
+
+    /// Record the packets received to a capture, including those for other port-addresses.
+    pub fn with_capture(mut self, capture: PcapWriter) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Return the port-addresses packets are received for.
+    pub fn port_addresses(&self) -> &[u16] {
+        &self.port_addresses
+    }
+
+    /// Return the address the receiver is listening on, once open.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.socket.as_ref().ok_or(Error::NotOpen)?.local_addr()?)
+    }
+
+    /// Block until an ArtDmx packet arrives for one of the port-addresses, and return its
+    /// port-address with the frame.
+    pub fn read_universe(&mut self) -> Result<(u16, ReceivedFrame), Error> {
+        let socket = self.socket.as_ref().ok_or(Error::NotOpen)?;
+        loop {
+            let (len, source) = socket.recv_from(&mut self.buf)?;
+            if self.capture.is_some() {
+                let destination = received_destination(socket.local_addr()?);
+                capture(&self.capture, source, destination, &self.buf[..len]);
+            }
+            match parse_art_dmx(&self.buf[..len]) {
+                Some((port_address, data)) if self.port_addresses.contains(&port_address) => {
+                    let frame = ReceivedFrame {
+                        start_code: 0,
+                        data: data.to_vec(),
+                    };
+                    return Ok((port_address, frame));
+                }
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl DmxInputPort for ArtNetReceiver {
+    /// Receivers listen for whatever port-addresses they are given, so there are none to
+    /// list; construct receivers directly instead.
+    fn available_ports() -> Result<InputPortListing, Error> {
+        Ok(Vec::new())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        if self.socket.is_none() {
+            self.socket = Some(UdpSocket::bind(self.bind_address)?);
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.socket = None;
+    }
+
+    /// Block until an ArtDmx packet arrives for one of the port-addresses.  Use
+    /// `read_universe` to find out which one.
+    fn read_frame(&mut self) -> Result<ReceivedFrame, Error> {
+        self.read_universe().map(|(_, frame)| frame)
+    }
+}
+
+impl fmt::Display for ArtNetReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Art-Net input {}", self.bind_address)?;
+        for port_address in &self.port_addresses {
+            write!(f, " {}", port_address)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pcap::CaptureBuffer;
+    use crate::{FrameLength, Padding};
+
+    #[test]
+    fn test_art_dmx_header() {
+        let mut buf = Vec::new();
+        write_art_dmx(7, 0x1234, &[1, 2, 3, 4], &mut buf);
+        assert_eq!(&buf[0..8], b"Art-Net\0");
+        assert_eq!(&buf[8..18], &[0x00, 0x50, 0, 14, 7, 0, 0x34, 0x12, 0, 4]);
+        assert_eq!(&buf[18..], &[1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_art_nzs() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut port = ArtNetDmxPort::new(Ipv4Addr::LOCALHOST.into(), 0x1234);
+        port.target_port = receiver.local_addr().unwrap().port();
+        port.open().unwrap();
+        port.write(&[1, 2]).unwrap();
+        port.write_with_start_code(0x17, b"abc").unwrap();
+        assert!(port.write_with_start_code(RDM_START_CODE, &[1]).is_err());
+        let mut buf = [0; 1024];
+        receiver.recv(&mut buf).unwrap();
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[0..8], b"Art-Net\0");
+        assert_eq!(&buf[8..18], &[0x00, 0x51, 0, 14, 2, 0x17, 0x34, 0x12, 0, 4]);
+        assert_eq!(&buf[18..len], b"abc\0");
+        assert_eq!(&port.last_frame().unwrap()[0..3], &[1, 2, 0]);
+    }
+
+    #[test]
+    fn test_receiver() {
+        let mut receiver = ArtNetReceiver::new([0x12, 0x34, 0x8012])
+            .with_bind_address((Ipv4Addr::LOCALHOST, 0).into());
+        assert_eq!(receiver.port_addresses(), &[0x12, 0x34]);
+        receiver.open().unwrap();
+        let address = receiver.local_addr().unwrap();
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut buf = Vec::new();
+        write_art_dmx(1, 0x99, &[9, 9], &mut buf);
+        socket.send_to(&buf, address).unwrap();
+        write_art_dmx(2, 0x34, &[1, 2, 3], &mut buf);
+        socket.send_to(&buf, address).unwrap();
+        let (port_address, frame) = receiver.read_universe().unwrap();
+        assert_eq!(port_address, 0x34);
+        assert_eq!(frame.data, vec![1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn test_parse_poll_reply() {
+        let mut packet = vec![0; 239];
+        packet[0..8].copy_from_slice(ARTNET_ID);
+        packet[8..10].copy_from_slice(&OP_POLL_REPLY.to_le_bytes());
+        packet[10..14].copy_from_slice(&[10, 0, 0, 5]);
+        packet[18] = 1; // net
+        packet[19] = 2; // sub-net
+        packet[26..30].copy_from_slice(b"node");
+        packet[44..53].copy_from_slice(b"long name");
+        packet[173] = 3; // ports
+        packet[174..177].copy_from_slice(&[0x80, 0x40, 0x80]);
+        packet[190..193].copy_from_slice(&[3, 4, 5]);
+        let node = parse_poll_reply(&packet).unwrap();
+        assert_eq!(node.address, Ipv4Addr::new(10, 0, 0, 5));
+        assert_eq!(node.short_name, "node");
+        assert_eq!(node.long_name, "long name");
+        assert_eq!(node.outputs, vec![0x123, 0x125]);
+        assert!(parse_poll_reply(&packet[..100]).is_none());
+    }
+
+    #[test]
+    fn test_received_destination() {
+        assert_eq!(
+            received_destination((Ipv4Addr::UNSPECIFIED, ARTNET_PORT).into()),
+            (Ipv4Addr::BROADCAST, ARTNET_PORT).into()
+        );
+        let local = (Ipv4Addr::new(10, 0, 0, 5), ARTNET_PORT).into();
+        assert_eq!(received_destination(local), local);
+    }
+
+    #[test]
+    fn test_sync_group() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let receiver_port = receiver.local_addr().unwrap().port();
+        let group = ArtNetSyncGroup::new()
+            .unwrap()
+            .with_sync_target(Ipv4Addr::LOCALHOST.into());
+        group.lock().sync_target.1 = receiver_port;
+        let out = CaptureBuffer::default();
+        let mut ports: Vec<_> = (1..=2)
+            .map(|port_address| {
+                let mut port = group.port(Ipv4Addr::LOCALHOST.into(), port_address);
+                port.target_port = receiver_port;
+                port.open().unwrap();
+                port
+            })
+            .collect();
+        ports[0].capture = Some(PcapWriter::new(out.clone()).unwrap());
+        ports[0].write(&[1, 2]).unwrap();
+        ports[0].write(&[3, 4]).unwrap();
+        ports[1].write(&[5, 6]).unwrap();
+        assert!(out.payloads().is_empty());
+        group.flush().unwrap();
+        let payloads = out.payloads();
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(&payloads[0][14..], &[1, 0, 0, 2, 3, 4]);
+
+        let mut buf = [0; 1024];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[14..len], &[1, 0, 0, 2, 3, 4]);
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[14..len], &[2, 0, 0, 2, 5, 6]);
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[8..len], &[0x00, 0x52, 0, 14, 0, 0]);
+    }
+
+    #[test]
+    fn test_target() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let localhost = IpAddr::from(Ipv4Addr::LOCALHOST);
+        let mut port =
+            ArtNetDmxPort::with_target(NetworkTarget::Unicast(vec![localhost, localhost]), 3);
+        port.target_port = receiver.local_addr().unwrap().port();
+        assert_eq!(port.name(), "artnet:127.0.0.1,127.0.0.1/3");
+        port.open().unwrap();
+        port.write(&[1, 2]).unwrap();
+        let mut buf = [0; 1024];
+        for _ in 0..2 {
+            let len = receiver.recv(&mut buf).unwrap();
+            assert_eq!(&buf[18..len], &[1, 2]);
+        }
+        let mut port = ArtNetDmxPort::with_target(NetworkTarget::Multicast, 3);
+        assert!(port.open().is_err());
+        let mut port = ArtNetDmxPort::with_target(NetworkTarget::Unicast(Vec::new()), 3);
+        assert!(port.open().is_err());
+        assert!(matches!(port.status(), PortStatus::Errored(_)));
+        port.close();
+        assert_eq!(port.status(), PortStatus::Closed);
+        // Saved names of broadcast ports predate targets.
+        assert_eq!(
+            ArtNetDmxPort::broadcast(3).name(),
+            "artnet:255.255.255.255/3"
+        );
+    }
+
+    #[test]
+    fn test_sequencing() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut buf = [0; 1024];
+        let mut sequences = |sequencing| {
+            let mut port =
+                ArtNetDmxPort::new(Ipv4Addr::LOCALHOST.into(), 1).with_sequencing(sequencing);
+            port.target_port = receiver.local_addr().unwrap().port();
+            let mut sequences = Vec::new();
+            for _ in 0..2 {
+                port.open().unwrap();
+                for _ in 0..2 {
+                    port.write(&[1, 2]).unwrap();
+                    receiver.recv(&mut buf).unwrap();
+                    sequences.push(buf[12]);
+                }
+                port.close();
+            }
+            sequences
+        };
+        assert_eq!(sequences(ArtNetSequencing::ResetOnOpen), vec![1, 2, 1, 2]);
+        assert_eq!(sequences(ArtNetSequencing::Continue), vec![1, 2, 3, 4]);
+        assert_eq!(sequences(ArtNetSequencing::Disabled), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_shape() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut port = ArtNetDmxPort::new(Ipv4Addr::LOCALHOST.into(), 1)
+            .with_shape(FrameShape::new().with_length(FrameLength::Fixed(4)));
+        port.target_port = receiver.local_addr().unwrap().port();
+        port.open().unwrap();
+        port.write(&[1, 2]).unwrap();
+        port.write(&[1, 2, 3, 4, 5, 6]).unwrap();
+        let mut buf = [0; 1024];
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[16..len], &[0, 4, 1, 2, 0, 0]);
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[16..len], &[0, 4, 1, 2, 3, 4]);
+        assert_eq!(port.last_frame().unwrap()[4], 5);
+        let mut port = port.with_shape(
+            FrameShape::new()
+                .with_length(FrameLength::Fixed(4))
+                .with_padding(Padding::to(512).with_value(7)),
+        );
+        port.write(&[1, 2]).unwrap();
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[16..len], &[0, 4, 1, 2, 7, 7]);
+        let mut port =
+            port.with_shape(FrameShape::new().with_padding(Padding::to(512).with_value(7)));
+        port.write(&[1, 2]).unwrap();
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(len, 18 + 512);
+        assert_eq!(buf[len - 1], 7);
+        // The byte evening up the length is padding too.
+        let mut port = port.with_shape(
+            FrameShape::new()
+                .with_length(FrameLength::Fixed(3))
+                .with_padding(Padding::default().with_value(9)),
+        );
+        port.write(&[1]).unwrap();
+        let len = receiver.recv(&mut buf).unwrap();
+        assert_eq!(&buf[16..len], &[0, 4, 1, 9, 9, 9]);
+    }
+
+    #[test]
+    fn test_local_address() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut port = ArtNetDmxPort::new(Ipv4Addr::LOCALHOST.into(), 1)
+            .with_local_address(Ipv4Addr::LOCALHOST.into());
+        port.target_port = receiver.local_addr().unwrap().port();
+        port.open().unwrap();
+        port.write(&[1, 2]).unwrap();
+        let (_, source) = receiver.recv_from(&mut [0; 1024]).unwrap();
+        assert_eq!(source.ip(), Ipv4Addr::LOCALHOST);
+        // An address from the documentation range isn't assigned to any interface.
+        let mut port = ArtNetDmxPort::new(Ipv4Addr::LOCALHOST.into(), 1)
+            .with_local_address(Ipv4Addr::new(192, 0, 2, 1).into());
+        assert!(port.open().is_err());
+    }
+
+    #[test]
+    fn test_art_dmx_length() {
+        let mut buf = Vec::new();
+        write_art_dmx(0, 0, &[], &mut buf);
+        assert_eq!(buf.len(), 18 + 2);
+        write_art_dmx(0, 0, &[1, 2, 3], &mut buf);
+        assert_eq!(&buf[16..], &[0, 4, 1, 2, 3, 0]);
+        write_art_dmx(0, 0, &[1; 600], &mut buf);
+        assert_eq!(buf.len(), 18 + 512);
+    }
+}
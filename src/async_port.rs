@@ -0,0 +1,34 @@
+//! Async counterpart of the DmxPort trait, for use from tokio applications.
+
+use async_trait::async_trait;
+use std::fmt;
+use std::io;
+use tokio::task::JoinError;
+
+use crate::Error;
+
+/// Trait for a DMX port that can be driven from async code without blocking the executor.
+/// Implementations perform blocking I/O on tokio's blocking thread pool.
+#[async_trait]
+pub trait AsyncDmxPort: fmt::Display + Send {
+    /// Return a string identifier for this port.
+    fn name(&self) -> &str;
+
+    /// Open the port for writing.  Implementations should no-op if this is
+    /// called twice rather than returning an error.
+    async fn open(&mut self) -> Result<(), Error>;
+
+    /// Close the port.
+    async fn close(&mut self);
+
+    /// Write a DMX frame out to the port, with the same padding and truncation behavior as
+    /// DmxPort::write.  If the returned future is dropped before completion, the port may be
+    /// left closed.
+    async fn write(&mut self, frame: &[u8]) -> Result<(), Error>;
+}
+
+impl From<JoinError> for Error {
+    fn from(e: JoinError) -> Self {
+        Error::IO(io::Error::other(e))
+    }
+}
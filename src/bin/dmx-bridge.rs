@@ -0,0 +1,181 @@
+//! Forwarding universes received over Art-Net or sACN to local DMX ports.
+
+use rust_dmx::{
+    open_port_by_name, ArtNetReceiver, DmxInputPort, DmxPort, Error, ReceivedFrame,
+    ReconnectingPort, SacnReceiver,
+};
+use std::env;
+use std::net::SocketAddr;
+use std::process::exit;
+
+const USAGE: &str = "\
+usage: dmx-bridge <artnet|sacn> [--bind <address>] <universe>=<port> [...]
+
+Receives Art-Net or sACN and writes each listed universe to a local port.  Universes are
+Art-Net port-addresses or sACN universe numbers.  Ports are matched by name or description
+like open_port_by_name, so a widget can be given by its serial number.  A universe may be
+sent to several ports by listing it more than once.
+
+The Art-Net or sACN port of every interface is listened on unless another address is given
+with --bind; sACN multicast is only joined when listening on every interface.";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
+/// A failure to report to the user.
+#[derive(Debug)]
+enum BridgeError {
+    Usage(String),
+    Dmx(Error),
+}
+
+impl From<Error> for BridgeError {
+    fn from(e: Error) -> Self {
+        BridgeError::Dmx(e)
+    }
+}
+
+impl std::fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BridgeError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+            BridgeError::Dmx(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+fn usage(message: &str) -> BridgeError {
+    BridgeError::Usage(message.to_string())
+}
+
+/// The network input being bridged.
+enum Receiver {
+    ArtNet(ArtNetReceiver),
+    Sacn(SacnReceiver),
+}
+
+impl Receiver {
+    fn input(&mut self) -> &mut dyn DmxInputPort {
+        match self {
+            Receiver::ArtNet(receiver) => receiver,
+            Receiver::Sacn(receiver) => receiver,
+        }
+    }
+
+    fn read_universe(&mut self) -> Result<(u16, ReceivedFrame), Error> {
+        match self {
+            Receiver::ArtNet(receiver) => receiver.read_universe(),
+            Receiver::Sacn(receiver) => receiver.read_universe(),
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), BridgeError> {
+    let (protocol, args) = args
+        .split_first()
+        .ok_or_else(|| usage("no protocol given"))?;
+    let (bind, args) = match args {
+        [flag, address, rest @ ..] if flag == "--bind" => {
+            let address: SocketAddr = address
+                .parse()
+                .map_err(|_| usage(&format!("{:?} is not an address and port", address)))?;
+            (Some(address), rest)
+        }
+        _ => (None, args),
+    };
+    if args.is_empty() {
+        return Err(usage("no universes given"));
+    }
+    let mut outputs = Vec::new();
+    for mapping in args {
+        let (universe, pattern) = parse_mapping(mapping)?;
+        let port = ReconnectingPort::new(open_port_by_name(pattern)?);
+        println!("universe {} -> {}", universe, port);
+        outputs.push((universe, port));
+    }
+    let universes = outputs.iter().map(|(universe, _)| *universe);
+    let mut receiver = match protocol.as_str() {
+        "artnet" => {
+            let receiver = ArtNetReceiver::new(universes);
+            Receiver::ArtNet(match bind {
+                Some(address) => receiver.with_bind_address(address),
+                None => receiver,
+            })
+        }
+        "sacn" => {
+            let receiver = SacnReceiver::new(universes);
+            Receiver::Sacn(match bind {
+                Some(address) => receiver.with_bind_address(address),
+                None => receiver,
+            })
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        other => return Err(usage(&format!("unknown protocol {:?}", other))),
+    };
+    receiver.input().open()?;
+    println!("listening on {}", receiver.input());
+    loop {
+        let (universe, frame) = match receiver.read_universe() {
+            Ok(received) => received,
+            // The interfaces keep sending the last frame, so lost sources can be waited out.
+            Err(Error::Timeout) => {
+                eprintln!("a universe lost all of its sources");
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+        for (_, port) in outputs.iter_mut().filter(|(u, _)| *u == universe) {
+            if let Err(e) = port.write(&frame.data) {
+                eprintln!("failed to write universe {} to {}: {}", universe, port, e);
+            }
+        }
+    }
+}
+
+/// Parse a `<universe>=<port>` argument.
+fn parse_mapping(arg: &str) -> Result<(u16, &str), BridgeError> {
+    let invalid = || usage(&format!("{:?} is not <universe>=<port>", arg));
+    let (universe, pattern) = arg.split_once('=').ok_or_else(invalid)?;
+    let universe = universe.parse().map_err(|_| invalid())?;
+    if pattern.is_empty() {
+        return Err(invalid());
+    }
+    Ok((universe, pattern))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_mapping() {
+        assert_eq!(parse_mapping("3=EN123456").unwrap(), (3, "EN123456"));
+        assert_eq!(parse_mapping("1=a=b").unwrap(), (1, "a=b"));
+        assert!(parse_mapping("3").is_err());
+        assert!(parse_mapping("3=").is_err());
+        assert!(parse_mapping("x=offline").is_err());
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert!(matches!(run(&[]), Err(BridgeError::Usage(_))));
+        assert!(matches!(run(&args(&["sacn"])), Err(BridgeError::Usage(_))));
+        assert!(matches!(
+            run(&args(&["sacn", "--bind", "nowhere", "1=offline"])),
+            Err(BridgeError::Usage(_))
+        ));
+        assert!(matches!(
+            run(&args(&["dmx", "1=offline"])),
+            Err(BridgeError::Usage(_))
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+//! Command line control of DMX ports, doubling as a hardware smoke test.
+
+use rust_dmx::{
+    open_first_hardware_port, open_port_from_env, open_port_spec, scan_ports, DmxFrame, DmxPort,
+    Error, DMX_PORT_VAR,
+};
+use std::env;
+use std::io::{self, BufRead};
+use std::process::exit;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "\
+usage: dmx-cli [--port <name>] <command>
+
+Commands:
+    list                              list the available ports
+    set <channel> <level> [...]       set channels, leaving the rest at zero
+    blackout                          set every channel to zero
+    ramp <channel> [<seconds>]        fade a channel up from zero to full, over 2 seconds by default
+    stream-from-stdin                 write a frame for each line of space-separated levels
+
+The first attached DMX interface is used unless a port is named with --port, or with the
+DMX_PORT environment variable.  Ports are named like open_port_spec: enttec:<serial number>,
+artnet:<address>/<port-address>, sacn:<universe>, or part of a port's name or description.";
+
+/// How often ramps are written.
+const RAMP_INTERVAL: Duration = Duration::from_millis(25);
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
+
+/// A failure to report to the user.
+#[derive(Debug)]
+enum CliError {
+    Usage(String),
+    Dmx(Error),
+}
+
+impl From<Error> for CliError {
+    fn from(e: Error) -> Self {
+        CliError::Dmx(e)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(e: io::Error) -> Self {
+        CliError::Dmx(e.into())
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}\n\n{}", message, USAGE),
+            CliError::Dmx(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+fn usage(message: &str) -> CliError {
+    CliError::Usage(message.to_string())
+}
+
+fn run(args: &[String]) -> Result<(), CliError> {
+    let (port_name, args) = match args {
+        [flag, name, rest @ ..] if flag == "--port" => (Some(name.as_str()), rest),
+        _ => (None, args),
+    };
+    let (command, args) = args
+        .split_first()
+        .ok_or_else(|| usage("no command given"))?;
+    let open = || match port_name {
+        Some(name) => open_port_spec(name),
+        None if env::var_os(DMX_PORT_VAR).is_some() => open_port_from_env(),
+        None => open_first_hardware_port(),
+    };
+    match command.as_str() {
+        "list" => list(),
+        "set" => set(open()?.as_mut(), args),
+        "blackout" => Ok(open()?.write(&DmxFrame::new())?),
+        "ramp" => ramp(open()?.as_mut(), args),
+        "stream-from-stdin" => stream(open()?.as_mut()),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(usage(&format!("unknown command {:?}", other))),
+    }
+}
+
+fn list() -> Result<(), CliError> {
+    let scan = scan_ports();
+    for error in &scan.errors {
+        eprintln!("warning: {}", error);
+    }
+    for (i, port) in scan.ports.iter().enumerate() {
+        println!("{}: {} ({})", i, port.name, port);
+        println!("    kind: {}", port.kind);
+        if let Some(alias) = &port.alias {
+            println!("    alias: {}", alias);
+        }
+        if let Some(path) = &port.hardware.path {
+            println!("    path: {}", path);
+        }
+        if let Some(description) = &port.hardware.description {
+            println!("    description: {}", description);
+        }
+        if let Some(sn) = &port.hardware.serial_number {
+            println!("    serial number: {}", sn);
+        }
+        if let (Some(vid), Some(pid)) = (port.hardware.vid, port.hardware.pid) {
+            println!("    USB ID: {:04x}:{:04x}", vid, pid);
+        }
+        if let Some(sn) = &port.hardware.device_serial_number {
+            println!("    device serial number: {}", sn);
+        }
+        if let Some(version) = &port.hardware.firmware_version {
+            println!("    firmware: {}", version);
+        }
+    }
+    Ok(())
+}
+
+fn parse_channel(arg: &str) -> Result<u16, CliError> {
+    match arg.parse() {
+        Ok(channel) if (1..=512).contains(&channel) => Ok(channel),
+        _ => Err(usage(&format!("{:?} is not a channel from 1 to 512", arg))),
+    }
+}
+
+fn parse_level(arg: &str) -> Result<u8, CliError> {
+    arg.parse()
+        .map_err(|_| usage(&format!("{:?} is not a level from 0 to 255", arg)))
+}
+
+fn set(port: &mut dyn DmxPort, args: &[String]) -> Result<(), CliError> {
+    if args.is_empty() || args.len() % 2 != 0 {
+        return Err(usage("set takes pairs of channels and levels"));
+    }
+    let mut frame = DmxFrame::new();
+    for pair in args.chunks(2) {
+        frame.set(parse_channel(&pair[0])?, parse_level(&pair[1])?);
+    }
+    Ok(port.write(&frame)?)
+}
+
+fn ramp(port: &mut dyn DmxPort, args: &[String]) -> Result<(), CliError> {
+    let (channel, duration) = match args {
+        [channel] => (parse_channel(channel)?, Duration::from_secs(2)),
+        [channel, seconds] => {
+            // Negative, NaN and overlong durations are rejected rather than panicking.
+            let duration = seconds
+                .parse()
+                .ok()
+                .and_then(|s: f64| Duration::try_from_secs_f64(s).ok())
+                .ok_or_else(|| usage(&format!("{:?} is not a number of seconds", seconds)))?;
+            (parse_channel(channel)?, duration)
+        }
+        _ => {
+            return Err(usage(
+                "ramp takes a channel and optionally a number of seconds",
+            ))
+        }
+    };
+    let mut frame = DmxFrame::new();
+    let start = Instant::now();
+    loop {
+        let elapsed = start.elapsed();
+        let progress = if duration.is_zero() {
+            1.0
+        } else {
+            (elapsed.as_secs_f64() / duration.as_secs_f64()).min(1.0)
+        };
+        frame.set(channel, (progress * 255.0).round() as u8);
+        port.write(&frame)?;
+        if progress >= 1.0 {
+            return Ok(());
+        }
+        sleep(RAMP_INTERVAL);
+    }
+}
+
+fn stream(port: &mut dyn DmxPort) -> Result<(), CliError> {
+    for line in io::stdin().lock().lines() {
+        let levels = line?
+            .split_whitespace()
+            .map(parse_level)
+            .collect::<Result<Vec<_>, _>>()?;
+        port.write(&levels)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rust_dmx::OfflineDmxPort;
+
+    #[test]
+    fn test_usage_errors() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let mut port = OfflineDmxPort::new();
+        port.open().unwrap();
+        assert!(matches!(
+            set(&mut port, &args(&["1"])),
+            Err(CliError::Usage(_))
+        ));
+        for seconds in ["-1", "NaN", "1e300", "soon"] {
+            assert!(matches!(
+                ramp(&mut port, &args(&["1", seconds])),
+                Err(CliError::Usage(_))
+            ));
+        }
+        set(&mut port, &args(&["1", "255", "2", "0"])).unwrap();
+        assert_eq!(&port.last_frame().unwrap()[0..2], &[255, 0]);
+    }
+}
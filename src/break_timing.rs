@@ -0,0 +1,22 @@
+//! DMX line timing for interfaces whose host generates the break.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Break and mark-after-break durations used when generating DMX timing in software.
+/// Sleep granularity means the actual durations will usually be somewhat longer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BreakTiming {
+    pub break_time: Duration,
+    pub mark_after_break_time: Duration,
+}
+
+impl Default for BreakTiming {
+    /// Comfortably above the minimums of 92 and 12 microseconds in the standard.
+    fn default() -> Self {
+        Self {
+            break_time: Duration::from_micros(110),
+            mark_after_break_time: Duration::from_micros(16),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! Calling back when received channels change.
+
+use std::cmp::min;
+use std::mem;
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
+
+use crate::{DmxInputPort, Error, ReceivedFrame, UNIVERSE_SIZE};
+
+/// Identifies a subscription to a `ChannelWatcher`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u32);
+
+/// A change to the channels a subscription watches.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelChange<'a> {
+    /// The number of the first channel watched, from 1.
+    pub first_channel: u16,
+    /// The levels of the watched channels before the change.
+    pub previous: &'a [u8],
+    /// The levels of the watched channels now.
+    pub levels: &'a [u8],
+}
+
+/// Which way a channel crossed a threshold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Crossing {
+    /// The channel rose from below the threshold to or above it.
+    Up,
+    /// The channel fell from at or above the threshold to below it.
+    Down,
+}
+
+type Callback = Box<dyn FnMut(&ChannelChange<'_>) + Send>;
+
+struct Subscription {
+    id: SubscriptionId,
+    /// Indices into the universe, from 0.
+    start: usize,
+    end: usize,
+    debounce: Duration,
+    /// The levels before a change that hasn't been reported yet, and when the channels last
+    /// changed.
+    pending: Option<(Vec<u8>, Instant)>,
+    callback: Callback,
+}
+
+/// Calls back when ranges of channels received on an input port change, so triggers don't
+/// need to compare whole frames.  Received frames are fed to `update`, or `run` reads them from
+/// a port.  Only frames with the null start code carry levels; others are ignored.
+///
+/// The first frame sets the levels changes are measured from, and is not reported.  A
+/// subscription with a debounce time reports a change only once its channels have been steady
+/// for that long, with the levels from before the first change; this is checked as frames
+/// arrive, which input ports deliver continuously.
+///
+/// ```no_run
+/// # use rust_dmx::{ChannelWatcher, Crossing, DmxInputPort};
+/// # use std::time::Duration;
+/// # fn run(port: &mut dyn DmxInputPort) -> Result<(), rust_dmx::Error> {
+/// let mut watcher = ChannelWatcher::new();
+/// watcher.on_crossing(1, 128, Duration::ZERO, |crossing| {
+///     if crossing == Crossing::Up {
+///         println!("go");
+///     }
+/// });
+/// watcher.run(port)
+/// # }
+/// ```
+#[derive(Default)]
+pub struct ChannelWatcher {
+    subscriptions: Vec<Subscription>,
+    next_id: u32,
+    /// The levels last received, and the ones received before them, once frames have been.
+    levels: Vec<u8>,
+    previous: Vec<u8>,
+}
+
+impl ChannelWatcher {
+    /// Create a watcher with no subscriptions.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Call back when any of the channels in the range change, once they have been steady for
+    /// the debounce time.  Channels are numbered from 1.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty or reaches outside 1 to 512.
+    pub fn on_change<F>(
+        &mut self,
+        channels: RangeInclusive<u16>,
+        debounce: Duration,
+        callback: F,
+    ) -> SubscriptionId
+    where
+        F: FnMut(&ChannelChange<'_>) + Send + 'static,
+    {
+        let (first, last) = channels.into_inner();
+        assert!(
+            1 <= first && first <= last && usize::from(last) <= UNIVERSE_SIZE,
+            "channels {}..={} are not a range within 1 to 512",
+            first,
+            last
+        );
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.subscriptions.push(Subscription {
+            id,
+            start: usize::from(first) - 1,
+            end: usize::from(last),
+            debounce,
+            pending: None,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// Call back when a channel crosses the threshold, once it has been steady for the
+    /// debounce time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is outside 1 to 512.
+    pub fn on_crossing<F>(
+        &mut self,
+        channel: u16,
+        threshold: u8,
+        debounce: Duration,
+        mut callback: F,
+    ) -> SubscriptionId
+    where
+        F: FnMut(Crossing) + Send + 'static,
+    {
+        self.on_change(channel..=channel, debounce, move |change| {
+            let (previous, level) = (change.previous[0], change.levels[0]);
+            if previous < threshold && level >= threshold {
+                callback(Crossing::Up);
+            } else if previous >= threshold && level < threshold {
+                callback(Crossing::Down);
+            }
+        })
+    }
+
+    /// Stop calling back for a subscription.  Return false if it had already been removed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let count = self.subscriptions.len();
+        self.subscriptions.retain(|s| s.id != id);
+        self.subscriptions.len() != count
+    }
+
+    /// Look for changes in a received frame, calling back for those to report.
+    pub fn update(&mut self, frame: &ReceivedFrame) {
+        self.update_at(frame, Instant::now());
+    }
+
+    fn update_at(&mut self, frame: &ReceivedFrame, now: Instant) {
+        if frame.start_code != 0 {
+            return;
+        }
+        mem::swap(&mut self.levels, &mut self.previous);
+        let first = self.levels.is_empty() && self.previous.is_empty();
+        self.levels.clear();
+        self.levels
+            .extend_from_slice(&frame.data[..min(frame.data.len(), UNIVERSE_SIZE)]);
+        self.levels.resize(UNIVERSE_SIZE, 0);
+        if first {
+            return;
+        }
+        for subscription in &mut self.subscriptions {
+            let range = subscription.start..subscription.end;
+            let (before, after) = (&self.previous[range.clone()], &self.levels[range]);
+            if before != after {
+                if subscription.debounce.is_zero() {
+                    let change = ChannelChange {
+                        first_channel: subscription.start as u16 + 1,
+                        previous: before,
+                        levels: after,
+                    };
+                    (subscription.callback)(&change);
+                    continue;
+                }
+                let from = match subscription.pending.take() {
+                    Some((from, _)) => from,
+                    None => before.to_vec(),
+                };
+                subscription.pending = Some((from, now));
+            } else if let Some((_, changed)) = &subscription.pending {
+                if now.duration_since(*changed) < subscription.debounce {
+                    continue;
+                }
+                let (from, _) = subscription.pending.take().expect("checked above");
+                if from != after {
+                    let change = ChannelChange {
+                        first_channel: subscription.start as u16 + 1,
+                        previous: &from,
+                        levels: after,
+                    };
+                    (subscription.callback)(&change);
+                }
+            }
+        }
+    }
+
+    /// Read frames from the port, which must be open, and look for changes in each, until
+    /// reading fails.
+    pub fn run(&mut self, port: &mut dyn DmxInputPort) -> Result<(), Error> {
+        loop {
+            let frame = port.read_frame()?;
+            self.update(&frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn frame(data: &[u8]) -> ReceivedFrame {
+        ReceivedFrame {
+            start_code: 0,
+            data: data.to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_on_change() {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let mut watcher = ChannelWatcher::new();
+        let recorded = changes.clone();
+        let id = watcher.on_change(2..=3, Duration::ZERO, move |change| {
+            recorded.lock().unwrap().push((
+                change.first_channel,
+                change.previous.to_vec(),
+                change.levels.to_vec(),
+            ));
+        });
+        watcher.update(&frame(&[0, 5, 6]));
+        watcher.update(&frame(&[9, 5, 6]));
+        watcher.update(&frame(&[9, 5, 7]));
+        watcher.update(&ReceivedFrame {
+            start_code: 0xCC,
+            data: vec![0; 3],
+        });
+        assert_eq!(*changes.lock().unwrap(), vec![(2, vec![5, 6], vec![5, 7])]);
+        assert!(watcher.unsubscribe(id));
+        assert!(!watcher.unsubscribe(id));
+        watcher.update(&frame(&[]));
+        assert_eq!(changes.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_debounce() {
+        let crossings = Arc::new(Mutex::new(Vec::new()));
+        let mut watcher = ChannelWatcher::new();
+        let recorded = crossings.clone();
+        watcher.on_crossing(1, 128, Duration::from_millis(100), move |crossing| {
+            recorded.lock().unwrap().push(crossing);
+        });
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        watcher.update_at(&frame(&[0]), at(0));
+        watcher.update_at(&frame(&[200]), at(10));
+        watcher.update_at(&frame(&[20]), at(20));
+        watcher.update_at(&frame(&[20]), at(200));
+        assert!(crossings.lock().unwrap().is_empty());
+        watcher.update_at(&frame(&[130]), at(210));
+        watcher.update_at(&frame(&[130]), at(250));
+        assert!(crossings.lock().unwrap().is_empty());
+        watcher.update_at(&frame(&[130]), at(310));
+        watcher.update_at(&frame(&[0]), at(320));
+        watcher.update_at(&frame(&[0]), at(420));
+        assert_eq!(
+            *crossings.lock().unwrap(),
+            vec![Crossing::Up, Crossing::Down]
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Writing frames at a steady rate.
+
+use std::ops::ControlFlow;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::{DmxFrame, DmxPort, Error};
+
+/// How close to a tick to stop sleeping and spin instead, since sleeps overshoot by up to a
+/// scheduler quantum.
+const SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+/// Ticks at a fixed rate, asking a callback for each frame and writing it to a port.
+///
+/// Ticks are scheduled from when the clock started rather than from when the last one
+/// finished, so time spent rendering and writing doesn't accumulate as drift, and the last
+/// moment before each tick is spun rather than slept for precision.  When a tick is missed
+/// altogether, because rendering took longer than a tick, the clock skips to the next one
+/// rather than bursting frames to catch up.
+pub struct OutputClock {
+    interval: Duration,
+    /// When the first tick was due.
+    start: Option<Instant>,
+    /// The number of the next tick, counted from the start.
+    next: u32,
+    missed: u64,
+}
+
+impl OutputClock {
+    /// Create a clock ticking once every interval; 25 ms gives the 40 Hz most fixtures expect.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the interval is zero.
+    pub fn new(interval: Duration) -> Self {
+        assert!(
+            !interval.is_zero(),
+            "output clock interval must not be zero"
+        );
+        Self {
+            interval,
+            start: None,
+            next: 0,
+            missed: 0,
+        }
+    }
+
+    /// Create a clock ticking the provided number of times a second.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rate isn't a positive number.
+    pub fn with_rate(frames_per_second: f64) -> Self {
+        assert!(
+            frames_per_second > 0.0 && frames_per_second.is_finite(),
+            "output clock rate must be positive"
+        );
+        Self::new(Duration::from_secs_f64(1.0 / frames_per_second))
+    }
+
+    /// Return the time between ticks.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Return how many ticks were skipped because the last one ran over.
+    pub fn missed_ticks(&self) -> u64 {
+        self.missed
+    }
+
+    /// Start counting ticks again from now, after the clock has been stopped for a while.
+    pub fn reset(&mut self) {
+        self.start = None;
+        self.next = 0;
+    }
+
+    /// Block until the next tick is due, returning when it was due.
+    pub fn wait(&mut self) -> Instant {
+        let deadline = self.schedule(Instant::now());
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return deadline;
+            }
+            let remaining = deadline - now;
+            if remaining > SPIN_MARGIN {
+                sleep(remaining - SPIN_MARGIN);
+            } else {
+                std::hint::spin_loop();
+            }
+        }
+    }
+
+    /// Return when the next tick is due, skipping any that have already passed.
+    fn schedule(&mut self, now: Instant) -> Instant {
+        let start = *self.start.get_or_insert(now);
+        let mut deadline = start + self.interval * self.next;
+        if now > deadline + self.interval {
+            let behind = (now - deadline).as_nanos() / self.interval.as_nanos();
+            self.missed += behind as u64;
+            self.next += behind as u32;
+            deadline = start + self.interval * self.next;
+        }
+        self.next += 1;
+        // Start over rather than overflow the tick count, after a few years at most rates.
+        if self.next == u32::MAX {
+            self.start = Some(deadline);
+            self.next = 1;
+        }
+        deadline
+    }
+
+    /// Write a frame to the port on every tick until the callback breaks.  The callback is
+    /// passed the frame written on the last tick to update.  A failed write stops the clock
+    /// and returns the error.
+    pub fn run<F>(&mut self, port: &mut dyn DmxPort, mut render: F) -> Result<(), Error>
+    where
+        F: FnMut(&mut DmxFrame) -> ControlFlow<()>,
+    {
+        let mut frame = DmxFrame::new();
+        loop {
+            self.wait();
+            if render(&mut frame).is_break() {
+                return Ok(());
+            }
+            port.write(&frame)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+
+    #[test]
+    fn test_schedule() {
+        let interval = Duration::from_millis(10);
+        let mut clock = OutputClock::new(interval);
+        let start = Instant::now();
+        assert_eq!(clock.schedule(start), start);
+        // A late tick is still due when it was scheduled.
+        assert_eq!(clock.schedule(start + interval * 2), start + interval);
+        assert_eq!(clock.missed_ticks(), 0);
+        assert_eq!(clock.schedule(start + interval * 5), start + interval * 5);
+        assert_eq!(clock.missed_ticks(), 3);
+        assert_eq!(clock.schedule(start + interval * 5), start + interval * 6);
+    }
+
+    #[test]
+    fn test_run() {
+        let mut port = MockDmxPort::new();
+        port.open().unwrap();
+        let mut clock = OutputClock::new(Duration::from_millis(1));
+        clock
+            .run(&mut port, |frame| {
+                if frame[0] == 3 {
+                    return ControlFlow::Break(());
+                }
+                frame[0] += 1;
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(port.write_count(), 3);
+    }
+}
@@ -0,0 +1,260 @@
+//! Showing what an application writes in the terminal.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+use crate::{
+    DmxFrame, DmxPort, Error, PortKind, PortStats, PortStatus, StatsCounter, StatusTracker,
+    UNIVERSE_SIZE,
+};
+
+/// How many channels a console port shows by default.
+const DEFAULT_CHANNELS: usize = 16;
+
+/// How often a console port redraws by default.
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How many characters wide a full level bar is.
+const BAR_WIDTH: usize = 32;
+
+/// How many levels a line of hex shows.
+const HEX_PER_LINE: usize = 16;
+
+/// How a `ConsoleDmxPort` draws channel levels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ConsoleStyle {
+    /// One line per channel, with its level as a number and a bar.
+    #[default]
+    Bars,
+    /// Sixteen levels per line in hex, like a hex dump.
+    Hex,
+}
+
+/// A port that draws the first channels of each frame written to it on standard output, for
+/// demos and for seeing what an application actually sends.  Each frame is drawn over the
+/// one before, using ANSI escape codes, and frames written less than the interval after the
+/// last one drawn are skipped so the terminal keeps up.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConsoleDmxPort {
+    channels: usize,
+    #[serde(default)]
+    style: ConsoleStyle,
+    interval: Duration,
+    #[serde(skip)]
+    last_drawn: Option<Instant>,
+    /// How many lines the last drawing took, to move back over them.
+    #[serde(skip)]
+    lines_drawn: usize,
+    #[serde(skip)]
+    open: bool,
+    #[serde(skip)]
+    stats: StatsCounter,
+    #[serde(skip)]
+    status: StatusTracker,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+}
+
+impl Default for ConsoleDmxPort {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ConsoleDmxPort {
+    /// Create a port showing the first 16 channels as bars, ten times a second.
+    pub fn new() -> Self {
+        Self {
+            channels: DEFAULT_CHANNELS,
+            style: ConsoleStyle::default(),
+            interval: DEFAULT_INTERVAL,
+            last_drawn: None,
+            lines_drawn: 0,
+            open: false,
+            stats: StatsCounter::new(),
+            status: StatusTracker::new(),
+            last_frame: None,
+        }
+    }
+
+    /// Show the provided number of channels, up to a whole universe.
+    pub fn with_channels(mut self, channels: usize) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Draw channel levels in the provided style.
+    pub fn with_style(mut self, style: ConsoleStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Redraw at most once per interval.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Return how many channels are shown.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Return how channel levels are drawn.
+    pub fn style(&self) -> ConsoleStyle {
+        self.style
+    }
+
+    /// Return the shortest time between redraws.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    fn draw(&mut self, frame: &[u8]) -> io::Result<()> {
+        let text = render(frame, self.channels, self.style);
+        let mut out = io::stdout().lock();
+        if self.lines_drawn > 0 {
+            // Move to the start of the first line drawn last time and clear to the end.
+            write!(out, "\x1b[{}F\x1b[J", self.lines_drawn)?;
+        }
+        out.write_all(text.as_bytes())?;
+        out.flush()?;
+        self.lines_drawn = text.lines().count();
+        Ok(())
+    }
+}
+
+/// Render the first channels of a frame as text, one line per channel or line of hex.
+/// Channels past the end of the frame are shown at zero.
+fn render(frame: &[u8], channels: usize, style: ConsoleStyle) -> String {
+    let levels = (0..channels.min(UNIVERSE_SIZE))
+        .map(|i| frame.get(i).copied().unwrap_or(0))
+        .collect::<Vec<_>>();
+    let mut text = String::new();
+    match style {
+        ConsoleStyle::Bars => {
+            for (i, level) in levels.iter().enumerate() {
+                let width = *level as usize * BAR_WIDTH / 255;
+                let _ = writeln!(
+                    text,
+                    "{:>3} {:>3} {}{}",
+                    i + 1,
+                    level,
+                    "#".repeat(width),
+                    ".".repeat(BAR_WIDTH - width)
+                );
+            }
+        }
+        ConsoleStyle::Hex => {
+            for (line, chunk) in levels.chunks(HEX_PER_LINE).enumerate() {
+                let _ = write!(text, "{:>3}:", line * HEX_PER_LINE + 1);
+                for level in chunk {
+                    let _ = write!(text, " {:02x}", level);
+                }
+                text.push('\n');
+            }
+        }
+    }
+    text
+}
+
+#[typetag::serde]
+impl DmxPort for ConsoleDmxPort {
+    fn name(&self) -> &str {
+        "console"
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        self.open = true;
+        self.status.clear();
+        Ok(())
+    }
+
+    /// Leave the last drawing in place, and start drawing below it if written to again.
+    fn close(&mut self) {
+        self.open = false;
+        self.status.clear();
+        self.last_drawn = None;
+        self.lines_drawn = 0;
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        if !self.open {
+            return self.status.record(Err(Error::NotOpen));
+        }
+        self.last_frame = Some(DmxFrame::from(frame));
+        let due = self
+            .last_drawn
+            .map_or(true, |drawn| drawn.elapsed() >= self.interval);
+        let result = match due {
+            true => {
+                self.last_drawn = Some(Instant::now());
+                self.draw(frame).map_err(Error::from)
+            }
+            false => Ok(()),
+        };
+        let result = self.stats.record(frame, result);
+        self.status.record(result)
+    }
+
+    fn kind(&self) -> PortKind {
+        PortKind::Offline
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        Some(self.stats.stats())
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(self.open)
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+}
+
+impl fmt::Display for ConsoleDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "console")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render(&[255, 0, 128], 3, ConsoleStyle::Bars),
+            format!(
+                "  1 255 {}\n  2   0 {}\n  3 128 {}{}\n",
+                "#".repeat(32),
+                ".".repeat(32),
+                "#".repeat(16),
+                ".".repeat(16)
+            )
+        );
+        let frame: Vec<u8> = (0..20).collect();
+        assert_eq!(
+            render(&frame, 18, ConsoleStyle::Hex),
+            "  1: 00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f\n 17: 10 11\n"
+        );
+        assert_eq!(render(&[7], 2, ConsoleStyle::Hex), "  1: 07 00\n");
+        assert_eq!(render(&[], 600, ConsoleStyle::Hex).lines().count(), 32);
+    }
+
+    #[test]
+    fn test_status() {
+        let mut port = ConsoleDmxPort::new();
+        assert!(!port.is_open());
+        assert!(matches!(port.write(&[1]), Err(Error::NotOpen)));
+        port.open().unwrap();
+        assert!(port.is_open());
+        port.close();
+        assert_eq!(port.status(), PortStatus::Closed);
+    }
+}
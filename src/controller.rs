@@ -0,0 +1,321 @@
+//! Driving several universes at once.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{ArtNetSyncGroup, DmxFrame, DmxPort, Error, SacnSyncGroup};
+
+/// A set of ports, each driving a numbered universe.  Frames are buffered by `set_universe`
+/// and only universes that changed are written by `flush`, so an application can update its
+/// universes at whatever pace it likes and flush once per render.
+///
+/// A grandmaster scales every channel of every universe as it is written, and a blackout
+/// zeroes them, without touching the frames the application set, so front-of-house controls
+/// can be layered over any application.
+///
+/// Universes are written back to back.  Where they go out over Art-Net or sACN, give the
+/// controller the sync group their ports belong to, and each flush ends with an ArtSync or
+/// sACN sync packet so receivers update every universe at once.
+///
+/// A controller serializes as a rig configuration: the ports and their universe numbers, but
+/// not the buffered frames, the grandmaster or the sync groups.  Ports need to be opened
+/// again with `open` after deserialization.
+#[derive(Serialize, Deserialize)]
+pub struct DmxController {
+    outputs: BTreeMap<u16, Output>,
+    #[serde(skip, default = "full")]
+    grandmaster: f64,
+    #[serde(skip)]
+    blackout: bool,
+    /// Scratch space for frames scaled by the grandmaster.
+    #[serde(skip)]
+    scaled: DmxFrame,
+    #[serde(skip)]
+    artnet_sync: Option<ArtNetSyncGroup>,
+    #[serde(skip)]
+    sacn_sync: Option<SacnSyncGroup>,
+}
+
+fn full() -> f64 {
+    1.0
+}
+
+impl Default for DmxController {
+    fn default() -> Self {
+        Self {
+            outputs: BTreeMap::new(),
+            grandmaster: full(),
+            blackout: false,
+            scaled: DmxFrame::new(),
+            artnet_sync: None,
+            sacn_sync: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Output {
+    port: Box<dyn DmxPort>,
+    #[serde(skip)]
+    frame: DmxFrame,
+    #[serde(skip)]
+    dirty: bool,
+}
+
+impl DmxController {
+    /// Create a controller with no ports.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Flush the provided Art-Net sync group after writing universes, sending the packets
+    /// its ports held followed by an ArtSync.
+    pub fn with_artnet_sync(mut self, group: ArtNetSyncGroup) -> Self {
+        self.artnet_sync = Some(group);
+        self
+    }
+
+    /// Send a sync packet for the provided sACN sync group after writing universes.
+    pub fn with_sacn_sync(mut self, group: SacnSyncGroup) -> Self {
+        self.sacn_sync = Some(group);
+        self
+    }
+
+    /// Drive a universe with the provided port, returning the port that drove it before.
+    /// The universe starts out blacked out, and is written on the next flush.
+    pub fn add_port(&mut self, universe: u16, port: Box<dyn DmxPort>) -> Option<Box<dyn DmxPort>> {
+        let output = Output {
+            port,
+            frame: DmxFrame::new(),
+            dirty: true,
+        };
+        self.outputs
+            .insert(universe, output)
+            .map(|output| output.port)
+    }
+
+    /// Stop driving a universe, returning its port.
+    pub fn remove_port(&mut self, universe: u16) -> Option<Box<dyn DmxPort>> {
+        self.outputs.remove(&universe).map(|output| output.port)
+    }
+
+    /// Return the port driving a universe.
+    pub fn port(&self, universe: u16) -> Option<&dyn DmxPort> {
+        self.outputs
+            .get(&universe)
+            .map(|output| output.port.as_ref())
+    }
+
+    /// Return the port driving a universe.
+    pub fn port_mut(&mut self, universe: u16) -> Option<&mut (dyn DmxPort + 'static)> {
+        self.outputs
+            .get_mut(&universe)
+            .map(|output| output.port.as_mut())
+    }
+
+    /// Iterate over the numbers of the universes being driven, in order.
+    pub fn universes(&self) -> impl Iterator<Item = u16> + '_ {
+        self.outputs.keys().copied()
+    }
+
+    /// Return the frame that is, or will be on the next flush, sent to a universe.
+    pub fn frame(&self, universe: u16) -> Option<&DmxFrame> {
+        self.outputs.get(&universe).map(|output| &output.frame)
+    }
+
+    /// Replace the frame for a universe.  Frames shorter than a universe leave the remaining
+    /// channels at zero.  The frame is written on the next flush.
+    pub fn set_universe(&mut self, universe: u16, frame: &[u8]) -> Result<(), Error> {
+        let output = self
+            .outputs
+            .get_mut(&universe)
+            .ok_or(Error::InvalidUniverse(universe))?;
+        let frame = DmxFrame::from(frame);
+        if output.frame != frame {
+            output.frame = frame;
+            output.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Set the grandmaster, from 0.0 for nothing to 1.0 for full.  Levels outside that range
+    /// are clamped.  Every universe is written again on the next flush.
+    pub fn set_grandmaster(&mut self, level: f64) {
+        let level = if level.is_nan() {
+            0.0
+        } else {
+            level.clamp(0.0, 1.0)
+        };
+        if level != self.grandmaster {
+            self.grandmaster = level;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// Return the grandmaster, from 0.0 to 1.0.
+    pub fn grandmaster(&self) -> f64 {
+        self.grandmaster
+    }
+
+    /// Send every channel to zero from the next flush on, until `restore` is called.  The
+    /// frames set by the application are kept.
+    pub fn blackout(&mut self) {
+        if !self.blackout {
+            self.blackout = true;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// End a blackout, sending the application's frames again from the next flush on.
+    pub fn restore(&mut self) {
+        if self.blackout {
+            self.blackout = false;
+            self.mark_all_dirty();
+        }
+    }
+
+    /// Return true during a blackout.
+    pub fn is_blacked_out(&self) -> bool {
+        self.blackout
+    }
+
+    fn mark_all_dirty(&mut self) {
+        for output in self.outputs.values_mut() {
+            output.dirty = true;
+        }
+    }
+
+    /// Open every port.  Every universe is written again on the next flush.
+    pub fn open(&mut self) -> Result<(), Error> {
+        for output in self.outputs.values_mut() {
+            output.port.open()?;
+            output.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Close every port.
+    pub fn close(&mut self) {
+        for output in self.outputs.values_mut() {
+            output.port.close();
+        }
+    }
+
+    /// Replace the frames of several universes and write them straight away, back to back,
+    /// along with any others that changed since the last flush.  Nothing is written if any of
+    /// the universes isn't driven by the controller.
+    pub fn write_many(&mut self, frames: &[(u16, &[u8])]) -> Result<(), Error> {
+        if let Some((universe, _)) = frames
+            .iter()
+            .find(|(universe, _)| !self.outputs.contains_key(universe))
+        {
+            return Err(Error::InvalidUniverse(*universe));
+        }
+        for (universe, frame) in frames {
+            let output = self.outputs.get_mut(universe).expect("checked above");
+            output.frame = DmxFrame::from(*frame);
+            output.dirty = true;
+        }
+        self.flush()
+    }
+
+    /// Write every universe that changed since the last flush, scaled by the grandmaster or
+    /// blacked out, then flush the sync groups if any universe was written.  A failing port
+    /// doesn't stop the others being written; the first error is returned, and the universes
+    /// that failed are tried again on the next flush.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let level = if self.blackout { 0.0 } else { self.grandmaster };
+        let mut result = Ok(());
+        let mut written = false;
+        for output in self.outputs.values_mut().filter(|output| output.dirty) {
+            written = true;
+            let frame = if level == 1.0 {
+                &output.frame
+            } else {
+                for (scaled, &value) in self.scaled.iter_mut().zip(output.frame.iter()) {
+                    *scaled = (f64::from(value) * level).round() as u8;
+                }
+                &self.scaled
+            };
+            match output.port.write(frame) {
+                Ok(()) => output.dirty = false,
+                Err(e) => {
+                    if result.is_ok() {
+                        result = Err(e);
+                    }
+                }
+            }
+        }
+        if written {
+            if let Some(group) = &self.artnet_sync {
+                result = result.and(group.flush());
+            }
+            if let Some(group) = &mut self.sacn_sync {
+                result = result.and(group.flush_sync());
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{MockDmxPort, OfflineDmxPort};
+
+    #[test]
+    fn test_controller() {
+        let mut controller = DmxController::new();
+        controller.add_port(2, Box::new(OfflineDmxPort::new()));
+        controller.add_port(1, Box::new(OfflineDmxPort::new()));
+        controller.open().unwrap();
+        controller.set_universe(2, &[1, 2, 3]).unwrap();
+        assert!(matches!(
+            controller.set_universe(3, &[1]),
+            Err(Error::InvalidUniverse(3))
+        ));
+        controller.flush().unwrap();
+        assert_eq!(controller.universes().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(controller.frame(2).unwrap().get(3), Some(3));
+    }
+
+    #[test]
+    fn test_write_many() {
+        let (one, two) = (MockDmxPort::new(), MockDmxPort::new());
+        let mut controller = DmxController::new();
+        controller.add_port(1, Box::new(one.clone()));
+        controller.add_port(2, Box::new(two.clone()));
+        controller.open().unwrap();
+        controller.flush().unwrap();
+        assert!(matches!(
+            controller.write_many(&[(1, &[9]), (3, &[9])]),
+            Err(Error::InvalidUniverse(3))
+        ));
+        assert_eq!(one.write_count(), 1);
+        controller.write_many(&[(1, &[1, 2]), (2, &[3])]).unwrap();
+        assert_eq!(&one.last_frame().unwrap()[0..2], &[1, 2]);
+        assert_eq!(two.last_frame().unwrap()[0], 3);
+        controller.write_many(&[(2, &[3])]).unwrap();
+        assert_eq!((one.write_count(), two.write_count()), (2, 3));
+    }
+
+    #[test]
+    fn test_grandmaster() {
+        let mock = MockDmxPort::new();
+        let mut controller = DmxController::new();
+        controller.add_port(1, Box::new(mock.clone()));
+        controller.open().unwrap();
+        controller.set_universe(1, &[200, 100]).unwrap();
+        controller.set_grandmaster(0.5);
+        controller.flush().unwrap();
+        assert_eq!(&mock.last_frame().unwrap()[0..3], &[100, 50, 0]);
+        controller.blackout();
+        controller.flush().unwrap();
+        assert_eq!(&mock.last_frame().unwrap()[0..2], &[0, 0]);
+        controller.restore();
+        controller.set_grandmaster(2.0);
+        controller.flush().unwrap();
+        assert_eq!(&mock.last_frame().unwrap()[0..2], &[200, 100]);
+        assert_eq!(controller.frame(1).unwrap().get(1), Some(200));
+    }
+}
@@ -0,0 +1,271 @@
+//! Correcting the response of dimmers before levels reach a port.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use crate::{
+    DmxFrame, DmxPort, Error, HardwareInfo, PortKind, PortStats, PortStatus, UNIVERSE_SIZE,
+};
+
+/// A transfer curve, mapping the level written for a channel to the level output.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Curve {
+    /// Output levels unchanged.
+    #[default]
+    Linear,
+    /// Raise levels, as a fraction of full, to the power, so a gamma of 2.2 makes an LED
+    /// dim the way an incandescent lamp does to the eye.
+    Gamma(f32),
+    /// Output 255 minus the level, for fixtures that dim the wrong way.
+    Inverted,
+    /// Ease in and out of the ends of the range, for a smoother start and end to fades.
+    SCurve,
+    /// Look up the output of each level in a table, indexed by level.  Levels past the end of
+    /// a table shorter than 256 entries are output unchanged.
+    Table(Vec<u8>),
+}
+
+impl Curve {
+    /// Return the level output for the level written.
+    pub fn apply(&self, level: u8) -> u8 {
+        let fraction = f32::from(level) / 255.0;
+        let scaled = |fraction: f32| (fraction.clamp(0.0, 1.0) * 255.0).round() as u8;
+        match self {
+            Curve::Linear => level,
+            Curve::Gamma(gamma) => scaled(fraction.powf(*gamma)),
+            Curve::Inverted => 255 - level,
+            Curve::SCurve => scaled(fraction * fraction * (3.0 - 2.0 * fraction)),
+            Curve::Table(table) => table.get(usize::from(level)).copied().unwrap_or(level),
+        }
+    }
+
+    /// Return the output of every level, indexed by level.
+    fn table(&self) -> [u8; 256] {
+        let mut table = [0; 256];
+        for (level, out) in (0..=255).zip(table.iter_mut()) {
+            *out = self.apply(level);
+        }
+        table
+    }
+}
+
+/// A curve applied to consecutive channels, numbered from 1.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChannelCurve {
+    pub first_channel: u16,
+    pub last_channel: u16,
+    pub curve: Curve,
+}
+
+/// A port that passes each channel's level through a transfer curve before writing to an inner
+/// port, so dimming curves of LED fixtures can be corrected at the output without changing the
+/// application.  Channels without a curve of their own use the port's default curve.  Where
+/// the channels of curves overlap, the curve added last applies.
+///
+/// The curves are serialized with the port, so corrections can be saved with a rig.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CurvePort {
+    port: Box<dyn DmxPort>,
+    #[serde(default)]
+    default: Curve,
+    #[serde(default)]
+    curves: Vec<ChannelCurve>,
+    /// The output of every level for each channel, worked out from the curves when first
+    /// needed.
+    #[serde(skip)]
+    tables: Vec<[u8; 256]>,
+    #[serde(skip)]
+    buf: Vec<u8>,
+    /// The last frame written, before the curves, which `write_range` updates.
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+}
+
+impl CurvePort {
+    /// Wrap a port, passing every channel through the default curve.
+    pub fn new(port: Box<dyn DmxPort>, default: Curve) -> Self {
+        Self {
+            port,
+            default,
+            curves: Vec::new(),
+            tables: Vec::new(),
+            buf: Vec::new(),
+            last_frame: None,
+        }
+    }
+
+    /// Pass the channels, numbered from 1, through their own curve.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty or reaches outside 1 to 512.
+    pub fn with_curve(mut self, channels: RangeInclusive<u16>, curve: Curve) -> Self {
+        self.set_curve(channels, curve);
+        self
+    }
+
+    /// Pass the channels, numbered from 1, through their own curve, replacing the curve of
+    /// any of them that had one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty or reaches outside 1 to 512.
+    pub fn set_curve(&mut self, channels: RangeInclusive<u16>, curve: Curve) {
+        let (first_channel, last_channel) = channels.into_inner();
+        assert!(
+            1 <= first_channel
+                && first_channel <= last_channel
+                && usize::from(last_channel) <= UNIVERSE_SIZE,
+            "channels {}..={} are not a range within 1 to 512",
+            first_channel,
+            last_channel
+        );
+        self.curves.push(ChannelCurve {
+            first_channel,
+            last_channel,
+            curve,
+        });
+        self.tables.clear();
+    }
+
+    /// Return the curve of channels without one of their own.
+    pub fn default_curve(&self) -> &Curve {
+        &self.default
+    }
+
+    /// Return the curves of ranges of channels, in the order they were added.
+    pub fn curves(&self) -> &[ChannelCurve] {
+        &self.curves
+    }
+
+    /// Return the wrapped port.
+    pub fn into_inner(self) -> Box<dyn DmxPort> {
+        self.port
+    }
+
+    fn build_tables(&mut self) {
+        let default = self.default.table();
+        self.tables = vec![default; UNIVERSE_SIZE];
+        for curve in &self.curves {
+            let table = curve.curve.table();
+            let first = usize::from(curve.first_channel).max(1) - 1;
+            let last = min(usize::from(curve.last_channel), UNIVERSE_SIZE);
+            for channel in self.tables.iter_mut().take(last).skip(first) {
+                *channel = table;
+            }
+        }
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for CurvePort {
+    fn name(&self) -> &str {
+        self.port.name()
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        self.port.open()
+    }
+
+    fn close(&mut self) {
+        self.port.close()
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        if self.tables.is_empty() {
+            self.build_tables();
+        }
+        self.buf.clear();
+        self.buf.extend(
+            frame
+                .iter()
+                .zip(&self.tables)
+                .map(|(&level, table)| table[usize::from(level)]),
+        );
+        self.port.write(&self.buf)?;
+        self.last_frame = Some(DmxFrame::from(frame));
+        Ok(())
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        match start_code {
+            0 => self.write(data),
+            _ => self.port.write_with_start_code(start_code, data),
+        }
+    }
+
+    fn kind(&self) -> PortKind {
+        self.port.kind()
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        self.port.hardware()
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        self.port.stats()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.port.status()
+    }
+
+    fn health_check(&mut self) -> Result<(), Error> {
+        self.port.health_check()
+    }
+
+    /// The last frame written, before the curves; the inner port's is curved already.
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+}
+
+impl fmt::Display for CurvePort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (curved)", self.port)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+
+    #[test]
+    fn test_curves() {
+        assert_eq!(Curve::Gamma(2.0).apply(128), 64);
+        assert_eq!(Curve::Gamma(2.2).apply(255), 255);
+        assert_eq!(Curve::Inverted.apply(10), 245);
+        assert_eq!(Curve::SCurve.apply(0), 0);
+        assert_eq!(Curve::SCurve.apply(64), 40);
+        assert_eq!(Curve::SCurve.apply(255), 255);
+        assert_eq!(Curve::Table(vec![5, 6]).apply(1), 6);
+        assert_eq!(Curve::Table(vec![5, 6]).apply(2), 2);
+    }
+
+    #[test]
+    fn test_write() {
+        let mock = MockDmxPort::new();
+        let mut port = CurvePort::new(Box::new(mock.clone()), Curve::Inverted)
+            .with_curve(2..=3, Curve::Linear)
+            .with_curve(3..=3, Curve::Gamma(2.0));
+        port.open().unwrap();
+        port.write(&[0, 128, 128, 255]).unwrap();
+        assert_eq!(mock.last_frame().unwrap(), vec![255, 128, 64, 0]);
+        port.write_with_start_code(0x17, &[0]).unwrap();
+        assert_eq!(mock.start_code_packets(), vec![(0x17, vec![0])]);
+    }
+
+    #[test]
+    fn test_write_range() {
+        let mock = MockDmxPort::new();
+        let mut port = CurvePort::new(Box::new(mock.clone()), Curve::Inverted);
+        port.open().unwrap();
+        port.write_range(1, &[10]).unwrap();
+        port.write_range(2, &[20]).unwrap();
+        assert_eq!(&port.last_frame().unwrap()[0..3], &[10, 20, 0]);
+        assert_eq!(&mock.last_frame().unwrap()[0..3], &[245, 235, 255]);
+    }
+}
@@ -0,0 +1,143 @@
+//! Skipping writes of frames that haven't changed.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::{DmxPort, Error, HardwareInfo, PortKind, PortStats, PortStatus};
+
+/// A port that skips writing a frame identical to the last one sent, cutting the traffic of
+/// mostly static looks.  Repeated frames are still sent once the keep-alive interval has
+/// passed since the last write that reached the port, so receivers that blank on signal loss
+/// keep their output.  Nothing is sent between writes, so the keep-alive only holds while
+/// frames keep being written.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DeduplicatingPort {
+    port: Box<dyn DmxPort>,
+    keep_alive: Duration,
+    #[serde(skip)]
+    last: Option<(Vec<u8>, Instant)>,
+}
+
+impl DeduplicatingPort {
+    /// Wrap a port, resending unchanged frames once per keep-alive interval.
+    pub fn new(port: Box<dyn DmxPort>, keep_alive: Duration) -> Self {
+        Self {
+            port,
+            keep_alive,
+            last: None,
+        }
+    }
+
+    /// Return how often unchanged frames are resent.
+    pub fn keep_alive(&self) -> Duration {
+        self.keep_alive
+    }
+
+    /// Return the wrapped port.
+    pub fn inner(&self) -> &dyn DmxPort {
+        self.port.as_ref()
+    }
+
+    /// Return the wrapped port.
+    pub fn into_inner(self) -> Box<dyn DmxPort> {
+        self.port
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for DeduplicatingPort {
+    fn name(&self) -> &str {
+        self.port.name()
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        self.last = None;
+        self.port.open()
+    }
+
+    fn close(&mut self) {
+        self.last = None;
+        self.port.close()
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        if let Some((last, sent)) = &self.last {
+            if last.as_slice() == frame && sent.elapsed() < self.keep_alive {
+                return Ok(());
+            }
+        }
+        // Forget the last frame until this one is sent, so a failed write is retried.
+        let mut last = self.last.take().map(|(last, _)| last).unwrap_or_default();
+        self.port.write(frame)?;
+        last.clear();
+        last.extend_from_slice(frame);
+        self.last = Some((last, Instant::now()));
+        Ok(())
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        match start_code {
+            0 => self.write(data),
+            _ => self.port.write_with_start_code(start_code, data),
+        }
+    }
+
+    fn kind(&self) -> PortKind {
+        self.port.kind()
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        self.port.hardware()
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        self.port.stats()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.port.status()
+    }
+
+    fn health_check(&mut self) -> Result<(), Error> {
+        self.port.health_check()
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.port.last_frame()
+    }
+}
+
+impl fmt::Display for DeduplicatingPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.port.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+
+    #[test]
+    fn test_dedup() {
+        let mock = MockDmxPort::new();
+        let mut port = DeduplicatingPort::new(Box::new(mock.clone()), Duration::from_secs(3600));
+        port.open().unwrap();
+        port.write(&[1]).unwrap();
+        port.write(&[1]).unwrap();
+        mock.fail_next_write(Error::Timeout);
+        assert!(port.write(&[2]).is_err());
+        port.write(&[2]).unwrap();
+        port.write(&[2]).unwrap();
+        assert_eq!(mock.frames(), vec![vec![1], vec![2]]);
+
+        port.keep_alive = Duration::ZERO;
+        port.write(&[2]).unwrap();
+        assert_eq!(mock.write_count(), 3);
+
+        port.write_with_start_code(0x17, &[2]).unwrap();
+        port.write_with_start_code(0x17, &[2]).unwrap();
+        assert_eq!(mock.start_code_packets().len(), 2);
+    }
+}
@@ -0,0 +1,270 @@
+//! Serial connections to enttec widgets, shared between the ports of multi-port widgets.
+
+use serialport::{new, SerialPort};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::mem;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::protocol::{self, Packet, PacketReader};
+use super::EnttecTimeouts;
+use crate::Error;
+
+/// Reads poll the serial port this often while waiting for a deadline.
+const READ_TIMEOUT: Duration = Duration::from_millis(1);
+
+/// How many messages received while waiting for a reply are kept for `read`.  Output ports
+/// never read, so the oldest are dropped beyond this.
+const MAX_QUEUED_MESSAGES: usize = 64;
+
+/// An open serial connection to a widget.
+pub struct Connection {
+    serial: Box<dyn SerialPort>,
+    reader: PacketReader,
+    /// Messages received while waiting for a reply to a request.
+    queue: VecDeque<Packet>,
+    /// Reused for every DMX frame sent, so sending frames doesn't allocate.
+    frame_message: Vec<u8>,
+    /// How much of the frame message was written, if writing it failed part way.  The widget
+    /// is waiting for the rest, and would read the next message as part of this one.
+    partial: Option<usize>,
+    write_timeout: Duration,
+}
+
+impl Connection {
+    /// Open the named serial port, giving up with `Error::Timeout` if the system takes longer
+    /// than the open timeout.  An open that is given up on is left to finish in the background,
+    /// and the port is closed again if it succeeds.
+    pub fn open(port_name: &str, timeouts: &EnttecTimeouts) -> Result<Self, Error> {
+        let (sender, receiver) = mpsc::channel();
+        let name = port_name.to_string();
+        thread::spawn(move || {
+            // baud rate is not used on FTDI
+            let _ = sender.send(new(&name, 57600).timeout(READ_TIMEOUT).open());
+        });
+        let serial = match receiver.recv_timeout(timeouts.open) {
+            Ok(serial) => serial?,
+            Err(RecvTimeoutError::Timeout) => return Err(Error::Timeout),
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::other("serial port open panicked").into())
+            }
+        };
+        Ok(Self {
+            serial,
+            reader: PacketReader::default(),
+            queue: VecDeque::new(),
+            frame_message: Vec::with_capacity(protocol::MAX_FRAME_MESSAGE_SIZE),
+            partial: None,
+            write_timeout: timeouts.write,
+        })
+    }
+
+    /// Set how long writes may block before failing with `Error::Timeout`.
+    pub fn set_write_timeout(&mut self, timeout: Duration) {
+        self.write_timeout = timeout;
+    }
+
+    /// Run a write with the write timeout in place of the read polling interval.
+    fn with_write_timeout(
+        &mut self,
+        write: impl FnOnce(&mut dyn SerialPort) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.serial.set_timeout(self.write_timeout)?;
+        let result = write(self.serial.as_mut());
+        self.serial.set_timeout(READ_TIMEOUT)?;
+        result.map_err(|e| match e {
+            Error::IO(e) if e.kind() == io::ErrorKind::TimedOut => Error::Timeout,
+            e => e,
+        })
+    }
+
+    /// Send a message to the widget.
+    pub fn write_packet(
+        &mut self,
+        label: u8,
+        payload: &[u8],
+        add_payload_pad_byte: bool,
+    ) -> Result<(), Error> {
+        self.finish_partial()?;
+        self.with_write_timeout(|serial| {
+            protocol::write_packet(label, payload, add_payload_pad_byte, serial)
+        })
+    }
+
+    /// Send a DMX frame to the widget using the provided label.  The message is assembled in
+    /// a reused buffer and sent with a single write: serialport doesn't implement vectored
+    /// writes, so writing the header, frame and footer separately would cost a system call
+    /// each.
+    ///
+    /// If a write fails part way through the message, the rest of it is sent before the next
+    /// message, so the widget doesn't lose track of where messages start.  Retrying a failed
+    /// write sends the frame again after it.
+    pub fn write_frame(&mut self, label: u8, start_code: u8, frame: &[u8]) -> Result<(), Error> {
+        self.finish_partial()?;
+        protocol::encode_frame(label, start_code, frame, &mut self.frame_message);
+        self.write_frame_message(0)
+    }
+
+    /// Send the rest of a frame message that was written part way.
+    fn finish_partial(&mut self) -> Result<(), Error> {
+        match self.partial {
+            Some(written) => self.write_frame_message(written),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the frame message from the provided offset, keeping track of how far it got.
+    fn write_frame_message(&mut self, mut written: usize) -> Result<(), Error> {
+        let message = mem::take(&mut self.frame_message);
+        let result =
+            self.with_write_timeout(|serial| Ok(write_from(serial, &message, &mut written)?));
+        self.partial = match result {
+            Err(_) if written > 0 => Some(written),
+            _ => None,
+        };
+        self.frame_message = message;
+        result
+    }
+
+    /// Read the next message from the widget, giving up at the deadline if one is provided.
+    /// Messages received while waiting for replies to requests come first.
+    pub fn read(&mut self, deadline: Option<Instant>) -> Result<Option<Packet>, Error> {
+        if let Some(packet) = self.queue.pop_front() {
+            return Ok(Some(packet));
+        }
+        self.reader.read(&mut self.serial, deadline)
+    }
+
+    /// Keep a message for `read`.
+    fn enqueue(&mut self, packet: Packet) {
+        if self.queue.len() == MAX_QUEUED_MESSAGES {
+            self.queue.pop_front();
+        }
+        self.queue.push_back(packet);
+    }
+
+    /// Send a message and wait for the widget to reply with one of the provided labels.
+    /// Other messages received in the meantime, such as received DMX, are kept for `read`.
+    pub fn request(
+        &mut self,
+        label: u8,
+        payload: &[u8],
+        reply_labels: &[u8],
+        timeout: Duration,
+    ) -> Result<Option<Packet>, Error> {
+        // Replies received before the request was sent answer an earlier one.
+        self.queue
+            .retain(|packet| !reply_labels.contains(&packet.label));
+        while let Some(packet) = self.reader.parse() {
+            if !reply_labels.contains(&packet.label) {
+                self.enqueue(packet);
+            }
+        }
+        self.write_packet(label, payload, false)?;
+        let deadline = Instant::now() + timeout;
+        while let Some(packet) = self.reader.read(&mut self.serial, Some(deadline))? {
+            if reply_labels.contains(&packet.label) {
+                return Ok(Some(packet));
+            }
+            self.enqueue(packet);
+        }
+        Ok(None)
+    }
+}
+
+/// Write the message from the offset written, advancing it as bytes are written, so a write
+/// that fails part way can be carried on from where it stopped.
+fn write_from(writer: &mut dyn Write, message: &[u8], written: &mut usize) -> io::Result<()> {
+    while *written < message.len() {
+        match writer.write(&message[*written..]) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(count) => *written += count,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// A connection shared by every port open on the same widget.
+pub type SharedConnection = Arc<Mutex<Connection>>;
+
+/// Open connections, keyed by serial port name.
+static CONNECTIONS: Mutex<Vec<(String, Weak<Mutex<Connection>>)>> = Mutex::new(Vec::new());
+
+/// Return the open connection to the named serial port, opening it if no port holds it yet.
+/// The serial port is closed when the last handle is dropped.
+pub fn connect(port_name: &str, timeouts: &EnttecTimeouts) -> Result<SharedConnection, Error> {
+    let mut connections = CONNECTIONS.lock().unwrap_or_else(PoisonError::into_inner);
+    connections.retain(|(_, connection)| connection.strong_count() > 0);
+    if let Some(connection) = connections
+        .iter()
+        .filter(|(name, _)| name == port_name)
+        .find_map(|(_, connection)| connection.upgrade())
+    {
+        return Ok(connection);
+    }
+    let connection = Arc::new(Mutex::new(Connection::open(port_name, timeouts)?));
+    connections.push((port_name.to_string(), Arc::downgrade(&connection)));
+    Ok(connection)
+}
+
+/// Return true if a port holds a connection to the named serial port.
+pub fn is_connected(port_name: &str) -> bool {
+    CONNECTIONS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .any(|(name, connection)| name == port_name && connection.strong_count() > 0)
+}
+
+/// Lock a shared connection.  A panic while holding the lock cannot leave the connection in
+/// an inconsistent state, so poisoning is ignored.
+pub fn lock(connection: &SharedConnection) -> MutexGuard<'_, Connection> {
+    connection.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Accepts a limited number of bytes, then times out.
+    struct Stalling {
+        written: Vec<u8>,
+        room: usize,
+    }
+
+    impl Write for Stalling {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let count = buf.len().min(self.room).min(3);
+            if count == 0 {
+                return Err(io::ErrorKind::TimedOut.into());
+            }
+            self.room -= count;
+            self.written.extend_from_slice(&buf[..count]);
+            Ok(count)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_from() {
+        let message = [0x7E, 6, 2, 0, 0, 1, 0xE7];
+        let mut writer = Stalling {
+            written: Vec::new(),
+            room: 4,
+        };
+        let mut written = 0;
+        assert!(write_from(&mut writer, &message, &mut written).is_err());
+        assert_eq!(written, 4);
+        writer.room = 10;
+        write_from(&mut writer, &message, &mut written).unwrap();
+        assert_eq!(writer.written, message);
+    }
+}
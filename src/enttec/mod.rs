@@ -0,0 +1,1254 @@
+//! Implementation of support for the Enttec USB DMX Pro dongle.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use std::sync::{Mutex, MutexGuard, OnceLock, PoisonError};
+use std::time::Duration;
+
+use crate::alias::write_aliased;
+use crate::rdm::{self, RdmTransport, Uid};
+use crate::trace;
+use crate::{
+    DmxFrame, DmxInputPort, FrameShape, HardwareInfo, InputPortListing, PortKind, PortListing,
+    PortProvider, PortStats, PortStatus, ReceivedFrame, RetryPolicy, StatsCounter, StatusTracker,
+    UNIVERSE_SIZE,
+};
+
+use super::{DmxPort, Error};
+use crate::serial::{hardware_info, relocate, write_device_name, DeviceLinks, SerialPortInfoDef};
+use connection::{connect, is_connected, lock, Connection, SharedConnection};
+use protocol::{Labels, Message, MIN_UNIVERSE_SIZE, PORT1_LABELS, PORT2_LABELS};
+use protocol::{ULTRA_DMX_PORT_A_LABELS, ULTRA_DMX_PORT_B_LABELS};
+use serialport::{available_ports, SerialPortInfo, SerialPortType, UsbPortInfo};
+
+mod connection;
+mod protocol;
+
+// RDM controller settings.
+const ENTTEC_ESTA_ID: u16 = 0x454E;
+const RDM_RESPONSE_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// How long to wait for the widget to answer a query.
+const QUERY_TIMEOUT: Duration = Duration::from_millis(100);
+
+// Status flags in a received DMX packet.
+const RECEIVE_OVERRUN: u8 = 0b10;
+
+/// Finds the Enttec-compatible widgets attached to this system.  Genuine widgets are
+/// recognized by the strings their USB interface reports; clones that report something else
+/// can be picked up by adding their USB vendor and product IDs, such as a DMXking widget's
+/// FTDI IDs:
+///
+/// ```no_run
+/// # use rust_dmx::EnttecScan;
+/// let ports = EnttecScan::new().with_usb_id(0x0403, 0x6001).output_ports()?;
+/// # Ok::<(), rust_dmx::Error>(())
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EnttecScan {
+    usb_ids: Vec<(u16, u16)>,
+}
+
+impl EnttecScan {
+    /// Look for genuine widgets only.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also recognize USB serial devices with the provided vendor and product IDs as widgets.
+    pub fn with_usb_id(mut self, vid: u16, pid: u16) -> Self {
+        if !self.usb_ids.contains(&(vid, pid)) {
+            self.usb_ids.push((vid, pid));
+        }
+        self
+    }
+
+    /// Return the extra USB vendor and product IDs recognized as widgets.
+    pub fn usb_ids(&self) -> &[(u16, u16)] {
+        &self.usb_ids
+    }
+
+    /// Return the output ports of the widgets found, listing widgets with several outputs
+    /// once for each of them.  Telling how many outputs a widget has means asking it, which
+    /// is only done the first time the widget is found.
+    pub fn output_ports(&self) -> Result<PortListing, Error> {
+        let mut ports: PortListing = Vec::new();
+        let infos = self.serial_ports()?;
+        forget_detached(&infos);
+        for info in infos {
+            let (kind, identity) = identified(&info);
+            for output in kind.outputs() {
+                let mut port = EnttecDmxPort::with_output(info.clone(), *output);
+                port.identity = identity;
+                ports.push(Box::new(port));
+            }
+        }
+        Ok(ports)
+    }
+
+    /// Return the input ports of the widgets found.
+    pub fn input_ports(&self) -> Result<InputPortListing, Error> {
+        Ok(self
+            .serial_ports()?
+            .into_iter()
+            .map(|info| Box::new(EnttecInputPort::new(info)) as Box<dyn DmxInputPort>)
+            .collect())
+    }
+
+    /// Return the serial ports of the widgets found.
+    fn serial_ports(&self) -> Result<Vec<SerialPortInfo>, Error> {
+        Ok(available_ports()?
+            .into_iter()
+            .filter(|info| match &info.port_type {
+                SerialPortType::UsbPort(usb) => self.matches(usb),
+                _ => false,
+            })
+            .collect())
+    }
+
+    fn matches(&self, usb: &UsbPortInfo) -> bool {
+        is_enttec(usb) || self.usb_ids.contains(&(usb.vid, usb.pid))
+    }
+
+    /// Return a scan that will find the widget attached as described by the provided port
+    /// info again, even if it is a clone only recognized by its USB IDs.
+    fn finding(info: &SerialPortInfo) -> Self {
+        match &info.port_type {
+            SerialPortType::UsbPort(usb) => Self::new().with_usb_id(usb.vid, usb.pid),
+            _ => Self::new(),
+        }
+    }
+}
+
+impl PortProvider for EnttecScan {
+    fn name(&self) -> &str {
+        "enttec"
+    }
+
+    /// Return the output ports of the widgets found, listing widgets with several outputs
+    /// once for each of them.
+    fn available_ports(&self) -> Result<PortListing, Error> {
+        self.output_ports()
+    }
+
+    fn kinds(&self) -> &[PortKind] {
+        &[PortKind::EnttecPro, PortKind::UltraDmxPro]
+    }
+}
+
+/// The kinds of Enttec-compatible widget with more than one output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WidgetKind {
+    Pro,
+    Mk2,
+    UltraDmxPro,
+}
+
+impl WidgetKind {
+    /// Return the outputs a widget of this kind provides.
+    fn outputs(self) -> &'static [EnttecOutput] {
+        match self {
+            WidgetKind::Pro => &[EnttecOutput::Port1],
+            WidgetKind::Mk2 => &[EnttecOutput::Port1, EnttecOutput::Port2],
+            WidgetKind::UltraDmxPro => &[EnttecOutput::UltraDmxPortA, EnttecOutput::UltraDmxPortB],
+        }
+    }
+}
+
+/// What a widget reports about itself.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct WidgetIdentity {
+    serial_number: Option<u32>,
+    firmware_version: Option<u16>,
+}
+
+impl WidgetIdentity {
+    /// Ask the widget for whatever it doesn't know yet.
+    fn query(&mut self, connection: &mut Connection) {
+        if self.serial_number.is_none() {
+            self.serial_number = query_serial_number(connection).ok();
+        }
+        if self.firmware_version.is_none() {
+            self.firmware_version = query_firmware_version(connection).ok();
+        }
+    }
+}
+
+/// Ask the widget for its serial number.
+fn query_serial_number(connection: &mut Connection) -> Result<u32, Error> {
+    let reply = connection.request(
+        protocol::GET_SERIAL_NUMBER,
+        &[],
+        &[protocol::GET_SERIAL_NUMBER],
+        QUERY_TIMEOUT,
+    )?;
+    match Message::decode(reply.ok_or(Error::Timeout)?, &PORT1_LABELS)? {
+        Message::SerialNumber(serial_number) => Ok(serial_number),
+        message => Err(unexpected(message)),
+    }
+}
+
+/// Ask the widget for its firmware version, which leads its reply to a parameters request.
+fn query_firmware_version(connection: &mut Connection) -> Result<u16, Error> {
+    let label = PORT1_LABELS.get_parameters;
+    let reply = connection.request(label, &[0, 0], &[label], QUERY_TIMEOUT)?;
+    match Message::decode(reply.ok_or(Error::Timeout)?, &PORT1_LABELS)? {
+        Message::Parameters {
+            firmware_version, ..
+        } => Ok(firmware_version),
+        message => Err(unexpected(message)),
+    }
+}
+
+/// Describe a message that isn't the reply that was asked for.
+fn unexpected(message: Message) -> Error {
+    Error::Protocol(format!("unexpected message {:?}", message))
+}
+
+/// Format a firmware version the way Enttec does, as major and minor version.
+fn format_firmware_version(version: u16) -> String {
+    format!("{}.{}", version >> 8, version & 0xFF)
+}
+
+/// Identifies a widget: the serial port it is attached to and its USB serial number.
+type WidgetKey = (String, Option<String>);
+
+/// A widget that has been asked what it is.
+struct IdentifiedWidget {
+    key: WidgetKey,
+    kind: WidgetKind,
+    identity: WidgetIdentity,
+}
+
+/// The widgets identified so far, so each is only asked what it is the first time it is
+/// listed rather than every time ports are listed.
+static IDENTIFIED: Mutex<Vec<IdentifiedWidget>> = Mutex::new(Vec::new());
+
+fn identified_widgets() -> MutexGuard<'static, Vec<IdentifiedWidget>> {
+    IDENTIFIED.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn widget_key(info: &SerialPortInfo) -> WidgetKey {
+    let serial_number = match &info.port_type {
+        SerialPortType::UsbPort(usb) => usb.serial_number.clone(),
+        _ => None,
+    };
+    (info.port_name.clone(), serial_number)
+}
+
+/// Return what was learned about a widget when it was identified, if it has been.
+fn lookup(key: &WidgetKey) -> Option<(WidgetKind, WidgetIdentity)> {
+    identified_widgets()
+        .iter()
+        .find(|widget| widget.key == *key)
+        .map(|widget| (widget.kind, widget.identity))
+}
+
+/// Return what kind of widget is attached as described, and what it reports about itself,
+/// from when it was first identified, asking the widget if it hasn't been yet.  Widgets that
+/// cannot be opened are assumed to be the original Pro, and asked again next time.
+fn identified(info: &SerialPortInfo) -> (WidgetKind, WidgetIdentity) {
+    if let Some(found) = lookup(&widget_key(info)) {
+        return found;
+    }
+    // Ports identify their widget as they open it, so a widget in use is only unknown here if
+    // it was plugged back in while open.  Asking it would hold up the frames being sent.
+    if is_connected(&info.port_name) {
+        return (WidgetKind::Pro, WidgetIdentity::default());
+    }
+    match connect(&info.port_name, &EnttecTimeouts::default()) {
+        Ok(connection) => identify(info, &connection),
+        Err(_) => (WidgetKind::Pro, WidgetIdentity::default()),
+    }
+}
+
+/// Forget the widgets that are no longer attached, so a widget plugged in later is
+/// identified afresh.
+fn forget_detached(attached: &[SerialPortInfo]) {
+    let keys: Vec<_> = attached.iter().map(widget_key).collect();
+    identified_widgets().retain(|widget| keys.contains(&widget.key));
+}
+
+/// Ask the widget attached as described what kind of widget it is, and what it reports about
+/// itself, unless it has been asked already.  Widgets that do not answer are assumed to be the
+/// original Pro.  The widget is asked without holding the lock on the widgets identified, so
+/// other listings aren't held up.
+fn identify(info: &SerialPortInfo, connection: &SharedConnection) -> (WidgetKind, WidgetIdentity) {
+    let key = widget_key(info);
+    if let Some(found) = lookup(&key) {
+        return found;
+    }
+    let mut connection = lock(connection);
+    let mut identity = WidgetIdentity::default();
+    identity.query(&mut connection);
+    let kind = identify_kind(&mut connection);
+    drop(connection);
+    identified_widgets().push(IdentifiedWidget {
+        key,
+        kind,
+        identity,
+    });
+    (kind, identity)
+}
+
+fn identify_kind(connection: &mut Connection) -> WidgetKind {
+    kind_from_replies(
+        |label| match connection.request(label, &[], &[label], QUERY_TIMEOUT) {
+            Ok(Some(packet)) => Some(packet.payload),
+            _ => None,
+        },
+    )
+}
+
+/// Tell what kind of widget answers the provided queries, which return the payload of the
+/// widget's reply to a message with the provided label, if it replies.
+fn kind_from_replies(mut query: impl FnMut(u8) -> Option<Vec<u8>>) -> WidgetKind {
+    // Manufacturer and device replies start with a little-endian ID, followed by a name.
+    let id = |payload: &[u8]| {
+        payload
+            .get(0..2)
+            .map(|id| u16::from_le_bytes([id[0], id[1]]))
+    };
+    if query(protocol::MANUFACTURER).and_then(|reply| id(&reply)) == Some(protocol::DMXKING_ESTA_ID)
+    {
+        if query(protocol::DEVICE).and_then(|reply| id(&reply))
+            == Some(protocol::ULTRA_DMX_PRO_DEVICE_ID)
+        {
+            return WidgetKind::UltraDmxPro;
+        }
+        return WidgetKind::Pro;
+    }
+    match query(protocol::HARDWARE_VERSION) {
+        Some(reply) if reply.first() == Some(&protocol::MK2_HARDWARE_VERSION) => WidgetKind::Mk2,
+        _ => WidgetKind::Pro,
+    }
+}
+
+/// How long talking to a widget may block before failing with `Error::Timeout`, so a wedged
+/// USB connection can't stall the thread writing frames.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnttecTimeouts {
+    /// How long opening the serial port may take.
+    pub open: Duration,
+    /// How long each write to the widget may take.
+    pub write: Duration,
+}
+
+impl Default for EnttecTimeouts {
+    fn default() -> Self {
+        Self {
+            open: Duration::from_secs(2),
+            write: Duration::from_millis(100),
+        }
+    }
+}
+
+/// The DMX timing a widget output uses.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnttecParams {
+    /// DMX output break time in 10.67 microsecond units. Valid range is 9 to 127.
+    break_time: u8,
+    /// DMX output Mark After Break time in 10.67 microsecond units. Valid range is 1 to 127.
+    mark_after_break_time: u8,
+    /// DMX output rate in packets per second. Valid range is 1 to 40, or 0 for fastest rate
+    /// possible (this will make the most difference when the output universe size is smallest).
+    output_rate: u8,
+}
+
+impl Default for EnttecParams {
+    /// Default parameters for the enttec port.
+    /// In summary: minimum break and mark times, fastest fixed framerate.
+    fn default() -> Self {
+        EnttecParams {
+            break_time: 9,
+            mark_after_break_time: 1,
+            output_rate: 40,
+        }
+    }
+}
+
+impl EnttecParams {
+    /// Create parameters, checking that each is in the range the widget accepts.
+    pub fn new(break_time: u8, mark_after_break_time: u8, output_rate: u8) -> Result<Self, Error> {
+        let invalid = |what| Err(io::Error::new(io::ErrorKind::InvalidInput, what).into());
+        if !(9..=127).contains(&break_time) {
+            return invalid("break time must be between 9 and 127");
+        }
+        if !(1..=127).contains(&mark_after_break_time) {
+            return invalid("mark after break time must be between 1 and 127");
+        }
+        if output_rate > 40 {
+            return invalid("output rate must be at most 40");
+        }
+        Ok(Self {
+            break_time,
+            mark_after_break_time,
+            output_rate,
+        })
+    }
+
+    /// Return the break time in 10.67 microsecond units.
+    pub fn break_time(&self) -> u8 {
+        self.break_time
+    }
+
+    /// Return the Mark After Break time in 10.67 microsecond units.
+    pub fn mark_after_break_time(&self) -> u8 {
+        self.mark_after_break_time
+    }
+
+    /// Return the output rate in packets per second, or 0 for as fast as possible.
+    pub fn output_rate(&self) -> u8 {
+        self.output_rate
+    }
+
+    fn write_into(&self, labels: &Labels, connection: &mut Connection) -> Result<(), Error> {
+        let payload = [
+            0, // user size lsb?
+            0, // user size msb?
+            self.break_time,
+            self.mark_after_break_time,
+            self.output_rate,
+        ];
+        connection.write_packet(labels.set_parameters, &payload, false)
+    }
+
+    /// Ask the widget for the parameters it is using.
+    fn read_from(labels: &Labels, connection: &mut Connection) -> Result<Self, Error> {
+        // Request no user configuration bytes.
+        let reply = connection.request(
+            labels.get_parameters,
+            &[0, 0],
+            &[labels.get_parameters],
+            QUERY_TIMEOUT,
+        )?;
+        match Message::decode(reply.ok_or(Error::Timeout)?, labels)? {
+            Message::Parameters { params, .. } => Ok(params),
+            message => Err(unexpected(message)),
+        }
+    }
+
+    /// Parse a parameters reply, which follows the firmware version with the parameters.
+    fn parse(payload: &[u8]) -> Result<Self, Error> {
+        match payload {
+            [_, _, break_time, mark_after_break_time, output_rate, ..] => Ok(Self {
+                break_time: *break_time,
+                mark_after_break_time: *mark_after_break_time,
+                output_rate: *output_rate,
+            }),
+            _ => Err(Error::Protocol(format!(
+                "short widget parameters reply of {} bytes",
+                payload.len()
+            ))),
+        }
+    }
+}
+
+/// Which DMX output of a widget a port drives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnttecOutput {
+    /// The only output of a DMX USB Pro, or the first output of a Mk2.
+    #[default]
+    Port1,
+    /// The second output of a Mk2.
+    Port2,
+    /// The first output of a DMXking ultraDMX Pro.
+    UltraDmxPortA,
+    /// The second output of a DMXking ultraDMX Pro.
+    UltraDmxPortB,
+}
+
+impl EnttecOutput {
+    fn labels(self) -> &'static Labels {
+        match self {
+            EnttecOutput::Port1 => &PORT1_LABELS,
+            EnttecOutput::Port2 => &PORT2_LABELS,
+            EnttecOutput::UltraDmxPortA => &ULTRA_DMX_PORT_A_LABELS,
+            EnttecOutput::UltraDmxPortB => &ULTRA_DMX_PORT_B_LABELS,
+        }
+    }
+
+    /// Return the widget's own name for this output, if it has more than one.
+    fn label(self) -> Option<&'static str> {
+        match self {
+            EnttecOutput::Port1 => None,
+            EnttecOutput::Port2 => Some("port 2"),
+            EnttecOutput::UltraDmxPortA => Some("port A"),
+            EnttecOutput::UltraDmxPortB => Some("port B"),
+        }
+    }
+
+    /// Return the product name of the widget this output belongs to.
+    fn product(self) -> &'static str {
+        match self {
+            EnttecOutput::Port1 | EnttecOutput::Port2 => "Enttec DMX USB PRO",
+            EnttecOutput::UltraDmxPortA | EnttecOutput::UltraDmxPortB => "DMXking ultraDMX Pro",
+        }
+    }
+
+    /// Return the kind of widget this output belongs to.
+    fn kind(self) -> PortKind {
+        match self {
+            EnttecOutput::Port1 | EnttecOutput::Port2 => PortKind::EnttecPro,
+            EnttecOutput::UltraDmxPortA | EnttecOutput::UltraDmxPortB => PortKind::UltraDmxPro,
+        }
+    }
+}
+
+/// Open the connection to a widget and prepare the provided output for use.
+fn open_output(
+    port_name: &str,
+    output: EnttecOutput,
+    params: &EnttecParams,
+    timeouts: &EnttecTimeouts,
+) -> Result<SharedConnection, Error> {
+    let shared = connect(port_name, timeouts)?;
+    {
+        let mut connection = lock(&shared);
+        connection.set_write_timeout(timeouts.write);
+        if output == EnttecOutput::Port2 {
+            // Unlock the second port and configure both ports as DMX outputs.
+            connection.write_packet(protocol::SET_API_KEY, &protocol::API_KEY, false)?;
+            connection.write_packet(protocol::SET_PORT_ASSIGNMENT, &[1, 1], false)?;
+        }
+        // send the parameters to the port
+        params.write_into(output.labels(), &mut connection)?;
+    }
+    Ok(shared)
+}
+
+/// Send a frame message, retrying transient failures.  The connection is locked for each
+/// attempt rather than across them, so the widget's other port isn't held up while this one
+/// waits to retry.
+fn write_frame(
+    connection: &SharedConnection,
+    retry: &RetryPolicy,
+    write_timeout: Duration,
+    label: u8,
+    start_code: u8,
+    frame: &[u8],
+) -> Result<(), Error> {
+    retry.run(|| {
+        let mut connection = lock(connection);
+        connection.set_write_timeout(write_timeout);
+        connection.write_frame(label, start_code, frame)
+    })
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct EnttecDmxPort {
+    params: EnttecParams,
+    #[serde(default)]
+    output: EnttecOutput,
+    #[serde(default)]
+    timeouts: EnttecTimeouts,
+    #[serde(default)]
+    retry: RetryPolicy,
+    #[serde(default)]
+    shape: FrameShape,
+    #[serde(skip)]
+    connection: Option<SharedConnection>,
+    #[serde(with = "SerialPortInfoDef")]
+    info: SerialPortInfo,
+    #[serde(default)]
+    links: DeviceLinks,
+    #[serde(skip)]
+    identity: WidgetIdentity,
+    #[serde(skip)]
+    transaction_number: u8,
+    #[serde(skip)]
+    name: OnceLock<String>,
+    #[serde(skip)]
+    stats: StatsCounter,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+impl EnttecDmxPort {
+    /// Create an enttec port.
+    /// The port is not opened yet.
+    pub fn new(info: SerialPortInfo) -> Self {
+        Self::with_output(info, EnttecOutput::Port1)
+    }
+
+    /// Create an enttec port driving the provided output of the widget.
+    /// The port is not opened yet.
+    pub fn with_output(info: SerialPortInfo, output: EnttecOutput) -> Self {
+        let params = EnttecParams::default();
+
+        Self {
+            params,
+            output,
+            timeouts: EnttecTimeouts::default(),
+            retry: RetryPolicy::default(),
+            shape: FrameShape::default(),
+            connection: None,
+            links: DeviceLinks::resolve(&info.port_name),
+            info,
+            identity: WidgetIdentity::default(),
+            transaction_number: 0,
+            name: OnceLock::new(),
+            stats: StatsCounter::new(),
+            last_frame: None,
+            status: StatusTracker::new(),
+        }
+    }
+
+    /// Create an enttec port and open it.
+    pub fn opened(info: SerialPortInfo) -> Result<Self, Error> {
+        let mut port = Self::new(info);
+        port.open()?;
+        Ok(port)
+    }
+
+    /// Set how long opening and writing to the widget may block.
+    pub fn with_timeouts(mut self, timeouts: EnttecTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Return how long opening and writing to the widget may block.
+    pub fn timeouts(&self) -> &EnttecTimeouts {
+        &self.timeouts
+    }
+
+    /// Set how writes that fail with a transient error, such as a timed out USB transfer,
+    /// are retried before the failure is returned.
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Return how failed writes are retried.
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    /// Set how each frame is shaped before it is transmitted: how many of its slots are sent,
+    /// and how shorter frames are padded.  By default frames are transmitted at the length
+    /// they're written, padded with zeros only as far as the protocol requires.
+    pub fn with_shape(mut self, shape: FrameShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Return how each frame is shaped before it is transmitted.
+    pub fn shape(&self) -> &FrameShape {
+        &self.shape
+    }
+
+    /// Set the DMX timing of this output, for fixtures that need a longer break than the
+    /// default.  The parameters are sent to the widget now if the port is open, and every time
+    /// it is opened.  For the ultraDMX Pro they apply to both outputs.
+    pub fn set_widget_parameters(
+        &mut self,
+        break_time: u8,
+        mab_time: u8,
+        refresh_rate: u8,
+    ) -> Result<(), Error> {
+        let params = EnttecParams::new(break_time, mab_time, refresh_rate)?;
+        if let Some(connection) = &self.connection {
+            params.write_into(self.output.labels(), &mut lock(connection))?;
+        }
+        self.params = params;
+        Ok(())
+    }
+
+    /// Ask the widget for the DMX timing this output is using.  The port must be open.
+    pub fn get_widget_parameters(&mut self) -> Result<EnttecParams, Error> {
+        let connection = self.connection.as_ref().ok_or(Error::NotOpen)?;
+        EnttecParams::read_from(self.output.labels(), &mut lock(connection))
+    }
+
+    /// Ask the widget for its serial number, which is printed on the widget and is not the
+    /// same as the USB serial number.  The port must be open.
+    pub fn serial_number(&mut self) -> Result<u32, Error> {
+        if let Some(serial_number) = self.identity.serial_number {
+            return Ok(serial_number);
+        }
+        let connection = self.connection.as_ref().ok_or(Error::NotOpen)?;
+        let serial_number = query_serial_number(&mut lock(connection))?;
+        self.identity.serial_number = Some(serial_number);
+        Ok(serial_number)
+    }
+
+    /// Ask the widget for its firmware version, with the major version in the high byte.
+    /// The port must be open.
+    pub fn firmware_version(&mut self) -> Result<u16, Error> {
+        if let Some(version) = self.identity.firmware_version {
+            return Ok(version);
+        }
+        let connection = self.connection.as_ref().ok_or(Error::NotOpen)?;
+        let version = query_firmware_version(&mut lock(connection))?;
+        self.identity.firmware_version = Some(version);
+        Ok(version)
+    }
+
+    /// Follow the widget to the serial port it is attached to now.
+    fn relocate(&mut self) {
+        let scan = EnttecScan::finding(&self.info);
+        if relocate(&mut self.info, &self.links, || scan.serial_ports()) {
+            self.name = OnceLock::new();
+        }
+        if self.links.is_empty() {
+            self.links = DeviceLinks::resolve(&self.info.port_name);
+        }
+    }
+
+    /// Return the udev links to the widget's serial port, which are saved with the port and
+    /// used to find the widget again after `ttyUSB` devices are renumbered.
+    pub fn device_links(&self) -> &DeviceLinks {
+        &self.links
+    }
+
+    /// Return the output of the widget this port drives.
+    pub fn output(&self) -> EnttecOutput {
+        self.output
+    }
+
+    /// Find the UIDs of all RDM responders connected to this output.
+    pub fn discover_rdm_devices(&mut self) -> Result<Vec<Uid>, Error> {
+        // The controller UID is made from the serial number, where the widget reports one.
+        let _ = self.serial_number();
+        rdm::discover(self)
+    }
+
+    /// Send an RDM message with the provided label and wait for the widget to relay a response.
+    fn rdm_transaction(
+        &mut self,
+        label: u8,
+        packet: &[u8],
+        expect_response: bool,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let labels = self.output.labels();
+        let connection = self.connection.as_ref().ok_or(Error::NotOpen)?;
+        let mut connection = lock(connection);
+        if !expect_response {
+            connection.write_packet(label, packet, false)?;
+            return Ok(None);
+        }
+        let reply = connection.request(
+            label,
+            packet,
+            &[labels.received_dmx, labels.rdm_timeout],
+            RDM_RESPONSE_TIMEOUT,
+        )?;
+        let reply = match reply {
+            Some(reply) => Message::decode(reply, labels)?,
+            None => return Ok(None),
+        };
+        Ok(match reply {
+            Message::ReceivedDmx {
+                start_code, data, ..
+            } => {
+                let mut response = Vec::with_capacity(data.len() + 1);
+                response.push(start_code);
+                response.extend_from_slice(&data);
+                Some(response)
+            }
+            _ => None,
+        })
+    }
+}
+
+impl RdmTransport for EnttecDmxPort {
+    /// Widgets are given UIDs made from their serial number, so controllers on different
+    /// widgets can be told apart.
+    fn controller_uid(&self) -> Uid {
+        Uid::new(ENTTEC_ESTA_ID, self.identity.serial_number.unwrap_or(1))
+    }
+
+    fn next_transaction_number(&mut self) -> u8 {
+        self.transaction_number = self.transaction_number.wrapping_add(1);
+        self.transaction_number
+    }
+
+    fn send_discovery(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        self.rdm_transaction(self.output.labels().rdm_discovery, packet, true)
+    }
+
+    fn send_request(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        let broadcast =
+            rdm::RdmPacket::decode(packet).is_some_and(|p| p.destination.is_broadcast());
+        self.rdm_transaction(self.output.labels().send_rdm, packet, !broadcast)
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for EnttecDmxPort {
+    fn name(&self) -> &str {
+        self.name.get_or_init(|| match self.output.label() {
+            None => self.info.port_name.clone(),
+            Some(label) => format!("{} {}", self.info.port_name, label),
+        })
+    }
+
+    /// Open the port, finding the widget by its USB serial number in case it has moved to
+    /// another serial port since the port was listed or saved.  A widget that wasn't listed is
+    /// asked what it is the first time one of its ports is opened, so listing never has to
+    /// interrupt a widget in use.
+    fn open(&mut self) -> Result<(), Error> {
+        if self.connection.is_some() {
+            return Ok(());
+        }
+        self.relocate();
+        let result = open_output(
+            &self.info.port_name,
+            self.output,
+            &self.params,
+            &self.timeouts,
+        )
+        .map(|connection| {
+            self.identity = identify(&self.info, &connection).1;
+            self.connection = Some(connection);
+        });
+        trace::opened(DmxPort::name(self), &result);
+        self.status.record(result)
+    }
+
+    fn close(&mut self) {
+        self.status.clear();
+        if self.connection.take().is_some() {
+            trace::closed(DmxPort::name(self));
+        }
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let sent = self
+            .shape
+            .apply(frame, |slots| slots.max(MIN_UNIVERSE_SIZE));
+        let result = match &self.connection {
+            Some(connection) => {
+                let label = self.output.labels().send_dmx;
+                write_frame(connection, &self.retry, self.timeouts.write, label, 0, sent)
+            }
+            None => Err(Error::NotOpen),
+        };
+        trace::wrote(DmxPort::name(self), frame.len(), &result);
+        if result.is_ok() {
+            self.last_frame = Some(DmxFrame::from(frame));
+        }
+        let result = self.stats.record(frame, result);
+        self.status.record(result)
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        if start_code == 0 {
+            return self.write(data);
+        }
+        let labels = self.output.labels();
+        let timeout = self.timeouts.write;
+        let result = match &self.connection {
+            // Widgets take RDM packets, START code included, with their own label.
+            Some(connection) if start_code == rdm::RDM_START_CODE => {
+                let mut packet = Vec::with_capacity(1 + data.len());
+                packet.push(start_code);
+                packet.extend_from_slice(data);
+                self.retry.run(|| {
+                    let mut connection = lock(connection);
+                    connection.set_write_timeout(timeout);
+                    connection.write_packet(labels.send_rdm, &packet, false)
+                })
+            }
+            Some(connection) => write_frame(
+                connection,
+                &self.retry,
+                timeout,
+                labels.send_dmx,
+                start_code,
+                data,
+            ),
+            None => Err(Error::NotOpen),
+        };
+        trace::wrote(DmxPort::name(self), data.len(), &result);
+        let result = self.stats.record(data, result);
+        self.status.record(result)
+    }
+
+    fn kind(&self) -> PortKind {
+        self.output.kind()
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        HardwareInfo {
+            device_serial_number: self.identity.serial_number.map(|sn| format!("{:08}", sn)),
+            firmware_version: self.identity.firmware_version.map(format_firmware_version),
+            ..hardware_info(&self.info)
+        }
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        Some(self.stats.stats())
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(self.connection.is_some())
+    }
+
+    /// Ask the widget for its parameters, which fails once it has been unplugged.
+    fn health_check(&mut self) -> Result<(), Error> {
+        let result = match &self.connection {
+            Some(connection) => {
+                EnttecParams::read_from(self.output.labels(), &mut lock(connection)).map(|_| ())
+            }
+            None => Err(Error::NotOpen),
+        };
+        self.status.record(result)
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl crate::AsyncDmxPort for EnttecDmxPort {
+    fn name(&self) -> &str {
+        DmxPort::name(self)
+    }
+
+    async fn open(&mut self) -> Result<(), Error> {
+        if self.connection.is_some() {
+            return Ok(());
+        }
+        self.relocate();
+        let port_name = self.info.port_name.clone();
+        let output = self.output;
+        let params = self.params.clone();
+        let timeouts = self.timeouts.clone();
+        let connection = tokio::task::spawn_blocking(move || {
+            open_output(&port_name, output, &params, &timeouts)
+        })
+        .await??;
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    async fn close(&mut self) {
+        self.status.clear();
+        self.connection = None;
+    }
+
+    async fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let connection = self.connection.clone().ok_or(Error::NotOpen)?;
+        let label = self.output.labels().send_dmx;
+        let write_timeout = self.timeouts.write;
+        let retry = self.retry.clone();
+        // Copy the frame into a fixed size universe rather than a fresh Vec, to move it to the
+        // blocking thread without allocating.
+        let sent = self
+            .shape
+            .apply(frame, |slots| slots.max(MIN_UNIVERSE_SIZE));
+        let size = sent.len().min(UNIVERSE_SIZE);
+        let owned = DmxFrame::from(sent);
+        let result = tokio::task::spawn_blocking(move || {
+            write_frame(&connection, &retry, write_timeout, label, 0, &owned[..size])
+        })
+        .await?;
+        if result.is_ok() {
+            self.last_frame = Some(DmxFrame::from(frame));
+        }
+        let result = self.stats.record(frame, result);
+        self.status.record(result)
+    }
+}
+
+impl fmt::Display for EnttecDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_aliased(f, &self.hardware(), |f| {
+            write_device_name(self.output.product(), &self.info, f)?;
+            if let Some(label) = self.output.label() {
+                write!(f, " {}", label)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// When a widget sends what it receives.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EnttecReceiveMode {
+    /// Send every DMX packet received.
+    #[default]
+    Always,
+    /// Send only the slots that changed, which takes much less of the USB link when levels
+    /// are steady.  Only packets with the null start code are tracked.
+    OnChange,
+}
+
+/// An enttec widget used to receive DMX.
+pub struct EnttecInputPort {
+    connection: Option<Connection>,
+    info: SerialPortInfo,
+    links: DeviceLinks,
+    mode: EnttecReceiveMode,
+    /// The universe as last reported in change of state mode, starting with the start code.
+    universe: Vec<u8>,
+}
+
+impl EnttecInputPort {
+    /// Create an enttec input port.
+    /// The port is not opened yet.
+    pub fn new(info: SerialPortInfo) -> Self {
+        Self {
+            connection: None,
+            links: DeviceLinks::resolve(&info.port_name),
+            info,
+            mode: EnttecReceiveMode::default(),
+            universe: Vec::new(),
+        }
+    }
+
+    /// Set when the widget sends what it receives.  Takes effect the next time the port is
+    /// opened.
+    pub fn with_receive_mode(mut self, mode: EnttecReceiveMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Return when the widget sends what it receives.
+    pub fn receive_mode(&self) -> EnttecReceiveMode {
+        self.mode
+    }
+
+    /// Create an enttec input port and open it.
+    pub fn opened(info: SerialPortInfo) -> Result<Self, Error> {
+        let mut port = Self::new(info);
+        port.open()?;
+        Ok(port)
+    }
+}
+
+impl DmxInputPort for EnttecInputPort {
+    /// Return the available enttec ports connected to this system.
+    fn available_ports() -> Result<InputPortListing, Error> {
+        EnttecScan::new().input_ports()
+    }
+
+    fn name(&self) -> &str {
+        &self.info.port_name
+    }
+
+    /// Open the port and ask the widget to send us DMX packets as it receives them, or the
+    /// changes to them in change of state mode.
+    fn open(&mut self) -> Result<(), Error> {
+        if self.connection.is_some() {
+            return Ok(());
+        }
+        let scan = EnttecScan::finding(&self.info);
+        relocate(&mut self.info, &self.links, || scan.serial_ports());
+        let mut connection = Connection::open(&self.info.port_name, &EnttecTimeouts::default())?;
+        let on_change = self.mode == EnttecReceiveMode::OnChange;
+        connection.write_packet(
+            PORT1_LABELS.receive_dmx_on_change,
+            &[on_change as u8],
+            false,
+        )?;
+        // Switching to change of state mode clears the widget's copy of the universe, so it
+        // reports every slot that isn't zero.
+        self.universe.clear();
+        self.universe.resize(UNIVERSE_SIZE + 1, 0);
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.connection = None;
+    }
+
+    /// Block until the widget delivers a DMX packet.
+    /// Packets the widget flags as overrun are dropped, as their contents are unreliable.
+    fn read_frame(&mut self) -> Result<ReceivedFrame, Error> {
+        let connection = self.connection.as_mut().ok_or(Error::NotOpen)?;
+        loop {
+            let packet = match connection.read(None)? {
+                Some(packet) => packet,
+                None => continue,
+            };
+            match Message::decode(packet, &PORT1_LABELS) {
+                Ok(Message::ChangeOfState(changes)) => {
+                    protocol::apply_change_of_state(&changes, &mut self.universe)?;
+                    return Ok(ReceivedFrame {
+                        start_code: self.universe[0],
+                        data: self.universe[1..].to_vec(),
+                    });
+                }
+                Ok(Message::ReceivedDmx {
+                    status,
+                    start_code,
+                    data,
+                }) if status & RECEIVE_OVERRUN == 0 => {
+                    return Ok(ReceivedFrame { start_code, data });
+                }
+                _ => continue,
+            }
+        }
+    }
+}
+
+impl fmt::Display for EnttecInputPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_device_name(EnttecOutput::Port1.product(), &self.info, f)?;
+        write!(f, " (input)")
+    }
+}
+
+#[cfg(unix)]
+fn is_enttec(info: &UsbPortInfo) -> bool {
+    if let Some(product) = &info.product {
+        return product == "DMX USB PRO" || product == "FT232 Serial (UART) IC";
+    }
+    false
+}
+
+#[cfg(windows)]
+fn is_enttec(info: &UsbPortInfo) -> bool {
+    if let Some(manufacturer) = &info.manufacturer {
+        return manufacturer == "FTDI";
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use std::{thread::sleep, time::Duration};
+
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_scan() {
+        let clone = UsbPortInfo {
+            vid: 0x0403,
+            pid: 0x6001,
+            serial_number: None,
+            manufacturer: None,
+            product: None,
+        };
+        assert!(!EnttecScan::new().matches(&clone));
+        let scan = EnttecScan::new()
+            .with_usb_id(0x0403, 0x6001)
+            .with_usb_id(0x0403, 0x6001);
+        assert!(scan.matches(&clone));
+        assert_eq!(scan.usb_ids(), &[(0x0403, 0x6001)]);
+    }
+
+    #[test]
+    fn test_identify_kind() {
+        let widget = |replies: &'static [(u8, &'static [u8])]| {
+            kind_from_replies(|label| {
+                replies
+                    .iter()
+                    .find(|(l, _)| *l == label)
+                    .map(|(_, reply)| reply.to_vec())
+            })
+        };
+        assert_eq!(widget(&[]), WidgetKind::Pro);
+        assert_eq!(
+            widget(&[(protocol::HARDWARE_VERSION, &[2])]),
+            WidgetKind::Mk2
+        );
+        assert_eq!(
+            widget(&[
+                (protocol::MANUFACTURER, b"\x6B\x6ADMXking.com"),
+                (protocol::DEVICE, b"\x02\x00ultraDMX Pro"),
+            ]),
+            WidgetKind::UltraDmxPro
+        );
+        // Other DMXking widgets behave as a Pro, whatever their hardware version.
+        assert_eq!(
+            widget(&[
+                (protocol::MANUFACTURER, b"\x6B\x6ADMXking.com"),
+                (protocol::DEVICE, b"\x01\x00ultraDMX Micro"),
+                (protocol::HARDWARE_VERSION, &[2]),
+            ]),
+            WidgetKind::Pro
+        );
+    }
+
+    #[test]
+    fn test_identified() {
+        let info = SerialPortInfo {
+            port_name: "/dev/rust-dmx-identified".to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid: 0x0403,
+                pid: 0x6001,
+                serial_number: Some("EN000001".to_string()),
+                manufacturer: None,
+                product: None,
+            }),
+        };
+        // A widget that can't be opened isn't remembered.
+        assert_eq!(identified(&info).0, WidgetKind::Pro);
+        assert!(identified_widgets().is_empty());
+        let identity = WidgetIdentity {
+            serial_number: Some(1),
+            firmware_version: None,
+        };
+        identified_widgets().push(IdentifiedWidget {
+            key: widget_key(&info),
+            kind: WidgetKind::Mk2,
+            identity,
+        });
+        assert_eq!(identified(&info), (WidgetKind::Mk2, identity));
+        forget_detached(std::slice::from_ref(&info));
+        assert_eq!(identified(&info), (WidgetKind::Mk2, identity));
+        forget_detached(&[]);
+        assert_eq!(identified(&info).0, WidgetKind::Pro);
+    }
+
+    #[test]
+    fn test_ultra_dmx_pro_outputs() {
+        let info = SerialPortInfo {
+            port_name: "/dev/ttyUSB0".to_string(),
+            port_type: SerialPortType::Unknown,
+        };
+        let ports: Vec<_> = WidgetKind::UltraDmxPro
+            .outputs()
+            .iter()
+            .map(|output| EnttecDmxPort::with_output(info.clone(), *output))
+            .collect();
+        assert_eq!(ports[0].name(), "/dev/ttyUSB0 port A");
+        assert_eq!(ports[1].name(), "/dev/ttyUSB0 port B");
+        assert_eq!(ports[1].kind(), PortKind::UltraDmxPro);
+        assert_eq!(ports[0].output.labels().send_dmx, 100);
+        assert_eq!(ports[1].output.labels().send_dmx, 101);
+    }
+
+    #[test]
+    fn test_open_missing() {
+        let mut port = EnttecDmxPort::new(SerialPortInfo {
+            port_name: "/dev/rust-dmx-missing".to_string(),
+            port_type: SerialPortType::Unknown,
+        });
+        assert!(matches!(port.open(), Err(crate::Error::DeviceDisconnected)));
+        assert!(!port.is_open());
+    }
+
+    #[test]
+    fn test_params() {
+        let params = EnttecParams::parse(&[0x44, 0x01, 20, 2, 30]).unwrap();
+        assert_eq!(params, EnttecParams::new(20, 2, 30).unwrap());
+        assert!(EnttecParams::parse(&[0x44, 0x01]).is_err());
+        assert!(EnttecParams::new(8, 1, 40).is_err());
+        assert!(EnttecParams::new(9, 0, 40).is_err());
+        assert!(EnttecParams::new(9, 1, 41).is_err());
+    }
+
+    #[test]
+    fn test() -> Result<(), Box<dyn Error>> {
+        let mut port = EnttecScan::new().output_ports()?.pop().unwrap();
+        println!("{}", port);
+        port.open()?;
+        for val in 0..255 {
+            port.write(&[val][..])?;
+            sleep(Duration::from_millis(25));
+        }
+        port.write(&[0][..])?;
+        Ok(())
+    }
+}
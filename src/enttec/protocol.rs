@@ -0,0 +1,417 @@
+//! Message framing for the Enttec USB DMX Pro API.
+
+use std::cmp::{max, min};
+use std::io::{self, Read, Write};
+use std::time::Instant;
+
+use super::EnttecParams;
+use crate::Error;
+
+// Some constants used for enttec message framing.
+const START_VAL: u8 = 0x7E;
+const END_VAL: u8 = 0xE7;
+
+// Universe size constraints.
+pub(crate) const MIN_UNIVERSE_SIZE: usize = 24;
+const MAX_UNIVERSE_SIZE: usize = 512;
+
+// Maximum size of a message payload.
+const MAX_PAYLOAD_SIZE: usize = 600;
+
+// Widget-wide labels.
+pub const GET_SERIAL_NUMBER: u8 = 10;
+pub const SET_API_KEY: u8 = 13;
+pub const HARDWARE_VERSION: u8 = 14;
+pub const MANUFACTURER: u8 = 77;
+pub const DEVICE: u8 = 78;
+pub const SET_PORT_ASSIGNMENT: u8 = 203;
+
+/// Message labels used to operate one DMX port of a widget.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Labels {
+    pub get_parameters: u8,
+    pub set_parameters: u8,
+    pub received_dmx: u8,
+    pub send_dmx: u8,
+    pub send_rdm: u8,
+    pub receive_dmx_on_change: u8,
+    pub received_dmx_change_of_state: u8,
+    pub rdm_discovery: u8,
+    pub rdm_timeout: u8,
+}
+
+/// Labels of the first port, as documented in the widget API.
+pub const PORT1_LABELS: Labels = Labels {
+    get_parameters: 3,
+    set_parameters: 4,
+    received_dmx: 5,
+    send_dmx: 6,
+    send_rdm: 7,
+    receive_dmx_on_change: 8,
+    received_dmx_change_of_state: 9,
+    rdm_discovery: 11,
+    rdm_timeout: 12,
+};
+
+/// Labels of the second port of a Mk2.  These are not fixed by the widget; they are the ones
+/// assigned to the API key below.
+pub const PORT2_LABELS: Labels = Labels {
+    get_parameters: 137,
+    set_parameters: 180,
+    received_dmx: 156,
+    send_dmx: 129,
+    send_rdm: 194,
+    receive_dmx_on_change: 163,
+    received_dmx_change_of_state: 164,
+    rdm_discovery: 196,
+    rdm_timeout: 218,
+};
+
+/// Labels of the two outputs of a DMXking ultraDMX Pro.  The widget has no RDM support, and
+/// shares its parameters between both outputs.
+pub const ULTRA_DMX_PORT_A_LABELS: Labels = Labels {
+    send_dmx: 100,
+    ..PORT1_LABELS
+};
+pub const ULTRA_DMX_PORT_B_LABELS: Labels = Labels {
+    send_dmx: 101,
+    ..PORT1_LABELS
+};
+
+/// API key that unlocks the second port of a Mk2.
+pub const API_KEY: [u8; 4] = [0xC9, 0xA4, 0x03, 0xE4];
+
+/// Hardware version reported by the Mk2.
+pub const MK2_HARDWARE_VERSION: u8 = 2;
+
+/// Decode a serial number reply, which holds the decimal digits of the number in little-endian
+/// BCD.
+pub fn parse_serial_number(payload: &[u8]) -> Option<u32> {
+    let bytes = payload.get(0..4)?;
+    bytes.iter().rev().try_fold(0u32, |number, byte| {
+        let (high, low) = (byte >> 4, byte & 0xF);
+        if high > 9 || low > 9 {
+            return None;
+        }
+        Some(number * 100 + u32::from(high) * 10 + u32::from(low))
+    })
+}
+
+/// ESTA manufacturer ID reported by DMXking widgets, and the device ID of the ultraDMX Pro.
+pub const DMXKING_ESTA_ID: u16 = 0x6A6B;
+pub const ULTRA_DMX_PRO_DEVICE_ID: u16 = 2;
+
+/// Format a byte buffer as an enttec message into the provided writer.
+/// Maximum valid size for payload is 600; no check is made here that the payload is within this range.
+pub fn write_packet<W: Write>(
+    message_type: u8,
+    payload: &[u8],
+    add_payload_pad_byte: bool,
+    mut w: W,
+) -> Result<(), Error> {
+    // Enttec messages are the size of the payload plus 5 bytes for type, length, and framing.
+    let payload_size = payload.len() + add_payload_pad_byte as usize;
+    let (len_lsb, len_msb) = (payload_size as u8, (payload_size >> 8) as u8);
+    let header = [START_VAL, message_type, len_lsb, len_msb];
+    w.write_all(&header)?;
+    if add_payload_pad_byte {
+        w.write_all(&[0][..])?;
+    }
+    w.write_all(payload)?;
+    w.write_all(&[END_VAL][..])?;
+    Ok(())
+}
+
+/// The size of the largest DMX frame message.
+pub const MAX_FRAME_MESSAGE_SIZE: usize = 4 + 1 + MAX_UNIVERSE_SIZE + 1;
+
+/// Format a DMX packet as an enttec message into the provided buffer, replacing its contents,
+/// and padding or truncating the frame to a valid universe size.  A buffer with capacity for
+/// `MAX_FRAME_MESSAGE_SIZE` bytes is never reallocated, so it can be reused for every frame.
+pub fn encode_frame(label: u8, start_code: u8, frame: &[u8], buf: &mut Vec<u8>) {
+    let frame = &frame[0..min(frame.len(), MAX_UNIVERSE_SIZE)];
+    // The payload starts with the start code.
+    let payload_size = 1 + max(frame.len(), MIN_UNIVERSE_SIZE);
+    let (len_lsb, len_msb) = (payload_size as u8, (payload_size >> 8) as u8);
+    buf.clear();
+    buf.extend_from_slice(&[START_VAL, label, len_lsb, len_msb, start_code]);
+    buf.extend_from_slice(frame);
+    buf.resize(4 + payload_size, 0);
+    buf.push(END_VAL);
+}
+
+/// Apply a change of state message to a received universe, where the first slot is the start
+/// code.  The message gives the first of 40 consecutive slots it covers, in units of 8 slots,
+/// then a bit for each of them that is set if the slot changed, then the new value of each
+/// changed slot.
+pub fn apply_change_of_state(payload: &[u8], universe: &mut [u8]) -> Result<(), Error> {
+    const CHANGED_BITS_SIZE: usize = 5;
+    if payload.len() < 1 + CHANGED_BITS_SIZE {
+        return Err(Error::Protocol(format!(
+            "short change of state message of {} bytes",
+            payload.len()
+        )));
+    }
+    let start = payload[0] as usize * 8;
+    let changed = &payload[1..1 + CHANGED_BITS_SIZE];
+    let mut values = payload[1 + CHANGED_BITS_SIZE..].iter();
+    for bit in 0..CHANGED_BITS_SIZE * 8 {
+        if changed[bit / 8] & (1 << (bit % 8)) == 0 {
+            continue;
+        }
+        let value = values
+            .next()
+            .ok_or_else(|| Error::Protocol("missing change of state value".to_string()))?;
+        if let Some(slot) = universe.get_mut(start + bit) {
+            *slot = *value;
+        }
+    }
+    Ok(())
+}
+
+/// A message received from the widget.
+#[derive(Debug, PartialEq)]
+pub struct Packet {
+    pub label: u8,
+    pub payload: Vec<u8>,
+}
+
+/// A message received from the widget, decoded according to the labels of the port it
+/// concerns.
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    /// The reply to a parameters request.
+    Parameters {
+        firmware_version: u16,
+        params: EnttecParams,
+    },
+    /// The reply to a serial number request.
+    SerialNumber(u32),
+    /// A DMX packet the widget received, or an RDM response it relayed, after the widget's
+    /// status flags.
+    ReceivedDmx {
+        status: u8,
+        start_code: u8,
+        data: Vec<u8>,
+    },
+    /// The slots that changed in the universe the widget is receiving, to be applied with
+    /// `apply_change_of_state`.
+    ChangeOfState(Vec<u8>),
+    /// No response arrived to an RDM request.
+    RdmTimeout,
+    /// A message this module doesn't decode, such as the reply to an identification query.
+    Other(Packet),
+}
+
+impl Message {
+    /// Decode a message.  Messages with a known label but a malformed payload fail.
+    pub fn decode(packet: Packet, labels: &Labels) -> Result<Self, Error> {
+        let payload = packet.payload;
+        Ok(match packet.label {
+            label if label == labels.get_parameters => Message::Parameters {
+                params: EnttecParams::parse(&payload)?,
+                firmware_version: u16::from_le_bytes([payload[0], payload[1]]),
+            },
+            GET_SERIAL_NUMBER => {
+                Message::SerialNumber(parse_serial_number(&payload).ok_or_else(|| {
+                    Error::Protocol(format!("invalid serial number {:02x?}", payload))
+                })?)
+            }
+            label if label == labels.received_dmx => match payload[..] {
+                [status, start_code, ..] => Message::ReceivedDmx {
+                    status,
+                    start_code,
+                    data: payload[2..].to_vec(),
+                },
+                _ => {
+                    return Err(Error::Protocol(format!(
+                        "short received DMX message of {} bytes",
+                        payload.len()
+                    )))
+                }
+            },
+            label if label == labels.received_dmx_change_of_state => {
+                Message::ChangeOfState(payload)
+            }
+            label if label == labels.rdm_timeout => Message::RdmTimeout,
+            label => Message::Other(Packet { label, payload }),
+        })
+    }
+}
+
+/// Accumulates bytes read from the widget and splits them into messages.
+#[derive(Default)]
+pub struct PacketReader {
+    buf: Vec<u8>,
+}
+
+impl PacketReader {
+    /// Read from the widget until a complete message is available or the deadline passes.
+    /// Read timeouts are retried, so without a deadline this blocks until the widget sends
+    /// something.
+    pub fn read<R: Read>(
+        &mut self,
+        mut r: R,
+        deadline: Option<Instant>,
+    ) -> Result<Option<Packet>, Error> {
+        let mut chunk = [0; MAX_PAYLOAD_SIZE];
+        loop {
+            if let Some(packet) = self.parse() {
+                return Ok(Some(packet));
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return Ok(None);
+            }
+            match r.read(&mut chunk) {
+                Ok(n) => self.buf.extend_from_slice(&chunk[0..n]),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Pop the first complete message off the buffer, discarding any malformed data before it.
+    pub fn parse(&mut self) -> Option<Packet> {
+        loop {
+            match self.buf.iter().position(|b| *b == START_VAL) {
+                Some(start) => {
+                    self.buf.drain(0..start);
+                }
+                None => {
+                    self.buf.clear();
+                    return None;
+                }
+            }
+            if self.buf.len() < 4 {
+                return None;
+            }
+            let size = self.buf[2] as usize | (self.buf[3] as usize) << 8;
+            if size > MAX_PAYLOAD_SIZE {
+                self.buf.remove(0);
+                continue;
+            }
+            if self.buf.len() < size + 5 {
+                return None;
+            }
+            if self.buf[size + 4] != END_VAL {
+                self.buf.remove(0);
+                continue;
+            }
+            let packet = Packet {
+                label: self.buf[1],
+                payload: self.buf[4..size + 4].to_vec(),
+            };
+            self.buf.drain(0..size + 5);
+            return Some(packet);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply_change_of_state() {
+        let mut universe = [0; MAX_UNIVERSE_SIZE + 1];
+        // Slots 8 + 1 and 8 + 9 changed.
+        apply_change_of_state(&[1, 0b10, 0b10, 0, 0, 0, 7, 9], &mut universe).unwrap();
+        assert_eq!(universe[9], 7);
+        assert_eq!(universe[17], 9);
+        assert_eq!(universe.iter().filter(|slot| **slot != 0).count(), 2);
+        assert!(apply_change_of_state(&[0, 1, 0, 0, 0, 0], &mut universe).is_err());
+        assert!(apply_change_of_state(&[0, 1], &mut universe).is_err());
+    }
+
+    #[test]
+    fn test_parse_serial_number() {
+        assert_eq!(
+            parse_serial_number(&[0x78, 0x56, 0x34, 0x12]),
+            Some(12_345_678)
+        );
+        assert_eq!(parse_serial_number(&[0x0A, 0, 0, 0]), None);
+        assert_eq!(parse_serial_number(&[0x01]), None);
+    }
+
+    #[test]
+    fn test_packet_reader() {
+        let mut reader = PacketReader::default();
+        // Leading garbage, a packet with a bad end byte, and a good packet split across reads.
+        reader
+            .buf
+            .extend_from_slice(&[1, 2, START_VAL, 5, 1, 0, 9, 0, START_VAL, 5, 2, 0]);
+        assert_eq!(reader.parse(), None);
+        reader.buf.extend_from_slice(&[0, 1, END_VAL]);
+        assert_eq!(
+            reader.parse(),
+            Some(Packet {
+                label: 5,
+                payload: vec![0, 1]
+            })
+        );
+        assert_eq!(reader.parse(), None);
+        assert!(reader.buf.is_empty());
+    }
+
+    #[test]
+    fn test_decode() {
+        let decode = |label, payload: &[u8]| {
+            let packet = Packet {
+                label,
+                payload: payload.to_vec(),
+            };
+            Message::decode(packet, &PORT2_LABELS)
+        };
+        assert_eq!(
+            decode(137, &[0x04, 0x01, 9, 1, 40]).unwrap(),
+            Message::Parameters {
+                firmware_version: 0x0104,
+                params: EnttecParams::new(9, 1, 40).unwrap(),
+            }
+        );
+        assert_eq!(
+            decode(GET_SERIAL_NUMBER, &[0x21, 0x43, 0, 0]).unwrap(),
+            Message::SerialNumber(4321)
+        );
+        assert_eq!(
+            decode(156, &[0, 0, 1, 2]).unwrap(),
+            Message::ReceivedDmx {
+                status: 0,
+                start_code: 0,
+                data: vec![1, 2],
+            }
+        );
+        assert!(decode(156, &[0]).is_err());
+        assert_eq!(decode(218, &[]).unwrap(), Message::RdmTimeout);
+        // The first port's labels mean nothing on the second.
+        assert_eq!(
+            decode(5, &[0, 0]).unwrap(),
+            Message::Other(Packet {
+                label: 5,
+                payload: vec![0, 0],
+            })
+        );
+    }
+
+    #[test]
+    fn test_encode_frame() -> Result<(), Error> {
+        let mut buf = Vec::with_capacity(MAX_FRAME_MESSAGE_SIZE);
+        encode_frame(6, 0, &[1, 2], &mut buf);
+        assert_eq!(&buf[0..5], &[START_VAL, 6, 25, 0, 0]);
+        assert_eq!(&buf[5..7], &[1, 2]);
+        assert_eq!(buf.len(), 4 + 1 + MIN_UNIVERSE_SIZE + 1);
+        assert_eq!(buf.last(), Some(&END_VAL));
+        let mut padded = [0; MIN_UNIVERSE_SIZE];
+        padded[0..2].copy_from_slice(&[1, 2]);
+        let mut written = Vec::new();
+        write_packet(6, &padded, true, &mut written)?;
+        assert_eq!(buf, written);
+
+        let capacity = buf.capacity();
+        encode_frame(129, 0x17, &[1; 600], &mut buf);
+        assert_eq!(&buf[0..5], &[START_VAL, 129, 0x01, 0x02, 0x17]);
+        assert_eq!(buf.len(), MAX_FRAME_MESSAGE_SIZE);
+        assert_eq!(buf.capacity(), capacity);
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+//! Duplicating output to several ports.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::OnceLock;
+
+use crate::{DmxFrame, DmxPort, Error, PortStatus};
+
+/// A port that writes every frame to each of several child ports, for example to mirror a
+/// universe driving real fixtures to a visualizer over Art-Net.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FanOutPort {
+    ports: Vec<Box<dyn DmxPort>>,
+    #[serde(skip)]
+    name: OnceLock<String>,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+}
+
+impl FanOutPort {
+    /// Create a port writing to each of the provided ports.
+    pub fn new(ports: Vec<Box<dyn DmxPort>>) -> Self {
+        Self {
+            ports,
+            name: OnceLock::new(),
+            last_frame: None,
+        }
+    }
+
+    /// Add another port to write to.
+    pub fn push(&mut self, port: Box<dyn DmxPort>) {
+        self.ports.push(port);
+        self.name = OnceLock::new();
+    }
+
+    /// Return the ports written to.
+    pub fn ports(&self) -> &[Box<dyn DmxPort>] {
+        &self.ports
+    }
+
+    /// Return the ports written to.
+    pub fn into_ports(self) -> Vec<Box<dyn DmxPort>> {
+        self.ports
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for FanOutPort {
+    /// The names of the child ports, joined with `+`.
+    fn name(&self) -> &str {
+        self.name.get_or_init(|| {
+            let names: Vec<_> = self.ports.iter().map(|port| port.name()).collect();
+            names.join("+")
+        })
+    }
+
+    /// Open every child port.
+    fn open(&mut self) -> Result<(), Error> {
+        for port in &mut self.ports {
+            port.open()?;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        for port in &mut self.ports {
+            port.close();
+        }
+    }
+
+    /// A failing port doesn't stop the frame being written to the others; the first error is
+    /// returned.  The frame is kept as the last one if any port took it.
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let mut result = Ok(());
+        let mut written = false;
+        for port in &mut self.ports {
+            match port.write(frame) {
+                Ok(()) => written = true,
+                Err(e) if result.is_ok() => result = Err(e),
+                Err(_) => {}
+            }
+        }
+        if written {
+            self.last_frame = Some(DmxFrame::from(frame));
+        }
+        result
+    }
+
+    /// Null start code packets are frames, and are written as such so the last frame stays
+    /// current.
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        if start_code == 0 {
+            return self.write(data);
+        }
+        let mut result = Ok(());
+        for port in &mut self.ports {
+            if let Err(e) = port.write_with_start_code(start_code, data) {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// The status of the first child port that isn't open, if any.
+    fn status(&self) -> PortStatus {
+        self.ports
+            .iter()
+            .map(|port| port.status())
+            .find(|status| *status != PortStatus::Open)
+            .unwrap_or(PortStatus::Open)
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+
+    /// Every child port is checked; the first error is returned.
+    fn health_check(&mut self) -> Result<(), Error> {
+        let mut result = Ok(());
+        for port in &mut self.ports {
+            if let Err(e) = port.health_check() {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+}
+
+impl fmt::Display for FanOutPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "fan-out to ")?;
+        for (i, port) in self.ports.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+
+    #[test]
+    fn test_write() {
+        let (first, second) = (MockDmxPort::new(), MockDmxPort::new());
+        let mut port = FanOutPort::new(vec![Box::new(first.clone())]);
+        assert_eq!(port.name(), "mock");
+        port.push(Box::new(second.clone()));
+        assert_eq!(port.name(), "mock+mock");
+        assert_eq!(port.status(), PortStatus::Closed);
+        port.open().unwrap();
+        assert!(port.is_open());
+
+        // A failing port doesn't stop the others being written to.
+        first.fail_next_write(Error::Timeout);
+        assert!(matches!(port.write(&[1]), Err(Error::Timeout)));
+        assert_eq!(first.write_count(), 0);
+        assert_eq!(second.frames(), vec![vec![1]]);
+        assert_eq!(&port.last_frame().unwrap()[0..1], &[1]);
+        assert_eq!(
+            port.status(),
+            PortStatus::Errored(Error::Timeout.to_string())
+        );
+        port.write(&[2]).unwrap();
+        assert!(port.is_open());
+        assert_eq!(first.frames(), second.frames()[1..]);
+    }
+
+    #[test]
+    fn test_write_range() {
+        let (first, second) = (MockDmxPort::new(), MockDmxPort::new());
+        let mut port = FanOutPort::new(vec![Box::new(first.clone()), Box::new(second.clone())]);
+        port.open().unwrap();
+        port.write_range(1, &[10]).unwrap();
+        port.write_range(2, &[20]).unwrap();
+        assert_eq!(&first.last_frame().unwrap()[0..3], &[10, 20, 0]);
+        assert_eq!(first.last_frame(), second.last_frame());
+    }
+
+    #[test]
+    fn test_write_with_start_code() {
+        let (first, second) = (MockDmxPort::new(), MockDmxPort::new());
+        let mut port = FanOutPort::new(vec![Box::new(first.clone()), Box::new(second.clone())]);
+        port.open().unwrap();
+        second.fail_next_write(Error::Timeout);
+        assert!(port.write_with_start_code(0x17, &[1]).is_err());
+        assert_eq!(first.start_code_packets(), vec![(0x17, vec![1])]);
+        port.write_with_start_code(0xDD, &[2]).unwrap();
+        assert_eq!(second.start_code_packets(), vec![(0xDD, vec![2])]);
+        port.write_with_start_code(0, &[3]).unwrap();
+        assert_eq!(first.last_frame(), Some(vec![3]));
+        assert_eq!(second.last_frame(), Some(vec![3]));
+        port.write_range(2, &[4]).unwrap();
+        assert_eq!(&first.last_frame().unwrap()[0..2], &[3, 4]);
+    }
+}
@@ -0,0 +1,229 @@
+//! A C ABI for listing, opening and writing to ports.  The declarations are in
+//! `include/rust_dmx.h`.  Build the shared library with
+//! `cargo rustc --release --features ffi --crate-type cdylib`.
+//!
+//! Functions that can fail return NULL or -1, and leave a description of the failure for
+//! `dmx_last_error` on the calling thread.  A panic is caught at the boundary and reported the
+//! same way, since unwinding into C would abort the host process.
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::{available_ports, open_port_by_name, DmxPort, Error};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // Interior nul bytes can't be represented; drop them rather than the whole message.
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Record the error, returning the value C callers check for failure.
+fn fail<T>(e: Error, failure: T) -> T {
+    set_last_error(e.to_string());
+    failure
+}
+
+/// Run the body of an exported function, returning the failure value if it panics.
+fn guard<T>(failure: T, body: impl FnOnce() -> T) -> T {
+    catch_unwind(AssertUnwindSafe(body)).unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        set_last_error(format!("panicked: {}", message));
+        failure
+    })
+}
+
+/// A listing of ports, with the names C callers can open them by.
+pub struct DmxPortList {
+    names: Vec<CString>,
+}
+
+/// An open port.
+pub struct DmxPortHandle {
+    port: Box<dyn DmxPort>,
+}
+
+/// Return a description of the last failure on this thread, or NULL if nothing has failed.
+/// The string is valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn dmx_last_error() -> *const c_char {
+    guard(ptr::null(), || {
+        LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+    })
+}
+
+/// List the available ports.  Returns NULL on failure.  Free the list with
+/// `dmx_port_list_free`.
+#[no_mangle]
+pub extern "C" fn dmx_available_ports() -> *mut DmxPortList {
+    guard(ptr::null_mut(), || {
+        let ports = match available_ports() {
+            Ok(ports) => ports,
+            Err(e) => return fail(e, ptr::null_mut()),
+        };
+        let names = ports
+            .into_iter()
+            .filter_map(|port| CString::new(port.name).ok())
+            .collect();
+        Box::into_raw(Box::new(DmxPortList { names }))
+    })
+}
+
+/// Return the number of ports in a listing.
+///
+/// # Safety
+///
+/// `list` must have been returned by `dmx_available_ports` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn dmx_port_list_len(list: *const DmxPortList) -> usize {
+    guard(0, || {
+        let list = &*list;
+        list.names.len()
+    })
+}
+
+/// Return the name of a port in a listing, or NULL if the index is out of range.  The name is
+/// valid until the listing is freed.
+///
+/// # Safety
+///
+/// `list` must have been returned by `dmx_available_ports` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn dmx_port_list_name(
+    list: *const DmxPortList,
+    index: usize,
+) -> *const c_char {
+    guard(ptr::null(), || {
+        let list = &*list;
+        list.names
+            .get(index)
+            .map_or(ptr::null(), |name| name.as_ptr())
+    })
+}
+
+/// Free a listing.  Passing NULL does nothing.
+///
+/// # Safety
+///
+/// `list` must be NULL or have been returned by `dmx_available_ports` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn dmx_port_list_free(list: *mut DmxPortList) {
+    guard((), || {
+        if !list.is_null() {
+            drop(Box::from_raw(list));
+        }
+    })
+}
+
+/// Open a port by name, or the first port whose name or description contains the provided
+/// text.  Returns NULL on failure.  Close the port with `dmx_close`.
+///
+/// # Safety
+///
+/// `name` must be a valid nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn dmx_open(name: *const c_char) -> *mut DmxPortHandle {
+    guard(ptr::null_mut(), || {
+        let name = match CStr::from_ptr(name).to_str() {
+            Ok(name) => name,
+            Err(e) => {
+                set_last_error(format!("port name is not UTF-8: {}", e));
+                return ptr::null_mut();
+            }
+        };
+        match open_port_by_name(name) {
+            Ok(port) => Box::into_raw(Box::new(DmxPortHandle { port })),
+            Err(e) => fail(e, ptr::null_mut()),
+        }
+    })
+}
+
+/// Write a frame of `len` channel levels to a port.  Returns 0 on success and -1 on failure.
+///
+/// # Safety
+///
+/// `port` must have been returned by `dmx_open` and not yet closed, and `frame` must point to
+/// at least `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn dmx_write(
+    port: *mut DmxPortHandle,
+    frame: *const u8,
+    len: usize,
+) -> c_int {
+    guard(-1, || {
+        let frame = if len == 0 {
+            &[]
+        } else {
+            slice::from_raw_parts(frame, len)
+        };
+        let port = &mut *port;
+        match port.port.write(frame) {
+            Ok(()) => 0,
+            Err(e) => fail(e, -1),
+        }
+    })
+}
+
+/// Close a port and free it.  Passing NULL does nothing.
+///
+/// # Safety
+///
+/// `port` must be NULL or have been returned by `dmx_open` and not yet closed.
+#[no_mangle]
+pub unsafe extern "C" fn dmx_close(port: *mut DmxPortHandle) {
+    guard((), || {
+        if !port.is_null() {
+            let mut port = Box::from_raw(port);
+            port.port.close();
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ffi() {
+        unsafe {
+            let list = dmx_available_ports();
+            assert!(!list.is_null());
+            let names: Vec<_> = (0..dmx_port_list_len(list))
+                .map(|index| CStr::from_ptr(dmx_port_list_name(list, index)))
+                .collect();
+            assert!(names.iter().any(|name| name.to_str() == Ok("offline")));
+            assert!(dmx_port_list_name(list, usize::MAX).is_null());
+            dmx_port_list_free(list);
+
+            let name = CString::new("offline").unwrap();
+            let port = dmx_open(name.as_ptr());
+            assert!(!port.is_null());
+            assert_eq!(dmx_write(port, [1, 2, 3].as_ptr(), 3), 0);
+            dmx_close(port);
+
+            let name = CString::new("no such port").unwrap();
+            assert!(dmx_open(name.as_ptr()).is_null());
+            // The error is that of a provider that couldn't list its ports, if there is one.
+            assert!(!CStr::from_ptr(dmx_last_error()).to_bytes().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_guard() {
+        assert_eq!(guard(-1, || 0), 0);
+        assert_eq!(guard(-1, || panic!("wedged")), -1);
+        let error = unsafe { CStr::from_ptr(dmx_last_error()) };
+        assert_eq!(error.to_str().unwrap(), "panicked: wedged");
+    }
+}
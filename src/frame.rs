@@ -0,0 +1,205 @@
+//! A full DMX universe, addressed by channel number.
+
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+/// The number of channels in a DMX universe.
+pub const UNIVERSE_SIZE: usize = 512;
+
+/// The levels of every channel in a DMX universe.  Channels are numbered from 1 as on a lighting
+/// desk; slicing the frame indexes from 0 like any other byte slice.  Frames dereference to
+/// `[u8]`, so they can be written to a port directly with `port.write(&frame)`.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct DmxFrame([u8; UNIVERSE_SIZE]);
+
+impl DmxFrame {
+    /// Create a frame with every channel at zero.
+    pub fn new() -> Self {
+        Self([0; UNIVERSE_SIZE])
+    }
+
+    /// Return the level of a channel, or None if the channel number is outside 1 to 512.
+    pub fn get(&self, channel: u16) -> Option<u8> {
+        index(channel).map(|i| self.0[i])
+    }
+
+    /// Set the level of a channel.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel number is outside 1 to 512.
+    pub fn set(&mut self, channel: u16, level: u8) {
+        self.0[checked_index(channel)] = level;
+    }
+
+    /// Set consecutive channels starting at the provided one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the channels are outside 1 to 512.
+    pub fn set_range(&mut self, first_channel: u16, levels: &[u8]) {
+        let start = checked_index(first_channel);
+        self.0[start..start + levels.len()].copy_from_slice(levels);
+    }
+
+    /// Return a 16-bit level from a pair of channels, the coarse byte in the provided channel
+    /// and the fine byte in the one after, or None if either is outside 1 to 512.
+    pub fn get_u16(&self, coarse_channel: u16) -> Option<u16> {
+        let coarse = self.get(coarse_channel)?;
+        let fine = self.get(coarse_channel.checked_add(1)?)?;
+        Some(u16::from_be_bytes([coarse, fine]))
+    }
+
+    /// Set a 16-bit level across a pair of channels, as fixtures take pan, tilt and fine
+    /// dimming: the coarse byte in the provided channel and the fine byte in the one after.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either channel is outside 1 to 512.
+    pub fn set_u16(&mut self, coarse_channel: u16, level: u16) {
+        let start = checked_index(coarse_channel);
+        checked_index(coarse_channel.saturating_add(1));
+        self.0[start..start + 2].copy_from_slice(&level.to_be_bytes());
+    }
+
+    /// Set a pair of channels to a fraction of full, from 0 to 1, rounded to the nearest
+    /// 16-bit level, so 1 is 65535 and 0.5 is 32768.  Fractions outside 0 to 1 are clamped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if either channel is outside 1 to 512.
+    pub fn set_u16_fraction(&mut self, coarse_channel: u16, fraction: f64) {
+        let level = (fraction.clamp(0.0, 1.0) * f64::from(u16::MAX)).round() as u16;
+        self.set_u16(coarse_channel, level);
+    }
+
+    /// Set every channel to the same level.
+    pub fn fill(&mut self, level: u8) {
+        self.0.fill(level);
+    }
+
+    /// Iterate over the channel numbers and levels of the frame.
+    pub fn channels(&self) -> impl Iterator<Item = (u16, u8)> + '_ {
+        (1..).zip(self.0.iter().copied())
+    }
+}
+
+fn index(channel: u16) -> Option<usize> {
+    match channel as usize {
+        0 => None,
+        c if c > UNIVERSE_SIZE => None,
+        c => Some(c - 1),
+    }
+}
+
+fn checked_index(channel: u16) -> usize {
+    index(channel).unwrap_or_else(|| panic!("DMX channel {} is outside 1 to 512", channel))
+}
+
+impl Default for DmxFrame {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for DmxFrame {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl DerefMut for DmxFrame {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl AsRef<[u8]> for DmxFrame {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Copy the start of a universe from a shorter frame; the remaining channels are zero and
+/// anything beyond 512 channels is ignored, as for `DmxPort::write`.
+impl From<&[u8]> for DmxFrame {
+    fn from(levels: &[u8]) -> Self {
+        let mut frame = Self::new();
+        let len = levels.len().min(UNIVERSE_SIZE);
+        frame.0[..len].copy_from_slice(&levels[..len]);
+        frame
+    }
+}
+
+impl From<[u8; UNIVERSE_SIZE]> for DmxFrame {
+    fn from(levels: [u8; UNIVERSE_SIZE]) -> Self {
+        Self(levels)
+    }
+}
+
+impl fmt::Debug for DmxFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Trailing zeros make up most of a typical frame and only add noise.
+        let used = self.0.iter().rposition(|&l| l != 0).map_or(0, |i| i + 1);
+        f.debug_tuple("DmxFrame").field(&&self.0[..used]).finish()
+    }
+}
+
+/// Frames are serialized as a sequence of levels.
+impl Serialize for DmxFrame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0[..].serialize(serializer)
+    }
+}
+
+/// Shorter sequences are padded with zeros, so hand-written configurations can leave out
+/// unused channels.
+impl<'de> Deserialize<'de> for DmxFrame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let levels = Vec::<u8>::deserialize(deserializer)?;
+        if levels.len() > UNIVERSE_SIZE {
+            return Err(de::Error::invalid_length(
+                levels.len(),
+                &"at most 512 channel levels",
+            ));
+        }
+        Ok(Self::from(&levels[..]))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_channels() {
+        let mut frame = DmxFrame::new();
+        frame.set(1, 10);
+        frame.set_range(511, &[20, 30]);
+        assert_eq!(frame[0], 10);
+        assert_eq!(frame.get(512), Some(30));
+        assert_eq!(frame.get(0), None);
+        assert_eq!(frame.get(513), None);
+        assert_eq!(frame.channels().nth(510), Some((511, 20)));
+        assert_eq!(frame.len(), UNIVERSE_SIZE);
+    }
+
+    #[test]
+    fn test_u16() {
+        let mut frame = DmxFrame::new();
+        frame.set_u16(1, 0x1234);
+        assert_eq!(&frame[0..2], &[0x12, 0x34]);
+        assert_eq!(frame.get_u16(1), Some(0x1234));
+        frame.set_u16_fraction(511, 0.5);
+        assert_eq!(&frame[510..512], &[0x80, 0x00]);
+        frame.set_u16_fraction(3, 2.0);
+        assert_eq!(frame.get_u16(3), Some(u16::MAX));
+        assert_eq!(frame.get_u16(512), None);
+        assert!(std::panic::catch_unwind(|| DmxFrame::new().set_u16(512, 1)).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! How many slots of each frame a port transmits.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+
+use crate::UNIVERSE_SIZE;
+
+/// How many slots a port transmits of each frame written to it.  A rig with fixtures patched
+/// only into the first 128 channels can transmit 128 slots, shortening each packet so the
+/// interface can refresh more often: a full universe takes about 23 ms on the wire, and 128
+/// slots about 6 ms.
+///
+/// Protocols with a minimum packet size, such as the 24 slots of a serial DMX packet, still
+/// pad shorter frames to that size, with the value of the port's `Padding`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FrameLength {
+    /// Transmit each frame at the length it was written, padded as the port's `Padding` says.
+    #[default]
+    AsWritten,
+    /// Transmit this many slots, up to 512, padding shorter frames and dropping the slots of
+    /// longer ones beyond it.
+    Fixed(u16),
+}
+
+/// How a port pads frames shorter than it transmits.  By default frames are padded with
+/// zeros only as far as the protocol requires.  Some dimmers only respond to a full universe,
+/// which a minimum of 512 slots sends them; others treat trailing slots at zero as an
+/// instruction, which a different pad value avoids.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Padding {
+    /// Pad shorter frames to at least this many slots, up to 512.
+    #[serde(default)]
+    pub min_slots: u16,
+    /// The level of the slots added.
+    #[serde(default)]
+    pub value: u8,
+}
+
+impl Padding {
+    /// Pad frames to at least the provided number of slots with zeros.
+    pub fn to(min_slots: u16) -> Self {
+        Self {
+            min_slots,
+            value: 0,
+        }
+    }
+
+    /// Pad with the provided level rather than zero.
+    pub fn with_value(self, value: u8) -> Self {
+        Self { value, ..self }
+    }
+}
+
+/// How a port shapes each frame before transmitting it: how many slots it transmits, and how
+/// frames shorter than that are padded.  Ports that can change the length of the frames they
+/// transmit hold one, set with their `with_shape`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameShape {
+    length: FrameLength,
+    padding: Padding,
+    /// Frames changed to the length transmitted.
+    #[serde(skip)]
+    buf: Vec<u8>,
+}
+
+impl FrameShape {
+    /// Transmit frames at the length they're written, padded with zeros only as far as the
+    /// protocol requires.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how many slots of each frame are transmitted.
+    pub fn with_length(mut self, length: FrameLength) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Return how many slots of each frame are transmitted.
+    pub fn length(&self) -> FrameLength {
+        self.length
+    }
+
+    /// Set how frames shorter than transmitted are padded.
+    pub fn with_padding(mut self, padding: Padding) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Return how frames shorter than transmitted are padded.
+    pub fn padding(&self) -> Padding {
+        self.padding
+    }
+
+    /// Return the frame to transmit, changed in a buffer if it needs to be.  `required` maps a
+    /// number of slots to the number the protocol sends in a packet holding them, such as
+    /// its minimum; the slots added are padded with the padding value too.
+    pub(crate) fn apply<'a>(
+        &'a mut self,
+        frame: &'a [u8],
+        required: impl FnOnce(usize) -> usize,
+    ) -> &'a [u8] {
+        let slots = match self.length {
+            FrameLength::AsWritten => frame.len().max(usize::from(self.padding.min_slots)),
+            FrameLength::Fixed(slots) => usize::from(slots),
+        };
+        let slots = min(required(min(slots, UNIVERSE_SIZE)), UNIVERSE_SIZE);
+        if frame.len() == slots {
+            return frame;
+        }
+        self.buf.clear();
+        self.buf
+            .extend_from_slice(&frame[..min(frame.len(), slots)]);
+        self.buf.resize(slots, self.padding.value);
+        &self.buf
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let apply =
+            |shape: FrameShape, frame: &[u8]| shape.clone().apply(frame, |slots| slots).to_vec();
+        let fixed = |slots| FrameShape::new().with_length(FrameLength::Fixed(slots));
+        assert_eq!(apply(FrameShape::new(), &[1, 2, 3]), &[1, 2, 3]);
+        assert_eq!(apply(fixed(2), &[1, 2, 3]), &[1, 2]);
+        assert_eq!(apply(fixed(4), &[1, 2, 3]), &[1, 2, 3, 0]);
+        assert_eq!(apply(fixed(600), &[]).len(), 512);
+        let padding = Padding::to(4).with_value(9);
+        let padded = FrameShape::new().with_padding(padding);
+        assert_eq!(apply(padded.clone(), &[1]), &[1, 9, 9, 9]);
+        assert_eq!(apply(padded, &[1; 5]), &[1; 5]);
+        assert_eq!(apply(fixed(2).with_padding(padding), &[1]), &[1, 9]);
+        let everything = FrameShape::new().with_padding(Padding::to(1000));
+        assert_eq!(apply(everything, &[]).len(), 512);
+    }
+
+    #[test]
+    fn test_required() {
+        let mut shape = FrameShape::new().with_padding(Padding::default().with_value(7));
+        assert_eq!(shape.apply(&[1], |slots| slots.max(3)), &[1, 7, 7]);
+        assert_eq!(
+            shape.apply(&[1; 3], |slots| slots + slots % 2),
+            &[1, 1, 1, 7]
+        );
+        assert_eq!(shape.apply(&[1; 512], |slots| slots + 1).len(), 512);
+    }
+}
@@ -0,0 +1,164 @@
+//! Receiving DMX from async code.
+
+use std::fmt;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::SystemTime;
+use tokio::sync::mpsc;
+
+use crate::{DmxInputPort, Error, ReceivedFrame};
+
+/// How many received frames are held for a `FrameStream` that isn't keeping up before the
+/// port stops being read.
+const STREAM_CAPACITY: usize = 64;
+
+/// A frame delivered by a `FrameStream`, with when and where it was received.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamedFrame {
+    pub frame: ReceivedFrame,
+    /// When the frame was read from the port.
+    pub received_at: SystemTime,
+    /// The name of the input port the frame was received on.
+    pub source: Arc<str>,
+}
+
+/// The frames received by an input port, for async applications.  The port is opened and
+/// read on a thread of its own, so reading never blocks the executor:
+///
+/// ```no_run
+/// # async fn run(port: Box<dyn rust_dmx::DmxInputPort>) {
+/// let mut frames = rust_dmx::FrameStream::new(port);
+/// while let Some(received) = frames.next().await {
+///     match received {
+///         Ok(received) => println!("{:?} from {}", received.frame.data, received.source),
+///         Err(e) => eprintln!("{}", e),
+///     }
+/// }
+/// # }
+/// ```
+///
+/// Errors that may go away by themselves, such as timeouts, are delivered and reading goes
+/// on; any other error, including failing to open the port, is delivered last and ends the
+/// stream.  With the `futures` feature, the stream implements `futures::Stream`, for
+/// applications that combine streams.
+///
+/// Dropping the stream stops reading once the port delivers its next frame, then the port is
+/// closed.
+pub struct FrameStream {
+    receiver: mpsc::Receiver<Result<StreamedFrame, Error>>,
+    source: Arc<str>,
+}
+
+impl FrameStream {
+    /// Start reading the provided port, opening it first if it isn't open.
+    pub fn new(mut port: Box<dyn DmxInputPort>) -> Self {
+        let source: Arc<str> = Arc::from(port.name());
+        let (sender, receiver) = mpsc::channel(STREAM_CAPACITY);
+        let name = source.clone();
+        thread::spawn(move || {
+            if let Err(e) = port.open() {
+                let _ = sender.blocking_send(Err(e));
+                return;
+            }
+            loop {
+                let received = port.read_frame().map(|frame| StreamedFrame {
+                    frame,
+                    received_at: SystemTime::now(),
+                    source: name.clone(),
+                });
+                let last = received.as_ref().is_err_and(|e| !e.is_transient());
+                if sender.blocking_send(received).is_err() || last {
+                    break;
+                }
+            }
+            port.close();
+        });
+        Self { receiver, source }
+    }
+
+    /// Wait for the next frame.  Returns None once the stream has ended.
+    pub async fn next(&mut self) -> Option<Result<StreamedFrame, Error>> {
+        self.receiver.recv().await
+    }
+
+    /// Poll for the next frame, as `futures::Stream::poll_next` does.
+    pub fn poll_next(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<StreamedFrame, Error>>> {
+        self.receiver.poll_recv(cx)
+    }
+
+    /// Return the name of the input port being read.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures_core::Stream for FrameStream {
+    type Item = Result<StreamedFrame, Error>;
+
+    fn poll_next(self: std::pin::Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_next(cx)
+    }
+}
+
+impl fmt::Debug for FrameStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameStream")
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{loopback, DmxPort};
+
+    #[test]
+    fn test_frame_stream() {
+        let (mut output, input) = loopback();
+        output.open().unwrap();
+        output.write(&[1, 2]).unwrap();
+        output.write(&[3]).unwrap();
+        drop(output);
+        let mut frames = FrameStream::new(Box::new(input));
+        assert_eq!(frames.source(), "loopback");
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let received = frames.next().await.unwrap().unwrap();
+            assert_eq!(&received.frame.data[0..2], &[1, 2]);
+            assert_eq!(&*received.source, "loopback");
+            assert_eq!(frames.next().await.unwrap().unwrap().frame.data[0], 3);
+            assert!(matches!(frames.next().await, Some(Err(Error::PortClosed))));
+            assert!(frames.next().await.is_none());
+        });
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn test_stream() {
+        use futures_core::Stream;
+        use std::pin::Pin;
+
+        let (mut output, input) = loopback();
+        output.open().unwrap();
+        output.write(&[4]).unwrap();
+        let mut frames = FrameStream::new(Box::new(input));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let received = runtime
+            .block_on(std::future::poll_fn(|cx| {
+                Stream::poll_next(Pin::new(&mut frames), cx)
+            }))
+            .unwrap()
+            .unwrap();
+        assert_eq!(received.frame.data[0], 4);
+    }
+}
@@ -0,0 +1,454 @@
+//! Implementation of Enttec DMX USB Pro and Open DMX USB support that talks to their FTDI
+//! chips directly through libusb, bypassing the operating system's serial driver.
+
+use rusb::{Context, Device, DeviceHandle, UsbContext};
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::alias::write_aliased;
+use crate::trace;
+use crate::usb::{usb_context, UsbLocation};
+use crate::{
+    BreakTiming, DmxFrame, DmxPort, Error, FrameShape, HardwareInfo, PortKind, PortListing,
+    PortProvider, PortStatus, StatusTracker,
+};
+
+/// USB vendor ID of FTDI.
+const FTDI_VID: u16 = 0x0403;
+/// Product IDs of the single-interface chips DMX interfaces are built on: the FT232R and
+/// FT245R, and the FT-X series.
+const FTDI_PIDS: [u16; 2] = [0x6001, 0x6015];
+
+/// Product string reported by the Enttec DMX USB Pro.
+const DMX_USB_PRO_PRODUCT: &str = "DMX USB PRO";
+
+// FTDI vendor requests.
+const SIO_RESET: u8 = 0x00;
+const SIO_SET_MODEM_CTRL: u8 = 0x01;
+const SIO_SET_FLOW_CTRL: u8 = 0x02;
+const SIO_SET_BAUDRATE: u8 = 0x03;
+const SIO_SET_DATA: u8 = 0x04;
+const SIO_POLL_MODEM_STATUS: u8 = 0x05;
+const SIO_SET_LATENCY_TIMER: u8 = 0x09;
+
+const SIO_RESET_SIO: u16 = 0;
+const SIO_RESET_PURGE_TX: u16 = 2;
+/// Drive RTS low, which enables the line driver of Open DMX style interfaces.
+const SIO_SET_RTS_LOW: u16 = 0x0200;
+/// Baud rate divisor for 250 kbaud from the chips' 3 MHz baud clock.
+const DMX_BAUD_DIVISOR: u16 = 12;
+/// 8 data bits, no parity and 2 stop bits.
+const LINE_8N2: u16 = 8 | (2 << 11);
+const LINE_BREAK: u16 = 1 << 14;
+/// Modem status bit set once the transmitter has sent everything it was given.
+const STATUS_TRANSMITTER_EMPTY: u8 = 0x40;
+/// Milliseconds the chip waits before returning a partly filled packet to the host.
+const LATENCY_TIMER: u16 = 1;
+
+/// The chips have a single interface, which vendor requests address as interface A.
+const INTERFACE: u8 = 0;
+const INTERFACE_INDEX: u16 = 1;
+const ENDPOINT_OUT: u8 = 0x02;
+
+const USB_TIMEOUT: Duration = Duration::from_millis(100);
+
+// DMX USB Pro framing.
+const PRO_START: u8 = 0x7E;
+const PRO_END: u8 = 0xE7;
+const PRO_SEND_DMX: u8 = 6;
+
+// Universe size constraints.
+const MIN_UNIVERSE_SIZE: usize = 24;
+const MAX_UNIVERSE_SIZE: usize = 512;
+
+/// How the interface behind an FTDI chip expects to be driven.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FtdiMode {
+    /// An Enttec DMX USB Pro, which generates DMX timing itself from messages it is sent.
+    EnttecPro,
+    /// An Open DMX USB or similar interface with no microcontroller, whose line the host
+    /// drives directly, generating the break itself.
+    OpenDmx,
+}
+
+/// Return true if the device is an FTDI chip of the kind DMX interfaces use.
+fn is_ftdi(device: &Device<Context>) -> bool {
+    device.device_descriptor().is_ok_and(|descriptor| {
+        descriptor.vendor_id() == FTDI_VID && FTDI_PIDS.contains(&descriptor.product_id())
+    })
+}
+
+fn product(device: &Device<Context>) -> Option<String> {
+    let descriptor = device.device_descriptor().ok()?;
+    device
+        .open()
+        .ok()?
+        .read_product_string_ascii(&descriptor)
+        .ok()
+}
+
+/// A DMX port on an Enttec DMX USB Pro or Open DMX USB that talks to the FTDI chip directly
+/// instead of going through the operating system's serial driver, for lower and steadier
+/// per-frame latency.  On Linux the kernel's serial driver is detached from the device while
+/// the port is open.  On Windows the device must be bound to WinUSB, not FTDI's VCP driver,
+/// for libusb to open it.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FtdiDmxPort {
+    name: String,
+    location: UsbLocation,
+    pid: u16,
+    mode: FtdiMode,
+    #[serde(default)]
+    timing: BreakTiming,
+    #[serde(default)]
+    shape: FrameShape,
+    /// Shared by clones, which take turns writing whole packets.
+    #[serde(skip)]
+    handle: Option<Arc<Mutex<DeviceHandle<Context>>>>,
+    #[serde(skip)]
+    buf: Vec<u8>,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+impl FtdiDmxPort {
+    /// Create a port for the device, guessing its mode from its product string.
+    fn new(device: &Device<Context>) -> Self {
+        let location = UsbLocation::of(device);
+        let mode = match product(device).as_deref() {
+            Some(DMX_USB_PRO_PRODUCT) => FtdiMode::EnttecPro,
+            _ => FtdiMode::OpenDmx,
+        };
+        Self {
+            name: format!("ftdi:{}:{}", location.bus, location.address),
+            location,
+            pid: device
+                .device_descriptor()
+                .map_or(FTDI_PIDS[0], |descriptor| descriptor.product_id()),
+            mode,
+            timing: BreakTiming::default(),
+            shape: FrameShape::default(),
+            handle: None,
+            buf: Vec::new(),
+            last_frame: None,
+            status: StatusTracker::new(),
+        }
+    }
+
+    /// Drive the interface in the provided mode, for interfaces whose product string doesn't
+    /// say what they are.
+    pub fn with_mode(mut self, mode: FtdiMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Return how the interface is driven.
+    pub fn mode(&self) -> FtdiMode {
+        self.mode
+    }
+
+    /// Use the provided break and mark after break durations in Open DMX mode.
+    pub fn with_timing(mut self, timing: BreakTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Set how each frame is shaped before it is transmitted: how many of its slots are sent,
+    /// and how shorter frames are padded.  By default frames are transmitted at the length
+    /// they're written, padded with zeros only as far as the protocol requires.
+    pub fn with_shape(mut self, shape: FrameShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Return how each frame is shaped before it is transmitted.
+    pub fn shape(&self) -> &FrameShape {
+        &self.shape
+    }
+
+    /// Open the device and set its chip up for the mode.
+    fn open_device(&self) -> Result<DeviceHandle<Context>, Error> {
+        let handle = self.location.find(&usb_context()?, is_ftdi)?.open()?;
+        match handle.set_auto_detach_kernel_driver(true) {
+            Ok(()) | Err(rusb::Error::NotSupported) => {}
+            Err(e) => return Err(e.into()),
+        }
+        handle.claim_interface(INTERFACE)?;
+        control(&handle, SIO_RESET, SIO_RESET_SIO, INTERFACE_INDEX)?;
+        control(&handle, SIO_RESET, SIO_RESET_PURGE_TX, INTERFACE_INDEX)?;
+        control(
+            &handle,
+            SIO_SET_LATENCY_TIMER,
+            LATENCY_TIMER,
+            INTERFACE_INDEX,
+        )?;
+        control(&handle, SIO_SET_FLOW_CTRL, 0, INTERFACE_INDEX)?;
+        if self.mode == FtdiMode::OpenDmx {
+            control(&handle, SIO_SET_BAUDRATE, DMX_BAUD_DIVISOR, 0)?;
+            control(&handle, SIO_SET_DATA, LINE_8N2, INTERFACE_INDEX)?;
+            control(
+                &handle,
+                SIO_SET_MODEM_CTRL,
+                SIO_SET_RTS_LOW,
+                INTERFACE_INDEX,
+            )?;
+        }
+        Ok(handle)
+    }
+
+    /// Send a packet with the start code.  Only DMX frames, with the null start code, are
+    /// changed to the length transmitted.
+    fn write_packet(&mut self, start_code: u8, frame: &[u8]) -> Result<(), Error> {
+        let handle = self.handle.as_ref().ok_or(Error::NotOpen)?;
+        let handle = handle.lock().unwrap_or_else(PoisonError::into_inner);
+        let frame = match start_code {
+            0 => self
+                .shape
+                .apply(frame, |slots| slots.max(MIN_UNIVERSE_SIZE)),
+            _ => frame,
+        };
+        encode_packet(self.mode, start_code, frame, &mut self.buf);
+        match self.mode {
+            FtdiMode::EnttecPro => write_all(&handle, &self.buf),
+            FtdiMode::OpenDmx => {
+                control(
+                    &handle,
+                    SIO_SET_DATA,
+                    LINE_8N2 | LINE_BREAK,
+                    INTERFACE_INDEX,
+                )?;
+                sleep(self.timing.break_time);
+                control(&handle, SIO_SET_DATA, LINE_8N2, INTERFACE_INDEX)?;
+                sleep(self.timing.mark_after_break_time);
+                write_all(&handle, &self.buf)?;
+                // Wait for the line to go idle so the next break doesn't cut this packet short.
+                wait_until_sent(&handle)
+            }
+        }
+    }
+}
+
+/// Format a packet for the mode into the provided buffer: a Send DMX message for the DMX USB
+/// Pro, or the bytes to follow the break for an Open DMX.  The data is truncated to 512 slots
+/// and padded with zeros to the minimum of 24.
+fn encode_packet(mode: FtdiMode, start_code: u8, frame: &[u8], buf: &mut Vec<u8>) {
+    let frame = &frame[0..min(frame.len(), MAX_UNIVERSE_SIZE)];
+    let size = frame.len().max(MIN_UNIVERSE_SIZE);
+    buf.clear();
+    if mode == FtdiMode::EnttecPro {
+        // The payload is the start code and the slots.
+        let payload_size = (size + 1) as u16;
+        buf.push(PRO_START);
+        buf.push(PRO_SEND_DMX);
+        buf.extend_from_slice(&payload_size.to_le_bytes());
+    }
+    let start = buf.len();
+    buf.push(start_code);
+    buf.extend_from_slice(frame);
+    buf.resize(start + size + 1, 0);
+    if mode == FtdiMode::EnttecPro {
+        buf.push(PRO_END);
+    }
+}
+
+/// Send a vendor request to the chip.
+fn control(
+    handle: &DeviceHandle<Context>,
+    request: u8,
+    value: u16,
+    index: u16,
+) -> Result<(), Error> {
+    let request_type = rusb::request_type(
+        rusb::Direction::Out,
+        rusb::RequestType::Vendor,
+        rusb::Recipient::Device,
+    );
+    handle.write_control(request_type, request, value, index, &[], USB_TIMEOUT)?;
+    Ok(())
+}
+
+fn write_all(handle: &DeviceHandle<Context>, mut data: &[u8]) -> Result<(), Error> {
+    while !data.is_empty() {
+        let written = handle.write_bulk(ENDPOINT_OUT, data, USB_TIMEOUT)?;
+        data = &data[written..];
+    }
+    Ok(())
+}
+
+/// Poll the chip's modem status until its transmitter is empty.
+fn wait_until_sent(handle: &DeviceHandle<Context>) -> Result<(), Error> {
+    let request_type = rusb::request_type(
+        rusb::Direction::In,
+        rusb::RequestType::Vendor,
+        rusb::Recipient::Device,
+    );
+    let deadline = Instant::now() + USB_TIMEOUT;
+    let mut status = [0; 2];
+    loop {
+        handle.read_control(
+            request_type,
+            SIO_POLL_MODEM_STATUS,
+            0,
+            INTERFACE_INDEX,
+            &mut status,
+            USB_TIMEOUT,
+        )?;
+        if status[1] & STATUS_TRANSMITTER_EMPTY != 0 {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            return Err(Error::Timeout);
+        }
+    }
+}
+
+/// Lists the FTDI chips attached to this system.  FTDI chips are also used by plenty of
+/// non-DMX devices, so this may list devices that are not DMX interfaces.
+///
+/// The Enttec and Open DMX providers already list these interfaces by their serial ports, so
+/// this provider isn't among the built-in ones; register it with `register_provider` to list
+/// FTDI ports as well, or use it in place of the serial providers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FtdiProvider;
+
+impl PortProvider for FtdiProvider {
+    fn name(&self) -> &str {
+        "ftdi"
+    }
+
+    fn available_ports(&self) -> Result<PortListing, Error> {
+        Ok(usb_context()?
+            .devices()?
+            .iter()
+            .filter(is_ftdi)
+            .map(|device| Box::new(FtdiDmxPort::new(&device)) as Box<dyn DmxPort>)
+            .collect())
+    }
+
+    fn kinds(&self) -> &[PortKind] {
+        &[PortKind::EnttecPro, PortKind::OpenDmx]
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for FtdiDmxPort {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Open the port.
+    fn open(&mut self) -> Result<(), Error> {
+        if self.handle.is_some() {
+            return Ok(());
+        }
+        let result = self
+            .open_device()
+            .map(|handle| self.handle = Some(Arc::new(Mutex::new(handle))));
+        trace::opened(&self.name, &result);
+        self.status.record(result)
+    }
+
+    fn close(&mut self) {
+        self.status.clear();
+        if self.handle.take().is_some() {
+            trace::closed(&self.name);
+        }
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let result = self.write_packet(0, frame);
+        trace::wrote(&self.name, frame.len(), &result);
+        if result.is_ok() {
+            self.last_frame = Some(DmxFrame::from(frame));
+        }
+        self.status.record(result)
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        if start_code == 0 {
+            return self.write(data);
+        }
+        let result = self.write_packet(start_code, data);
+        self.status.record(result)
+    }
+
+    fn kind(&self) -> PortKind {
+        match self.mode {
+            FtdiMode::EnttecPro => PortKind::EnttecPro,
+            FtdiMode::OpenDmx => PortKind::OpenDmx,
+        }
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        HardwareInfo {
+            path: Some(format!("{}:{}", self.location.bus, self.location.address)),
+            serial_number: self.location.serial_number.clone(),
+            vid: Some(FTDI_VID),
+            pid: Some(self.pid),
+            ..HardwareInfo::default()
+        }
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(self.handle.is_some())
+    }
+
+    /// Look for the device among those attached.
+    fn health_check(&mut self) -> Result<(), Error> {
+        let result = match self.handle {
+            Some(_) => usb_context()
+                .and_then(|context| self.location.find(&context, is_ftdi))
+                .map(|_| ()),
+            None => Err(Error::NotOpen),
+        };
+        self.status.record(result)
+    }
+}
+
+impl fmt::Display for FtdiDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = self.kind();
+        write_aliased(f, &self.hardware(), |f| {
+            match &self.location.serial_number {
+                Some(sn) => write!(f, "{} {} (FTDI)", kind, sn),
+                None => write!(
+                    f,
+                    "{} on bus {} address {} (FTDI)",
+                    kind, self.location.bus, self.location.address
+                ),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_encode_packet() {
+        let mut buf = Vec::new();
+        encode_packet(FtdiMode::EnttecPro, 0, &[1, 2, 3], &mut buf);
+        // Header, start code, 24 slots and the end byte.
+        assert_eq!(buf.len(), 4 + 1 + 24 + 1);
+        assert_eq!(&buf[0..8], &[PRO_START, PRO_SEND_DMX, 25, 0, 0, 1, 2, 3]);
+        assert!(buf[8..29].iter().all(|&slot| slot == 0));
+        assert_eq!(buf[29], PRO_END);
+        encode_packet(FtdiMode::EnttecPro, 0x17, &[7; 600], &mut buf);
+        assert_eq!(&buf[0..5], &[PRO_START, PRO_SEND_DMX, 0x01, 0x02, 0x17]);
+        assert_eq!(buf.len(), 4 + 1 + 512 + 1);
+        encode_packet(FtdiMode::OpenDmx, 0, &[1, 2, 3], &mut buf);
+        assert_eq!(buf.len(), 1 + 24);
+        assert_eq!(&buf[0..4], &[0, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,67 @@
+//! Support for receiving DMX.
+
+use std::fmt;
+
+use crate::Error;
+
+/// A DMX packet received from an input port.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReceivedFrame {
+    /// The start code of the packet; 0 for ordinary dimmer data.
+    pub start_code: u8,
+    /// The slots following the start code.
+    pub data: Vec<u8>,
+}
+
+/// Trait for the general notion of a port that receives DMX.
+pub trait DmxInputPort: fmt::Display + Send {
+    /// Return the available input ports.  The ports will need to be opened before use.
+    fn available_ports() -> Result<InputPortListing, Error>
+    where
+        Self: Sized;
+
+    /// Return a string identifier for this port.
+    fn name(&self) -> &str;
+
+    /// Open the port for reading.  Implementations should no-op if this is
+    /// called twice rather than returning an error.
+    fn open(&mut self) -> Result<(), Error>;
+
+    /// Close the port.
+    fn close(&mut self);
+
+    /// Block until the next packet is received.
+    fn read_frame(&mut self) -> Result<ReceivedFrame, Error>;
+
+    /// Return a blocking iterator over received packets.
+    fn frames(&mut self) -> Frames<'_, Self>
+    where
+        Self: Sized,
+    {
+        Frames::new(self)
+    }
+}
+
+/// A listing of available input ports.
+pub type InputPortListing = Vec<Box<dyn DmxInputPort>>;
+
+/// Blocking iterator over the packets received by an input port.
+/// The iterator never ends on its own; it yields an error if reading fails.
+pub struct Frames<'a, P: DmxInputPort + ?Sized> {
+    port: &'a mut P,
+}
+
+impl<'a, P: DmxInputPort + ?Sized> Frames<'a, P> {
+    /// Iterate over the packets received by the provided port, which may be a trait object.
+    pub fn new(port: &'a mut P) -> Self {
+        Self { port }
+    }
+}
+
+impl<P: DmxInputPort + ?Sized> Iterator for Frames<'_, P> {
+    type Item = Result<ReceivedFrame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.port.read_frame())
+    }
+}
@@ -0,0 +1,181 @@
+//! Smoothing sparse frames into a steady stream.
+
+use std::time::{Duration, Instant};
+
+use crate::{DmxFrame, UNIVERSE_SIZE};
+
+/// Fills in the frames between the ones an application renders, for applications that render
+/// less often than the interface refreshes.  Each frame pushed is faded to over the time
+/// between the last two pushes, so the output follows the application's levels one frame
+/// behind, moving steadily instead of in steps.  `render` produces the frame for the present
+/// moment, to be written at the refresh rate, by an `OutputClock` for example:
+///
+/// ```no_run
+/// # use rust_dmx::{DmxPort, Interpolator, OutputClock};
+/// # use std::ops::ControlFlow;
+/// # use std::time::Duration;
+/// # fn run(
+/// #     port: &mut dyn DmxPort,
+/// #     targets: std::sync::mpsc::Receiver<Vec<u8>>,
+/// # ) -> Result<(), rust_dmx::Error> {
+/// let mut interpolator = Interpolator::new().with_dithering(true);
+/// OutputClock::new(Duration::from_millis(25)).run(port, |frame| {
+///     while let Ok(target) = targets.try_recv() {
+///         interpolator.push(&target);
+///     }
+///     interpolator.render(frame);
+///     ControlFlow::Continue(())
+/// })
+/// # }
+/// ```
+///
+/// Levels are interpolated with fractions kept, which 8-bit channels can't show, so a slow
+/// fade still moves in steps of one level.  With dithering, each channel alternates between
+/// the levels either side of its fractional level, in proportion, so the steps blur into a
+/// smooth fade at the cost of a flicker of one level.
+#[derive(Clone, Debug)]
+pub struct Interpolator {
+    /// The levels output when the last frame was pushed.
+    from: Vec<f32>,
+    /// The last frame pushed.
+    to: Vec<f32>,
+    /// The levels of the last frame rendered.
+    levels: Vec<f32>,
+    /// For dithering, how far each channel's output has been from its level so far.
+    error: Vec<f32>,
+    /// When the last frame was pushed, and the time to fade to it over.
+    pushed: Option<(Instant, Duration)>,
+    max_interval: Duration,
+    dithering: bool,
+}
+
+impl Default for Interpolator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpolator {
+    /// Create an interpolator outputting a blacked out frame, without dithering.
+    pub fn new() -> Self {
+        Self {
+            from: vec![0.0; UNIVERSE_SIZE],
+            to: vec![0.0; UNIVERSE_SIZE],
+            levels: vec![0.0; UNIVERSE_SIZE],
+            error: vec![0.0; UNIVERSE_SIZE],
+            pushed: None,
+            max_interval: Duration::from_secs(1),
+            dithering: false,
+        }
+    }
+
+    /// Dither levels that fall between two 8-bit values over successive frames.
+    pub fn with_dithering(mut self, dithering: bool) -> Self {
+        self.dithering = dithering;
+        self
+    }
+
+    /// Fade over at most the provided time, a second by default, so a frame pushed after the
+    /// application has paused isn't faded to as slowly as the pause was long.
+    pub fn with_max_interval(mut self, max_interval: Duration) -> Self {
+        self.max_interval = max_interval;
+        self
+    }
+
+    /// Start fading to a frame from the levels output now.  Frames shorter than a universe
+    /// fade the remaining channels to zero.  The first frame pushed is output straight away.
+    pub fn push(&mut self, frame: &[u8]) {
+        self.push_at(frame, Instant::now());
+    }
+
+    fn push_at(&mut self, frame: &[u8], now: Instant) {
+        self.advance(now);
+        self.from.copy_from_slice(&self.levels);
+        let frame = frame.iter().copied().chain(std::iter::repeat(0));
+        for (to, level) in self.to.iter_mut().zip(frame) {
+            *to = f32::from(level);
+        }
+        let interval = match self.pushed {
+            Some((last, _)) => (now - last).min(self.max_interval),
+            None => Duration::ZERO,
+        };
+        self.pushed = Some((now, interval));
+        self.advance(now);
+    }
+
+    /// Set the frame to the levels for the present moment.
+    pub fn render(&mut self, frame: &mut DmxFrame) {
+        self.render_at(frame, Instant::now());
+    }
+
+    fn render_at(&mut self, frame: &mut DmxFrame, now: Instant) {
+        self.advance(now);
+        for ((out, &level), error) in frame.iter_mut().zip(&self.levels).zip(&mut self.error) {
+            if self.dithering {
+                let wanted = level + *error;
+                *out = wanted.round().clamp(0.0, 255.0) as u8;
+                *error = wanted - f32::from(*out);
+            } else {
+                *out = level.round() as u8;
+            }
+        }
+    }
+
+    /// Move the levels to where the fade has got to by now.
+    fn advance(&mut self, now: Instant) {
+        let (pushed, interval) = match self.pushed {
+            Some(pushed) => pushed,
+            None => return,
+        };
+        let progress = if now - pushed >= interval {
+            1.0
+        } else {
+            (now - pushed).as_secs_f32() / interval.as_secs_f32()
+        };
+        for ((level, &from), &to) in self.levels.iter_mut().zip(&self.from).zip(&self.to) {
+            *level = from + (to - from) * progress;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut interpolator = Interpolator::new();
+        let mut frame = DmxFrame::new();
+        interpolator.push_at(&[100, 0], at(0));
+        interpolator.render_at(&mut frame, at(10));
+        assert_eq!(&frame[0..2], &[100, 0]);
+        interpolator.push_at(&[0, 200], at(100));
+        interpolator.render_at(&mut frame, at(125));
+        assert_eq!(&frame[0..2], &[75, 50]);
+        // A frame pushed part way carries on from the level reached.
+        interpolator.push_at(&[75, 0], at(150));
+        interpolator.render_at(&mut frame, at(175));
+        assert_eq!(&frame[0..2], &[63, 50]);
+        interpolator.render_at(&mut frame, at(500));
+        assert_eq!(&frame[0..2], &[75, 0]);
+    }
+
+    #[test]
+    fn test_dithering() {
+        let start = Instant::now();
+        let mut interpolator = Interpolator::new().with_dithering(true);
+        interpolator.push_at(&[10], start);
+        interpolator.push_at(&[11], start + Duration::from_secs(1));
+        // Half way between, alternating between the levels either side.
+        let mut frame = DmxFrame::new();
+        let mut total = 0;
+        for _ in 0..10 {
+            interpolator.render_at(&mut frame, start + Duration::from_millis(1500));
+            assert!(frame[0] == 10 || frame[0] == 11);
+            total += u32::from(frame[0]);
+        }
+        assert_eq!(total, 105);
+    }
+}
@@ -0,0 +1,208 @@
+//! Implementation of Philips Color Kinetics KiNET output over UDP.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::sync::Arc;
+
+use crate::{DmxFrame, DmxPort, Error, PortKind, PortStatus, StatusTracker};
+
+/// The UDP port KiNET power supplies listen on.
+pub const KINET_PORT: u16 = 6038;
+
+// Packet header constants.
+const MAGIC: &[u8; 4] = &[0x04, 0x01, 0xDC, 0x4A];
+const VERSION_1: u16 = 0x0001;
+const VERSION_2: u16 = 0x0002;
+const TYPE_DMX_OUT: u16 = 0x0101;
+const TYPE_PORT_OUT: u16 = 0x0108;
+/// Power supplies ignore the universe field; it's all ones by convention.
+const ANY_UNIVERSE: u32 = 0xFFFF_FFFF;
+
+const MAX_SLOTS: usize = 512;
+
+/// The version of KiNET to send, which depends on the power supply.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KinetVersion {
+    /// DMXOUT packets, for single-output supplies like the PDS-150e.
+    V1,
+    /// PORTOUT packets addressed to one output of a multi-output supply like the sPDS-480ca.
+    /// Outputs are numbered from 1.
+    V2 { output: u8 },
+}
+
+/// Format a DMX frame as a KiNET packet into the provided buffer.
+/// The frame is truncated to 512 slots.  Version 1 packets are padded to a full universe.
+fn write_packet(version: KinetVersion, frame: &[u8], buf: &mut Vec<u8>) {
+    let data = &frame[0..min(frame.len(), MAX_SLOTS)];
+    buf.clear();
+    buf.extend_from_slice(MAGIC);
+    match version {
+        KinetVersion::V1 => {
+            buf.extend_from_slice(&VERSION_1.to_le_bytes());
+            buf.extend_from_slice(&TYPE_DMX_OUT.to_le_bytes());
+            buf.extend_from_slice(&0u32.to_le_bytes()); // sequence; unused
+            buf.push(0); // port
+            buf.push(0); // padding
+            buf.extend_from_slice(&0u16.to_le_bytes()); // flags
+            buf.extend_from_slice(&ANY_UNIVERSE.to_le_bytes());
+            buf.push(0); // start code
+            buf.extend_from_slice(data);
+            buf.resize(buf.len() + MAX_SLOTS - data.len(), 0);
+        }
+        KinetVersion::V2 { output } => {
+            buf.extend_from_slice(&VERSION_2.to_le_bytes());
+            buf.extend_from_slice(&TYPE_PORT_OUT.to_le_bytes());
+            buf.extend_from_slice(&0u32.to_le_bytes()); // sequence; unused
+            buf.extend_from_slice(&ANY_UNIVERSE.to_le_bytes());
+            buf.push(output);
+            buf.push(0); // padding
+            buf.extend_from_slice(&0u16.to_le_bytes()); // flags
+            buf.extend_from_slice(&(data.len() as u16).to_le_bytes());
+            buf.extend_from_slice(&0u16.to_le_bytes()); // start code
+            buf.extend_from_slice(data);
+        }
+    }
+}
+
+/// A DMX port that drives a Color Kinetics power supply over KiNET.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KinetDmxPort {
+    name: String,
+    /// IP address of the power supply.
+    target: IpAddr,
+    version: KinetVersion,
+    #[serde(skip)]
+    socket: Option<Arc<UdpSocket>>,
+    #[serde(skip)]
+    buf: Vec<u8>,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+impl KinetDmxPort {
+    /// Create a port that sends to the provided power supply.
+    /// The port is not opened yet.
+    pub fn new(target: IpAddr, version: KinetVersion) -> Self {
+        let name = match version {
+            KinetVersion::V1 => format!("kinet:{}", target),
+            KinetVersion::V2 { output } => format!("kinet:{}/{}", target, output),
+        };
+        Self {
+            name,
+            target,
+            version,
+            socket: None,
+            buf: Vec::new(),
+            last_frame: None,
+            status: StatusTracker::new(),
+        }
+    }
+
+    /// Create a port that sends to the provided power supply, and open it.
+    pub fn opened(target: IpAddr, version: KinetVersion) -> Result<Self, Error> {
+        let mut port = Self::new(target, version);
+        port.open()?;
+        Ok(port)
+    }
+
+    /// Return the address of the power supply.
+    pub fn target(&self) -> IpAddr {
+        self.target
+    }
+
+    /// Return the version of KiNET this port sends.
+    pub fn version(&self) -> KinetVersion {
+        self.version
+    }
+
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let socket = self.socket.as_ref().ok_or(Error::NotOpen)?;
+        write_packet(self.version, frame, &mut self.buf);
+        socket.send_to(&self.buf, (self.target, KINET_PORT))?;
+        self.last_frame = Some(DmxFrame::from(frame));
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for KinetDmxPort {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Open the port.
+    fn open(&mut self) -> Result<(), Error> {
+        if self.socket.is_some() {
+            return Ok(());
+        }
+        let result = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+            .and_then(|socket| socket.set_broadcast(true).map(|()| socket))
+            .map(|socket| self.socket = Some(Arc::new(socket)))
+            .map_err(Error::from);
+        self.status.record(result)
+    }
+
+    fn close(&mut self) {
+        self.status.clear();
+        self.socket = None;
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let result = self.send_frame(frame);
+        self.status.record(result)
+    }
+
+    fn kind(&self) -> PortKind {
+        PortKind::Kinet
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(self.socket.is_some())
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+}
+
+impl fmt::Display for KinetDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.version {
+            KinetVersion::V1 => write!(f, "KiNET {}", self.target),
+            KinetVersion::V2 { output } => write!(f, "KiNET {} output {}", self.target, output),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_packets() {
+        let mut buf = Vec::new();
+        write_packet(KinetVersion::V1, &[1, 2, 3], &mut buf);
+        assert_eq!(buf.len(), 21 + 512);
+        assert_eq!(
+            &buf[0..21],
+            &[
+                0x04, 0x01, 0xDC, 0x4A, 1, 0, 0x01, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF,
+                0xFF, 0
+            ]
+        );
+        assert_eq!(&buf[21..25], &[1, 2, 3, 0]);
+
+        write_packet(KinetVersion::V2 { output: 2 }, &[1, 2, 3], &mut buf);
+        assert_eq!(
+            buf,
+            vec![
+                0x04, 0x01, 0xDC, 0x4A, 2, 0, 0x08, 0x01, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 2, 0,
+                0, 0, 3, 0, 0, 0, 1, 2, 3
+            ]
+        );
+    }
+}
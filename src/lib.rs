@@ -1,25 +1,172 @@
 use derive_more::Display;
-use io::Write;
+use dyn_clone::DynClone;
+use io::{BufRead, Write};
+#[cfg(feature = "enttec")]
 use serialport::Error as SerialError;
 use std::error::Error as StdError;
 use std::fmt;
 use std::io;
 
+mod alias;
+mod artnet;
+#[cfg(feature = "tokio")]
+mod async_port;
+mod break_timing;
+mod channel_watch;
+mod clock;
+mod console;
+mod controller;
+mod curve;
+mod dedup;
+#[cfg(feature = "enttec")]
 mod enttec;
+mod fade;
+mod fan_out;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod frame;
+mod frame_length;
+#[cfg(feature = "tokio")]
+mod frame_stream;
+#[cfg(feature = "ftdi")]
+mod ftdi;
+mod input;
+mod interpolate;
+mod kinet;
+mod loopback;
+mod merge;
+mod mock;
 mod offline;
+mod ola;
+#[cfg(feature = "enttec")]
+mod open_dmx;
+#[cfg(feature = "osc")]
+mod osc;
+mod patch;
+mod pcap;
+mod player;
+mod port_info;
+mod port_spec;
+mod provider;
+#[cfg(feature = "python")]
+mod python;
+mod rate_limit;
+#[cfg(feature = "enttec")]
+mod raw_serial;
+pub mod rdm;
+pub mod rdmnet;
+mod reconnect;
+mod recording;
+mod refresh;
+mod retry;
+mod rig;
+mod sacn;
+#[cfg(feature = "enttec")]
+mod serial;
+mod snapshot;
+mod stateful;
+mod stats;
+mod status;
+mod target;
+mod threaded;
+mod trace;
+#[cfg(feature = "tui")]
+mod tui;
+#[cfg(feature = "udmx")]
+mod udmx;
+#[cfg(any(feature = "udmx", feature = "ftdi"))]
+mod usb;
+mod watch;
+#[cfg(all(feature = "websocket", target_arch = "wasm32"))]
+mod websocket;
 
-pub use enttec::EnttecDmxPort;
-pub use offline::OfflineDmxPort;
+pub use alias::{port_aliases, set_port_aliases, PortAliases};
+pub use artnet::{
+    discover_artnet_nodes, ArtNetDmxPort, ArtNetNode, ArtNetProvider, ArtNetReceiver,
+    ArtNetSequencing, ArtNetSyncGroup,
+};
+#[cfg(feature = "tokio")]
+pub use async_port::AsyncDmxPort;
+pub use break_timing::BreakTiming;
+pub use channel_watch::{ChannelChange, ChannelWatcher, Crossing, SubscriptionId};
+pub use clock::OutputClock;
+pub use console::{ConsoleDmxPort, ConsoleStyle};
+pub use controller::DmxController;
+pub use curve::{ChannelCurve, Curve, CurvePort};
+pub use dedup::DeduplicatingPort;
+#[cfg(feature = "enttec")]
+pub use enttec::{
+    EnttecDmxPort, EnttecInputPort, EnttecOutput, EnttecParams, EnttecReceiveMode, EnttecScan,
+    EnttecTimeouts,
+};
+pub use fade::Crossfader;
+pub use fan_out::FanOutPort;
+pub use frame::{DmxFrame, UNIVERSE_SIZE};
+pub use frame_length::{FrameLength, FrameShape, Padding};
+#[cfg(feature = "tokio")]
+pub use frame_stream::{FrameStream, StreamedFrame};
+#[cfg(feature = "ftdi")]
+pub use ftdi::{FtdiDmxPort, FtdiMode, FtdiProvider};
+pub use input::{DmxInputPort, Frames, InputPortListing, ReceivedFrame};
+pub use interpolate::Interpolator;
+pub use kinet::{KinetDmxPort, KinetVersion};
+pub use loopback::{loopback, LoopbackDmxPort, LoopbackInputPort};
+pub use merge::{MergeMode, MergePort, SourceHandle};
+pub use mock::MockDmxPort;
+pub use offline::{OfflineDmxPort, OfflineProvider, SimulatedFaults};
+pub use ola::OlaDmxPort;
+#[cfg(feature = "enttec")]
+pub use open_dmx::{OpenDmxPort, OpenDmxProvider};
+#[cfg(feature = "osc")]
+pub use osc::{OscBridge, OSC_PORT};
+pub use patch::{Patch, PatchedPort};
+pub use pcap::PcapWriter;
+pub use player::Player;
+pub use port_info::{HardwareInfo, PortInfo, PortKind, PortScan, ProviderError};
+pub use port_spec::{open_port_from_env, open_port_spec, DMX_PORT_VAR};
+pub use provider::{register_provider, unregister_provider, PortProvider};
+pub use rate_limit::RateLimitedPort;
+#[cfg(feature = "enttec")]
+pub use raw_serial::RawSerialDmxPort;
+pub use reconnect::{ReconnectPolicy, ReconnectingPort};
+pub use recording::{Record, RecordReader, RecordingPort};
+pub use refresh::RefreshingPort;
+pub use retry::RetryPolicy;
+pub use rig::{Rig, RigError, RigFailure};
+pub use sacn::{
+    discover_sacn_universes, SacnDmxPort, SacnProvider, SacnReceiver, SacnSource, SacnSyncGroup,
+    SacnUniverseAnnouncer,
+};
+#[cfg(feature = "enttec")]
+pub use serial::DeviceLinks;
+pub use snapshot::{read_snapshot, write_snapshot, SnapshotPort};
+pub use stateful::StatefulPort;
+pub use stats::{PortStats, StatsCounter};
+pub use status::{PortStatus, StatusTracker};
+pub use target::NetworkTarget;
+pub use threaded::ThreadedPort;
+#[cfg(feature = "tui")]
+pub use tui::select_port_tui;
+#[cfg(feature = "udmx")]
+pub use udmx::{UdmxDmxPort, UdmxProvider};
+pub use watch::{watch_ports, PortEvent, PortWatcher};
+#[cfg(all(feature = "websocket", target_arch = "wasm32"))]
+pub use websocket::{WebSocketDmxPort, WebSocketEncoding};
 
 /// Trait for the general notion of a DMX port.
 /// This enables creation of an "offline" port to slot into place if an API requires an output.
+/// Ports are `Send`, so they can be moved to the thread that renders frames.
+///
+/// Boxed ports are `Clone`, for handing the same rig to several parts of an application.
+/// Clones of an open port share its connection to the interface, except where that can't be
+/// shared, in which case the clone starts closed.
+///
+/// Deserializing a port never touches its interface, so configurations load on a machine
+/// without the interfaces attached, such as a laptop a show is programmed on.  The ports
+/// load closed, and opening one whose interface is missing fails with
+/// `Error::DeviceDisconnected`.
 #[typetag::serde(tag = "type")]
-pub trait DmxPort: fmt::Display {
-    /// Return the available ports.  The ports will need to be opened before use.
-    fn available_ports() -> Result<PortListing, Error>
-    where
-        Self: Sized;
-
+pub trait DmxPort: DynClone + fmt::Display + Send {
     /// Return a string identifier for this port.
     fn name(&self) -> &str;
 
@@ -33,68 +180,320 @@ pub trait DmxPort: fmt::Display {
 
     /// Write a DMX frame out to the port.  If the frame is smaller than the minimum universe size,
     /// it will be padded with zeros.  If the frame is larger than the maximum universe size, the
-    /// values beyond the max size will be ignored.
+    /// values beyond the max size will be ignored.  Ports that support it can be configured to
+    /// pad further, or with another value, with `Padding`.  A `DmxFrame` can be passed as
+    /// `&frame`.
     fn write(&mut self, frame: &[u8]) -> Result<(), Error>;
+
+    /// Write a packet with an alternate START code, such as 0x17 for text or 0xDD for
+    /// per-address priority, followed by its data.  `write` sends the null START code of a
+    /// DMX frame; a START code of 0 here is the same as calling it.  Alternate START code
+    /// packets aren't padded beyond the protocol's minimum, and don't change `last_frame`.
+    ///
+    /// Ports that can't send alternate START codes return an error of kind `Unsupported`.
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        if start_code == 0 {
+            return self.write(data);
+        }
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{} can't send START code {:#04x}", self.name(), start_code),
+        )
+        .into())
+    }
+
+    /// Return the kind of interface behind this port.
+    fn kind(&self) -> PortKind {
+        PortKind::Other
+    }
+
+    /// Return what is known about the hardware behind this port.
+    fn hardware(&self) -> HardwareInfo {
+        HardwareInfo::default()
+    }
+
+    /// Return what this port has written, if it keeps count.
+    fn stats(&self) -> Option<PortStats> {
+        None
+    }
+
+    /// Return whether the port is ready to write, and the last error if it isn't.  Ports that
+    /// don't keep track report themselves open.
+    fn status(&self) -> PortStatus {
+        PortStatus::Open
+    }
+
+    /// Return true if the port is open and the last thing done with it succeeded.
+    fn is_open(&self) -> bool {
+        self.status() == PortStatus::Open
+    }
+
+    /// Check that the interface behind the port is still there without writing a frame, so a
+    /// vanished device is noticed before the next write.  Ports that can't tell without
+    /// writing only check that they're open.
+    fn health_check(&mut self) -> Result<(), Error> {
+        match self.status() {
+            PortStatus::Closed => Err(Error::NotOpen),
+            _ => Ok(()),
+        }
+    }
+
+    /// Return the last frame written, for ports that keep it.
+    fn last_frame(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Write levels to consecutive channels, numbered from 1, leaving the rest of the last
+    /// frame as it was.  Ports that don't keep their last frame, or haven't written one yet,
+    /// start from a universe at zero.
+    fn write_range(&mut self, start_channel: u16, levels: &[u8]) -> Result<(), Error> {
+        check_channels(start_channel, levels.len())?;
+        let mut frame = self.last_frame().map(DmxFrame::from).unwrap_or_default();
+        frame.set_range(start_channel, levels);
+        self.write(&frame)
+    }
+}
+
+dyn_clone::clone_trait_object!(DmxPort);
+
+/// Check that a number of consecutive channels, numbered from 1, fit in a universe.
+fn check_channels(start_channel: u16, count: usize) -> Result<(), Error> {
+    if start_channel == 0 || start_channel as usize + count > UNIVERSE_SIZE + 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} channels from channel {} don't fit in a universe",
+                count, start_channel
+            ),
+        )
+        .into());
+    }
+    Ok(())
 }
 
 /// A listing of available ports.
-type PortListing = Vec<Box<dyn DmxPort>>;
+pub type PortListing = Vec<Box<dyn DmxPort>>;
 
 /// Gather up all of the providers and use them to get listings of all ports they have available.
-/// Return them as a vector of names plus opener functions.
+/// Return them as descriptions that can be used to open the ports.
 /// This function does not check whether or not any of the ports are in use already.
-pub fn available_ports() -> Result<PortListing, Error> {
-    let mut ports = Vec::new();
-    ports.extend(OfflineDmxPort::available_ports()?.into_iter());
-    ports.extend(EnttecDmxPort::available_ports()?.into_iter());
+///
+/// Providers that fail to list their ports are skipped, so the ports of the others are still
+/// returned; use `scan_ports` to find out which failed and why.
+pub fn available_ports() -> Result<Vec<PortInfo>, Error> {
+    Ok(scan_ports().ports)
+}
+
+/// Gather up the ports of all providers, including those added with `register_provider`, as
+/// `available_ports` does, along with the errors of the providers that couldn't list theirs,
+/// such as serial enumeration being denied.
+pub fn scan_ports() -> PortScan {
+    let mut scan = PortScan::default();
+    for provider in provider::providers() {
+        scan.list(provider.as_ref());
+    }
+    port_aliases().apply(&mut scan.ports);
+    trace::listed(scan.ports.len());
+    scan
+}
+
+/// Gather up the input ports of all providers that support receiving DMX.
+pub fn available_input_ports() -> Result<InputPortListing, Error> {
+    #[cfg_attr(not(feature = "enttec"), allow(unused_mut))]
+    let mut ports: InputPortListing = Vec::new();
+    #[cfg(feature = "enttec")]
+    ports.extend(EnttecInputPort::available_ports()?);
     Ok(ports)
 }
 
+/// Open the port with the provided name, or failing that the first port whose name, alias or
+/// description contains the pattern, ignoring case.  If no port matches, fail with
+/// `Error::DeviceDisconnected`, or if a provider couldn't list its ports, that provider's
+/// error, since the port may have been among them.
+pub fn open_port_by_name(pattern: &str) -> Result<Box<dyn DmxPort>, Error> {
+    let PortScan { mut ports, errors } = scan_ports();
+    let lowercase = pattern.to_lowercase();
+    let index = ports
+        .iter()
+        .position(|port| port.name == pattern)
+        .or_else(|| {
+            ports.iter().position(|port| {
+                port.name.to_lowercase().contains(&lowercase)
+                    || port.to_string().to_lowercase().contains(&lowercase)
+            })
+        })
+        .ok_or_else(|| no_match(errors))?;
+    ports.swap_remove(index).open()
+}
+
+/// Open the first port backed by a DMX interface attached to this system, skipping the
+/// offline and network ports.  If there is none, fail with `Error::DeviceDisconnected`, or if a
+/// provider couldn't list its ports, that provider's error.
+pub fn open_first_hardware_port() -> Result<Box<dyn DmxPort>, Error> {
+    let PortScan { ports, errors } = scan_ports();
+    ports
+        .into_iter()
+        .find(|port| port.kind.is_hardware())
+        .ok_or_else(|| no_match(errors))?
+        .open()
+}
+
+/// Open the first port backed by a DMX interface attached to this system that opens, or an
+/// offline port if there is none, so examples, tests and demos run anywhere.  Falling back is
+/// reported as a warning with the `tracing` feature.
+pub fn open_default_port() -> Box<dyn DmxPort> {
+    let hardware = scan_ports()
+        .ports
+        .into_iter()
+        .filter(|port| port.kind.is_hardware());
+    for port in hardware {
+        if let Ok(port) = port.open() {
+            return port;
+        }
+    }
+    trace::fell_back_to_offline();
+    Box::new(OfflineDmxPort::new())
+}
+
+fn not_found(message: &str) -> Error {
+    io::Error::new(io::ErrorKind::NotFound, message).into()
+}
+
+/// Return the error of the first provider that couldn't list its ports, or failing that
+/// `Error::DeviceDisconnected`, as for opening a port whose interface is missing.
+fn no_match(errors: Vec<ProviderError>) -> Error {
+    errors
+        .into_iter()
+        .next()
+        .map_or(Error::DeviceDisconnected, |failed| failed.error)
+}
+
 /// Prompt the user to select a port via the command prompt.
 pub fn select_port() -> Result<Box<dyn DmxPort>, Error> {
-    let mut ports = available_ports()?;
-    println!("Available DMX ports:");
+    select_port_from(io::stdin().lock(), io::stdout())
+}
+
+/// Prompt the user to select a port, reading their answers from the reader and writing the
+/// prompt to the writer.
+pub fn select_port_from(
+    reader: impl BufRead,
+    writer: impl Write,
+) -> Result<Box<dyn DmxPort>, Error> {
+    prompt_for_port(available_ports()?, reader, writer)?.open()
+}
+
+/// Ask the user to choose one of the ports.
+fn prompt_for_port(
+    mut ports: Vec<PortInfo>,
+    mut reader: impl BufRead,
+    mut writer: impl Write,
+) -> Result<PortInfo, Error> {
+    writeln!(writer, "Available DMX ports:")?;
     for (i, port) in ports.iter().enumerate() {
-        println!("{}: {}", i, port.name());
+        match &port.alias {
+            Some(alias) => writeln!(writer, "{}: {} ({})", i, alias, port.name)?,
+            None => writeln!(writer, "{}: {}", i, port.name)?,
+        }
     }
-    let mut port = loop {
-        print!("Select a port: ");
-        io::stdout().flush()?;
-        let input = read_string()?;
-        let index = match input.trim().parse::<usize>() {
+    loop {
+        write!(writer, "Select a port: ")?;
+        writer.flush()?;
+        let input = read_string(&mut reader)?;
+        let index = match input.parse::<usize>() {
             Ok(num) => num,
             Err(e) => {
-                println!("{}; please enter an integer.", e);
+                writeln!(writer, "{}; please enter an integer.", e)?;
                 continue;
             }
         };
         if index >= ports.len() {
-            println!("Please enter a value less than {}.", ports.len());
+            writeln!(writer, "Please enter a value less than {}.", ports.len())?;
             continue;
         }
-        break ports.swap_remove(index);
-    };
-    port.open()?;
-    Ok(port)
+        return Ok(ports.swap_remove(index));
+    }
 }
 
-/// Read a line of input from stdin.
-fn read_string() -> Result<String, io::Error> {
+/// Read a line of input.  Running out of input is an error, since the user can't be asked
+/// again.
+fn read_string(reader: &mut impl BufRead) -> Result<String, io::Error> {
     let mut line = String::new();
-    io::stdin().read_line(&mut line)?;
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
     Ok(line.trim().to_string())
 }
 
 #[derive(Debug, Display)]
+#[non_exhaustive]
 pub enum Error {
+    #[cfg(feature = "enttec")]
     Serial(SerialError),
     IO(std::io::Error),
+    /// The port was written to before being opened, or after being closed.
+    #[display(fmt = "port not open")]
+    NotOpen,
+    /// The other end of the port went away, such as the input side of a loopback pair.
+    #[display(fmt = "port closed")]
     PortClosed,
+    /// The device behind the port is no longer attached.
+    #[display(fmt = "device disconnected")]
+    DeviceDisconnected,
+    /// The device didn't respond in time.
+    #[display(fmt = "timed out")]
+    Timeout,
+    #[display(fmt = "invalid universe {}", _0)]
+    InvalidUniverse(u16),
+    #[display(fmt = "invalid frame: {}", _0)]
+    InvalidFrame(String),
+    /// The device sent something that doesn't follow its protocol.
+    #[display(fmt = "protocol error: {}", _0)]
+    Protocol(String),
+    Rdm(rdm::RdmError),
+    #[cfg(any(feature = "udmx", feature = "ftdi"))]
+    Usb(rusb::Error),
+}
+
+impl Error {
+    /// Return true if the error is likely to go away by itself, such that retrying the same
+    /// operation shortly afterwards may succeed.
+    pub fn is_transient(&self) -> bool {
+        use Error::*;
+        let transient_io = |kind| {
+            matches!(
+                kind,
+                io::ErrorKind::TimedOut | io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock
+            )
+        };
+        match self {
+            Timeout => true,
+            IO(e) => transient_io(e.kind()),
+            #[cfg(feature = "enttec")]
+            Serial(e) => match e.kind {
+                serialport::ErrorKind::Io(kind) => transient_io(kind),
+                _ => false,
+            },
+            Rdm(e) => matches!(e, rdm::RdmError::NoResponse | rdm::RdmError::AckTimer(_)),
+            #[cfg(any(feature = "udmx", feature = "ftdi"))]
+            Usb(e) => matches!(
+                e,
+                rusb::Error::Timeout | rusb::Error::Busy | rusb::Error::Interrupted
+            ),
+            NotOpen | PortClosed | DeviceDisconnected | InvalidUniverse(_) | InvalidFrame(_)
+            | Protocol(_) => false,
+        }
+    }
 }
 
+#[cfg(feature = "enttec")]
 impl From<SerialError> for Error {
     fn from(e: SerialError) -> Self {
-        Error::Serial(e)
+        // A serial device that isn't attached has no device file to open.
+        match e.kind {
+            serialport::ErrorKind::NoDevice
+            | serialport::ErrorKind::Io(io::ErrorKind::NotFound) => Error::DeviceDisconnected,
+            _ => Error::Serial(e),
+        }
     }
 }
 
@@ -104,13 +503,155 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<rdm::RdmError> for Error {
+    fn from(e: rdm::RdmError) -> Self {
+        Error::Rdm(e)
+    }
+}
+
+#[cfg(any(feature = "udmx", feature = "ftdi"))]
+impl From<rusb::Error> for Error {
+    fn from(e: rusb::Error) -> Self {
+        match e {
+            rusb::Error::NoDevice => Error::DeviceDisconnected,
+            e => Error::Usb(e),
+        }
+    }
+}
+
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         use Error::*;
         match *self {
+            #[cfg(feature = "enttec")]
             Serial(ref e) => Some(e),
             IO(ref e) => Some(e),
-            PortClosed => None,
+            Rdm(ref e) => Some(e),
+            #[cfg(any(feature = "udmx", feature = "ftdi"))]
+            Usb(ref e) => Some(e),
+            NotOpen | PortClosed | DeviceDisconnected | Timeout | InvalidUniverse(_)
+            | InvalidFrame(_) | Protocol(_) => None,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_clone_boxed_port() {
+        let mock = MockDmxPort::new();
+        let mut port: Box<dyn DmxPort> = Box::new(mock.clone());
+        port.open().unwrap();
+        let mut clone = port.clone();
+        clone.write(&[1]).unwrap();
+        port.write(&[2]).unwrap();
+        assert_eq!(mock.frames(), vec![vec![1], vec![2]]);
+    }
+
+    #[test]
+    fn test_write_range() {
+        let mut port = OfflineDmxPort::new();
+        port.write_range(2, &[5, 6]).unwrap();
+        port.write_range(4, &[7]).unwrap();
+        assert_eq!(&port.last_frame().unwrap()[0..5], &[0, 5, 6, 7, 0]);
+        assert!(port.write_range(0, &[1]).is_err());
+        assert!(port.write_range(512, &[1, 2]).is_err());
+        port.write_range(512, &[1]).unwrap();
+    }
+
+    #[test]
+    fn test_write_with_start_code() {
+        let mut port: Box<dyn DmxPort> = Box::new(OfflineDmxPort::new());
+        port.open().unwrap();
+        port.write_with_start_code(0, &[1, 2]).unwrap();
+        assert_eq!(&port.last_frame().unwrap()[0..2], &[1, 2]);
+        assert!(matches!(
+            port.write_with_start_code(0x17, b"text"),
+            Err(Error::IO(e)) if e.kind() == io::ErrorKind::Unsupported
+        ));
+    }
+
+    #[test]
+    fn test_ports_are_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Box<dyn DmxPort>>();
+        assert_send::<OfflineDmxPort>();
+        #[cfg(feature = "enttec")]
+        assert_send::<EnttecDmxPort>();
+    }
+
+    #[test]
+    fn test_prompt_for_port() {
+        let ports = vec![
+            PortInfo::new(Box::new(OfflineDmxPort::new())),
+            PortInfo::new(Box::new(MockDmxPort::new())),
+        ];
+        let mut output = Vec::new();
+        let port = prompt_for_port(ports, &b"one\n5\n1\n"[..], &mut output).unwrap();
+        assert_eq!(port.name, "mock");
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Available DMX ports:\n0: offline\n1: mock\n"));
+        assert!(output.contains("please enter an integer"));
+        assert!(output.contains("Please enter a value less than 2."));
+
+        let ports = vec![PortInfo::new(Box::new(OfflineDmxPort::new()))];
+        assert!(prompt_for_port(ports, &b""[..], io::sink()).is_err());
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(Error::Timeout.is_transient());
+        assert!(Error::from(io::Error::from(io::ErrorKind::TimedOut)).is_transient());
+        assert!(!Error::NotOpen.is_transient());
+        assert!(!Error::from(io::Error::from(io::ErrorKind::PermissionDenied)).is_transient());
+    }
+
+    #[cfg(feature = "enttec")]
+    #[test]
+    fn test_missing_serial_device() {
+        let missing = SerialError::new(serialport::ErrorKind::Io(io::ErrorKind::NotFound), "");
+        assert!(matches!(Error::from(missing), Error::DeviceDisconnected));
+        let denied = SerialError::new(
+            serialport::ErrorKind::Io(io::ErrorKind::PermissionDenied),
+            "",
+        );
+        assert!(matches!(Error::from(denied), Error::Serial(_)));
+    }
+
+    #[test]
+    fn test_no_match() {
+        assert!(matches!(no_match(Vec::new()), Error::DeviceDisconnected));
+        let errors = vec![ProviderError {
+            provider: "udmx".to_string(),
+            kinds: vec![PortKind::Udmx],
+            error: Error::Timeout,
+        }];
+        assert!(matches!(no_match(errors), Error::Timeout));
+    }
+
+    struct NamedProvider;
+
+    impl PortProvider for NamedProvider {
+        fn name(&self) -> &str {
+            "open-by-name-test"
+        }
+
+        fn available_ports(&self) -> Result<PortListing, Error> {
+            Ok(vec![Box::new(MockDmxPort::new())])
+        }
+    }
+
+    #[test]
+    fn test_open_port_by_name() {
+        register_provider(NamedProvider);
+        let port = open_port_by_name("mock").unwrap();
+        assert_eq!(port.name(), "mock");
+        assert!(port.is_open());
+        // Patterns match part of the name or description, ignoring case.
+        assert_eq!(open_port_by_name("MOC").unwrap().name(), "mock");
+        assert!(open_port_by_name("rust-dmx-no-such-port").is_err());
+        unregister_provider("open-by-name-test");
+    }
+}
@@ -0,0 +1,185 @@
+//! A connected output and input pair for testing.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::{max, min};
+use std::fmt;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use crate::{
+    DmxFrame, DmxInputPort, DmxPort, Error, InputPortListing, PortStatus, ReceivedFrame,
+    StatusTracker,
+};
+
+// Frames are padded and truncated as the Enttec widgets do it.
+const MIN_UNIVERSE_SIZE: usize = 24;
+const MAX_UNIVERSE_SIZE: usize = 512;
+
+/// Create an output port and an input port receiving everything written to it, so tests can
+/// check what a consumer of `DmxPort` actually emits.  Frames arrive as they would on the
+/// wire: padded to 24 channels and truncated to 512.
+pub fn loopback() -> (LoopbackDmxPort, LoopbackInputPort) {
+    let (sender, receiver) = channel();
+    let output = LoopbackDmxPort {
+        sender: Some(sender),
+        open: false,
+        last_frame: None,
+        status: StatusTracker::new(),
+    };
+    let input = LoopbackInputPort {
+        receiver,
+        open: false,
+    };
+    (output, input)
+}
+
+/// The output side of a loopback pair.  A deserialized loopback port is not connected to any
+/// input, and fails to open.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct LoopbackDmxPort {
+    #[serde(skip)]
+    sender: Option<Sender<Vec<u8>>>,
+    #[serde(skip)]
+    open: bool,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+#[typetag::serde]
+impl DmxPort for LoopbackDmxPort {
+    fn name(&self) -> &str {
+        "loopback"
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        if self.sender.is_none() {
+            return Err(Error::PortClosed);
+        }
+        self.open = true;
+        self.status.clear();
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.open = false;
+        self.status.clear();
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let sender = match &self.sender {
+            Some(sender) if self.open => sender,
+            _ => return Err(Error::NotOpen),
+        };
+        let mut data = frame[0..min(frame.len(), MAX_UNIVERSE_SIZE)].to_vec();
+        data.resize(max(data.len(), MIN_UNIVERSE_SIZE), 0);
+        // The input side having been dropped looks like a cable being pulled out.
+        let result = sender.send(data).map_err(|_| Error::PortClosed);
+        if result.is_ok() {
+            self.last_frame = Some(DmxFrame::from(frame));
+        }
+        self.status.record(result)
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(self.open)
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+}
+
+impl fmt::Display for LoopbackDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "loopback output")
+    }
+}
+
+/// The input side of a loopback pair.  Frames written while the input is closed are still
+/// queued, so none are missed by opening it late.
+pub struct LoopbackInputPort {
+    receiver: Receiver<Vec<u8>>,
+    open: bool,
+}
+
+impl LoopbackInputPort {
+    /// Wait up to the timeout for the next frame, returning None if none arrives in time.
+    pub fn read_frame_timeout(
+        &mut self,
+        timeout: Duration,
+    ) -> Result<Option<ReceivedFrame>, Error> {
+        if !self.open {
+            return Err(Error::NotOpen);
+        }
+        match self.receiver.recv_timeout(timeout) {
+            Ok(data) => Ok(Some(ReceivedFrame {
+                start_code: 0,
+                data,
+            })),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::PortClosed),
+        }
+    }
+}
+
+impl DmxInputPort for LoopbackInputPort {
+    /// Loopback ports are created in pairs by `loopback`, so there are none to list.
+    fn available_ports() -> Result<InputPortListing, Error> {
+        Ok(Vec::new())
+    }
+
+    fn name(&self) -> &str {
+        "loopback"
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        self.open = true;
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Block until the next frame is written.  Fails with `PortClosed` once the output side
+    /// has been dropped and every frame it wrote has been read.
+    fn read_frame(&mut self) -> Result<ReceivedFrame, Error> {
+        if !self.open {
+            return Err(Error::NotOpen);
+        }
+        let data = self.receiver.recv().map_err(|_| Error::PortClosed)?;
+        Ok(ReceivedFrame {
+            start_code: 0,
+            data,
+        })
+    }
+}
+
+impl fmt::Display for LoopbackInputPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "loopback input")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_loopback() {
+        let (mut output, mut input) = loopback();
+        output.open().unwrap();
+        input.open().unwrap();
+        output.write(&[1, 2, 3]).unwrap();
+        output.write(&[7; 600]).unwrap();
+        let frame = input.read_frame().unwrap();
+        assert_eq!(&frame.data[..4], &[1, 2, 3, 0]);
+        assert_eq!(frame.data.len(), MIN_UNIVERSE_SIZE);
+        assert_eq!(input.read_frame().unwrap().data, vec![7; MAX_UNIVERSE_SIZE]);
+        assert_eq!(input.read_frame_timeout(Duration::ZERO).unwrap(), None);
+        drop(output);
+        assert!(matches!(input.read_frame(), Err(Error::PortClosed)));
+    }
+}
@@ -0,0 +1,180 @@
+//! Merging frames from several sources into one port.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::{max, min};
+use std::fmt;
+
+use crate::{DmxPort, Error, UNIVERSE_SIZE};
+
+/// How the levels of several sources are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergeMode {
+    /// Highest takes precedence: each channel outputs the highest level of any source.
+    #[default]
+    Htp,
+    /// Latest takes precedence: each channel outputs the level it was most recently changed
+    /// to, by whichever source changed it.
+    Ltp,
+}
+
+/// Identifies a source feeding a `MergePort`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SourceHandle(u32);
+
+struct Source {
+    handle: SourceHandle,
+    /// The last frame the source wrote, once it has written one.
+    frame: Option<Vec<u8>>,
+}
+
+/// A port fed by several sources, such as a backup desk and the application, whose frames are
+/// merged before being written to the underlying port.  Every write from a source writes the
+/// merged frame.
+///
+/// Serializing a merge port keeps the port and merge mode; sources need to be added again.
+#[derive(Serialize, Deserialize)]
+pub struct MergePort {
+    port: Box<dyn DmxPort>,
+    #[serde(default)]
+    mode: MergeMode,
+    #[serde(skip)]
+    sources: Vec<Source>,
+    #[serde(skip)]
+    next_handle: u32,
+    #[serde(skip)]
+    merged: Vec<u8>,
+}
+
+impl MergePort {
+    /// Merge sources into the provided port.
+    pub fn new(port: Box<dyn DmxPort>, mode: MergeMode) -> Self {
+        Self {
+            port,
+            mode,
+            sources: Vec::new(),
+            next_handle: 0,
+            merged: Vec::new(),
+        }
+    }
+
+    /// Return the wrapped port.
+    pub fn port(&self) -> &dyn DmxPort {
+        self.port.as_ref()
+    }
+
+    /// Return the wrapped port.
+    pub fn port_mut(&mut self) -> &mut (dyn DmxPort + 'static) {
+        self.port.as_mut()
+    }
+
+    /// Return how sources are merged.
+    pub fn mode(&self) -> MergeMode {
+        self.mode
+    }
+
+    /// Return the frame last written to the port.
+    pub fn merged(&self) -> &[u8] {
+        &self.merged
+    }
+
+    /// Add a source.  It doesn't contribute to the output until it writes a frame.
+    pub fn add_source(&mut self) -> SourceHandle {
+        let handle = SourceHandle(self.next_handle);
+        self.next_handle += 1;
+        self.sources.push(Source {
+            handle,
+            frame: None,
+        });
+        handle
+    }
+
+    /// Remove a source.  With HTP merging its levels are dropped from the output on the next
+    /// write; with LTP merging the channels it set keep their levels until changed by another
+    /// source.
+    pub fn remove_source(&mut self, handle: SourceHandle) {
+        self.sources.retain(|source| source.handle != handle);
+    }
+
+    /// Replace the frame of a source and write the merged frame to the port.  Writing with a
+    /// source that has been removed is an error.
+    pub fn write(&mut self, handle: SourceHandle, frame: &[u8]) -> Result<(), Error> {
+        let frame = &frame[0..min(frame.len(), UNIVERSE_SIZE)];
+        let source = self
+            .sources
+            .iter_mut()
+            .find(|source| source.handle == handle)
+            .ok_or(Error::PortClosed)?;
+        let previous = source.frame.replace(frame.to_vec());
+        match self.mode {
+            MergeMode::Htp => {
+                let frames = self.sources.iter().filter_map(|s| s.frame.as_deref());
+                merge_htp(frames, &mut self.merged);
+            }
+            MergeMode::Ltp => merge_ltp(previous.as_deref(), frame, &mut self.merged),
+        }
+        self.port.write(&self.merged)
+    }
+}
+
+/// Set each channel to the highest level of any frame.
+fn merge_htp<'a>(frames: impl Iterator<Item = &'a [u8]>, merged: &mut Vec<u8>) {
+    merged.clear();
+    for frame in frames {
+        merged.resize(max(merged.len(), frame.len()), 0);
+        for (out, &level) in merged.iter_mut().zip(frame) {
+            *out = max(*out, level);
+        }
+    }
+}
+
+/// Copy the channels that changed since a source's previous frame.
+fn merge_ltp(previous: Option<&[u8]>, frame: &[u8], merged: &mut Vec<u8>) {
+    merged.resize(max(merged.len(), frame.len()), 0);
+    for (i, &level) in frame.iter().enumerate() {
+        if previous.and_then(|p| p.get(i)) != Some(&level) {
+            merged[i] = level;
+        }
+    }
+}
+
+impl fmt::Display for MergePort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            MergeMode::Htp => "HTP",
+            MergeMode::Ltp => "LTP",
+        };
+        write!(f, "{} ({} merge)", self.port, mode)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::OfflineDmxPort;
+
+    #[test]
+    fn test_htp() {
+        let mut port = MergePort::new(Box::new(OfflineDmxPort::new()), MergeMode::Htp);
+        let desk = port.add_source();
+        let app = port.add_source();
+        port.write(desk, &[100, 0, 50]).unwrap();
+        port.write(app, &[50, 200]).unwrap();
+        assert_eq!(port.merged(), &[100, 200, 50]);
+        port.remove_source(desk);
+        port.write(app, &[50, 200]).unwrap();
+        assert_eq!(port.merged(), &[50, 200]);
+        assert!(port.write(desk, &[0]).is_err());
+    }
+
+    #[test]
+    fn test_ltp() {
+        let mut port = MergePort::new(Box::new(OfflineDmxPort::new()), MergeMode::Ltp);
+        let desk = port.add_source();
+        let app = port.add_source();
+        port.write(desk, &[100, 100]).unwrap();
+        port.write(app, &[50]).unwrap();
+        assert_eq!(port.merged(), &[50, 100]);
+        port.write(desk, &[100, 20]).unwrap();
+        assert_eq!(port.merged(), &[50, 20]);
+    }
+}
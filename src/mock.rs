@@ -0,0 +1,194 @@
+//! A port for testing code that writes DMX.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::{DmxPort, Error, PortKind, PortStatus, StatusTracker};
+
+/// A port that keeps every frame written to it, so tests can check exactly what was sent.
+/// Clones share what has been recorded, so one clone can be handed to the code under test and
+/// another kept to inspect it.  Opening and writing can be made to fail.
+///
+/// Like a real port, writes fail with `NotOpen` until the port is opened.  Writes are
+/// recorded exactly as passed, without padding or truncation.  Packets with alternate START
+/// codes are recorded apart from frames.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct MockDmxPort {
+    #[serde(skip)]
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    open: bool,
+    opens: usize,
+    frames: Vec<Vec<u8>>,
+    packets: Vec<(u8, Vec<u8>)>,
+    open_error: Option<Error>,
+    write_error: Option<Error>,
+    kind: Option<PortKind>,
+    status: StatusTracker,
+}
+
+impl MockDmxPort {
+    /// Create a closed mock port.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Report the port as the provided kind, such as a hardware kind, rather than `Other`.
+    pub fn with_kind(self, kind: PortKind) -> Self {
+        self.lock().kind = Some(kind);
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Return every frame written so far, oldest first.
+    pub fn frames(&self) -> Vec<Vec<u8>> {
+        self.lock().frames.clone()
+    }
+
+    /// Return the most recently written frame.
+    pub fn last_frame(&self) -> Option<Vec<u8>> {
+        self.lock().frames.last().cloned()
+    }
+
+    /// Return every packet written with an alternate START code, with its START code, oldest
+    /// first.
+    pub fn start_code_packets(&self) -> Vec<(u8, Vec<u8>)> {
+        self.lock().packets.clone()
+    }
+
+    /// Return how many frames have been written.
+    pub fn write_count(&self) -> usize {
+        self.lock().frames.len()
+    }
+
+    /// Return how many times the port has been opened.
+    pub fn open_count(&self) -> usize {
+        self.lock().opens
+    }
+
+    /// Return true if the port is open.
+    pub fn is_open(&self) -> bool {
+        self.lock().open
+    }
+
+    /// Forget the frames and packets written so far.
+    pub fn clear(&self) {
+        let mut state = self.lock();
+        state.frames.clear();
+        state.packets.clear();
+    }
+
+    /// Make the next call to open fail with the provided error.
+    pub fn fail_next_open(&self, error: Error) {
+        self.lock().open_error = Some(error);
+    }
+
+    /// Make the next call to write fail with the provided error.  The frame is not recorded.
+    pub fn fail_next_write(&self, error: Error) {
+        self.lock().write_error = Some(error);
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for MockDmxPort {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        let mut state = self.lock();
+        if let Some(e) = state.open_error.take() {
+            return state.status.record(Err(e));
+        }
+        state.open = true;
+        state.opens += 1;
+        state.status.record(Ok(()))
+    }
+
+    fn close(&mut self) {
+        let mut state = self.lock();
+        state.open = false;
+        state.status.clear();
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let mut state = self.lock();
+        if !state.open {
+            return Err(Error::NotOpen);
+        }
+        if let Some(e) = state.write_error.take() {
+            return state.status.record(Err(e));
+        }
+        state.frames.push(frame.to_vec());
+        state.status.record(Ok(()))
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        if start_code == 0 {
+            return self.write(data);
+        }
+        let mut state = self.lock();
+        if !state.open {
+            return Err(Error::NotOpen);
+        }
+        if let Some(e) = state.write_error.take() {
+            return state.status.record(Err(e));
+        }
+        state.packets.push((start_code, data.to_vec()));
+        state.status.record(Ok(()))
+    }
+
+    fn kind(&self) -> PortKind {
+        self.lock().kind.unwrap_or(PortKind::Other)
+    }
+
+    fn status(&self) -> PortStatus {
+        let state = self.lock();
+        state.status.status(state.open)
+    }
+}
+
+impl fmt::Display for MockDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "mock")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mock() {
+        let mock = MockDmxPort::new();
+        let mut port: Box<dyn DmxPort> = Box::new(mock.clone());
+        assert!(matches!(port.write(&[1]), Err(Error::NotOpen)));
+        mock.fail_next_open(Error::InvalidUniverse(0));
+        assert!(port.open().is_err());
+        port.open().unwrap();
+        port.write(&[1, 2]).unwrap();
+        mock.fail_next_write(Error::DeviceDisconnected);
+        assert!(port.write(&[3]).is_err());
+        assert_eq!(
+            port.status(),
+            PortStatus::Errored("device disconnected".to_string())
+        );
+        port.write(&[4]).unwrap();
+        assert!(port.is_open());
+        port.write_with_start_code(0x17, &[5]).unwrap();
+        port.write_with_start_code(0, &[6]).unwrap();
+        assert_eq!(mock.frames(), vec![vec![1, 2], vec![4], vec![6]]);
+        assert_eq!(mock.start_code_packets(), vec![(0x17, vec![5])]);
+        assert_eq!(mock.write_count(), 3);
+        assert_eq!(mock.open_count(), 1);
+        port.close();
+        assert_eq!(port.status(), PortStatus::Closed);
+    }
+}
@@ -1,29 +1,212 @@
-use crate::{DmxPort, Error, PortListing};
+use crate::{
+    DmxFrame, DmxPort, Error, PortKind, PortListing, PortProvider, PortStats, PortStatus,
+    StatsCounter, StatusTracker,
+};
 use serde::{Deserialize, Serialize};
 
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::Duration;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct OfflineDmxPort;
+/// Faults an `OfflineDmxPort` simulates, so an application's error handling and reconnection
+/// logic can be exercised without unplugging hardware.  The default simulates none.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SimulatedFaults {
+    /// How long each write blocks, as writing to a slow interface would.
+    pub latency: Duration,
+    /// The fraction of writes, from 0 to 1, that fail with `Error::Timeout`.
+    pub write_error_rate: f64,
+    /// Lose the connection after this many writes since the port was opened, so writes fail
+    /// with `Error::DeviceDisconnected` until it is opened again.
+    pub disconnect_after: Option<u64>,
+    /// Which writes fail is chosen pseudo-randomly from this seed, so runs can be repeated.
+    pub seed: u64,
+}
+
+/// A port that discards everything written to it, while counting it.  It can also simulate
+/// a misbehaving interface; see `with_faults` and `unplug`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct OfflineDmxPort {
+    #[serde(default)]
+    faults: SimulatedFaults,
+    /// Shared by clones, so a test can unplug a port handed to the code under test.
+    #[serde(skip)]
+    unplugged: Arc<AtomicBool>,
+    /// True once the simulated connection is lost, until the port is opened again.
+    #[serde(skip)]
+    disconnected: bool,
+    #[serde(skip)]
+    writes_since_open: u64,
+    /// State of the generator choosing which writes fail, or zero before the first.
+    #[serde(skip)]
+    rng: u64,
+    #[serde(skip)]
+    stats: StatsCounter,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+impl OfflineDmxPort {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Simulate the provided faults.
+    pub fn with_faults(mut self, faults: SimulatedFaults) -> Self {
+        self.faults = faults;
+        self.rng = 0;
+        self
+    }
+
+    /// Return the faults the port simulates.
+    pub fn faults(&self) -> &SimulatedFaults {
+        &self.faults
+    }
+
+    /// Simulate unplugging the interface.  Writing to, opening and checking this port and its
+    /// clones fail with `Error::DeviceDisconnected` until `plug_in` is called, and as with a
+    /// real interface the ports then have to be opened again.
+    pub fn unplug(&self) {
+        self.unplugged.store(true, Ordering::Relaxed);
+    }
+
+    /// Simulate plugging the interface back in after `unplug`.
+    pub fn plug_in(&self) {
+        self.unplugged.store(false, Ordering::Relaxed);
+    }
+
+    /// Decide whether a write succeeds, after any latency has been simulated.
+    fn write_result(&mut self, frame: &[u8]) -> Result<(), Error> {
+        if self.unplugged.load(Ordering::Relaxed)
+            || self
+                .faults
+                .disconnect_after
+                .is_some_and(|writes| self.writes_since_open >= writes)
+        {
+            self.disconnected = true;
+        }
+        let result = if self.disconnected {
+            Err(Error::DeviceDisconnected)
+        } else {
+            self.writes_since_open += 1;
+            if self.random() < self.faults.write_error_rate {
+                Err(Error::Timeout)
+            } else {
+                self.last_frame = Some(DmxFrame::from(frame));
+                Ok(())
+            }
+        };
+        let result = self.stats.record(frame, result);
+        self.status.record(result)
+    }
+
+    /// Return a pseudo-random number from 0 up to 1, using xorshift.
+    fn random(&mut self) -> f64 {
+        if self.rng == 0 {
+            self.rng = self.faults.seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+        }
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Lists a single offline port.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OfflineProvider;
+
+impl PortProvider for OfflineProvider {
+    fn name(&self) -> &str {
+        "offline"
+    }
+
+    fn available_ports(&self) -> Result<PortListing, Error> {
+        Ok(vec![Box::new(OfflineDmxPort::new())])
+    }
+
+    fn kinds(&self) -> &[PortKind] {
+        &[PortKind::Offline]
+    }
+}
 
 #[typetag::serde]
 impl DmxPort for OfflineDmxPort {
-    fn available_ports() -> Result<PortListing, Error> {
-        Ok(vec![(Box::new(Self))])
+    fn name(&self) -> &str {
+        "offline"
     }
 
+    fn open(&mut self) -> Result<(), Error> {
+        if self.unplugged.load(Ordering::Relaxed) {
+            return self.status.record(Err(Error::DeviceDisconnected));
+        }
+        self.disconnected = false;
+        self.writes_since_open = 0;
+        self.status.record(Ok(()))
+    }
+
+    fn close(&mut self) {
+        self.status.clear();
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        sleep(self.faults.latency);
+        self.write_result(frame)
+    }
+
+    fn kind(&self) -> PortKind {
+        PortKind::Offline
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        Some(self.stats.stats())
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(true)
+    }
+
+    fn health_check(&mut self) -> Result<(), Error> {
+        if self.unplugged.load(Ordering::Relaxed) {
+            self.disconnected = true;
+        }
+        match self.disconnected {
+            true => self.status.record(Err(Error::DeviceDisconnected)),
+            false => Ok(()),
+        }
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[async_trait::async_trait]
+impl crate::AsyncDmxPort for OfflineDmxPort {
     fn name(&self) -> &str {
         "offline"
     }
 
-    fn open(&mut self) -> Result<(), Error> {
-        Ok(())
+    async fn open(&mut self) -> Result<(), Error> {
+        DmxPort::open(self)
     }
 
-    fn close(&mut self) {}
+    async fn close(&mut self) {
+        DmxPort::close(self)
+    }
 
-    fn write(&mut self, _: &[u8]) -> Result<(), Error> {
-        Ok(())
+    async fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let latency = self.faults.latency;
+        if !latency.is_zero() {
+            tokio::task::spawn_blocking(move || sleep(latency)).await?;
+        }
+        self.write_result(frame)
     }
 }
 
@@ -32,3 +215,63 @@ impl fmt::Display for OfflineDmxPort {
         write!(f, "offline")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_faults() {
+        let mut port = OfflineDmxPort::new().with_faults(SimulatedFaults {
+            disconnect_after: Some(2),
+            ..SimulatedFaults::default()
+        });
+        assert!(port.write(&[1]).is_ok());
+        assert!(port.write(&[2]).is_ok());
+        assert!(matches!(port.write(&[3]), Err(Error::DeviceDisconnected)));
+        assert!(!port.is_open());
+        assert_eq!(port.last_frame().unwrap()[0], 2);
+        port.open().unwrap();
+        assert!(port.write(&[4]).is_ok());
+        assert_eq!(port.stats().unwrap().errors, 1);
+
+        let mut port = OfflineDmxPort::new().with_faults(SimulatedFaults {
+            write_error_rate: 0.5,
+            seed: 7,
+            ..SimulatedFaults::default()
+        });
+        let failures = (0..1000)
+            .filter(|_| matches!(port.write(&[0]), Err(Error::Timeout)))
+            .count();
+        assert!((400..600).contains(&failures), "{} failures", failures);
+    }
+
+    #[test]
+    fn test_unplug() {
+        let mut port = OfflineDmxPort::new();
+        let handle = port.clone();
+        handle.unplug();
+        assert!(matches!(port.write(&[1]), Err(Error::DeviceDisconnected)));
+        assert!(port.health_check().is_err());
+        assert!(port.open().is_err());
+        handle.plug_in();
+        assert!(port.write(&[1]).is_err());
+        port.open().unwrap();
+        assert!(port.write(&[1]).is_ok());
+        assert!(port.is_open());
+    }
+
+    #[test]
+    fn test_deserialize_unit_config() {
+        use serde::de::value::{Error as ValueError, MapDeserializer};
+
+        // Configs saved when the port was a unit struct hold only its type.
+        let saved =
+            MapDeserializer::<_, ValueError>::new(vec![("type", "OfflineDmxPort")].into_iter());
+        let port = Box::<dyn DmxPort>::deserialize(saved).unwrap();
+        assert_eq!(port.name(), "offline");
+        let saved = MapDeserializer::<_, ValueError>::new(Vec::<(&str, &str)>::new().into_iter());
+        let port = OfflineDmxPort::deserialize(saved).unwrap();
+        assert_eq!(port.faults(), &SimulatedFaults::default());
+    }
+}
@@ -0,0 +1,394 @@
+//! Output through a local OLA (Open Lighting Architecture) daemon, using its RPC protocol.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+use crate::trace;
+use crate::{DmxFrame, DmxPort, Error, PortKind, PortStatus, StatusTracker};
+
+/// The TCP port olad listens for RPC clients on.
+pub const OLA_PORT: u16 = 9010;
+
+/// How long to wait for olad to acknowledge a frame.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// RPC framing: messages are preceded by a native-endian header holding the protocol version
+// in the top 4 bits and the message size in the rest.
+const PROTOCOL_VERSION: u32 = 1;
+const SIZE_MASK: u32 = 0x0FFF_FFFF;
+/// olad's messages are small; anything bigger means the stream is out of sync.
+const MAX_MESSAGE_SIZE: usize = 1 << 20;
+
+// RpcMessage types.
+const REQUEST: u64 = 1;
+const RESPONSE: u64 = 2;
+const RESPONSE_FAILED: u64 = 4;
+
+const UPDATE_DMX_DATA: &str = "UpdateDmxData";
+
+const MAX_SLOTS: usize = 512;
+
+// Protobuf wire types.
+const VARINT: u64 = 0;
+const FIXED64: u64 = 1;
+const LENGTH_DELIMITED: u64 = 2;
+const FIXED32: u64 = 5;
+
+fn write_varint(mut value: u64, buf: &mut Vec<u8>) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn write_varint_field(field: u64, value: u64, buf: &mut Vec<u8>) {
+    write_varint(field << 3 | VARINT, buf);
+    write_varint(value, buf);
+}
+
+fn write_bytes_field(field: u64, value: &[u8], buf: &mut Vec<u8>) {
+    write_varint(field << 3 | LENGTH_DELIMITED, buf);
+    write_varint(value.len() as u64, buf);
+    buf.extend_from_slice(value);
+}
+
+fn malformed() -> Error {
+    Error::Protocol("malformed message from olad".to_string())
+}
+
+fn read_varint(buf: &mut &[u8]) -> Result<u64, Error> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = buf.split_first().ok_or_else(malformed)?;
+        *buf = rest;
+        value |= u64::from(byte & 0x7F) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(malformed())
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if buf.len() < len {
+        return Err(malformed());
+    }
+    let (value, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(value)
+}
+
+/// One field of a protobuf message; fields this module doesn't use are skipped.
+enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Other,
+}
+
+fn read_field<'a>(buf: &mut &'a [u8]) -> Result<(u64, Field<'a>), Error> {
+    let key = read_varint(buf)?;
+    let field = match key & 0x7 {
+        VARINT => Field::Varint(read_varint(buf)?),
+        LENGTH_DELIMITED => {
+            let len = read_varint(buf)? as usize;
+            Field::Bytes(take(buf, len)?)
+        }
+        FIXED64 => take(buf, 8).map(|_| Field::Other)?,
+        FIXED32 => take(buf, 4).map(|_| Field::Other)?,
+        _ => return Err(malformed()),
+    };
+    Ok((key >> 3, field))
+}
+
+/// The fields of an RpcMessage this port uses.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct RpcMessage<'a> {
+    kind: u64,
+    id: u64,
+    name: &'a str,
+    buffer: &'a [u8],
+}
+
+impl<'a> RpcMessage<'a> {
+    fn write_into(&self, buf: &mut Vec<u8>) {
+        write_varint_field(1, self.kind, buf);
+        write_varint_field(2, self.id, buf);
+        write_bytes_field(3, self.name.as_bytes(), buf);
+        write_bytes_field(4, self.buffer, buf);
+    }
+
+    fn parse(mut buf: &'a [u8]) -> Result<Self, Error> {
+        let mut message = Self::default();
+        while !buf.is_empty() {
+            match read_field(&mut buf)? {
+                (1, Field::Varint(kind)) => message.kind = kind,
+                (2, Field::Varint(id)) => message.id = id,
+                (3, Field::Bytes(name)) => {
+                    message.name = std::str::from_utf8(name).map_err(|_| malformed())?
+                }
+                (4, Field::Bytes(buffer)) => message.buffer = buffer,
+                _ => {}
+            }
+        }
+        Ok(message)
+    }
+}
+
+/// Encode a DmxData request, truncating the frame to 512 slots.
+fn write_dmx_data(universe: u32, frame: &[u8], buf: &mut Vec<u8>) {
+    buf.clear();
+    write_varint_field(1, u64::from(universe), buf);
+    write_bytes_field(2, &frame[0..min(frame.len(), MAX_SLOTS)], buf);
+}
+
+/// Write a message preceded by its RPC header.
+fn write_message(stream: &mut impl Write, message: &[u8]) -> io::Result<()> {
+    let header = PROTOCOL_VERSION << 28 | (message.len() as u32 & SIZE_MASK);
+    let mut packet = Vec::with_capacity(4 + message.len());
+    packet.extend_from_slice(&header.to_ne_bytes());
+    packet.extend_from_slice(message);
+    stream.write_all(&packet)
+}
+
+/// Read a message preceded by its RPC header into the provided buffer.
+fn read_message(stream: &mut impl Read, buf: &mut Vec<u8>) -> Result<(), Error> {
+    let mut header = [0; 4];
+    stream.read_exact(&mut header)?;
+    let header = u32::from_ne_bytes(header);
+    let size = (header & SIZE_MASK) as usize;
+    if header >> 28 != PROTOCOL_VERSION || size > MAX_MESSAGE_SIZE {
+        return Err(Error::Protocol(format!(
+            "unsupported RPC header {:#010x} from olad",
+            header
+        )));
+    }
+    buf.resize(size, 0);
+    stream.read_exact(buf)?;
+    Ok(())
+}
+
+fn connect(address: SocketAddr) -> Result<TcpStream, Error> {
+    let stream = TcpStream::connect(address)?;
+    stream.set_nodelay(true)?;
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    Ok(stream)
+}
+
+/// A DMX port that sends a universe through olad, so any interface OLA supports can be used
+/// without a driver in this crate.  Each frame waits for olad to acknowledge it.
+#[derive(Serialize, Deserialize)]
+pub struct OlaDmxPort {
+    name: String,
+    /// The address olad is listening on.
+    address: SocketAddr,
+    /// The OLA universe to send to.
+    universe: u32,
+    #[serde(skip)]
+    stream: Option<TcpStream>,
+    #[serde(skip)]
+    id: u64,
+    #[serde(skip)]
+    data: Vec<u8>,
+    #[serde(skip)]
+    buf: Vec<u8>,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+/// Replies are matched to requests on each connection, so clones start closed and make their
+/// own connection when opened.
+impl Clone for OlaDmxPort {
+    fn clone(&self) -> Self {
+        Self::with_address(self.address, self.universe)
+    }
+}
+
+impl OlaDmxPort {
+    /// Create a port sending to the provided universe of the olad running on this machine.
+    /// The port is not opened yet.
+    pub fn new(universe: u32) -> Self {
+        Self::with_address((Ipv4Addr::LOCALHOST, OLA_PORT).into(), universe)
+    }
+
+    /// Create a port sending to the provided universe of the olad listening on an address.
+    /// The port is not opened yet.
+    pub fn with_address(address: SocketAddr, universe: u32) -> Self {
+        Self {
+            name: format!("ola:{}", universe),
+            address,
+            universe,
+            stream: None,
+            id: 0,
+            data: Vec::new(),
+            buf: Vec::new(),
+            last_frame: None,
+            status: StatusTracker::new(),
+        }
+    }
+
+    /// Create a port sending to the provided universe of the local olad, and open it.
+    pub fn opened(universe: u32) -> Result<Self, Error> {
+        let mut port = Self::new(universe);
+        port.open()?;
+        Ok(port)
+    }
+
+    /// Return the OLA universe this port sends to.
+    pub fn universe(&self) -> u32 {
+        self.universe
+    }
+
+    /// Send a request and wait for olad's response to it.
+    fn call(&mut self, method: &str) -> Result<(), Error> {
+        let stream = self.stream.as_mut().ok_or(Error::NotOpen)?;
+        self.id = self.id.wrapping_add(1);
+        self.buf.clear();
+        RpcMessage {
+            kind: REQUEST,
+            id: self.id,
+            name: method,
+            buffer: &self.data,
+        }
+        .write_into(&mut self.buf);
+        write_message(stream, &self.buf)?;
+        loop {
+            read_message(stream, &mut self.buf).map_err(|e| match e {
+                Error::IO(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    Error::Timeout
+                }
+                Error::IO(e) if e.kind() == io::ErrorKind::UnexpectedEof => Error::PortClosed,
+                e => e,
+            })?;
+            let response = RpcMessage::parse(&self.buf)?;
+            // Responses to requests that timed out may still arrive; skip them.
+            if response.id != self.id {
+                continue;
+            }
+            return match response.kind {
+                RESPONSE => Ok(()),
+                RESPONSE_FAILED => Err(Error::Protocol(format!(
+                    "olad: {}",
+                    String::from_utf8_lossy(response.buffer)
+                ))),
+                kind => Err(Error::Protocol(format!(
+                    "unexpected response type {} from olad",
+                    kind
+                ))),
+            };
+        }
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for OlaDmxPort {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Connect to olad.
+    fn open(&mut self) -> Result<(), Error> {
+        if self.stream.is_some() {
+            return Ok(());
+        }
+        let result = connect(self.address).map(|stream| self.stream = Some(stream));
+        trace::opened(&self.name, &result);
+        self.status.record(result)
+    }
+
+    fn close(&mut self) {
+        self.status.clear();
+        if self.stream.take().is_some() {
+            trace::closed(&self.name);
+        }
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        if self.stream.is_none() {
+            return Err(Error::NotOpen);
+        }
+        write_dmx_data(self.universe, frame, &mut self.data);
+        let result = self.call(UPDATE_DMX_DATA);
+        trace::wrote(&self.name, frame.len(), &result);
+        match result {
+            Ok(()) => self.last_frame = Some(DmxFrame::from(frame)),
+            Err(Error::PortClosed) => self.stream = None,
+            Err(_) => {}
+        }
+        self.status.record(result)
+    }
+
+    fn kind(&self) -> PortKind {
+        PortKind::Ola
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(self.stream.is_some())
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+}
+
+impl fmt::Display for OlaDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "OLA universe {} at {}", self.universe, self.address)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_update_dmx_data() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let olad = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut buf = Vec::new();
+            let mut requests = Vec::new();
+            for kind in [RESPONSE, RESPONSE_FAILED] {
+                read_message(&mut stream, &mut buf).unwrap();
+                let request = RpcMessage::parse(&buf).unwrap();
+                assert_eq!(request.kind, REQUEST);
+                assert_eq!(request.name, UPDATE_DMX_DATA);
+                requests.push(request.buffer.to_vec());
+                let mut response = Vec::new();
+                RpcMessage {
+                    kind,
+                    id: request.id,
+                    buffer: b"no universe",
+                    ..Default::default()
+                }
+                .write_into(&mut response);
+                write_message(&mut stream, &response).unwrap();
+            }
+            requests
+        });
+
+        let mut port = OlaDmxPort::with_address(address, 300);
+        port.open().unwrap();
+        port.write(&[1, 2, 3]).unwrap();
+        assert!(matches!(port.write(&[4]), Err(Error::Protocol(_))));
+        assert_eq!(&port.last_frame().unwrap()[0..4], &[1, 2, 3, 0]);
+        let requests = olad.join().unwrap();
+        // universe 300, then the frame
+        assert_eq!(requests[0], vec![0x08, 0xAC, 0x02, 0x12, 3, 1, 2, 3]);
+        assert_eq!(requests[1], vec![0x08, 0xAC, 0x02, 0x12, 1, 4]);
+    }
+}
@@ -0,0 +1,215 @@
+//! Implementation of support for the Enttec Open DMX USB and similar FTDI-only interfaces.
+
+use serde::{Deserialize, Serialize};
+use serialport::{available_ports, new, DataBits, Parity, SerialPort, StopBits};
+use serialport::{SerialPortInfo, SerialPortType};
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::alias::write_aliased;
+use crate::serial::{
+    hardware_info, relocate, write_device_name, write_with_break, DeviceLinks, SerialPortInfoDef,
+    DMX_BAUD_RATE, MIN_UNIVERSE_SIZE,
+};
+use crate::trace;
+use crate::{
+    BreakTiming, DmxFrame, DmxPort, Error, FrameShape, HardwareInfo, PortKind, PortListing,
+    PortProvider, PortStatus, StatusTracker,
+};
+
+/// USB vendor ID of FTDI, whose chips these interfaces are built on.
+const FTDI_VID: u16 = 0x0403;
+
+/// Product string reported by the Enttec DMX USB Pro, which has its own port type.
+const DMX_USB_PRO_PRODUCT: &str = "DMX USB PRO";
+
+/// A DMX port on an FTDI serial chip with no microcontroller of its own.  The host generates
+/// all DMX timing, so output is only as steady as the thread calling write.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OpenDmxPort {
+    #[serde(with = "SerialPortInfoDef")]
+    info: SerialPortInfo,
+    #[serde(default)]
+    links: DeviceLinks,
+    #[serde(default)]
+    timing: BreakTiming,
+    #[serde(default)]
+    shape: FrameShape,
+    /// Shared by clones, which take turns writing whole packets.
+    #[serde(skip)]
+    port: Option<Arc<Mutex<Box<dyn SerialPort>>>>,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+impl OpenDmxPort {
+    /// Create an Open DMX port.
+    /// The port is not opened yet.
+    pub fn new(info: SerialPortInfo) -> Self {
+        Self {
+            links: DeviceLinks::resolve(&info.port_name),
+            info,
+            timing: BreakTiming::default(),
+            shape: FrameShape::default(),
+            port: None,
+            last_frame: None,
+            status: StatusTracker::new(),
+        }
+    }
+
+    /// Create an Open DMX port and open it.
+    pub fn opened(info: SerialPortInfo) -> Result<Self, Error> {
+        let mut port = Self::new(info);
+        port.open()?;
+        Ok(port)
+    }
+
+    /// Return the udev links to the interface's serial port, which are saved with the port
+    /// and used to find the interface again after `ttyUSB` devices are renumbered.
+    pub fn device_links(&self) -> &DeviceLinks {
+        &self.links
+    }
+
+    /// Set how each frame is shaped before it is transmitted: how many of its slots are sent,
+    /// and how shorter frames are padded.  By default frames are transmitted at the length
+    /// they're written, padded with zeros only as far as the protocol requires.
+    pub fn with_shape(mut self, shape: FrameShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Return how each frame is shaped before it is transmitted.
+    pub fn shape(&self) -> &FrameShape {
+        &self.shape
+    }
+}
+
+/// Return the FTDI serial ports on this system that aren't DMX USB Pro widgets.
+fn open_dmx_ports() -> Result<Vec<SerialPortInfo>, Error> {
+    Ok(available_ports()?
+        .into_iter()
+        .filter(|info| match &info.port_type {
+            SerialPortType::UsbPort(usb) => {
+                usb.vid == FTDI_VID && usb.product.as_deref() != Some(DMX_USB_PRO_PRODUCT)
+            }
+            _ => false,
+        })
+        .collect())
+}
+
+/// Lists the FTDI serial ports on this system that aren't DMX USB Pro widgets as Open DMX
+/// ports.  FTDI chips are also used by plenty of non-DMX devices, so this may list ports that
+/// are not DMX interfaces.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OpenDmxProvider;
+
+impl PortProvider for OpenDmxProvider {
+    fn name(&self) -> &str {
+        "open-dmx"
+    }
+
+    fn available_ports(&self) -> Result<PortListing, Error> {
+        Ok(open_dmx_ports()?
+            .into_iter()
+            .map(|info| Box::new(OpenDmxPort::new(info)) as Box<dyn DmxPort>)
+            .collect())
+    }
+
+    fn kinds(&self) -> &[PortKind] {
+        &[PortKind::OpenDmx]
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for OpenDmxPort {
+    fn name(&self) -> &str {
+        &self.info.port_name
+    }
+
+    /// Open the port, following the interface to the serial port it is attached to now.
+    fn open(&mut self) -> Result<(), Error> {
+        if self.port.is_some() {
+            return Ok(());
+        }
+        relocate(&mut self.info, &self.links, open_dmx_ports);
+        if self.links.is_empty() {
+            self.links = DeviceLinks::resolve(&self.info.port_name);
+        }
+        let result = new(&self.info.port_name, DMX_BAUD_RATE)
+            .data_bits(DataBits::Eight)
+            .parity(Parity::None)
+            .stop_bits(StopBits::Two)
+            .timeout(Duration::from_millis(100))
+            .open()
+            .map(|port| self.port = Some(Arc::new(Mutex::new(port))))
+            .map_err(Error::from);
+        trace::opened(&self.info.port_name, &result);
+        self.status.record(result)
+    }
+
+    fn close(&mut self) {
+        self.status.clear();
+        if self.port.take().is_some() {
+            trace::closed(&self.info.port_name);
+        }
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let sent = self
+            .shape
+            .apply(frame, |slots| slots.max(MIN_UNIVERSE_SIZE));
+        let result = match &self.port {
+            Some(port) => {
+                let mut port = port.lock().unwrap_or_else(PoisonError::into_inner);
+                write_with_break(port.as_mut(), &self.timing, 0, sent)
+            }
+            None => Err(Error::NotOpen),
+        };
+        trace::wrote(&self.info.port_name, frame.len(), &result);
+        if result.is_ok() {
+            self.last_frame = Some(DmxFrame::from(frame));
+        }
+        self.status.record(result)
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        if start_code == 0 {
+            return self.write(data);
+        }
+        let result = match &self.port {
+            Some(port) => {
+                let mut port = port.lock().unwrap_or_else(PoisonError::into_inner);
+                write_with_break(port.as_mut(), &self.timing, start_code, data)
+            }
+            None => Err(Error::NotOpen),
+        };
+        self.status.record(result)
+    }
+
+    fn kind(&self) -> PortKind {
+        PortKind::OpenDmx
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        hardware_info(&self.info)
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(self.port.is_some())
+    }
+}
+
+impl fmt::Display for OpenDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_aliased(f, &self.hardware(), |f| {
+            write_device_name("Open DMX USB", &self.info, f)
+        })
+    }
+}
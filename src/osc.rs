@@ -0,0 +1,323 @@
+//! Driving ports from OSC (Open Sound Control) messages, as sent by control surfaces like
+//! TouchOSC.
+
+use std::net::{Ipv4Addr, SocketAddr, UdpSocket};
+
+use crate::{DmxPort, Error, StatefulPort};
+
+/// The UDP port the bridge listens on unless told otherwise; TouchOSC sends to it by default.
+pub const OSC_PORT: u16 = 8000;
+
+/// OSC packets are carried in single datagrams.
+const MAX_PACKET_SIZE: usize = 65536;
+
+const BUNDLE_TAG: &[u8] = b"#bundle\0";
+/// A bundle's tag is followed by its 8-byte time tag.
+const BUNDLE_HEADER_SIZE: usize = 16;
+
+/// Bundles may nest; deeper ones are ignored rather than risk overflowing the stack.
+const MAX_BUNDLE_DEPTH: usize = 8;
+
+/// Read a null-terminated string padded to a multiple of 4 bytes.
+fn read_string<'a>(buf: &mut &'a [u8]) -> Option<&'a str> {
+    let len = buf.iter().position(|b| *b == 0)?;
+    let value = std::str::from_utf8(&buf[..len]).ok()?;
+    let padded = (len + 4) & !3;
+    *buf = buf.get(padded..)?;
+    Some(value)
+}
+
+fn read_u32(buf: &mut &[u8]) -> Option<u32> {
+    let value = buf.get(0..4)?;
+    *buf = &buf[4..];
+    Some(u32::from_be_bytes([value[0], value[1], value[2], value[3]]))
+}
+
+/// Return the universe and channel addressed by `/dmx/universe/<universe>/channel/<channel>`.
+fn parse_address(address: &str) -> Option<(u16, u16)> {
+    let mut parts = address.strip_prefix("/dmx/universe/")?.split('/');
+    let universe = parts.next()?.parse().ok()?;
+    if parts.next()? != "channel" {
+        return None;
+    }
+    let channel = parts.next()?.parse().ok()?;
+    match parts.next() {
+        None => Some((universe, channel)),
+        Some(_) => None,
+    }
+}
+
+/// Read the arguments of a message as levels.  Integers are levels from 0 to 255, floats
+/// run from 0.0 to 1.0 as faders send them, and booleans are full or off.  Arguments after
+/// one of a type that isn't understood are dropped.
+fn parse_levels(type_tags: &str, mut buf: &[u8]) -> Vec<u8> {
+    let mut levels = Vec::new();
+    for tag in type_tags.chars() {
+        let level = match tag {
+            'i' => match read_u32(&mut buf) {
+                Some(value) => (value as i32).clamp(0, 255) as u8,
+                None => break,
+            },
+            'f' => match read_u32(&mut buf) {
+                Some(value) => (f32::from_bits(value).clamp(0.0, 1.0) * 255.0).round() as u8,
+                None => break,
+            },
+            'T' => 255,
+            'F' => 0,
+            _ => break,
+        };
+        levels.push(level);
+    }
+    levels
+}
+
+/// A channel write asked for by an OSC message: levels for consecutive channels of a
+/// universe.
+#[derive(Debug, PartialEq, Eq)]
+struct ChannelWrite {
+    universe: u16,
+    channel: u16,
+    levels: Vec<u8>,
+}
+
+/// Collect the channel writes in an OSC packet, which may be a bundle of further packets.
+/// Messages to other addresses, and malformed packets, are ignored.
+fn parse_packet(mut buf: &[u8], depth: usize, writes: &mut Vec<ChannelWrite>) {
+    if buf.starts_with(BUNDLE_TAG) {
+        if depth >= MAX_BUNDLE_DEPTH || buf.len() < BUNDLE_HEADER_SIZE {
+            return;
+        }
+        buf = &buf[BUNDLE_HEADER_SIZE..];
+        while let Some(size) = read_u32(&mut buf) {
+            let element = match buf.get(..size as usize) {
+                Some(element) => element,
+                None => return,
+            };
+            parse_packet(element, depth + 1, writes);
+            buf = &buf[size as usize..];
+        }
+        return;
+    }
+    let (address, type_tags) = match (read_string(&mut buf), read_string(&mut buf)) {
+        (Some(address), Some(type_tags)) => (address, type_tags),
+        _ => return,
+    };
+    if let (Some((universe, channel)), Some(type_tags)) =
+        (parse_address(address), type_tags.strip_prefix(','))
+    {
+        writes.push(ChannelWrite {
+            universe,
+            channel,
+            levels: parse_levels(type_tags, buf),
+        });
+    }
+}
+
+/// Receives OSC messages and writes the channels they address to the port configured for
+/// each universe.  A message to `/dmx/universe/1/channel/5` with one argument sets channel 5
+/// of universe 1; further arguments set the channels after it.  Universes and channels are
+/// numbered as configured and from 1 respectively.
+///
+/// Each universe's port keeps its levels, so every packet sends whole frames to the ports
+/// whose universes it changed.
+pub struct OscBridge {
+    bind_address: SocketAddr,
+    universes: Vec<(u16, StatefulPort)>,
+    socket: Option<UdpSocket>,
+    buf: Vec<u8>,
+}
+
+impl OscBridge {
+    /// Create a bridge listening on the OSC port of every interface, with no universes yet.
+    /// The bridge is not opened yet.
+    pub fn new() -> Self {
+        Self {
+            bind_address: (Ipv4Addr::UNSPECIFIED, OSC_PORT).into(),
+            universes: Vec::new(),
+            socket: None,
+            buf: vec![0; MAX_PACKET_SIZE],
+        }
+    }
+
+    /// Listen on the provided address instead.
+    pub fn with_bind_address(mut self, bind_address: SocketAddr) -> Self {
+        self.bind_address = bind_address;
+        self
+    }
+
+    /// Write the universe to the port, replacing any port already configured for it.
+    pub fn with_port(mut self, universe: u16, port: Box<dyn DmxPort>) -> Self {
+        self.universes.retain(|(u, _)| *u != universe);
+        self.universes.push((universe, StatefulPort::new(port)));
+        self
+    }
+
+    /// Return the port a universe is written to.
+    pub fn port(&self, universe: u16) -> Option<&StatefulPort> {
+        self.universes
+            .iter()
+            .find(|(u, _)| *u == universe)
+            .map(|(_, port)| port)
+    }
+
+    /// Open the ports and start listening.
+    pub fn open(&mut self) -> Result<(), Error> {
+        for (_, port) in &mut self.universes {
+            port.open()?;
+        }
+        if self.socket.is_none() {
+            self.socket = Some(UdpSocket::bind(self.bind_address)?);
+        }
+        Ok(())
+    }
+
+    /// Stop listening and close the ports.
+    pub fn close(&mut self) {
+        self.socket = None;
+        for (_, port) in &mut self.universes {
+            port.close();
+        }
+    }
+
+    /// Return the address the bridge is listening on, once open.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.socket.as_ref().ok_or(Error::NotOpen)?.local_addr()?)
+    }
+
+    /// Block until an OSC packet arrives, set the channels it addresses and write the
+    /// universes that changed.  Return how many channels were set; channels of universes
+    /// without a port, or beyond the end of the universe, are ignored.
+    pub fn process(&mut self) -> Result<usize, Error> {
+        let socket = self.socket.as_ref().ok_or(Error::NotOpen)?;
+        let len = socket.recv(&mut self.buf)?;
+        let mut writes = Vec::new();
+        parse_packet(&self.buf[..len], 0, &mut writes);
+        let mut set = 0;
+        let mut changed = Vec::new();
+        for write in writes {
+            let port = match self
+                .universes
+                .iter_mut()
+                .find(|(u, _)| *u == write.universe)
+            {
+                Some((_, port)) => port,
+                None => continue,
+            };
+            for (offset, level) in write.levels.iter().enumerate() {
+                let channel = write.channel.saturating_add(offset as u16);
+                if port.set_channel(channel, *level).is_err() {
+                    break;
+                }
+                set += 1;
+                if !changed.contains(&write.universe) {
+                    changed.push(write.universe);
+                }
+            }
+        }
+        for (universe, port) in &mut self.universes {
+            if changed.contains(universe) {
+                port.flush()?;
+            }
+        }
+        Ok(set)
+    }
+
+    /// Process packets until receiving or writing fails.
+    pub fn run(&mut self) -> Result<(), Error> {
+        loop {
+            self.process()?;
+        }
+    }
+}
+
+impl Default for OscBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+
+    fn write_string(value: &str, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(value.as_bytes());
+        buf.resize((buf.len() + 4) & !3, 0);
+    }
+
+    fn message(address: &str, type_tags: &str, args: &[u32]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_string(address, &mut buf);
+        write_string(type_tags, &mut buf);
+        for arg in args {
+            buf.extend_from_slice(&arg.to_be_bytes());
+        }
+        buf
+    }
+
+    #[test]
+    fn test_parse_packet() {
+        let mut bundle = BUNDLE_TAG.to_vec();
+        bundle.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+        for element in [
+            message("/dmx/universe/1/channel/5", ",if", &[300, 0.5f32.to_bits()]),
+            message("/dmx/universe/2/channel/1", ",TF", &[]),
+            message("/dmx/universe/1/level/5", ",i", &[1]),
+            message("/dmx/universe/1/channel/5/x", ",i", &[1]),
+        ] {
+            bundle.extend_from_slice(&(element.len() as u32).to_be_bytes());
+            bundle.extend_from_slice(&element);
+        }
+        let mut writes = Vec::new();
+        parse_packet(&bundle, 0, &mut writes);
+        assert_eq!(
+            writes,
+            vec![
+                ChannelWrite {
+                    universe: 1,
+                    channel: 5,
+                    levels: vec![255, 128],
+                },
+                ChannelWrite {
+                    universe: 2,
+                    channel: 1,
+                    levels: vec![255, 0],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bridge() {
+        let mock = MockDmxPort::new();
+        let mut bridge = OscBridge::new()
+            .with_bind_address((Ipv4Addr::LOCALHOST, 0).into())
+            .with_port(1, Box::new(mock.clone()));
+        bridge.open().unwrap();
+        let sender = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = bridge.local_addr().unwrap();
+        sender
+            .send_to(
+                &message("/dmx/universe/1/channel/2", ",ii", &[10, 20]),
+                address,
+            )
+            .unwrap();
+        assert_eq!(bridge.process().unwrap(), 2);
+        sender
+            .send_to(&message("/dmx/universe/3/channel/1", ",i", &[1]), address)
+            .unwrap();
+        assert_eq!(bridge.process().unwrap(), 0);
+        sender
+            .send_to(
+                &message("/dmx/universe/1/channel/512", ",ii", &[5, 6]),
+                address,
+            )
+            .unwrap();
+        assert_eq!(bridge.process().unwrap(), 1);
+        assert_eq!(mock.write_count(), 2);
+        let frame = mock.last_frame().unwrap();
+        assert_eq!(&frame[0..3], &[0, 10, 20]);
+        assert_eq!(frame[511], 5);
+    }
+}
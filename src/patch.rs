@@ -0,0 +1,218 @@
+//! Remapping channels before they reach a port.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::{max, min};
+use std::fmt;
+
+use crate::{
+    DmxFrame, DmxPort, Error, HardwareInfo, PortKind, PortStats, PortStatus, UNIVERSE_SIZE,
+};
+
+/// Routing of one channel of written frames to a channel of the output.  Channels are numbered
+/// from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Patch {
+    pub source: u16,
+    pub destination: u16,
+    /// Whether to output 255 minus the level, for fixtures wired to dim the wrong way.
+    #[serde(default)]
+    pub invert: bool,
+}
+
+impl Patch {
+    /// Route a channel to another.
+    pub fn new(source: u16, destination: u16) -> Self {
+        Self {
+            source,
+            destination,
+            invert: false,
+        }
+    }
+
+    /// Invert the level on its way through.
+    pub fn inverted(self) -> Self {
+        Self {
+            invert: true,
+            ..self
+        }
+    }
+
+    /// Return the zero-based source and destination, if both are valid channels.
+    fn indices(&self) -> Option<(usize, usize)> {
+        let valid = |c: u16| (1..=UNIVERSE_SIZE).contains(&(c as usize));
+        if valid(self.source) && valid(self.destination) {
+            Some((self.source as usize - 1, self.destination as usize - 1))
+        } else {
+            None
+        }
+    }
+}
+
+/// A port that remaps channels before writing to an inner port, to correct a miswired rig
+/// without changing the application.  Output channels that are not the destination of a patch
+/// pass the same channel through unchanged.  Patches naming channels outside 1 to 512 are
+/// ignored.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PatchedPort {
+    port: Box<dyn DmxPort>,
+    patches: Vec<Patch>,
+    #[serde(skip)]
+    buf: Vec<u8>,
+    /// The last frame written, before patching, which `write_range` updates.
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+}
+
+impl PatchedPort {
+    /// Wrap a port, applying the provided patches to every frame.
+    pub fn new(port: Box<dyn DmxPort>, patches: Vec<Patch>) -> Self {
+        Self {
+            port,
+            patches,
+            buf: Vec::new(),
+            last_frame: None,
+        }
+    }
+
+    /// Return the patches applied to every frame.
+    pub fn patches(&self) -> &[Patch] {
+        &self.patches
+    }
+
+    /// Replace the patches applied to every frame.
+    pub fn set_patches(&mut self, patches: Vec<Patch>) {
+        self.patches = patches;
+    }
+
+    /// Return the wrapped port.
+    pub fn into_inner(self) -> Box<dyn DmxPort> {
+        self.port
+    }
+}
+
+/// Apply patches to a frame.  The output is long enough to include every patched channel.
+fn apply(patches: &[Patch], frame: &[u8], out: &mut Vec<u8>) {
+    let frame = &frame[0..min(frame.len(), UNIVERSE_SIZE)];
+    let len = patches
+        .iter()
+        .filter_map(Patch::indices)
+        .fold(frame.len(), |len, (_, destination)| {
+            max(len, destination + 1)
+        });
+    out.clear();
+    out.extend_from_slice(frame);
+    out.resize(len, 0);
+    for patch in patches {
+        if let Some((source, destination)) = patch.indices() {
+            let level = frame.get(source).copied().unwrap_or(0);
+            out[destination] = if patch.invert { 255 - level } else { level };
+        }
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for PatchedPort {
+    fn name(&self) -> &str {
+        self.port.name()
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        self.port.open()
+    }
+
+    fn close(&mut self) {
+        self.port.close()
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        apply(&self.patches, frame, &mut self.buf);
+        self.port.write(&self.buf)?;
+        self.last_frame = Some(DmxFrame::from(frame));
+        Ok(())
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        match start_code {
+            0 => self.write(data),
+            _ => self.port.write_with_start_code(start_code, data),
+        }
+    }
+
+    fn kind(&self) -> PortKind {
+        self.port.kind()
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        self.port.hardware()
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        self.port.stats()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.port.status()
+    }
+
+    fn health_check(&mut self) -> Result<(), Error> {
+        self.port.health_check()
+    }
+
+    /// The last frame written, before patching; the inner port's is patched already.
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+}
+
+impl fmt::Display for PatchedPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (patched)", self.port)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+
+    #[test]
+    fn test_apply() {
+        let patches = [
+            Patch::new(1, 2),
+            Patch::new(2, 1),
+            Patch::new(3, 3).inverted(),
+            Patch::new(1, 6),
+            Patch::new(0, 4),
+        ];
+        let mut out = Vec::new();
+        apply(&patches, &[10, 20, 30, 40], &mut out);
+        assert_eq!(out, vec![20, 10, 225, 40, 0, 10]);
+    }
+
+    #[test]
+    fn test_write_range() {
+        let mock = MockDmxPort::new();
+        let mut port = PatchedPort::new(
+            Box::new(mock.clone()),
+            vec![Patch::new(1, 2), Patch::new(2, 1)],
+        );
+        port.open().unwrap();
+        port.write_range(1, &[10]).unwrap();
+        port.write_range(3, &[30]).unwrap();
+        assert_eq!(&port.last_frame().unwrap()[0..3], &[10, 0, 30]);
+        assert_eq!(&mock.last_frame().unwrap()[0..3], &[0, 10, 30]);
+    }
+
+    #[test]
+    fn test_write_with_start_code() {
+        let mock = MockDmxPort::new();
+        let mut port = PatchedPort::new(Box::new(mock.clone()), vec![Patch::new(1, 2)]);
+        port.open().unwrap();
+        // Alternate START code data isn't channel levels, so it isn't patched.
+        port.write_with_start_code(0x17, &[1, 2]).unwrap();
+        assert_eq!(mock.start_code_packets(), vec![(0x17, vec![1, 2])]);
+        port.write_with_start_code(0, &[1, 2]).unwrap();
+        assert_eq!(&mock.last_frame().unwrap()[0..2], &[1, 1]);
+        assert_eq!(&port.last_frame().unwrap()[0..2], &[1, 2]);
+    }
+}
@@ -0,0 +1,265 @@
+//! Capturing network DMX packets to pcap files.
+//!
+//! Captures use the classic pcap format with raw IP link-layer headers, so each record is an
+//! IPv4 or IPv6 header, a UDP header and the packet as it went over the wire.  Wireshark and
+//! tcpdump read them, and dissect Art-Net and sACN by their UDP ports.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::Error;
+
+const MAGIC: u32 = 0xa1b2_c3d4;
+const VERSION_MAJOR: u16 = 2;
+const VERSION_MINOR: u16 = 4;
+const SNAPLEN: u32 = 65535;
+/// Records start with an IP header, with no link-layer header before it.
+const LINKTYPE_RAW: u32 = 101;
+
+const IPV4_HEADER_LENGTH: usize = 20;
+const IPV6_HEADER_LENGTH: usize = 40;
+const UDP_HEADER_LENGTH: usize = 8;
+const PROTOCOL_UDP: u8 = 17;
+/// The TTL or hop limit recorded; lighting networks are usually a single hop.
+const HOP_LIMIT: u8 = 64;
+
+/// Writes UDP packets to a pcap file, so the traffic an application sends and receives can be
+/// inspected in Wireshark alongside the rest of a show network's.  Clones write to the same
+/// file, so one capture can be shared by several ports and receivers.
+///
+/// Records are buffered, and written out by `flush` or when the last clone is dropped.
+#[derive(Clone)]
+pub struct PcapWriter {
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl PcapWriter {
+    /// Create a capture file, replacing any file at the path.
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Start a capture written to the provided writer, writing the file header.
+    pub fn new(mut out: impl Write + Send + 'static) -> Result<Self, Error> {
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&MAGIC.to_le_bytes());
+        header.extend_from_slice(&VERSION_MAJOR.to_le_bytes());
+        header.extend_from_slice(&VERSION_MINOR.to_le_bytes());
+        // The timezone offset and timestamp accuracy, which are always zero.
+        header.extend_from_slice(&[0; 8]);
+        header.extend_from_slice(&SNAPLEN.to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+        out.write_all(&header)?;
+        Ok(Self {
+            out: Arc::new(Mutex::new(Box::new(out))),
+        })
+    }
+
+    /// Record a UDP packet sent between the provided addresses now.
+    pub fn record(
+        &self,
+        source: SocketAddr,
+        destination: SocketAddr,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        self.record_at(SystemTime::now(), source, destination, payload)
+    }
+
+    fn record_at(
+        &self,
+        time: SystemTime,
+        source: SocketAddr,
+        destination: SocketAddr,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        let mut packet = Vec::with_capacity(IPV6_HEADER_LENGTH + UDP_HEADER_LENGTH + payload.len());
+        write_ip_udp(source, destination, payload, &mut packet);
+        let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let mut header = Vec::with_capacity(16);
+        header.extend_from_slice(&(since_epoch.as_secs() as u32).to_le_bytes());
+        header.extend_from_slice(&since_epoch.subsec_micros().to_le_bytes());
+        header.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        out.write_all(&header)?;
+        out.write_all(&packet)?;
+        Ok(())
+    }
+
+    /// Write out buffered records, so the capture can be read while it is still being made.
+    pub fn flush(&self) -> Result<(), Error> {
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        Ok(out.flush()?)
+    }
+}
+
+/// Record a packet to a capture, if there is one.  Failing to capture is reported but doesn't
+/// fail sending or receiving, which matters more than the capture.
+pub(crate) fn capture(
+    capture: &Option<PcapWriter>,
+    source: SocketAddr,
+    destination: SocketAddr,
+    payload: &[u8],
+) {
+    if let Some(capture) = capture {
+        let result = capture.record(source, destination, payload);
+        crate::trace::captured(&result);
+    }
+}
+
+/// Format the IP and UDP headers of a packet, followed by its payload.  A source of the other
+/// IP version than the destination, as a socket bound to every interface reports, is recorded
+/// as the unspecified address.
+fn write_ip_udp(source: SocketAddr, destination: SocketAddr, payload: &[u8], buf: &mut Vec<u8>) {
+    let udp_length = (UDP_HEADER_LENGTH + payload.len()) as u16;
+    let mut udp = Vec::with_capacity(usize::from(udp_length));
+    udp.extend_from_slice(&source.port().to_be_bytes());
+    udp.extend_from_slice(&destination.port().to_be_bytes());
+    udp.extend_from_slice(&udp_length.to_be_bytes());
+    udp.extend_from_slice(&[0, 0]);
+    udp.extend_from_slice(payload);
+    // The checksum covers a pseudo-header of the addresses, protocol and length.
+    let mut pseudo_header = Vec::with_capacity(36);
+    match destination.ip() {
+        IpAddr::V4(destination) => {
+            let source = match source.ip() {
+                IpAddr::V4(source) => source,
+                IpAddr::V6(source) => source.to_ipv4_mapped().unwrap_or(Ipv4Addr::UNSPECIFIED),
+            };
+            let total_length = IPV4_HEADER_LENGTH as u16 + udp_length;
+            let start = buf.len();
+            buf.extend_from_slice(&[0x45, 0]);
+            buf.extend_from_slice(&total_length.to_be_bytes());
+            // Identification, and flags with don't fragment set.
+            buf.extend_from_slice(&[0, 0, 0x40, 0]);
+            buf.extend_from_slice(&[HOP_LIMIT, PROTOCOL_UDP, 0, 0]);
+            buf.extend_from_slice(&source.octets());
+            buf.extend_from_slice(&destination.octets());
+            let checksum = internet_checksum(&buf[start..]);
+            buf[start + 10..start + 12].copy_from_slice(&checksum.to_be_bytes());
+            pseudo_header.extend_from_slice(&source.octets());
+            pseudo_header.extend_from_slice(&destination.octets());
+            pseudo_header.extend_from_slice(&[0, PROTOCOL_UDP]);
+            pseudo_header.extend_from_slice(&udp_length.to_be_bytes());
+        }
+        IpAddr::V6(destination) => {
+            let source = match source.ip() {
+                IpAddr::V6(source) => source,
+                IpAddr::V4(_) => Ipv6Addr::UNSPECIFIED,
+            };
+            buf.extend_from_slice(&[0x60, 0, 0, 0]);
+            buf.extend_from_slice(&udp_length.to_be_bytes());
+            buf.extend_from_slice(&[PROTOCOL_UDP, HOP_LIMIT]);
+            buf.extend_from_slice(&source.octets());
+            buf.extend_from_slice(&destination.octets());
+            pseudo_header.extend_from_slice(&source.octets());
+            pseudo_header.extend_from_slice(&destination.octets());
+            pseudo_header.extend_from_slice(&u32::from(udp_length).to_be_bytes());
+            pseudo_header.extend_from_slice(&[0, 0, 0, PROTOCOL_UDP]);
+        }
+    }
+    pseudo_header.extend_from_slice(&udp);
+    // A computed checksum of zero is sent as all ones, since zero means none was computed.
+    let checksum = match internet_checksum(&pseudo_header) {
+        0 => 0xffff,
+        checksum => checksum,
+    };
+    udp[6..8].copy_from_slice(&checksum.to_be_bytes());
+    buf.extend_from_slice(&udp);
+}
+
+/// Compute the ones' complement checksum used by IP and UDP headers.
+fn internet_checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data
+        .chunks(2)
+        .map(|pair| u32::from(u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)])))
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// A writer whose output can be inspected after a capture has taken it, for tests of the
+/// ports that capture.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub(crate) struct CaptureBuffer(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl CaptureBuffer {
+    /// Return everything written so far.
+    pub(crate) fn bytes(&self) -> Vec<u8> {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Return the UDP payloads of the IPv4 packets captured so far.
+    pub(crate) fn payloads(&self) -> Vec<Vec<u8>> {
+        let bytes = self.bytes();
+        let mut payloads = Vec::new();
+        let mut records = &bytes[24..];
+        while !records.is_empty() {
+            let len =
+                u32::from_le_bytes([records[8], records[9], records[10], records[11]]) as usize;
+            payloads.push(records[16 + IPV4_HEADER_LENGTH + UDP_HEADER_LENGTH..16 + len].to_vec());
+            records = &records[16 + len..];
+        }
+        payloads
+    }
+}
+
+#[cfg(test)]
+impl Write for CaptureBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_record() {
+        let out = CaptureBuffer::default();
+        let capture = PcapWriter::new(out.clone()).unwrap();
+        let time = UNIX_EPOCH + Duration::from_micros(5_000_007);
+        let source = "10.0.0.1:6454".parse().unwrap();
+        let destination = "10.0.0.2:6454".parse().unwrap();
+        capture
+            .record_at(time, source, destination, &[1, 2, 3])
+            .unwrap();
+        let bytes = out.bytes();
+        assert_eq!(&bytes[0..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(&bytes[20..24], &[101, 0, 0, 0]);
+        let record = &bytes[24..];
+        assert_eq!(
+            &record[0..16],
+            &[5, 0, 0, 0, 7, 0, 0, 0, 31, 0, 0, 0, 31, 0, 0, 0]
+        );
+        let packet = &record[16..];
+        assert_eq!(packet.len(), 31);
+        assert_eq!(&packet[0..4], &[0x45, 0, 0, 31]);
+        assert_eq!(internet_checksum(&packet[..20]), 0);
+        assert_eq!(&packet[12..20], &[10, 0, 0, 1, 10, 0, 0, 2]);
+        assert_eq!(&packet[20..26], &[0x19, 0x36, 0x19, 0x36, 0, 11]);
+        assert_eq!(&packet[28..], &[1, 2, 3]);
+
+        let mut buf = Vec::new();
+        let source = "[::1]:5568".parse().unwrap();
+        let destination = "[ff18::8300:1]:5568".parse().unwrap();
+        write_ip_udp(source, destination, &[9], &mut buf);
+        assert_eq!(buf.len(), 49);
+        assert_eq!(&buf[0..8], &[0x60, 0, 0, 0, 0, 9, 17, 64]);
+        assert_eq!(&buf[40..46], &[0x15, 0xc0, 0x15, 0xc0, 0, 9]);
+    }
+}
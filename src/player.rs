@@ -0,0 +1,197 @@
+//! Replaying recordings made by `RecordingPort`.
+
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use crate::{DmxPort, Error, Record, RecordReader};
+
+/// Replays a recording into a port with its original timing.  Call `update` regularly, from a
+/// render loop for example, to write the frames that have become due; `play` does that until
+/// the end of the recording.  Only the latest due frame is written by each update, so a slow
+/// update rate skips frames rather than falling behind.
+pub struct Player {
+    /// Frames paired with their time from the start of the recording.
+    frames: Vec<(Duration, Vec<u8>)>,
+    /// Index of the next frame to write.
+    next: usize,
+    position: Duration,
+    rate: f64,
+    paused: bool,
+    /// When the position was last advanced, while playing.
+    last_update: Option<Instant>,
+    /// Whether the frame before `next` needs writing again, after a seek.
+    rewrite: bool,
+}
+
+impl Player {
+    /// Load a recording file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Ok(Self::new(
+            RecordReader::open(path)?.collect::<Result<_, _>>()?,
+        ))
+    }
+
+    /// Play back the provided records, which should be in the order they were recorded.
+    pub fn new(records: Vec<Record>) -> Self {
+        let start = records.first().map(|record| record.time);
+        let frames = records
+            .into_iter()
+            .map(|record| {
+                let offset = start
+                    .and_then(|start| record.time.duration_since(start).ok())
+                    .unwrap_or_default();
+                (offset, record.frame)
+            })
+            .collect();
+        Self {
+            frames,
+            next: 0,
+            position: Duration::ZERO,
+            rate: 1.0,
+            paused: false,
+            last_update: None,
+            rewrite: false,
+        }
+    }
+
+    /// Return the time from the first frame of the recording to the last.
+    pub fn duration(&self) -> Duration {
+        self.frames
+            .last()
+            .map_or(Duration::ZERO, |(offset, _)| *offset)
+    }
+
+    /// Return how far into the recording playback is.
+    pub fn position(&self) -> Duration {
+        self.position
+    }
+
+    /// Return true once every frame has been written.
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.frames.len() && !self.rewrite
+    }
+
+    /// Return the playback speed, where 1 is the original speed.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Set the playback speed, where 1 is the original speed and 2 twice as fast.  Use
+    /// `pause` to stop playback rather than a rate of zero, which `play` would wait on forever.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rate isn't a positive number.
+    pub fn set_rate(&mut self, rate: f64) {
+        assert!(
+            rate > 0.0 && rate.is_finite(),
+            "playback rate must be positive"
+        );
+        self.rate = rate;
+    }
+
+    /// Return true if playback is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stop the position advancing.  The last frame written stays on the port.
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.last_update = None;
+    }
+
+    /// Carry on from where playback was paused.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Jump to a time from the start of the recording.  The frame that was current at that time
+    /// is written on the next update.
+    pub fn seek(&mut self, position: Duration) {
+        self.position = position;
+        self.next = self
+            .frames
+            .partition_point(|(offset, _)| *offset <= position);
+        self.rewrite = self.next > 0;
+    }
+
+    /// Advance playback to the present and write the latest frame that has become due.
+    pub fn update(&mut self, port: &mut dyn DmxPort) -> Result<(), Error> {
+        let now = Instant::now();
+        let elapsed = match self.last_update {
+            Some(last) if !self.paused => now - last,
+            _ => Duration::ZERO,
+        };
+        if !self.paused {
+            self.last_update = Some(now);
+        }
+        self.advance(elapsed, port)
+    }
+
+    fn advance(&mut self, elapsed: Duration, port: &mut dyn DmxPort) -> Result<(), Error> {
+        if !self.paused {
+            self.position += elapsed.mul_f64(self.rate);
+        }
+        let due = self.frames[self.next..].partition_point(|(offset, _)| *offset <= self.position);
+        if due == 0 && !self.rewrite {
+            return Ok(());
+        }
+        self.next += due;
+        self.rewrite = false;
+        port.write(&self.frames[self.next - 1].1)
+    }
+
+    /// Play the rest of the recording, blocking until the last frame has been written.
+    pub fn play(&mut self, port: &mut dyn DmxPort) -> Result<(), Error> {
+        self.resume();
+        loop {
+            self.update(port)?;
+            if self.is_finished() {
+                return Ok(());
+            }
+            let (offset, _) = self.frames[self.next];
+            let wait = (offset - self.position).div_f64(self.rate);
+            sleep(wait.min(Duration::from_millis(100)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::OfflineDmxPort;
+    use std::time::UNIX_EPOCH;
+
+    #[test]
+    fn test_advance() {
+        let record = |millis, level| Record {
+            time: UNIX_EPOCH + Duration::from_millis(millis),
+            frame: vec![level],
+        };
+        let mut player = Player::new(vec![record(1000, 1), record(1100, 2), record(1200, 3)]);
+        let mut port = OfflineDmxPort::new();
+        assert_eq!(player.duration(), Duration::from_millis(200));
+        player.advance(Duration::ZERO, &mut port).unwrap();
+        assert_eq!(player.next, 1);
+        player.set_rate(2.0);
+        player
+            .advance(Duration::from_millis(50), &mut port)
+            .unwrap();
+        assert_eq!(player.next, 2);
+        player.seek(Duration::from_millis(150));
+        assert_eq!(player.next, 2);
+        assert!(player.rewrite);
+        player
+            .advance(Duration::from_millis(25), &mut port)
+            .unwrap();
+        assert!(player.is_finished());
+    }
+
+    #[test]
+    #[should_panic(expected = "playback rate must be positive")]
+    fn test_zero_rate() {
+        Player::new(Vec::new()).set_rate(0.0);
+    }
+}
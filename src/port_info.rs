@@ -0,0 +1,215 @@
+//! Descriptions of available ports, detailed enough to tell identical interfaces apart.
+
+use derive_more::Display;
+use std::error::Error as StdError;
+use std::fmt;
+
+use crate::{DmxPort, Error, PortProvider};
+
+/// The kinds of interface a port can be backed by.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash)]
+pub enum PortKind {
+    #[display(fmt = "offline")]
+    Offline,
+    #[display(fmt = "Enttec DMX USB Pro")]
+    EnttecPro,
+    #[display(fmt = "DMXking ultraDMX Pro")]
+    UltraDmxPro,
+    #[display(fmt = "Open DMX USB")]
+    OpenDmx,
+    #[display(fmt = "serial")]
+    RawSerial,
+    #[display(fmt = "uDMX")]
+    Udmx,
+    #[display(fmt = "Art-Net")]
+    ArtNet,
+    #[display(fmt = "sACN")]
+    Sacn,
+    #[display(fmt = "OLA")]
+    Ola,
+    #[display(fmt = "KiNET")]
+    Kinet,
+    /// A port implemented outside this crate, or one combining other ports.
+    #[display(fmt = "other")]
+    Other,
+}
+
+impl PortKind {
+    /// Return true for kinds of port backed by an interface attached to this system.
+    pub fn is_hardware(self) -> bool {
+        match self {
+            PortKind::EnttecPro
+            | PortKind::UltraDmxPro
+            | PortKind::OpenDmx
+            | PortKind::RawSerial
+            | PortKind::Udmx => true,
+            PortKind::Offline
+            | PortKind::ArtNet
+            | PortKind::Sacn
+            | PortKind::Ola
+            | PortKind::Kinet
+            | PortKind::Other => false,
+        }
+    }
+}
+
+/// What is known about the hardware behind a port.  Network and virtual ports have none.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HardwareInfo {
+    /// The system path of the device, such as a serial port name.
+    pub path: Option<String>,
+    pub serial_number: Option<String>,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    /// The name the operating system gives the device, such as the friendly name Windows
+    /// shows for a serial port.
+    pub description: Option<String>,
+    /// The serial number the device reports itself, where it differs from the USB one.
+    pub device_serial_number: Option<String>,
+    pub firmware_version: Option<String>,
+}
+
+/// An available port and its description.  The port needs to be opened before use.
+pub struct PortInfo {
+    pub name: String,
+    pub kind: PortKind,
+    pub hardware: HardwareInfo,
+    /// The label the operator gave the interface, from the installed `PortAliases`.
+    pub alias: Option<String>,
+    port: Box<dyn DmxPort>,
+}
+
+impl PortInfo {
+    /// Describe an unopened port.
+    pub fn new(port: Box<dyn DmxPort>) -> Self {
+        Self {
+            name: port.name().to_string(),
+            kind: port.kind(),
+            hardware: port.hardware(),
+            alias: None,
+            port,
+        }
+    }
+
+    /// Return the port itself.
+    pub fn port(&self) -> &dyn DmxPort {
+        self.port.as_ref()
+    }
+
+    /// Return the port itself.
+    pub fn port_mut(&mut self) -> &mut (dyn DmxPort + 'static) {
+        self.port.as_mut()
+    }
+
+    /// Return the port without opening it.
+    pub fn into_port(self) -> Box<dyn DmxPort> {
+        self.port
+    }
+
+    /// Open the port.
+    pub fn open(self) -> Result<Box<dyn DmxPort>, Error> {
+        let mut port = self.port;
+        port.open()?;
+        Ok(port)
+    }
+}
+
+impl fmt::Display for PortInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.alias {
+            Some(alias) => write!(f, "{} ({:#})", alias, self.port),
+            None => self.port.fmt(f),
+        }
+    }
+}
+
+impl fmt::Debug for PortInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PortInfo")
+            .field("name", &self.name)
+            .field("kind", &self.kind)
+            .field("hardware", &self.hardware)
+            .field("alias", &self.alias)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A provider of ports that couldn't list them, and why.
+#[derive(Debug)]
+pub struct ProviderError {
+    /// The provider's name, such as `enttec`.
+    pub provider: String,
+    /// The kinds of port the provider lists.
+    pub kinds: Vec<PortKind>,
+    pub error: Error,
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "listing {} ports: {}", self.provider, self.error)
+    }
+}
+
+impl StdError for ProviderError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// The ports listed by every provider, with the errors of any provider that couldn't list
+/// its ports, so one failing backend doesn't hide the ports of the others.
+#[derive(Debug, Default)]
+pub struct PortScan {
+    pub ports: Vec<PortInfo>,
+    pub errors: Vec<ProviderError>,
+}
+
+impl PortScan {
+    /// Add the ports a provider lists, or its error.
+    pub(crate) fn list(&mut self, provider: &dyn PortProvider) {
+        match provider.available_ports() {
+            Ok(ports) => self.ports.extend(ports.into_iter().map(PortInfo::new)),
+            Err(error) => {
+                crate::trace::provider_failed(provider.name(), &error);
+                self.errors.push(ProviderError {
+                    provider: provider.name().to_string(),
+                    kinds: provider.kinds().to_vec(),
+                    error,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{OfflineProvider, PortListing};
+
+    /// A provider that always fails to list ports.
+    struct UnlistableProvider;
+
+    impl PortProvider for UnlistableProvider {
+        fn name(&self) -> &str {
+            "unlistable"
+        }
+
+        fn available_ports(&self) -> Result<PortListing, Error> {
+            Err(Error::Timeout)
+        }
+    }
+
+    #[test]
+    fn test_scan() {
+        let mut scan = PortScan::default();
+        scan.list(&UnlistableProvider);
+        scan.list(&OfflineProvider);
+        assert_eq!(scan.ports.len(), 1);
+        assert_eq!(scan.ports[0].name, "offline");
+        assert_eq!(scan.errors.len(), 1);
+        assert_eq!(
+            scan.errors[0].to_string(),
+            "listing unlistable ports: timed out"
+        );
+    }
+}
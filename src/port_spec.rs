@@ -0,0 +1,167 @@
+//! Choosing a port from a short text description, such as an environment variable.
+
+use std::env;
+use std::io;
+
+use crate::provider::providers;
+use crate::{
+    not_found, open_port_by_name, port_aliases, ArtNetDmxPort, DmxPort, Error, NetworkTarget,
+    PortInfo, SacnDmxPort,
+};
+
+/// The environment variable `open_port_from_env` reads.
+pub const DMX_PORT_VAR: &str = "DMX_PORT";
+
+/// Open the port described by the `DMX_PORT` environment variable, so containerized and
+/// scripted deployments can choose a port without a prompt or a configuration file.  See
+/// `open_port_spec` for how ports are described.
+pub fn open_port_from_env() -> Result<Box<dyn DmxPort>, Error> {
+    let spec =
+        env::var(DMX_PORT_VAR).map_err(|_| not_found(&format!("{} is not set", DMX_PORT_VAR)))?;
+    open_port_spec(&spec)
+}
+
+/// Open the port described by the spec, which is one of:
+///
+/// - `artnet:<target>/<port-address>`, such as `artnet:10.0.0.5/0`, or
+///   `artnet:<port-address>` to broadcast.
+/// - `sacn:<universe>` to multicast a universe, or `sacn:<target>/<universe>`.
+/// - `<provider>:<selector>`, such as `enttec:EN123456`, for the port of the provider whose
+///   name, alias, path or serial number is the selector.  A provider alone, such as `udmx`, opens
+///   its first port.
+/// - Anything else is looked up with `open_port_by_name`.
+///
+/// Targets are written as network ports display them: `broadcast`, `multicast`, or a
+/// comma-separated list of addresses.
+pub fn open_port_spec(spec: &str) -> Result<Box<dyn DmxPort>, Error> {
+    let (kind, selector) = match spec.split_once(':') {
+        Some((kind, selector)) => (kind, selector),
+        None => (spec, ""),
+    };
+    let mut port: Box<dyn DmxPort> = match kind {
+        "artnet" => {
+            let (target, port_address) = split_target(spec, selector)?;
+            Box::new(ArtNetDmxPort::with_target(
+                target.unwrap_or(NetworkTarget::Broadcast),
+                parse(spec, port_address)?,
+            ))
+        }
+        "sacn" => {
+            let (target, universe) = split_target(spec, selector)?;
+            let port = SacnDmxPort::new(parse(spec, universe)?);
+            match target {
+                Some(target) => Box::new(port.with_target(target)),
+                None => Box::new(port),
+            }
+        }
+        _ => match providers().into_iter().find(|p| p.name() == kind) {
+            Some(provider) => {
+                let mut ports: Vec<_> = provider
+                    .available_ports()?
+                    .into_iter()
+                    .map(PortInfo::new)
+                    .collect();
+                port_aliases().apply(&mut ports);
+                find_port(ports, spec, selector)?.into_port()
+            }
+            None => return open_port_by_name(spec),
+        },
+    };
+    port.open()?;
+    Ok(port)
+}
+
+/// Return the first port the selector selects, or the first port if there is no selector.
+fn find_port(ports: Vec<PortInfo>, spec: &str, selector: &str) -> Result<PortInfo, Error> {
+    ports
+        .into_iter()
+        .find(|port| selector.is_empty() || port.name == spec || selects(port, selector))
+        .ok_or(Error::DeviceDisconnected)
+}
+
+/// Return true if the selector is the port's name, alias, path or serial number.
+fn selects(port: &PortInfo, selector: &str) -> bool {
+    let hardware = &port.hardware;
+    port.name == selector
+        || port.alias.as_deref() == Some(selector)
+        || [
+            &hardware.path,
+            &hardware.serial_number,
+            &hardware.device_serial_number,
+        ]
+        .iter()
+        .any(|field| field.as_deref() == Some(selector))
+}
+
+/// Split `<target>/<number>` at the last slash, so IPv6 targets can contain colons.  Without a
+/// slash there is no target.
+fn split_target<'a>(
+    spec: &str,
+    selector: &'a str,
+) -> Result<(Option<NetworkTarget>, &'a str), Error> {
+    match selector.rsplit_once('/') {
+        Some((target, number)) => {
+            let target = target.parse().map_err(|_| invalid(spec))?;
+            Ok((Some(target), number))
+        }
+        None => Ok((None, selector)),
+    }
+}
+
+fn parse(spec: &str, number: &str) -> Result<u16, Error> {
+    number.parse().map_err(|_| invalid(spec))
+}
+
+fn invalid(spec: &str) -> Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{:?} is not a DMX port", spec),
+    )
+    .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{MockDmxPort, OfflineDmxPort, PortAliases};
+    use std::net::IpAddr;
+
+    #[test]
+    fn test_open_port_spec() {
+        assert_eq!(open_port_spec("offline").unwrap().name(), "offline");
+        assert_eq!(open_port_spec("offline:").unwrap().name(), "offline");
+        let port = open_port_spec("sacn:127.0.0.1/5").unwrap();
+        assert_eq!(port.name(), "sacn:127.0.0.1/5");
+        let port = open_port_spec("artnet:127.0.0.1/3").unwrap();
+        assert_eq!(port.name(), "artnet:127.0.0.1/3");
+        assert!(matches!(
+            open_port_spec("artnet:nowhere/3"),
+            Err(Error::IO(e)) if e.kind() == io::ErrorKind::InvalidInput
+        ));
+        assert!(open_port_spec("sacn:").is_err());
+        assert!(open_port_spec("offline:missing").is_err());
+    }
+
+    #[test]
+    fn test_split_target() {
+        let (target, number) = split_target("", "::1/7").unwrap();
+        assert_eq!(target, Some(IpAddr::from([0, 0, 0, 0, 0, 0, 0, 1]).into()));
+        assert_eq!(number, "7");
+        assert_eq!(split_target("", "7").unwrap(), (None, "7"));
+    }
+
+    #[test]
+    fn test_find_port_by_alias() {
+        let mut aliases = PortAliases::new();
+        aliases.set("EN123456", "FOH rig");
+        let mut ports = vec![
+            PortInfo::new(Box::new(OfflineDmxPort::new())),
+            PortInfo::new(Box::new(MockDmxPort::new())),
+        ];
+        ports[1].hardware.serial_number = Some("EN123456".to_string());
+        aliases.apply(&mut ports);
+        let port = find_port(ports, "mock:FOH rig", "FOH rig").unwrap();
+        assert_eq!(port.name, "mock");
+        assert_eq!(port.to_string(), "FOH rig (mock)");
+    }
+}
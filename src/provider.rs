@@ -0,0 +1,131 @@
+//! Listing the ports of each kind of interface, and providers registered by other crates.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::{Error, OfflineProvider, PortKind, PortListing, SacnProvider};
+
+/// Lists the ports of one kind of interface.  Listing is kept apart from `DmxPort`, which only
+/// drives a port once it exists, so providers can be held as trait objects and registered.
+pub trait PortProvider: Send + Sync {
+    /// Return a short name for the provider, such as `enttec`.
+    fn name(&self) -> &str;
+
+    /// Return the available ports.  The ports will need to be opened before use.
+    fn available_ports(&self) -> Result<PortListing, Error>;
+
+    /// Return the kinds of port the provider lists, so ports of those kinds can be told apart
+    /// from missing ones when the provider can't list them.
+    fn kinds(&self) -> &[PortKind] {
+        &[]
+    }
+}
+
+static PROVIDERS: Mutex<Vec<Arc<dyn PortProvider>>> = Mutex::new(Vec::new());
+
+/// Add a provider of ports, so the ports it lists are included by `available_ports`,
+/// `select_port` and everything else that lists ports.  This lets a crate implementing
+/// `DmxPort` for its own hardware plug it in alongside the built-in backends:
+///
+/// ```no_run
+/// # use rust_dmx::{register_provider, Error, PortListing, PortProvider};
+/// struct MyProvider;
+///
+/// impl PortProvider for MyProvider {
+///     fn name(&self) -> &str {
+///         "my-interface"
+///     }
+///
+///     fn available_ports(&self) -> Result<PortListing, Error> {
+///         // Enumerate the hardware here.
+///         Ok(Vec::new())
+///     }
+/// }
+///
+/// register_provider(MyProvider);
+/// ```
+///
+/// Registering a provider with the name of one already registered replaces it.  Registered
+/// providers are listed after the built-in ones, in the order they were first registered.  For
+/// ports to be deserialized, their `DmxPort` impl must use `#[typetag::serde]` as the built-in
+/// ones do.
+pub fn register_provider(provider: impl PortProvider + 'static) {
+    let provider: Arc<dyn PortProvider> = Arc::new(provider);
+    let mut providers = PROVIDERS.lock().unwrap_or_else(PoisonError::into_inner);
+    match providers
+        .iter_mut()
+        .find(|registered| registered.name() == provider.name())
+    {
+        Some(registered) => *registered = provider,
+        None => providers.push(provider),
+    }
+}
+
+/// Remove a registered provider.  Return false if none was registered under the name.
+pub fn unregister_provider(name: &str) -> bool {
+    let mut providers = PROVIDERS.lock().unwrap_or_else(PoisonError::into_inner);
+    let count = providers.len();
+    providers.retain(|registered| registered.name() != name);
+    providers.len() != count
+}
+
+/// Return the built-in providers of the enabled backends, followed by the registered ones.
+pub(crate) fn providers() -> Vec<Arc<dyn PortProvider>> {
+    let mut providers: Vec<Arc<dyn PortProvider>> = vec![Arc::new(OfflineProvider)];
+    #[cfg(feature = "enttec")]
+    providers.push(Arc::new(crate::EnttecScan::new()));
+    #[cfg(feature = "enttec")]
+    providers.push(Arc::new(crate::OpenDmxProvider));
+    providers.push(Arc::new(SacnProvider));
+    #[cfg(feature = "udmx")]
+    providers.push(Arc::new(crate::UdmxProvider));
+    providers.extend(registered());
+    providers
+}
+
+/// Return the registered providers.  They are copied out so a provider that registers others
+/// while listing doesn't deadlock.
+fn registered() -> Vec<Arc<dyn PortProvider>> {
+    PROVIDERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+
+    struct TestProvider {
+        fail: bool,
+    }
+
+    impl PortProvider for TestProvider {
+        fn name(&self) -> &str {
+            "test-provider"
+        }
+
+        fn available_ports(&self) -> Result<PortListing, Error> {
+            match self.fail {
+                true => Err(Error::Timeout),
+                false => Ok(vec![Box::new(MockDmxPort::new())]),
+            }
+        }
+    }
+
+    #[test]
+    fn test_register_provider() {
+        register_provider(TestProvider { fail: true });
+        register_provider(TestProvider { fail: false });
+        let found: Vec<_> = registered()
+            .into_iter()
+            .filter(|provider| provider.name() == "test-provider")
+            .collect();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].available_ports().unwrap()[0].name(), "mock");
+        assert!(providers().iter().any(|p| p.name() == "test-provider"));
+        assert!(unregister_provider("test-provider"));
+        assert!(!unregister_provider("test-provider"));
+        assert!(registered().iter().all(|p| p.name() != "test-provider"));
+    }
+}
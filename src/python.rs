@@ -0,0 +1,177 @@
+//! Python bindings, so show control scripts can drive ports without talking to the widgets
+//! themselves.  Build the `rust_dmx` module with `maturin develop --features python`.
+//!
+//! ```python
+//! import rust_dmx
+//!
+//! port = rust_dmx.open_port("DMX USB PRO")
+//! port.write(bytes([255, 0, 128]))
+//! port.close()
+//! ```
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+
+use crate::{available_ports, open_port_by_name, DmxPort, Error, OfflineDmxPort};
+
+create_exception!(
+    rust_dmx,
+    DmxError,
+    PyException,
+    "Raised when a DMX port fails."
+);
+
+impl From<Error> for PyErr {
+    fn from(e: Error) -> Self {
+        DmxError::new_err(e.to_string())
+    }
+}
+
+/// A DMX port.  Ports aren't thread safe, so they can only be used from the thread that
+/// created them.
+#[pyclass(name = "DmxPort", subclass, unsendable)]
+struct PyDmxPort {
+    port: Box<dyn DmxPort>,
+}
+
+impl PyDmxPort {
+    /// Start initializing a subclass wrapping the provided port.
+    fn initializer(port: impl DmxPort + 'static) -> PyClassInitializer<Self> {
+        PyClassInitializer::from(Self {
+            port: Box::new(port),
+        })
+    }
+}
+
+#[pymethods]
+impl PyDmxPort {
+    /// The short name of the port.
+    #[getter]
+    fn name(&self) -> &str {
+        self.port.name()
+    }
+
+    /// The kind of interface behind the port, such as "Enttec DMX USB Pro".
+    #[getter]
+    fn kind(&self) -> String {
+        self.port.kind().to_string()
+    }
+
+    fn open(&mut self) -> PyResult<()> {
+        Ok(self.port.open()?)
+    }
+
+    fn close(&mut self) {
+        self.port.close()
+    }
+
+    /// Write a frame of channel levels, starting from channel 1.
+    fn write(&mut self, frame: Vec<u8>) -> PyResult<()> {
+        Ok(self.port.write(&frame)?)
+    }
+
+    fn __str__(&self) -> String {
+        self.port.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("<DmxPort {:?}: {}>", self.port.name(), self.port)
+    }
+}
+
+/// A port that discards everything written to it.
+#[pyclass(name = "OfflineDmxPort", extends = PyDmxPort, unsendable)]
+struct PyOfflineDmxPort;
+
+#[pymethods]
+impl PyOfflineDmxPort {
+    #[new]
+    fn new() -> PyClassInitializer<Self> {
+        PyDmxPort::initializer(OfflineDmxPort::new()).add_subclass(Self)
+    }
+}
+
+/// An Enttec DMX USB Pro or compatible widget on the provided serial port.  The port is not
+/// opened yet.
+#[cfg(feature = "enttec")]
+#[pyclass(name = "EnttecDmxPort", extends = PyDmxPort, unsendable)]
+struct PyEnttecDmxPort;
+
+#[cfg(feature = "enttec")]
+#[pymethods]
+impl PyEnttecDmxPort {
+    #[new]
+    fn new(path: String) -> PyClassInitializer<Self> {
+        let info = serialport::SerialPortInfo {
+            port_name: path,
+            port_type: serialport::SerialPortType::Unknown,
+        };
+        PyDmxPort::initializer(crate::EnttecDmxPort::new(info)).add_subclass(Self)
+    }
+}
+
+/// List the available ports.  None of them are opened yet.
+#[pyfunction(name = "available_ports")]
+fn py_available_ports() -> PyResult<Vec<PyDmxPort>> {
+    Ok(available_ports()?
+        .into_iter()
+        .map(|info| PyDmxPort {
+            port: info.into_port(),
+        })
+        .collect())
+}
+
+/// Open a port by name, or the first port whose name or description contains the provided
+/// text.
+#[pyfunction]
+fn open_port(name: &str) -> PyResult<PyDmxPort> {
+    Ok(PyDmxPort {
+        port: open_port_by_name(name)?,
+    })
+}
+
+#[pymodule]
+fn rust_dmx(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("DmxError", m.py().get_type::<DmxError>())?;
+    m.add_class::<PyDmxPort>()?;
+    m.add_class::<PyOfflineDmxPort>()?;
+    #[cfg(feature = "enttec")]
+    m.add_class::<PyEnttecDmxPort>()?;
+    m.add_function(wrap_pyfunction!(py_available_ports, m)?)?;
+    m.add_function(wrap_pyfunction!(open_port, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pyo3::ffi::c_str;
+    use pyo3::types::PyDict;
+
+    #[test]
+    fn test_python() {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "rust_dmx").unwrap();
+            rust_dmx(&module).unwrap();
+            let locals = PyDict::new(py);
+            locals.set_item("rust_dmx", module).unwrap();
+            py.run(
+                c_str!(
+                    "port = rust_dmx.OfflineDmxPort()\n\
+                     port.open()\n\
+                     port.write(bytes([1, 2, 3]))\n\
+                     assert port.name == 'offline'\n\
+                     assert port.kind == 'offline'\n\
+                     assert 'offline' in [p.name for p in rust_dmx.available_ports()]\n\
+                     try:\n    rust_dmx.open_port('no such port')\n    assert False\n\
+                     except rust_dmx.DmxError:\n    pass\n"
+                ),
+                None,
+                Some(&locals),
+            )
+            .unwrap();
+        });
+    }
+}
@@ -0,0 +1,186 @@
+//! Limiting how often frames are sent to a port.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::{DmxPort, Error, HardwareInfo, PortKind, PortStats, PortStatus};
+
+/// A port that sends at most one frame per interval, for applications that render frames
+/// faster than the interface can take them.  Frames written too soon after the last one sent
+/// are held, each replacing the one before, and the latest is sent by the first write once the
+/// interval has passed.  Writing never blocks.
+///
+/// **A held frame is only sent by a later call on the port.**  Once its interval has passed it
+/// is sent by `health_check`, and regardless of the interval by `flush` and when the port is
+/// closed.  An application that stops writing after a burst of frames must keep calling
+/// `health_check`, or call `flush`, or the last frame of the burst is never shown.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RateLimitedPort {
+    port: Box<dyn DmxPort>,
+    interval: Duration,
+    #[serde(skip)]
+    last_sent: Option<Instant>,
+    #[serde(skip)]
+    held: Option<Vec<u8>>,
+}
+
+impl RateLimitedPort {
+    /// Wrap a port, sending at most one frame per interval.  An interval of
+    /// `Duration::from_secs(1) / 40` limits the port to 40 frames per second.
+    pub fn new(port: Box<dyn DmxPort>, interval: Duration) -> Self {
+        Self {
+            port,
+            interval,
+            last_sent: None,
+            held: None,
+        }
+    }
+
+    /// Return the shortest time between frames sent to the wrapped port.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Return the wrapped port.
+    pub fn inner(&self) -> &dyn DmxPort {
+        self.port.as_ref()
+    }
+
+    /// Return the wrapped port.  A held frame is discarded.
+    pub fn into_inner(self) -> Box<dyn DmxPort> {
+        self.port
+    }
+
+    /// Send the held frame now, if there is one, regardless of the interval.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        match self.held.take() {
+            Some(frame) => self.send(&frame),
+            None => Ok(()),
+        }
+    }
+
+    fn is_due(&self) -> bool {
+        self.last_sent
+            .map_or(true, |last_sent| last_sent.elapsed() >= self.interval)
+    }
+
+    fn send(&mut self, frame: &[u8]) -> Result<(), Error> {
+        self.last_sent = Some(Instant::now());
+        self.port.write(frame)
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for RateLimitedPort {
+    fn name(&self) -> &str {
+        self.port.name()
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        self.port.open()
+    }
+
+    fn close(&mut self) {
+        let _ = self.flush();
+        self.port.close();
+        self.last_sent = None;
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        if self.is_due() {
+            self.held = None;
+            return self.send(frame);
+        }
+        let held = self.held.get_or_insert_with(Vec::new);
+        held.clear();
+        held.extend_from_slice(frame);
+        Ok(())
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        match start_code {
+            0 => self.write(data),
+            _ => self.port.write_with_start_code(start_code, data),
+        }
+    }
+
+    fn kind(&self) -> PortKind {
+        self.port.kind()
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        self.port.hardware()
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        self.port.stats()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.port.status()
+    }
+
+    /// Send the held frame if its interval has passed, then check the wrapped port.
+    fn health_check(&mut self) -> Result<(), Error> {
+        if self.is_due() {
+            self.flush()?;
+        }
+        self.port.health_check()
+    }
+
+    /// Return the held frame if there is one, since that is the frame the port is showing
+    /// next, or the last frame sent otherwise.
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.held.as_deref().or_else(|| self.port.last_frame())
+    }
+}
+
+impl fmt::Display for RateLimitedPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.port.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+
+    #[test]
+    fn test_rate_limit() {
+        let mock = MockDmxPort::new();
+        let mut port = RateLimitedPort::new(Box::new(mock.clone()), Duration::from_secs(3600));
+        port.open().unwrap();
+        port.write(&[1]).unwrap();
+        port.write(&[2]).unwrap();
+        port.write(&[3]).unwrap();
+        assert_eq!(mock.frames(), vec![vec![1]]);
+        port.write_with_start_code(0x17, &[1]).unwrap();
+        assert_eq!(mock.start_code_packets(), vec![(0x17, vec![1])]);
+        port.flush().unwrap();
+        port.flush().unwrap();
+        assert_eq!(mock.frames(), vec![vec![1], vec![3]]);
+        port.write(&[4]).unwrap();
+        port.close();
+        assert_eq!(mock.last_frame(), Some(vec![4]));
+    }
+
+    #[test]
+    fn test_last_frame_of_burst() {
+        let mock = MockDmxPort::new();
+        let interval = Duration::from_millis(100);
+        let mut port = RateLimitedPort::new(Box::new(mock.clone()), interval);
+        port.open().unwrap();
+        port.write(&[1]).unwrap();
+        port.write(&[2]).unwrap();
+        port.write(&[3]).unwrap();
+        port.health_check().unwrap();
+        assert_eq!(mock.frames(), vec![vec![1]]);
+        std::thread::sleep(interval);
+        port.health_check().unwrap();
+        assert_eq!(mock.frames(), vec![vec![1], vec![3]]);
+        port.health_check().unwrap();
+        assert_eq!(mock.write_count(), 2);
+    }
+}
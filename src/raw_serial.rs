@@ -0,0 +1,187 @@
+//! Implementation of DMX output through any plain RS485 serial adapter.
+
+use serde::{Deserialize, Serialize};
+use serialport::{new, DataBits, Parity, SerialPort, StopBits};
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use crate::serial::{write_with_break, DMX_BAUD_RATE, MIN_UNIVERSE_SIZE};
+use crate::trace;
+use crate::{
+    BreakTiming, DmxFrame, DmxPort, Error, FrameShape, PortKind, PortStatus, StatusTracker,
+};
+
+/// A DMX port on a generic USB-RS485 adapter, or any other serial device wired to a DMX line.
+/// The line is driven at 250 kbaud and the break and mark after break are generated by
+/// holding and releasing the break condition, so the adapter's driver must support it.  As
+/// with the Open DMX USB, the host generates all DMX timing.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RawSerialDmxPort {
+    /// The OS path of the serial device, such as `/dev/ttyUSB0` or `COM3`.
+    path: String,
+    #[serde(default)]
+    timing: BreakTiming,
+    #[serde(default)]
+    shape: FrameShape,
+    /// Shared by clones, which take turns writing whole packets.
+    #[serde(skip)]
+    port: Option<Arc<Mutex<Box<dyn SerialPort>>>>,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+impl RawSerialDmxPort {
+    /// Create a port on the serial device at the provided path.
+    /// The port is not opened yet.
+    pub fn new<S: Into<String>>(path: S) -> Self {
+        Self {
+            path: path.into(),
+            timing: BreakTiming::default(),
+            shape: FrameShape::default(),
+            port: None,
+            last_frame: None,
+            status: StatusTracker::new(),
+        }
+    }
+
+    /// Create a port on the serial device at the provided path and open it.
+    pub fn opened<S: Into<String>>(path: S) -> Result<Self, Error> {
+        let mut port = Self::new(path);
+        port.open()?;
+        Ok(port)
+    }
+
+    /// Use the provided break and mark after break durations, such as for fixtures that need
+    /// a longer break than the default.
+    pub fn with_timing(mut self, timing: BreakTiming) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Return the break and mark after break durations.
+    pub fn timing(&self) -> BreakTiming {
+        self.timing
+    }
+
+    /// Set how each frame is shaped before it is transmitted: how many of its slots are sent,
+    /// and how shorter frames are padded.  By default frames are transmitted at the length
+    /// they're written, padded with zeros only as far as the protocol requires.
+    pub fn with_shape(mut self, shape: FrameShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Return how each frame is shaped before it is transmitted.
+    pub fn shape(&self) -> &FrameShape {
+        &self.shape
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for RawSerialDmxPort {
+    fn name(&self) -> &str {
+        &self.path
+    }
+
+    /// Open the port.
+    fn open(&mut self) -> Result<(), Error> {
+        if self.port.is_some() {
+            return Ok(());
+        }
+        let result = new(&self.path, DMX_BAUD_RATE)
+            .data_bits(DataBits::Eight)
+            .parity(Parity::None)
+            .stop_bits(StopBits::Two)
+            .timeout(Duration::from_millis(100))
+            .open()
+            .map(|port| self.port = Some(Arc::new(Mutex::new(port))))
+            .map_err(Error::from);
+        trace::opened(&self.path, &result);
+        self.status.record(result)
+    }
+
+    fn close(&mut self) {
+        self.status.clear();
+        if self.port.take().is_some() {
+            trace::closed(&self.path);
+        }
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let sent = self
+            .shape
+            .apply(frame, |slots| slots.max(MIN_UNIVERSE_SIZE));
+        let result = match &self.port {
+            Some(port) => {
+                let mut port = port.lock().unwrap_or_else(PoisonError::into_inner);
+                write_with_break(port.as_mut(), &self.timing, 0, sent)
+            }
+            None => Err(Error::NotOpen),
+        };
+        trace::wrote(&self.path, frame.len(), &result);
+        if result.is_ok() {
+            self.last_frame = Some(DmxFrame::from(frame));
+        }
+        self.status.record(result)
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        if start_code == 0 {
+            return self.write(data);
+        }
+        let result = match &self.port {
+            Some(port) => {
+                let mut port = port.lock().unwrap_or_else(PoisonError::into_inner);
+                write_with_break(port.as_mut(), &self.timing, start_code, data)
+            }
+            None => Err(Error::NotOpen),
+        };
+        self.status.record(result)
+    }
+
+    fn kind(&self) -> PortKind {
+        PortKind::RawSerial
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(self.port.is_some())
+    }
+}
+
+impl fmt::Display for RawSerialDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "serial DMX {}", self.path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_open_missing() {
+        let mut port = RawSerialDmxPort::new("/dev/rust-dmx-missing");
+        assert_eq!(port.name(), "/dev/rust-dmx-missing");
+        assert_eq!(port.to_string(), "serial DMX /dev/rust-dmx-missing");
+        assert!(matches!(port.write(&[1]), Err(Error::NotOpen)));
+        assert!(matches!(
+            port.write_with_start_code(0x17, &[1]),
+            Err(Error::NotOpen)
+        ));
+        assert!(matches!(port.open(), Err(Error::DeviceDisconnected)));
+        assert_eq!(
+            port.status(),
+            PortStatus::Errored(Error::DeviceDisconnected.to_string())
+        );
+        port.close();
+        assert_eq!(port.status(), PortStatus::Closed);
+        assert!(port.last_frame().is_none());
+    }
+}
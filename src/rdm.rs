@@ -0,0 +1,886 @@
+//! Remote Device Management (ANSI E1.20) message types and discovery.
+
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+use crate::Error;
+
+/// The alternate START code that introduces an RDM packet.
+pub const RDM_START_CODE: u8 = 0xCC;
+const SUB_START_CODE: u8 = 0x01;
+
+/// Size of an RDM packet up to the parameter data, including the START code.
+const HEADER_SIZE: usize = 24;
+const MAX_PARAMETER_DATA_SIZE: usize = 231;
+
+// Discovery parameter IDs.
+pub const DISC_UNIQUE_BRANCH: u16 = 0x0001;
+pub const DISC_MUTE: u16 = 0x0002;
+pub const DISC_UN_MUTE: u16 = 0x0003;
+
+// Parameter IDs with typed accessors.
+pub const SUPPORTED_PARAMETERS: u16 = 0x0050;
+pub const DEVICE_INFO: u16 = 0x0060;
+pub const DEVICE_MODEL_DESCRIPTION: u16 = 0x0080;
+pub const MANUFACTURER_LABEL: u16 = 0x0081;
+pub const DEVICE_LABEL: u16 = 0x0082;
+pub const SOFTWARE_VERSION_LABEL: u16 = 0x00C0;
+pub const DMX_PERSONALITY: u16 = 0x00E0;
+pub const DMX_START_ADDRESS: u16 = 0x00F0;
+pub const IDENTIFY_DEVICE: u16 = 0x1000;
+
+// Response types, carried in the port ID field of a response.
+const RESPONSE_TYPE_ACK: u8 = 0x00;
+const RESPONSE_TYPE_ACK_TIMER: u8 = 0x01;
+const RESPONSE_TYPE_NACK_REASON: u8 = 0x02;
+const RESPONSE_TYPE_ACK_OVERFLOW: u8 = 0x03;
+
+/// Labels are limited to 32 characters.
+const MAX_LABEL_SIZE: usize = 32;
+
+// Framing of a DISC_UNIQUE_BRANCH response.
+const DUB_PREAMBLE: u8 = 0xFE;
+const DUB_SEPARATOR: u8 = 0xAA;
+const DUB_MAX_PREAMBLE: usize = 7;
+const DUB_ENCODED_SIZE: usize = 16;
+
+/// Unique identifier of an RDM responder or controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Uid {
+    /// ESTA manufacturer ID.
+    pub manufacturer: u16,
+    /// Device ID, unique within the manufacturer.
+    pub device: u32,
+}
+
+impl Uid {
+    /// The UID addressing all devices.
+    pub const BROADCAST: Uid = Uid::new(0xFFFF, 0xFFFF_FFFF);
+    /// The lowest possible UID.
+    pub const MIN: Uid = Uid::new(0, 0);
+    /// The highest UID a device may have; everything above is reserved for broadcasts.
+    pub const MAX: Uid = Uid::new(0x7FFF, 0xFFFF_FFFF);
+
+    pub const fn new(manufacturer: u16, device: u32) -> Self {
+        Self {
+            manufacturer,
+            device,
+        }
+    }
+
+    /// Return the UID addressing all devices of one manufacturer.
+    pub const fn manufacturer_broadcast(manufacturer: u16) -> Self {
+        Self::new(manufacturer, 0xFFFF_FFFF)
+    }
+
+    /// Return the UID an RDMnet client uses to ask its broker to assign it a device ID
+    /// under the manufacturer.
+    pub const fn dynamic(manufacturer: u16) -> Self {
+        Self::new(0x8000 | manufacturer, 0)
+    }
+
+    fn from_u64(v: u64) -> Self {
+        Self::new((v >> 32) as u16, v as u32)
+    }
+
+    fn to_u64(self) -> u64 {
+        (self.manufacturer as u64) << 32 | self.device as u64
+    }
+
+    pub(crate) fn from_bytes(b: &[u8]) -> Self {
+        Self::new(
+            u16::from_be_bytes([b[0], b[1]]),
+            u32::from_be_bytes([b[2], b[3], b[4], b[5]]),
+        )
+    }
+
+    /// Return true if this UID addresses more than one device.
+    pub fn is_broadcast(self) -> bool {
+        self.device == 0xFFFF_FFFF
+    }
+
+    pub fn to_bytes(self) -> [u8; 6] {
+        let [m0, m1] = self.manufacturer.to_be_bytes();
+        let [d0, d1, d2, d3] = self.device.to_be_bytes();
+        [m0, m1, d0, d1, d2, d3]
+    }
+}
+
+impl fmt::Display for Uid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04X}:{:08X}", self.manufacturer, self.device)
+    }
+}
+
+/// The command class of an RDM packet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CommandClass {
+    Discovery,
+    DiscoveryResponse,
+    Get,
+    GetResponse,
+    Set,
+    SetResponse,
+}
+
+impl CommandClass {
+    fn to_u8(self) -> u8 {
+        use CommandClass::*;
+        match self {
+            Discovery => 0x10,
+            DiscoveryResponse => 0x11,
+            Get => 0x20,
+            GetResponse => 0x21,
+            Set => 0x30,
+            SetResponse => 0x31,
+        }
+    }
+
+    fn from_u8(v: u8) -> Option<Self> {
+        use CommandClass::*;
+        Some(match v {
+            0x10 => Discovery,
+            0x11 => DiscoveryResponse,
+            0x20 => Get,
+            0x21 => GetResponse,
+            0x30 => Set,
+            0x31 => SetResponse,
+            _ => return None,
+        })
+    }
+
+    /// Return the command class of a response to this command class.
+    fn response(self) -> Self {
+        use CommandClass::*;
+        match self {
+            Discovery | DiscoveryResponse => DiscoveryResponse,
+            Get | GetResponse => GetResponse,
+            Set | SetResponse => SetResponse,
+        }
+    }
+}
+
+/// Reasons a responder may give for refusing a request.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum NackReason {
+    UnknownPid,
+    FormatError,
+    HardwareFault,
+    ProxyReject,
+    WriteProtect,
+    UnsupportedCommandClass,
+    DataOutOfRange,
+    BufferFull,
+    PacketSizeUnsupported,
+    SubDeviceOutOfRange,
+    ProxyBufferFull,
+    #[display(fmt = "Other({:#06X})", _0)]
+    Other(u16),
+}
+
+impl From<u16> for NackReason {
+    fn from(v: u16) -> Self {
+        use NackReason::*;
+        match v {
+            0x0000 => UnknownPid,
+            0x0001 => FormatError,
+            0x0002 => HardwareFault,
+            0x0003 => ProxyReject,
+            0x0004 => WriteProtect,
+            0x0005 => UnsupportedCommandClass,
+            0x0006 => DataOutOfRange,
+            0x0007 => BufferFull,
+            0x0008 => PacketSizeUnsupported,
+            0x0009 => SubDeviceOutOfRange,
+            0x000A => ProxyBufferFull,
+            v => Other(v),
+        }
+    }
+}
+
+/// Ways an RDM transaction can fail.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq)]
+pub enum RdmError {
+    /// The responder did not answer.
+    NoResponse,
+    /// The response was malformed or did not match the request.
+    InvalidResponse,
+    /// The responder refused the request.
+    #[display(fmt = "request refused: {}", _0)]
+    Nack(NackReason),
+    /// The responder will have the answer ready after the provided delay; it can then be
+    /// collected with QUEUED_MESSAGE.
+    #[display(fmt = "response delayed by {:?}", _0)]
+    AckTimer(Duration),
+}
+
+impl std::error::Error for RdmError {}
+
+/// A standard RDM packet, either a request or a response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RdmPacket {
+    pub destination: Uid,
+    pub source: Uid,
+    pub transaction_number: u8,
+    /// Port ID in requests, response type in responses.
+    pub port_id: u8,
+    pub message_count: u8,
+    pub sub_device: u16,
+    pub command_class: CommandClass,
+    pub parameter_id: u16,
+    pub parameter_data: Vec<u8>,
+}
+
+impl RdmPacket {
+    /// Encode the packet, starting with the RDM START code and ending with the checksum.
+    /// Parameter data beyond the 231 bytes allowed by the standard is ignored.
+    pub fn encode(&self) -> Vec<u8> {
+        let pd = &self.parameter_data
+            [0..std::cmp::min(self.parameter_data.len(), MAX_PARAMETER_DATA_SIZE)];
+        let mut buf = Vec::with_capacity(HEADER_SIZE + pd.len() + 2);
+        buf.push(RDM_START_CODE);
+        buf.push(SUB_START_CODE);
+        buf.push((HEADER_SIZE + pd.len()) as u8);
+        buf.extend_from_slice(&self.destination.to_bytes());
+        buf.extend_from_slice(&self.source.to_bytes());
+        buf.push(self.transaction_number);
+        buf.push(self.port_id);
+        buf.push(self.message_count);
+        buf.extend_from_slice(&self.sub_device.to_be_bytes());
+        buf.push(self.command_class.to_u8());
+        buf.extend_from_slice(&self.parameter_id.to_be_bytes());
+        buf.push(pd.len() as u8);
+        buf.extend_from_slice(pd);
+        let checksum = checksum(&buf);
+        buf.extend_from_slice(&checksum.to_be_bytes());
+        buf
+    }
+
+    /// Decode a packet starting with the RDM START code.
+    /// Return None if the packet is truncated, malformed or fails its checksum.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < HEADER_SIZE + 2 || buf[0] != RDM_START_CODE || buf[1] != SUB_START_CODE {
+            return None;
+        }
+        let length = buf[2] as usize;
+        let pdl = buf[23] as usize;
+        if length != HEADER_SIZE + pdl || buf.len() < length + 2 {
+            return None;
+        }
+        let expected = u16::from_be_bytes([buf[length], buf[length + 1]]);
+        if checksum(&buf[0..length]) != expected {
+            return None;
+        }
+        Some(Self {
+            destination: Uid::from_bytes(&buf[3..9]),
+            source: Uid::from_bytes(&buf[9..15]),
+            transaction_number: buf[15],
+            port_id: buf[16],
+            message_count: buf[17],
+            sub_device: u16::from_be_bytes([buf[18], buf[19]]),
+            command_class: CommandClass::from_u8(buf[20])?,
+            parameter_id: u16::from_be_bytes([buf[21], buf[22]]),
+            parameter_data: buf[HEADER_SIZE..length].to_vec(),
+        })
+    }
+}
+
+/// The RDM checksum is the 16-bit sum of every byte it covers.
+fn checksum(buf: &[u8]) -> u16 {
+    buf.iter().fold(0u16, |sum, b| sum.wrapping_add(*b as u16))
+}
+
+/// Decode the response to a DISC_UNIQUE_BRANCH request.
+/// Return None if the response is malformed, which usually means several devices answered at
+/// once.
+pub fn decode_dub_response(buf: &[u8]) -> Option<Uid> {
+    let preamble = buf
+        .iter()
+        .take(DUB_MAX_PREAMBLE)
+        .take_while(|b| **b == DUB_PREAMBLE)
+        .count();
+    let buf = &buf[preamble..];
+    if buf.len() < DUB_ENCODED_SIZE + 1 || buf[0] != DUB_SEPARATOR {
+        return None;
+    }
+    let encoded = &buf[1..DUB_ENCODED_SIZE + 1];
+    let decoded: Vec<u8> = encoded.chunks(2).map(|pair| pair[0] & pair[1]).collect();
+    let expected = u16::from_be_bytes([decoded[6], decoded[7]]);
+    if checksum(&encoded[0..12]) != expected {
+        return None;
+    }
+    Some(Uid::from_bytes(&decoded[0..6]))
+}
+
+/// Encode a DISC_UNIQUE_BRANCH response, as a responder would send it.
+#[cfg(test)]
+fn encode_dub_response(uid: Uid) -> Vec<u8> {
+    let mut buf = vec![DUB_PREAMBLE; DUB_MAX_PREAMBLE];
+    buf.push(DUB_SEPARATOR);
+    let mut encoded = Vec::new();
+    for b in uid.to_bytes().iter() {
+        encoded.extend_from_slice(&[b | 0xAA, b | 0x55]);
+    }
+    let checksum = checksum(&encoded);
+    buf.extend_from_slice(&encoded);
+    for b in checksum.to_be_bytes().iter() {
+        buf.extend_from_slice(&[b | 0xAA, b | 0x55]);
+    }
+    buf
+}
+
+/// A link that can carry RDM traffic to the responders on a DMX line.
+pub trait RdmTransport {
+    /// Return the UID this controller uses as the source of its requests.
+    fn controller_uid(&self) -> Uid;
+
+    /// Return the next transaction number to use.
+    fn next_transaction_number(&mut self) -> u8;
+
+    /// Send a DISC_UNIQUE_BRANCH request and return the raw response, if any device answered.
+    fn send_discovery(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Send an RDM request and return the raw response, if one is received.
+    /// For broadcast requests no response is expected, and None should be returned
+    /// without waiting.
+    fn send_request(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+
+    /// Send a GET request and return the parameter data of the response.
+    fn rdm_get(
+        &mut self,
+        destination: Uid,
+        sub_device: u16,
+        parameter_id: u16,
+        parameter_data: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        transact(
+            self,
+            destination,
+            sub_device,
+            CommandClass::Get,
+            parameter_id,
+            parameter_data,
+        )
+    }
+
+    /// Send a SET request and return the parameter data of the response.
+    fn rdm_set(
+        &mut self,
+        destination: Uid,
+        sub_device: u16,
+        parameter_id: u16,
+        parameter_data: &[u8],
+    ) -> Result<Vec<u8>, Error> {
+        transact(
+            self,
+            destination,
+            sub_device,
+            CommandClass::Set,
+            parameter_id,
+            parameter_data,
+        )
+    }
+
+    /// Return the parameter IDs a device supports beyond the required minimum.
+    fn supported_parameters(&mut self, uid: Uid) -> Result<Vec<u16>, Error> {
+        let data = self.rdm_get(uid, 0, SUPPORTED_PARAMETERS, &[])?;
+        Ok(data
+            .chunks_exact(2)
+            .map(|pid| u16::from_be_bytes([pid[0], pid[1]]))
+            .collect())
+    }
+
+    fn device_info(&mut self, uid: Uid) -> Result<DeviceInfo, Error> {
+        Ok(DeviceInfo::parse(&self.rdm_get(
+            uid,
+            0,
+            DEVICE_INFO,
+            &[],
+        )?)?)
+    }
+
+    fn device_model_description(&mut self, uid: Uid) -> Result<String, Error> {
+        Ok(read_label(&self.rdm_get(
+            uid,
+            0,
+            DEVICE_MODEL_DESCRIPTION,
+            &[],
+        )?))
+    }
+
+    fn manufacturer_label(&mut self, uid: Uid) -> Result<String, Error> {
+        Ok(read_label(&self.rdm_get(
+            uid,
+            0,
+            MANUFACTURER_LABEL,
+            &[],
+        )?))
+    }
+
+    fn device_label(&mut self, uid: Uid) -> Result<String, Error> {
+        Ok(read_label(&self.rdm_get(uid, 0, DEVICE_LABEL, &[])?))
+    }
+
+    /// Set the user-assigned label of a device.  Labels longer than 32 bytes are truncated.
+    fn set_device_label(&mut self, uid: Uid, label: &str) -> Result<(), Error> {
+        let label = &label.as_bytes()[0..label.len().min(MAX_LABEL_SIZE)];
+        self.rdm_set(uid, 0, DEVICE_LABEL, label)?;
+        Ok(())
+    }
+
+    fn software_version_label(&mut self, uid: Uid) -> Result<String, Error> {
+        Ok(read_label(&self.rdm_get(
+            uid,
+            0,
+            SOFTWARE_VERSION_LABEL,
+            &[],
+        )?))
+    }
+
+    fn dmx_personality(&mut self, uid: Uid) -> Result<DmxPersonality, Error> {
+        Ok(DmxPersonality::parse(&self.rdm_get(
+            uid,
+            0,
+            DMX_PERSONALITY,
+            &[],
+        )?)?)
+    }
+
+    /// Select a personality, starting from 1.
+    fn set_dmx_personality(&mut self, uid: Uid, personality: u8) -> Result<(), Error> {
+        self.rdm_set(uid, 0, DMX_PERSONALITY, &[personality])?;
+        Ok(())
+    }
+
+    fn dmx_start_address(&mut self, uid: Uid) -> Result<u16, Error> {
+        Ok(read_u16(&self.rdm_get(uid, 0, DMX_START_ADDRESS, &[])?, 0)?)
+    }
+
+    /// Set the DMX start address of a device, in the range 1 to 512.
+    fn set_dmx_start_address(&mut self, uid: Uid, address: u16) -> Result<(), Error> {
+        self.rdm_set(uid, 0, DMX_START_ADDRESS, &address.to_be_bytes())?;
+        Ok(())
+    }
+
+    fn identify_device(&mut self, uid: Uid) -> Result<bool, Error> {
+        Ok(read_u8(&self.rdm_get(uid, 0, IDENTIFY_DEVICE, &[])?, 0)? != 0)
+    }
+
+    /// Turn a device's identify mode, usually a flashing light, on or off.
+    fn set_identify_device(&mut self, uid: Uid, identify: bool) -> Result<(), Error> {
+        self.rdm_set(uid, 0, IDENTIFY_DEVICE, &[identify as u8])?;
+        Ok(())
+    }
+}
+
+/// Build a request from the controller of the transport.
+fn request<T: RdmTransport + ?Sized>(
+    transport: &mut T,
+    destination: Uid,
+    sub_device: u16,
+    command_class: CommandClass,
+    parameter_id: u16,
+    parameter_data: Vec<u8>,
+) -> RdmPacket {
+    RdmPacket {
+        destination,
+        source: transport.controller_uid(),
+        transaction_number: transport.next_transaction_number(),
+        port_id: 1,
+        message_count: 0,
+        sub_device,
+        command_class,
+        parameter_id,
+        parameter_data,
+    }
+}
+
+/// Build a discovery command from the controller of the transport.
+fn discovery_request<T: RdmTransport + ?Sized>(
+    transport: &mut T,
+    destination: Uid,
+    parameter_id: u16,
+    parameter_data: Vec<u8>,
+) -> RdmPacket {
+    request(
+        transport,
+        destination,
+        0,
+        CommandClass::Discovery,
+        parameter_id,
+        parameter_data,
+    )
+}
+
+/// Perform a GET or SET transaction and return the parameter data of the response.
+/// Overflowing responses are collected into a single buffer; broadcast requests return
+/// empty data, as nobody answers them.
+fn transact<T: RdmTransport + ?Sized>(
+    transport: &mut T,
+    destination: Uid,
+    sub_device: u16,
+    command_class: CommandClass,
+    parameter_id: u16,
+    parameter_data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    loop {
+        let request = request(
+            transport,
+            destination,
+            sub_device,
+            command_class,
+            parameter_id,
+            parameter_data.to_vec(),
+        );
+        let response = transport.send_request(&request.encode())?;
+        if destination.is_broadcast() {
+            return Ok(data);
+        }
+        let response = response.ok_or(RdmError::NoResponse)?;
+        let response = RdmPacket::decode(&response).ok_or(RdmError::InvalidResponse)?;
+        if response.source != destination
+            || response.transaction_number != request.transaction_number
+            || response.command_class != command_class.response()
+            || response.parameter_id != parameter_id
+        {
+            return Err(RdmError::InvalidResponse.into());
+        }
+        match response.port_id {
+            RESPONSE_TYPE_ACK => {
+                data.extend_from_slice(&response.parameter_data);
+                return Ok(data);
+            }
+            RESPONSE_TYPE_ACK_OVERFLOW => data.extend_from_slice(&response.parameter_data),
+            RESPONSE_TYPE_ACK_TIMER => {
+                let delay = read_u16(&response.parameter_data, 0)?;
+                return Err(RdmError::AckTimer(Duration::from_millis(delay as u64 * 100)).into());
+            }
+            RESPONSE_TYPE_NACK_REASON => {
+                let reason = read_u16(&response.parameter_data, 0)?;
+                return Err(RdmError::Nack(reason.into()).into());
+            }
+            _ => return Err(RdmError::InvalidResponse.into()),
+        }
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, RdmError> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or(RdmError::InvalidResponse)
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, RdmError> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(RdmError::InvalidResponse)
+}
+
+fn read_u8(data: &[u8], offset: usize) -> Result<u8, RdmError> {
+    data.get(offset).copied().ok_or(RdmError::InvalidResponse)
+}
+
+/// Labels are ASCII, possibly null-padded; anything else is replaced rather than rejected.
+fn read_label(data: &[u8]) -> String {
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[0..end]).into_owned()
+}
+
+/// The response to a DEVICE_INFO request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub protocol_version: u16,
+    pub device_model_id: u16,
+    pub product_category: u16,
+    pub software_version_id: u32,
+    /// Number of DMX slots the device occupies in its current personality.
+    pub dmx_footprint: u16,
+    pub dmx_personality: DmxPersonality,
+    /// DMX start address, or 0xFFFF if the device has no footprint.
+    pub dmx_start_address: u16,
+    pub sub_device_count: u16,
+    pub sensor_count: u8,
+}
+
+impl DeviceInfo {
+    fn parse(data: &[u8]) -> Result<Self, RdmError> {
+        Ok(Self {
+            protocol_version: read_u16(data, 0)?,
+            device_model_id: read_u16(data, 2)?,
+            product_category: read_u16(data, 4)?,
+            software_version_id: read_u32(data, 6)?,
+            dmx_footprint: read_u16(data, 10)?,
+            dmx_personality: DmxPersonality {
+                current: read_u8(data, 12)?,
+                count: read_u8(data, 13)?,
+            },
+            dmx_start_address: read_u16(data, 14)?,
+            sub_device_count: read_u16(data, 16)?,
+            sensor_count: read_u8(data, 18)?,
+        })
+    }
+}
+
+/// The personality currently selected on a device, out of the number it supports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DmxPersonality {
+    /// The current personality, starting from 1.
+    pub current: u8,
+    pub count: u8,
+}
+
+impl DmxPersonality {
+    fn parse(data: &[u8]) -> Result<Self, RdmError> {
+        Ok(Self {
+            current: read_u8(data, 0)?,
+            count: read_u8(data, 1)?,
+        })
+    }
+}
+
+/// Find the UIDs of every responder reachable through the transport.
+/// Previously discovered devices are unmuted first, so the full population is returned.
+pub fn discover<T: RdmTransport + ?Sized>(transport: &mut T) -> Result<Vec<Uid>, Error> {
+    let unmute = discovery_request(transport, Uid::BROADCAST, DISC_UN_MUTE, Vec::new());
+    transport.send_request(&unmute.encode())?;
+    let mut found = Vec::new();
+    discover_branch(transport, Uid::MIN, Uid::MAX, &mut found)?;
+    found.sort();
+    Ok(found)
+}
+
+/// Binary search of one branch of the UID space for unmuted responders.
+fn discover_branch<T: RdmTransport + ?Sized>(
+    transport: &mut T,
+    lower: Uid,
+    upper: Uid,
+    found: &mut Vec<Uid>,
+) -> Result<(), Error> {
+    loop {
+        let mut bounds = lower.to_bytes().to_vec();
+        bounds.extend_from_slice(&upper.to_bytes());
+        let request = discovery_request(transport, Uid::BROADCAST, DISC_UNIQUE_BRANCH, bounds);
+        let response = match transport.send_discovery(&request.encode())? {
+            Some(response) if !response.is_empty() => response,
+            _ => return Ok(()),
+        };
+        match decode_dub_response(&response) {
+            Some(uid) if uid >= lower && uid <= upper => {
+                // Keep searching this branch until it goes quiet; a device that will not
+                // acknowledge the mute is ignored rather than queried forever.
+                if !mute(transport, uid)? {
+                    return Ok(());
+                }
+                if !found.contains(&uid) {
+                    found.push(uid);
+                }
+            }
+            _ => {
+                // Collision: split the branch in half and search both.
+                if lower == upper {
+                    return Ok(());
+                }
+                let mid = Uid::from_u64((lower.to_u64() + upper.to_u64()) / 2);
+                discover_branch(transport, lower, mid, found)?;
+                return discover_branch(transport, Uid::from_u64(mid.to_u64() + 1), upper, found);
+            }
+        }
+    }
+}
+
+/// Mute a responder so it stops answering discovery; return whether it acknowledged.
+fn mute<T: RdmTransport + ?Sized>(transport: &mut T, uid: Uid) -> Result<bool, Error> {
+    let request = discovery_request(transport, uid, DISC_MUTE, Vec::new());
+    Ok(transport
+        .send_request(&request.encode())?
+        .and_then(|response| RdmPacket::decode(&response))
+        .map(|response| {
+            response.source == uid
+                && response.command_class == CommandClass::DiscoveryResponse
+                && response.parameter_id == DISC_MUTE
+        })
+        .unwrap_or(false))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A DMX line populated with simulated responders.
+    struct Line {
+        devices: Vec<(Uid, bool)>,
+        transaction_number: u8,
+    }
+
+    impl RdmTransport for Line {
+        fn controller_uid(&self) -> Uid {
+            Uid::new(0x454E, 1)
+        }
+
+        fn next_transaction_number(&mut self) -> u8 {
+            self.transaction_number = self.transaction_number.wrapping_add(1);
+            self.transaction_number
+        }
+
+        fn send_discovery(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+            let request = RdmPacket::decode(packet).unwrap();
+            let lower = Uid::from_bytes(&request.parameter_data[0..6]);
+            let upper = Uid::from_bytes(&request.parameter_data[6..12]);
+            let responding: Vec<_> = self
+                .devices
+                .iter()
+                .filter(|(uid, muted)| !muted && *uid >= lower && *uid <= upper)
+                .map(|(uid, _)| encode_dub_response(*uid))
+                .collect();
+            Ok(match responding.len() {
+                0 => None,
+                1 => Some(responding[0].clone()),
+                // Overlapping responses corrupt each other.
+                _ => Some(responding.iter().fold(vec![0; 24], |acc, r| {
+                    acc.iter().zip(r).map(|(a, b)| a | b).collect()
+                })),
+            })
+        }
+
+        fn send_request(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+            let request = RdmPacket::decode(packet).unwrap();
+            let source = request.source;
+            for (uid, muted) in self.devices.iter_mut() {
+                if request.destination == Uid::BROADCAST || request.destination == *uid {
+                    *muted = request.parameter_id == DISC_MUTE;
+                }
+                if request.destination == *uid {
+                    let response = RdmPacket {
+                        destination: source,
+                        source: *uid,
+                        command_class: CommandClass::DiscoveryResponse,
+                        parameter_data: vec![0, 0],
+                        ..request
+                    };
+                    return Ok(Some(response.encode()));
+                }
+            }
+            Ok(None)
+        }
+    }
+
+    /// A single responder that only knows its DMX start address.
+    struct Responder {
+        uid: Uid,
+        start_address: u16,
+        transaction_number: u8,
+    }
+
+    impl RdmTransport for Responder {
+        fn controller_uid(&self) -> Uid {
+            Uid::new(0x454E, 1)
+        }
+
+        fn next_transaction_number(&mut self) -> u8 {
+            self.transaction_number = self.transaction_number.wrapping_add(1);
+            self.transaction_number
+        }
+
+        fn send_discovery(&mut self, _: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+            Ok(None)
+        }
+
+        fn send_request(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+            let request = RdmPacket::decode(packet).unwrap();
+            let (port_id, parameter_data) = match (request.command_class, request.parameter_id) {
+                (CommandClass::Get, DMX_START_ADDRESS) => {
+                    (RESPONSE_TYPE_ACK, self.start_address.to_be_bytes().to_vec())
+                }
+                (CommandClass::Set, DMX_START_ADDRESS) => {
+                    self.start_address = read_u16(&request.parameter_data, 0)?;
+                    (RESPONSE_TYPE_ACK, vec![])
+                }
+                _ => (RESPONSE_TYPE_NACK_REASON, vec![0, 0]),
+            };
+            let response = RdmPacket {
+                destination: request.source,
+                source: self.uid,
+                port_id,
+                command_class: request.command_class.response(),
+                parameter_data,
+                ..request
+            };
+            Ok(Some(response.encode()))
+        }
+    }
+
+    #[test]
+    fn test_get_set() -> Result<(), Error> {
+        let uid = Uid::new(0x1234, 1);
+        let mut responder = Responder {
+            uid,
+            start_address: 1,
+            transaction_number: 0,
+        };
+        assert_eq!(responder.dmx_start_address(uid)?, 1);
+        responder.set_dmx_start_address(uid, 101)?;
+        assert_eq!(responder.dmx_start_address(uid)?, 101);
+        match responder.device_info(uid) {
+            Err(Error::Rdm(RdmError::Nack(NackReason::UnknownPid))) => (),
+            other => panic!("expected a NACK, got {:?}", other),
+        }
+        match responder.dmx_start_address(Uid::new(0x1234, 2)) {
+            Err(Error::Rdm(RdmError::InvalidResponse)) => (),
+            other => panic!("expected an invalid response, got {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_packet_round_trip() {
+        let packet = RdmPacket {
+            destination: Uid::new(0x1234, 0x5678_9ABC),
+            source: Uid::new(0x454E, 1),
+            transaction_number: 3,
+            port_id: 1,
+            message_count: 0,
+            sub_device: 0,
+            command_class: CommandClass::Get,
+            parameter_id: 0x0060,
+            parameter_data: vec![],
+        };
+        let encoded = packet.encode();
+        assert_eq!(encoded.len(), 26);
+        assert_eq!(&encoded[0..3], &[0xCC, 0x01, 24]);
+        assert_eq!(RdmPacket::decode(&encoded), Some(packet));
+    }
+
+    #[test]
+    fn test_dub_response() {
+        let uid = Uid::new(0x454E, 0x0102_0304);
+        assert_eq!(decode_dub_response(&encode_dub_response(uid)), Some(uid));
+        assert_eq!(
+            decode_dub_response(&encode_dub_response(uid)[3..]),
+            Some(uid)
+        );
+        let mut corrupt = encode_dub_response(uid);
+        corrupt[10] = 0xFF;
+        assert_eq!(decode_dub_response(&corrupt), None);
+    }
+
+    #[test]
+    fn test_discovery() -> Result<(), Error> {
+        let uids = vec![
+            Uid::new(0x454E, 0x0000_0001),
+            Uid::new(0x454E, 0x0000_0002),
+            Uid::new(0x0001, 0xFFFF_0000),
+            Uid::new(0x7FFF, 0xFFFF_FFFF),
+        ];
+        let mut line = Line {
+            devices: uids.iter().map(|uid| (*uid, true)).collect(),
+            transaction_number: 0,
+        };
+        let mut expected = uids.clone();
+        expected.sort();
+        assert_eq!(discover(&mut line)?, expected);
+        Ok(())
+    }
+}
@@ -0,0 +1,720 @@
+//! Experimental RDMnet (ANSI E1.33) support: a controller that connects to a broker so RDM
+//! commands can be sent to gateways and devices on the network.
+//!
+//! Brokers must be given by address; finding them with DNS-SD and talking to targets without
+//! a broker over LLRP are not supported yet.
+
+use std::cmp::min;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use crate::rdm::{RdmPacket, RdmTransport, Uid, RDM_START_CODE};
+use crate::sacn::{random_cid, Cid};
+use crate::Error;
+
+/// The scope brokers and clients use unless configured otherwise.
+pub const DEFAULT_SCOPE: &str = "default";
+
+/// The endpoint addressing the gateway or device itself rather than one of its DMX ports.
+pub const NULL_ENDPOINT: u16 = 0;
+
+/// How long to wait for the broker to answer, or for a gateway to relay an RDM response.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Broker messages are small; anything bigger means the stream is out of sync.
+const MAX_MESSAGE_SIZE: usize = 1 << 20;
+
+// TCP framing: messages start with the ACN packet identifier and the size of the root layer.
+const TCP_PREAMBLE: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const ROOT_PDU_OFFSET: usize = 16;
+const CID_SIZE: usize = 16;
+
+// Root layer vectors.
+const VECTOR_ROOT_RPT: u32 = 0x0000_0005;
+const VECTOR_ROOT_BROKER: u32 = 0x0000_0009;
+
+// Broker PDU vectors.
+const VECTOR_BROKER_CONNECT: u16 = 0x0001;
+const VECTOR_BROKER_CONNECT_REPLY: u16 = 0x0002;
+const VECTOR_BROKER_FETCH_CLIENT_LIST: u16 = 0x0006;
+const VECTOR_BROKER_CONNECTED_CLIENT_LIST: u16 = 0x0007;
+const VECTOR_BROKER_DISCONNECT: u16 = 0x000E;
+const VECTOR_BROKER_NULL: u16 = 0x000F;
+
+const E133_VERSION: u16 = 1;
+const SCOPE_LENGTH: usize = 63;
+const DOMAIN_LENGTH: usize = 231;
+const DEFAULT_DOMAIN: &str = "local.";
+const DISCONNECT_SHUTDOWN: u16 = 0x0000;
+
+// Client entries.
+const CLIENT_PROTOCOL_RPT: u32 = 0x0000_0005;
+const RPT_CLIENT_TYPE_DEVICE: u8 = 0x00;
+const RPT_CLIENT_TYPE_CONTROLLER: u8 = 0x01;
+const CLIENT_ENTRY_SIZE: usize = CID_SIZE + 6 + 1 + CID_SIZE;
+
+// RPT PDU vectors.
+const VECTOR_RPT_REQUEST: u32 = 0x0000_0001;
+const VECTOR_RPT_STATUS: u32 = 0x0000_0002;
+const VECTOR_RPT_NOTIFICATION: u32 = 0x0000_0003;
+const VECTOR_REQUEST_RDM_CMD: u32 = 0x0000_0001;
+const VECTOR_NOTIFICATION_RDM_CMD: u32 = 0x0000_0001;
+const RPT_HEADER_SIZE: usize = 21;
+
+// RPT status codes.
+const VECTOR_RPT_STATUS_RDM_TIMEOUT: u16 = 0x0002;
+
+fn malformed() -> Error {
+    Error::Protocol("malformed message from RDMnet broker".to_string())
+}
+
+/// Start a PDU with room for its flags and length, returning where it starts.
+fn begin_pdu(buf: &mut Vec<u8>) -> usize {
+    let start = buf.len();
+    buf.extend_from_slice(&[0; 3]);
+    start
+}
+
+/// Write the flags and 20-bit length of a PDU that runs to the end of the buffer.
+fn end_pdu(buf: &mut [u8], start: usize) {
+    let length = (buf.len() - start) as u32 | 0x00F0_0000;
+    buf[start..start + 3].copy_from_slice(&length.to_be_bytes()[1..]);
+}
+
+/// Start a message in the buffer with the TCP preamble and a root layer carrying the vector.
+fn begin_message(vector: u32, cid: &Cid, buf: &mut Vec<u8>) {
+    buf.clear();
+    buf.extend_from_slice(TCP_PREAMBLE);
+    buf.extend_from_slice(&[0; 4]);
+    begin_pdu(buf);
+    buf.extend_from_slice(&vector.to_be_bytes());
+    buf.extend_from_slice(cid);
+}
+
+/// Fill in the sizes of the message and its root layer once everything has been written.
+fn end_message(buf: &mut [u8]) {
+    let size = (buf.len() - ROOT_PDU_OFFSET) as u32;
+    buf[12..ROOT_PDU_OFFSET].copy_from_slice(&size.to_be_bytes());
+    end_pdu(buf, ROOT_PDU_OFFSET);
+}
+
+/// Format a broker message into the buffer.
+fn write_broker_message(cid: &Cid, vector: u16, data: &[u8], buf: &mut Vec<u8>) {
+    begin_message(VECTOR_ROOT_BROKER, cid, buf);
+    let pdu = begin_pdu(buf);
+    buf.extend_from_slice(&vector.to_be_bytes());
+    buf.extend_from_slice(data);
+    end_pdu(buf, pdu);
+    end_message(buf);
+}
+
+/// Write a null-terminated string field, truncated to fit.
+fn write_padded(value: &str, length: usize, buf: &mut Vec<u8>) {
+    let value = value.as_bytes();
+    let value = &value[0..min(value.len(), length - 1)];
+    buf.extend_from_slice(value);
+    buf.resize(buf.len() + length - value.len(), 0);
+}
+
+/// Format a request to connect to the broker as an RPT controller.
+fn write_connect(cid: &Cid, scope: &str, uid: Uid, buf: &mut Vec<u8>) {
+    begin_message(VECTOR_ROOT_BROKER, cid, buf);
+    let broker = begin_pdu(buf);
+    buf.extend_from_slice(&VECTOR_BROKER_CONNECT.to_be_bytes());
+    write_padded(scope, SCOPE_LENGTH, buf);
+    buf.extend_from_slice(&E133_VERSION.to_be_bytes());
+    write_padded(DEFAULT_DOMAIN, DOMAIN_LENGTH, buf);
+    buf.push(0); // connection flags: no incremental client list updates
+    let entry = begin_pdu(buf);
+    buf.extend_from_slice(&CLIENT_PROTOCOL_RPT.to_be_bytes());
+    buf.extend_from_slice(cid);
+    buf.extend_from_slice(&uid.to_bytes());
+    buf.push(RPT_CLIENT_TYPE_CONTROLLER);
+    buf.extend_from_slice(&[0; CID_SIZE]); // binding CID
+    end_pdu(buf, entry);
+    end_pdu(buf, broker);
+    end_message(buf);
+}
+
+/// The addressing of an RPT message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct RptHeader {
+    source: Uid,
+    source_endpoint: u16,
+    destination: Uid,
+    destination_endpoint: u16,
+    sequence: u32,
+}
+
+impl RptHeader {
+    fn write_into(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.source.to_bytes());
+        buf.extend_from_slice(&self.source_endpoint.to_be_bytes());
+        buf.extend_from_slice(&self.destination.to_bytes());
+        buf.extend_from_slice(&self.destination_endpoint.to_be_bytes());
+        buf.extend_from_slice(&self.sequence.to_be_bytes());
+        buf.push(0); // reserved
+    }
+
+    fn parse(buf: &[u8]) -> Result<Self, Error> {
+        if buf.len() < RPT_HEADER_SIZE {
+            return Err(malformed());
+        }
+        Ok(Self {
+            source: Uid::from_bytes(&buf[0..6]),
+            source_endpoint: u16::from_be_bytes([buf[6], buf[7]]),
+            destination: Uid::from_bytes(&buf[8..14]),
+            destination_endpoint: u16::from_be_bytes([buf[14], buf[15]]),
+            sequence: u32::from_be_bytes([buf[16], buf[17], buf[18], buf[19]]),
+        })
+    }
+}
+
+/// Format an RPT request or notification carrying RDM packets, each starting with the RDM
+/// START code.
+fn write_rpt_message(
+    cid: &Cid,
+    vector: u32,
+    header: &RptHeader,
+    packets: &[&[u8]],
+    buf: &mut Vec<u8>,
+) {
+    begin_message(VECTOR_ROOT_RPT, cid, buf);
+    let rpt = begin_pdu(buf);
+    buf.extend_from_slice(&vector.to_be_bytes());
+    header.write_into(buf);
+    let inner = begin_pdu(buf);
+    // Requests and notifications both carry RDM commands with the same vector.
+    buf.extend_from_slice(&VECTOR_REQUEST_RDM_CMD.to_be_bytes());
+    for packet in packets {
+        // The START code is the vector of an RDM command PDU.
+        let command = begin_pdu(buf);
+        buf.extend_from_slice(packet);
+        end_pdu(buf, command);
+    }
+    end_pdu(buf, inner);
+    end_pdu(buf, rpt);
+    end_message(buf);
+}
+
+/// Split the first PDU off a block, returning its vector and the rest of the PDU.
+fn next_pdu<'a>(buf: &mut &'a [u8], vector_size: usize) -> Result<(u32, &'a [u8]), Error> {
+    if buf.len() < 3 {
+        return Err(malformed());
+    }
+    let length = (u32::from_be_bytes([0, buf[0], buf[1], buf[2]]) & 0x000F_FFFF) as usize;
+    if length < 3 + vector_size || length > buf.len() {
+        return Err(malformed());
+    }
+    let vector = buf[3..3 + vector_size]
+        .iter()
+        .fold(0, |vector, b| vector << 8 | u32::from(*b));
+    let data = &buf[3 + vector_size..length];
+    *buf = &buf[length..];
+    Ok((vector, data))
+}
+
+/// The parts of a message from the broker this client uses.
+enum Message<'a> {
+    Broker {
+        vector: u16,
+        data: &'a [u8],
+    },
+    Rpt {
+        vector: u32,
+        header: RptHeader,
+        data: &'a [u8],
+    },
+    Other,
+}
+
+fn parse_message(mut buf: &[u8]) -> Result<Message<'_>, Error> {
+    let (vector, data) = next_pdu(&mut buf, 4)?;
+    let mut data = data.get(CID_SIZE..).ok_or_else(malformed)?;
+    Ok(match vector {
+        VECTOR_ROOT_BROKER => {
+            let (vector, data) = next_pdu(&mut data, 2)?;
+            Message::Broker {
+                vector: vector as u16,
+                data,
+            }
+        }
+        VECTOR_ROOT_RPT => {
+            let (vector, data) = next_pdu(&mut data, 4)?;
+            Message::Rpt {
+                vector,
+                header: RptHeader::parse(data)?,
+                data: &data[RPT_HEADER_SIZE..],
+            }
+        }
+        _ => Message::Other,
+    })
+}
+
+/// Read a message into the buffer, without its TCP preamble.
+fn read_message(stream: &mut impl Read, buf: &mut Vec<u8>) -> Result<(), Error> {
+    let mut preamble = [0; ROOT_PDU_OFFSET];
+    stream.read_exact(&mut preamble)?;
+    let size = u32::from_be_bytes([preamble[12], preamble[13], preamble[14], preamble[15]]);
+    if &preamble[0..12] != TCP_PREAMBLE || size as usize > MAX_MESSAGE_SIZE {
+        return Err(malformed());
+    }
+    buf.resize(size as usize, 0);
+    stream.read_exact(buf)?;
+    Ok(())
+}
+
+/// Describe why a broker refused a connection.
+fn connection_refused(code: u16) -> Error {
+    let reason = match code {
+        0x0001 => "scope mismatch",
+        0x0002 => "capacity exceeded",
+        0x0003 => "duplicate UID",
+        0x0004 => "invalid client entry",
+        0x0005 => "invalid UID",
+        _ => "unknown reason",
+    };
+    Error::Protocol(format!(
+        "RDMnet broker refused connection: {} ({:#06x})",
+        reason, code
+    ))
+}
+
+/// Turn an RPT status other than a timeout into an error.
+fn status_error(code: u16, message: &[u8]) -> Error {
+    let reason = match code {
+        0x0001 => "unknown RPT UID",
+        0x0003 => "invalid RDM response",
+        0x0004 => "unknown RDM UID",
+        0x0005 => "unknown endpoint",
+        0x0006 => "broadcast complete",
+        0x0007 => "unknown vector",
+        0x0008 => "invalid message",
+        0x0009 => "invalid command class",
+        _ => "unknown status",
+    };
+    let message = String::from_utf8_lossy(message);
+    let message = message.trim_end_matches('\0');
+    if message.is_empty() {
+        Error::Protocol(format!("RDMnet: {}", reason))
+    } else {
+        Error::Protocol(format!("RDMnet: {}: {}", reason, message))
+    }
+}
+
+/// Return the first response in a notification, starting with the RDM START code.  The
+/// command being answered comes first, so it's skipped.
+fn first_response(data: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    let mut data = data;
+    let (vector, mut commands) = next_pdu(&mut data, 4)?;
+    if vector != VECTOR_NOTIFICATION_RDM_CMD {
+        return Err(malformed());
+    }
+    let mut index = 0;
+    while !commands.is_empty() {
+        let (start_code, command) = next_pdu(&mut commands, 1)?;
+        if index > 0 && start_code == u32::from(RDM_START_CODE) {
+            let mut response = vec![RDM_START_CODE];
+            response.extend_from_slice(command);
+            return Ok(Some(response));
+        }
+        index += 1;
+    }
+    Ok(None)
+}
+
+/// Whether a client of a broker is a device or a controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RdmnetClientKind {
+    /// A device or gateway, which answers RDM commands.
+    Device,
+    /// A controller, which sends them.
+    Controller,
+}
+
+/// A client connected to the same broker.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RdmnetClient {
+    pub uid: Uid,
+    pub cid: Cid,
+    pub kind: RdmnetClientKind,
+}
+
+/// A controller connected to an RDMnet broker.  The broker disconnects clients it hasn't
+/// heard from in 45 seconds, so call `heartbeat` at least every 15 seconds while idle.
+///
+/// RDM commands are sent through an endpoint of a gateway or device, which implements
+/// `RdmTransport`:
+///
+/// ```no_run
+/// # use rust_dmx::rdm::{RdmTransport, Uid};
+/// # use rust_dmx::rdmnet::{RdmnetController, DEFAULT_SCOPE};
+/// let address = "192.168.1.10:8888".parse().unwrap();
+/// let mut controller = RdmnetController::connect(address, DEFAULT_SCOPE, Uid::dynamic(0x7FF0))?;
+/// let gateway = Uid::new(0x6574, 1);
+/// let fixture = Uid::new(0x1234, 5);
+/// let address = controller.endpoint(gateway, 1).dmx_start_address(fixture)?;
+/// # Ok::<(), rust_dmx::Error>(())
+/// ```
+pub struct RdmnetController {
+    stream: TcpStream,
+    cid: Cid,
+    uid: Uid,
+    broker_uid: Uid,
+    sequence: u32,
+    transaction_number: u8,
+    buf: Vec<u8>,
+}
+
+impl RdmnetController {
+    /// Connect to the broker at the address as a controller in the scope.  Pass a UID made
+    /// with `Uid::dynamic` to have the broker assign one.
+    pub fn connect(address: SocketAddr, scope: &str, uid: Uid) -> Result<Self, Error> {
+        let stream = TcpStream::connect_timeout(&address, RESPONSE_TIMEOUT)?;
+        stream.set_nodelay(true)?;
+        let mut controller = Self {
+            stream,
+            cid: random_cid(),
+            uid,
+            broker_uid: Uid::MIN,
+            sequence: 0,
+            transaction_number: 0,
+            buf: Vec::new(),
+        };
+        write_connect(&controller.cid, scope, uid, &mut controller.buf);
+        controller.stream.write_all(&controller.buf)?;
+        let reply = controller.expect_broker(VECTOR_BROKER_CONNECT_REPLY)?;
+        if reply.len() < 16 {
+            return Err(malformed());
+        }
+        let code = u16::from_be_bytes([reply[0], reply[1]]);
+        if code != 0 {
+            return Err(connection_refused(code));
+        }
+        controller.broker_uid = Uid::from_bytes(&reply[4..10]);
+        controller.uid = Uid::from_bytes(&reply[10..16]);
+        Ok(controller)
+    }
+
+    /// Return the UID of this controller, as assigned by the broker if it was dynamic.
+    pub fn uid(&self) -> Uid {
+        self.uid
+    }
+
+    /// Return the UID of the broker.
+    pub fn broker_uid(&self) -> Uid {
+        self.broker_uid
+    }
+
+    /// Ask the broker for the RPT clients connected to it.
+    pub fn clients(&mut self) -> Result<Vec<RdmnetClient>, Error> {
+        write_broker_message(
+            &self.cid,
+            VECTOR_BROKER_FETCH_CLIENT_LIST,
+            &[],
+            &mut self.buf,
+        );
+        self.stream.write_all(&self.buf)?;
+        let data = self.expect_broker(VECTOR_BROKER_CONNECTED_CLIENT_LIST)?;
+        let mut entries = data.as_slice();
+        let mut clients = Vec::new();
+        while !entries.is_empty() {
+            let (protocol, entry) = next_pdu(&mut entries, 4)?;
+            if protocol != CLIENT_PROTOCOL_RPT {
+                continue;
+            }
+            if entry.len() < CLIENT_ENTRY_SIZE {
+                return Err(malformed());
+            }
+            let mut cid = [0; CID_SIZE];
+            cid.copy_from_slice(&entry[0..CID_SIZE]);
+            let kind = match entry[CID_SIZE + 6] {
+                RPT_CLIENT_TYPE_DEVICE => RdmnetClientKind::Device,
+                RPT_CLIENT_TYPE_CONTROLLER => RdmnetClientKind::Controller,
+                _ => return Err(malformed()),
+            };
+            clients.push(RdmnetClient {
+                uid: Uid::from_bytes(&entry[CID_SIZE..CID_SIZE + 6]),
+                cid,
+                kind,
+            });
+        }
+        Ok(clients)
+    }
+
+    /// Let the broker know this controller is still there.
+    pub fn heartbeat(&mut self) -> Result<(), Error> {
+        write_broker_message(&self.cid, VECTOR_BROKER_NULL, &[], &mut self.buf);
+        Ok(self.stream.write_all(&self.buf)?)
+    }
+
+    /// Return a transport for RDM commands sent through an endpoint of a gateway or device.
+    /// Use `NULL_ENDPOINT` to address the gateway or device itself.
+    pub fn endpoint(&mut self, uid: Uid, endpoint: u16) -> RdmnetEndpoint<'_> {
+        RdmnetEndpoint {
+            controller: self,
+            uid,
+            endpoint,
+        }
+    }
+
+    /// Wait for a message from the broker, giving up at the deadline.
+    fn receive(&mut self, deadline: Instant) -> Result<(), Error> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(Error::Timeout);
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        read_message(&mut self.stream, &mut self.buf).map_err(|e| match e {
+            Error::IO(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                Error::Timeout
+            }
+            Error::IO(e) if e.kind() == io::ErrorKind::UnexpectedEof => Error::PortClosed,
+            e => e,
+        })
+    }
+
+    /// Wait for a broker message with the vector, skipping anything else, and return its data.
+    fn expect_broker(&mut self, expected: u16) -> Result<Vec<u8>, Error> {
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        loop {
+            self.receive(deadline)?;
+            if let Message::Broker { vector, data } = parse_message(&self.buf)? {
+                if vector == expected {
+                    return Ok(data.to_vec());
+                }
+            }
+        }
+    }
+
+    /// Send an RDM packet through an endpoint and wait for the response.
+    fn send_rdm(
+        &mut self,
+        uid: Uid,
+        endpoint: u16,
+        packet: &[u8],
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let request = RdmPacket::decode(packet)
+            .ok_or_else(|| Error::InvalidFrame("not an RDM packet".to_string()))?;
+        self.sequence = self.sequence.wrapping_add(1);
+        let header = RptHeader {
+            source: self.uid,
+            source_endpoint: NULL_ENDPOINT,
+            destination: uid,
+            destination_endpoint: endpoint,
+            sequence: self.sequence,
+        };
+        write_rpt_message(
+            &self.cid,
+            VECTOR_RPT_REQUEST,
+            &header,
+            &[packet],
+            &mut self.buf,
+        );
+        self.stream.write_all(&self.buf)?;
+        if request.destination.is_broadcast() {
+            return Ok(None);
+        }
+        let deadline = Instant::now() + RESPONSE_TIMEOUT;
+        loop {
+            match self.receive(deadline) {
+                Ok(()) => {}
+                Err(Error::Timeout) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+            match parse_message(&self.buf)? {
+                Message::Rpt {
+                    vector: VECTOR_RPT_NOTIFICATION,
+                    header,
+                    data,
+                } if header.sequence == self.sequence => return first_response(data),
+                Message::Rpt {
+                    vector: VECTOR_RPT_STATUS,
+                    header,
+                    mut data,
+                } if header.sequence == self.sequence => {
+                    let (code, message) = next_pdu(&mut data, 2)?;
+                    return match code as u16 {
+                        VECTOR_RPT_STATUS_RDM_TIMEOUT => Ok(None),
+                        code => Err(status_error(code, message)),
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Tell the broker this controller is going away.
+impl Drop for RdmnetController {
+    fn drop(&mut self) {
+        write_broker_message(
+            &self.cid,
+            VECTOR_BROKER_DISCONNECT,
+            &DISCONNECT_SHUTDOWN.to_be_bytes(),
+            &mut self.buf,
+        );
+        let _ = self.stream.write_all(&self.buf);
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// An endpoint of a gateway or device, reached through a broker.  Gateways discover the
+/// responders on their DMX ports themselves, so `rdm::discover` can't be used through one.
+pub struct RdmnetEndpoint<'a> {
+    controller: &'a mut RdmnetController,
+    uid: Uid,
+    endpoint: u16,
+}
+
+impl RdmTransport for RdmnetEndpoint<'_> {
+    fn controller_uid(&self) -> Uid {
+        self.controller.uid
+    }
+
+    fn next_transaction_number(&mut self) -> u8 {
+        self.controller.transaction_number = self.controller.transaction_number.wrapping_add(1);
+        self.controller.transaction_number
+    }
+
+    fn send_discovery(&mut self, _: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "RDMnet gateways discover their responders themselves",
+        )
+        .into())
+    }
+
+    fn send_request(&mut self, packet: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+        self.controller.send_rdm(self.uid, self.endpoint, packet)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rdm::{CommandClass, RdmError, DMX_START_ADDRESS};
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::thread;
+
+    /// Format an RPT status message, as a broker relays it from a gateway.
+    fn write_status(cid: &Cid, header: &RptHeader, code: u16, buf: &mut Vec<u8>) {
+        begin_message(VECTOR_ROOT_RPT, cid, buf);
+        let rpt = begin_pdu(buf);
+        buf.extend_from_slice(&VECTOR_RPT_STATUS.to_be_bytes());
+        header.write_into(buf);
+        let status = begin_pdu(buf);
+        buf.extend_from_slice(&code.to_be_bytes());
+        end_pdu(buf, status);
+        end_pdu(buf, rpt);
+        end_message(buf);
+    }
+
+    #[test]
+    fn test_controller() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        let gateway = Uid::new(0x6574, 1);
+        let assigned = Uid::new(0xFFF0, 7);
+        let broker = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let cid = random_cid();
+            let mut buf = Vec::new();
+            let mut out = Vec::new();
+
+            read_message(&mut stream, &mut buf).unwrap();
+            match parse_message(&buf).unwrap() {
+                Message::Broker { vector, data } => {
+                    assert_eq!(vector, VECTOR_BROKER_CONNECT);
+                    assert_eq!(&data[0..8], b"default\0");
+                }
+                _ => panic!("expected a connect message"),
+            }
+            let mut reply = vec![0, 0, 0, 1];
+            reply.extend_from_slice(&Uid::new(0x6574, 0xB0).to_bytes());
+            reply.extend_from_slice(&assigned.to_bytes());
+            write_broker_message(&cid, VECTOR_BROKER_CONNECT_REPLY, &reply, &mut out);
+            stream.write_all(&out).unwrap();
+
+            // Answer a GET of the start address, after an unrelated heartbeat.
+            read_message(&mut stream, &mut buf).unwrap();
+            let (header, request) = match parse_message(&buf).unwrap() {
+                Message::Rpt {
+                    vector: VECTOR_RPT_REQUEST,
+                    header,
+                    mut data,
+                } => {
+                    let (_, mut commands) = next_pdu(&mut data, 4).unwrap();
+                    let (start_code, command) = next_pdu(&mut commands, 1).unwrap();
+                    let mut packet = vec![start_code as u8];
+                    packet.extend_from_slice(command);
+                    (header, RdmPacket::decode(&packet).unwrap())
+                }
+                _ => panic!("expected an RPT request"),
+            };
+            assert_eq!(header.source, assigned);
+            assert_eq!(header.destination, gateway);
+            assert_eq!(header.destination_endpoint, 1);
+            assert_eq!(request.parameter_id, DMX_START_ADDRESS);
+            let response = RdmPacket {
+                destination: request.source,
+                source: request.destination,
+                port_id: 0,
+                command_class: CommandClass::GetResponse,
+                parameter_data: vec![0, 101],
+                ..request.clone()
+            };
+            let header = RptHeader {
+                source: gateway,
+                source_endpoint: 1,
+                destination: assigned,
+                destination_endpoint: NULL_ENDPOINT,
+                ..header
+            };
+            write_broker_message(&cid, VECTOR_BROKER_NULL, &[], &mut out);
+            stream.write_all(&out).unwrap();
+            write_rpt_message(
+                &cid,
+                VECTOR_RPT_NOTIFICATION,
+                &header,
+                &[&request.encode(), &response.encode()],
+                &mut out,
+            );
+            stream.write_all(&out).unwrap();
+
+            // Report that the next request timed out.
+            read_message(&mut stream, &mut buf).unwrap();
+            let header = match parse_message(&buf).unwrap() {
+                Message::Rpt { header, .. } => header,
+                _ => panic!("expected an RPT request"),
+            };
+            write_status(&cid, &header, VECTOR_RPT_STATUS_RDM_TIMEOUT, &mut out);
+            stream.write_all(&out).unwrap();
+
+            read_message(&mut stream, &mut buf).unwrap();
+            match parse_message(&buf).unwrap() {
+                Message::Broker { vector, .. } => assert_eq!(vector, VECTOR_BROKER_DISCONNECT),
+                _ => panic!("expected a disconnect message"),
+            }
+        });
+
+        let mut controller =
+            RdmnetController::connect(address, DEFAULT_SCOPE, Uid::dynamic(0x7FF0)).unwrap();
+        assert_eq!(controller.uid(), assigned);
+        let fixture = Uid::new(0x1234, 5);
+        let mut endpoint = controller.endpoint(gateway, 1);
+        assert_eq!(endpoint.dmx_start_address(fixture).unwrap(), 101);
+        match endpoint.dmx_start_address(fixture) {
+            Err(Error::Rdm(RdmError::NoResponse)) => (),
+            other => panic!("expected no response, got {:?}", other),
+        }
+        drop(controller);
+        broker.join().unwrap();
+    }
+}
@@ -0,0 +1,328 @@
+//! Reconnecting to interfaces that are unplugged and plugged back in.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::trace;
+use crate::{
+    available_ports, DmxPort, Error, HardwareInfo, PortInfo, PortKind, PortStats, PortStatus,
+};
+
+/// How hard a `ReconnectingPort` tries to get its interface back after a failed write.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReconnectPolicy {
+    /// How many times to try reconnecting before the write fails.
+    pub attempts: u32,
+    /// How long to wait before each attempt.
+    pub delay: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            delay: Duration::from_millis(500),
+        }
+    }
+}
+
+/// A port that reconnects to its interface when a write fails, for example because the
+/// interface was unplugged.  The port is first reopened as it was configured.  If that fails,
+/// the attached ports are listed again and the one with the same USB serial number is used
+/// instead, since the interface may have come back on a different path.  Ports without a
+/// serial number are found again by name.
+///
+/// Reconnecting happens on the thread calling write, which blocks while it is tried.  A write
+/// that still fails once the policy's attempts are used up returns the error; the next write
+/// will try again.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ReconnectingPort {
+    port: Box<dyn DmxPort>,
+    #[serde(default)]
+    policy: ReconnectPolicy,
+}
+
+impl ReconnectingPort {
+    /// Wrap a port, reconnecting with the default policy.
+    pub fn new(port: Box<dyn DmxPort>) -> Self {
+        Self::with_policy(port, ReconnectPolicy::default())
+    }
+
+    /// Wrap a port, reconnecting with the provided policy.
+    pub fn with_policy(port: Box<dyn DmxPort>, policy: ReconnectPolicy) -> Self {
+        Self { port, policy }
+    }
+
+    /// Return the wrapped port.
+    pub fn inner(&self) -> &dyn DmxPort {
+        self.port.as_ref()
+    }
+
+    /// Return the wrapped port, which may have been replaced by a reconnect.
+    pub fn into_inner(self) -> Box<dyn DmxPort> {
+        self.port
+    }
+
+    /// Try to get a working port back, replacing the wrapped port if it has moved.
+    fn reconnect(&mut self) -> Result<(), Error> {
+        self.port.close();
+        if self.port.open().is_ok() {
+            return Ok(());
+        }
+        let found = available_ports()?
+            .into_iter()
+            .find(|info| is_same_interface(self.port.as_ref(), info))
+            .ok_or(Error::DeviceDisconnected)?;
+        self.port = found.open()?;
+        Ok(())
+    }
+}
+
+/// Return true if a listed port refers to the same output as a port found earlier.  Ports
+/// describe themselves by serial number and output, so descriptions are compared when a
+/// serial number is known.
+pub(crate) fn is_same_interface(port: &dyn DmxPort, info: &PortInfo) -> bool {
+    if port.kind() != info.kind {
+        return false;
+    }
+    match &port.hardware().serial_number {
+        Some(sn) => {
+            info.hardware.serial_number.as_ref() == Some(sn) && port.to_string() == info.to_string()
+        }
+        None => port.name() == info.name,
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for ReconnectingPort {
+    fn name(&self) -> &str {
+        self.port.name()
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        self.port.open()
+    }
+
+    fn close(&mut self) {
+        self.port.close()
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let mut result = self.port.write(frame);
+        let mut attempts = 0;
+        while attempts < self.policy.attempts {
+            // A closed port was closed on purpose, not lost.
+            match &result {
+                Ok(()) | Err(Error::NotOpen) => break,
+                Err(e) => trace::reconnecting(self.port.name(), attempts + 1, e),
+            }
+            attempts += 1;
+            sleep(self.policy.delay);
+            result = self.reconnect().and_then(|()| self.port.write(frame));
+        }
+        if attempts > 0 {
+            trace::reconnected(self.port.name(), &result);
+        }
+        result
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        match start_code {
+            0 => self.write(data),
+            _ => self.port.write_with_start_code(start_code, data),
+        }
+    }
+
+    fn kind(&self) -> PortKind {
+        self.port.kind()
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        self.port.hardware()
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        self.port.stats()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.port.status()
+    }
+
+    fn health_check(&mut self) -> Result<(), Error> {
+        self.port.health_check()
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.port.last_frame()
+    }
+}
+
+impl fmt::Display for ReconnectingPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.port.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{register_provider, unregister_provider, PortListing, PortProvider};
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// A port whose writes fail until it has been reopened.
+    #[derive(Clone, Default, Serialize, Deserialize)]
+    struct Unplugged {
+        opens: u32,
+    }
+
+    #[typetag::serde]
+    impl DmxPort for Unplugged {
+        fn name(&self) -> &str {
+            "unplugged"
+        }
+
+        fn open(&mut self) -> Result<(), Error> {
+            self.opens += 1;
+            Ok(())
+        }
+
+        fn close(&mut self) {}
+
+        fn write(&mut self, _: &[u8]) -> Result<(), Error> {
+            if self.opens < 2 {
+                return Err(io::Error::from(io::ErrorKind::BrokenPipe).into());
+            }
+            Ok(())
+        }
+    }
+
+    impl fmt::Display for Unplugged {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "unplugged")
+        }
+    }
+
+    #[test]
+    fn test_reconnect() {
+        let policy = ReconnectPolicy {
+            attempts: 1,
+            delay: Duration::ZERO,
+        };
+        let mut port = ReconnectingPort::with_policy(Box::<Unplugged>::default(), policy);
+        port.open().unwrap();
+        port.write(&[1, 2, 3]).unwrap();
+        port.close();
+    }
+
+    /// A widget on a path, which stops working when unplugged from it.
+    #[derive(Clone, Serialize, Deserialize)]
+    struct Widget {
+        path: String,
+        serial_number: String,
+        plugged: bool,
+        #[serde(skip)]
+        opens: Arc<AtomicUsize>,
+    }
+
+    impl Widget {
+        fn new(path: &str, serial_number: &str, plugged: bool) -> Self {
+            Self {
+                path: path.to_string(),
+                serial_number: serial_number.to_string(),
+                plugged,
+                opens: Arc::default(),
+            }
+        }
+    }
+
+    #[typetag::serde]
+    impl DmxPort for Widget {
+        fn name(&self) -> &str {
+            &self.path
+        }
+
+        fn open(&mut self) -> Result<(), Error> {
+            self.opens.fetch_add(1, Ordering::SeqCst);
+            match self.plugged {
+                true => Ok(()),
+                false => Err(Error::DeviceDisconnected),
+            }
+        }
+
+        fn close(&mut self) {}
+
+        fn write(&mut self, _: &[u8]) -> Result<(), Error> {
+            match self.plugged {
+                true => Ok(()),
+                false => Err(io::Error::from(io::ErrorKind::BrokenPipe).into()),
+            }
+        }
+
+        fn hardware(&self) -> HardwareInfo {
+            HardwareInfo {
+                path: Some(self.path.clone()),
+                serial_number: Some(self.serial_number.clone()),
+                ..HardwareInfo::default()
+            }
+        }
+    }
+
+    impl fmt::Display for Widget {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "widget {}", self.serial_number)
+        }
+    }
+
+    /// Lists the widgets that have come back on other paths.
+    struct Replugged;
+
+    impl PortProvider for Replugged {
+        fn name(&self) -> &str {
+            "reconnect-test"
+        }
+
+        fn available_ports(&self) -> Result<PortListing, Error> {
+            Ok(vec![
+                Box::new(Widget::new("reconnect-test-2", "OTHER", true)),
+                Box::new(Widget::new("reconnect-test-1", "MOVED", true)),
+            ])
+        }
+    }
+
+    #[test]
+    fn test_reconnect_moved() {
+        register_provider(Replugged);
+        let policy = ReconnectPolicy {
+            attempts: 1,
+            delay: Duration::ZERO,
+        };
+        let widget = Widget::new("reconnect-test-0", "MOVED", false);
+        let mut port = ReconnectingPort::with_policy(Box::new(widget), policy);
+        let result = port.write(&[1, 2, 3]);
+        unregister_provider("reconnect-test");
+        result.unwrap();
+        assert_eq!(port.name(), "reconnect-test-1");
+        assert_eq!(port.hardware().serial_number.as_deref(), Some("MOVED"));
+    }
+
+    #[test]
+    fn test_give_up() {
+        let policy = ReconnectPolicy {
+            attempts: 3,
+            delay: Duration::ZERO,
+        };
+        let widget = Widget::new("reconnect-test-gone", "GONE", false);
+        let opens = widget.opens.clone();
+        let mut port = ReconnectingPort::with_policy(Box::new(widget), policy);
+        assert!(matches!(port.write(&[1]), Err(Error::DeviceDisconnected)));
+        assert_eq!(opens.load(Ordering::SeqCst), 3);
+        // The next write tries again.
+        assert!(port.write(&[1]).is_err());
+        assert_eq!(opens.load(Ordering::SeqCst), 6);
+    }
+}
@@ -0,0 +1,246 @@
+//! Recording written frames to a file.
+//!
+//! A recording starts with an 8 byte header, `DMXREC` followed by a zero byte and the format
+//! version.  Each frame follows as a record of the time it was written, in microseconds since
+//! the Unix epoch as a little-endian u64, the number of channels as a little-endian u16, and
+//! the channel levels.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{DmxPort, Error, HardwareInfo, PortKind, PortStats, PortStatus, UNIVERSE_SIZE};
+
+const MAGIC: &[u8; 8] = b"DMXREC\x00\x01";
+
+/// A frame read back from a recording.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    pub time: SystemTime,
+    pub frame: Vec<u8>,
+}
+
+fn write_record(time: SystemTime, frame: &[u8], buf: &mut Vec<u8>) {
+    let micros = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+    buf.extend_from_slice(&micros.to_le_bytes());
+    buf.extend_from_slice(&(frame.len() as u16).to_le_bytes());
+    buf.extend_from_slice(frame);
+}
+
+/// Reads the frames of a recording in order.
+pub struct RecordReader<R> {
+    reader: R,
+}
+
+impl RecordReader<io::BufReader<File>> {
+    /// Open a recording file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::new(io::BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read> RecordReader<R> {
+    /// Start reading a recording, checking its header.
+    pub fn new(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a DMX recording").into());
+        }
+        Ok(Self { reader })
+    }
+
+    /// Read the next frame, or None at the end of the recording.  A record cut short, as the
+    /// last one may be if the recording program was killed, is treated as the end.
+    pub fn read_record(&mut self) -> Result<Option<Record>, Error> {
+        let mut micros = [0; 8];
+        let mut len = [0; 2];
+        if !self.read_all(&mut micros)? || !self.read_all(&mut len)? {
+            return Ok(None);
+        }
+        let micros = u64::from_le_bytes(micros);
+        let len = u16::from_le_bytes(len) as usize;
+        let mut frame = vec![0; len];
+        if !self.read_all(&mut frame)? {
+            return Ok(None);
+        }
+        Ok(Some(Record {
+            time: UNIX_EPOCH + Duration::from_micros(micros),
+            frame,
+        }))
+    }
+
+    /// Fill the buffer, returning false if the recording ends first.
+    fn read_all(&mut self, buf: &mut [u8]) -> Result<bool, Error> {
+        match self.reader.read_exact(buf) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = Result<Record, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+/// A port that passes writes through to an inner port while appending every frame, with the
+/// time it was written, to a recording file.  Frames are recorded before being passed on, so
+/// the recording shows what the application wrote even if the port failed.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RecordingPort {
+    port: Box<dyn DmxPort>,
+    path: PathBuf,
+    #[serde(skip)]
+    file: Option<Arc<File>>,
+    #[serde(skip)]
+    buf: Vec<u8>,
+}
+
+impl RecordingPort {
+    /// Wrap a port, recording to the provided file when opened.  Frames are appended if the
+    /// file already exists.
+    pub fn new(port: Box<dyn DmxPort>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            port,
+            path: path.into(),
+            file: None,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Return the path of the recording file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Return the wrapped port.
+    pub fn into_inner(self) -> Box<dyn DmxPort> {
+        self.port
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for RecordingPort {
+    fn name(&self) -> &str {
+        self.port.name()
+    }
+
+    /// Open the inner port and the recording file.
+    fn open(&mut self) -> Result<(), Error> {
+        if self.file.is_none() {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            if file.metadata()?.len() == 0 {
+                file.write_all(MAGIC)?;
+            }
+            self.file = Some(Arc::new(file));
+        }
+        self.port.open()
+    }
+
+    fn close(&mut self) {
+        self.port.close();
+        self.file = None;
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let mut file = self.file.as_deref().ok_or(Error::NotOpen)?;
+        self.buf.clear();
+        write_record(
+            SystemTime::now(),
+            &frame[0..min(frame.len(), UNIVERSE_SIZE)],
+            &mut self.buf,
+        );
+        // One write per record, so a crash or a clone writing to the same file can't
+        // interleave partial records.
+        file.write_all(&self.buf)?;
+        self.port.write(frame)
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        match start_code {
+            0 => self.write(data),
+            _ => self.port.write_with_start_code(start_code, data),
+        }
+    }
+
+    fn kind(&self) -> PortKind {
+        self.port.kind()
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        self.port.hardware()
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        self.port.stats()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.port.status()
+    }
+
+    fn health_check(&mut self) -> Result<(), Error> {
+        self.port.health_check()
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.port.last_frame()
+    }
+}
+
+impl fmt::Display for RecordingPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (recording to {})", self.port, self.path.display())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_read_records() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut buf = MAGIC.to_vec();
+        write_record(start, &[1, 2, 3], &mut buf);
+        write_record(start + Duration::from_millis(25), &[4], &mut buf);
+        // A record cut short by a crash.
+        write_record(start, &[5, 6], &mut buf);
+        buf.pop();
+
+        let records: Vec<_> = RecordReader::new(&buf[..])
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            records,
+            vec![
+                Record {
+                    time: start,
+                    frame: vec![1, 2, 3]
+                },
+                Record {
+                    time: start + Duration::from_millis(25),
+                    frame: vec![4]
+                },
+            ]
+        );
+        assert!(RecordReader::new(&b"DMXREC\x00\x02"[..]).is_err());
+    }
+}
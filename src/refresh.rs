@@ -0,0 +1,344 @@
+//! Keeping DMX output alive by retransmitting the last frame.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::{DmxFrame, DmxPort, Error, HardwareInfo, PortKind, PortStats, PortStatus};
+
+/// A port that retransmits the last frame written to it at a steady rate from a background
+/// thread, so fixtures that blank on signal loss stay lit while the application writes
+/// sporadically.  Nothing is transmitted until the first frame is written, and closing the
+/// port stops transmitting until the next one.
+///
+/// The wrapped port is held by the background thread and called through a lock, so the other
+/// methods wait for a transmission in progress.  Errors from the background thread are
+/// returned by the next call to write.  Clones share the background thread, which stops once
+/// the last clone is dropped.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(from = "Config", into = "Config")]
+pub struct RefreshingPort {
+    name: String,
+    interval: Duration,
+    refresher: Arc<Refresher>,
+    last_frame: Option<DmxFrame>,
+}
+
+/// What a `RefreshingPort` is saved as.
+#[derive(Serialize, Deserialize)]
+struct Config {
+    port: Box<dyn DmxPort>,
+    interval: Duration,
+}
+
+/// The background thread, which is stopped when dropped.
+struct Refresher {
+    shared: Arc<Shared>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct Shared {
+    port: Mutex<Box<dyn DmxPort>>,
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    frame: Option<Vec<u8>>,
+    pending: bool,
+    error: Option<Error>,
+    stop: bool,
+}
+
+impl Shared {
+    /// Lock the shared state.  The wrapped port is never called with the lock held, so
+    /// poisoning is ignored.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the wrapped port.  A port that panicked is passed on by joining the thread, so
+    /// poisoning is ignored here too.
+    fn port(&self) -> MutexGuard<'_, Box<dyn DmxPort>> {
+        self.port.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Refresher {
+    fn stop(&self) {
+        let thread = match self
+            .thread
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            Some(thread) => thread,
+            None => return,
+        };
+        self.shared.lock().stop = true;
+        self.shared.changed.notify_one();
+        // The thread only panics if the wrapped port does, so pass that on.
+        thread
+            .join()
+            .unwrap_or_else(|e| std::panic::resume_unwind(e));
+    }
+}
+
+impl Drop for Refresher {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            self.stop();
+        }
+    }
+}
+
+impl RefreshingPort {
+    /// Start retransmitting to the port once every interval; 25 ms gives the 40 Hz most
+    /// fixtures expect.
+    pub fn new(port: Box<dyn DmxPort>, interval: Duration) -> Self {
+        let name = port.name().to_string();
+        let shared = Arc::new(Shared {
+            port: Mutex::new(port),
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+        });
+        let thread = {
+            let shared = shared.clone();
+            thread::spawn(move || refresh(&shared, interval))
+        };
+        Self {
+            name,
+            interval,
+            refresher: Arc::new(Refresher {
+                shared,
+                thread: Mutex::new(Some(thread)),
+            }),
+            last_frame: None,
+        }
+    }
+
+    /// Return how often the last frame is retransmitted.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Stop retransmitting and return the wrapped port.  Clones stop retransmitting too.
+    pub fn into_inner(self) -> Box<dyn DmxPort> {
+        self.refresher.stop();
+        self.shared().port().clone()
+    }
+
+    fn shared(&self) -> &Shared {
+        &self.refresher.shared
+    }
+}
+
+impl From<Config> for RefreshingPort {
+    fn from(config: Config) -> Self {
+        Self::new(config.port, config.interval)
+    }
+}
+
+impl From<RefreshingPort> for Config {
+    fn from(port: RefreshingPort) -> Self {
+        Self {
+            port: port.shared().port().clone(),
+            interval: port.interval,
+        }
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for RefreshingPort {
+    /// The name of the wrapped port.
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        self.shared().port().open()
+    }
+
+    fn close(&mut self) {
+        let mut state = self.shared().lock();
+        state.frame = None;
+        state.pending = false;
+        drop(state);
+        self.shared().port().close();
+    }
+
+    /// Replace the frame being transmitted.  The frame is sent right away rather than at the
+    /// next refresh.  Returns the error from the last failed transmission, if any.
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let mut state = self.shared().lock();
+        match &mut state.frame {
+            Some(current) => {
+                current.clear();
+                current.extend_from_slice(frame);
+            }
+            None => state.frame = Some(frame.to_vec()),
+        }
+        state.pending = true;
+        let error = state.error.take();
+        drop(state);
+        self.shared().changed.notify_one();
+        self.last_frame = Some(DmxFrame::from(frame));
+        error.map_or(Ok(()), Err)
+    }
+
+    /// Alternate START code packets are sent once, straight to the wrapped port.
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        match start_code {
+            0 => self.write(data),
+            _ => self.shared().port().write_with_start_code(start_code, data),
+        }
+    }
+
+    fn kind(&self) -> PortKind {
+        self.shared().port().kind()
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        self.shared().port().hardware()
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        self.shared().port().stats()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.shared().port().status()
+    }
+
+    fn health_check(&mut self) -> Result<(), Error> {
+        self.shared().port().health_check()
+    }
+
+    /// The frame being retransmitted.
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+}
+
+impl fmt::Display for RefreshingPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.shared().port().fmt(f)
+    }
+}
+
+/// Transmit the current frame once every interval, or as soon as it changes, until stopped.
+fn refresh(shared: &Shared, interval: Duration) {
+    let mut frame = Vec::new();
+    let mut state = shared.lock();
+    loop {
+        if state.stop {
+            return;
+        }
+        if let Some(current) = &state.frame {
+            frame.clear();
+            frame.extend_from_slice(current);
+            state.pending = false;
+            // Don't hold the lock while writing, so writes from the application never wait on
+            // the hardware.
+            drop(state);
+            let result = shared.port().write(&frame);
+            state = shared.lock();
+            // A write that failed because the port was closed meanwhile isn't reported.
+            if let (Err(e), Some(_)) = (result, &state.frame) {
+                state.error = Some(e);
+            }
+        }
+        // A frame written while the last one was being sent goes out straight away.
+        if !state.pending && !state.stop {
+            state = shared
+                .changed
+                .wait_timeout(state, interval)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A port that counts the frames written to it.
+    #[derive(Clone, Default, Serialize, Deserialize)]
+    struct Counting {
+        #[serde(skip)]
+        writes: Arc<AtomicUsize>,
+    }
+
+    #[typetag::serde]
+    impl DmxPort for Counting {
+        fn name(&self) -> &str {
+            "counting"
+        }
+
+        fn open(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn close(&mut self) {}
+
+        fn write(&mut self, _: &[u8]) -> Result<(), Error> {
+            self.writes.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    impl fmt::Display for Counting {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "counting")
+        }
+    }
+
+    #[test]
+    fn test_refresh() {
+        let port = Counting::default();
+        let writes = port.writes.clone();
+        let mut port = RefreshingPort::new(Box::new(port), Duration::from_millis(5));
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(writes.load(Ordering::SeqCst), 0);
+        port.write(&[1, 2, 3]).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        assert!(writes.load(Ordering::SeqCst) >= 3);
+        port.into_inner();
+    }
+
+    #[test]
+    fn test_port() {
+        let mock = MockDmxPort::new();
+        let mut port = RefreshingPort::new(Box::new(mock.clone()), Duration::from_secs(3600));
+        assert_eq!(port.name(), "mock");
+        assert_eq!(port.to_string(), "mock");
+        assert_eq!(port.status(), PortStatus::Closed);
+        port.open().unwrap();
+        assert!(port.is_open());
+        port.write_range(2, &[5]).unwrap();
+        port.write_range(1, &[4]).unwrap();
+        assert_eq!(&port.last_frame().unwrap()[0..3], &[4, 5, 0]);
+        port.write_with_start_code(0x17, &[1]).unwrap();
+        assert_eq!(mock.start_code_packets(), vec![(0x17, vec![1])]);
+        port.health_check().unwrap();
+        while mock.last_frame().map_or(true, |frame| frame[0] != 4) {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(&mock.last_frame().unwrap()[0..3], &[4, 5, 0]);
+        let clone = port.clone();
+        port.close();
+        assert!(!mock.is_open());
+        assert_eq!(clone.status(), PortStatus::Closed);
+        assert!(port.health_check().is_err());
+        drop(clone);
+        let mut inner = port.into_inner();
+        inner.open().unwrap();
+        assert!(mock.is_open());
+    }
+}
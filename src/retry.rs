@@ -0,0 +1,103 @@
+//! Retrying operations that fail with transient errors.
+
+use serde::{Deserialize, Serialize};
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::Error;
+
+/// How many times an operation that fails with a transient error is tried, and how long to
+/// wait between tries, so a single glitched USB transaction doesn't fail a write.  Errors that
+/// aren't transient are returned straight away.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetryPolicy {
+    /// How many times to try the operation, including the first; 1 disables retrying.
+    pub max_attempts: u32,
+    /// How long to wait before the first retry.  The wait doubles for each retry after it.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// Retry twice, quickly enough that a retried frame is still on time at 44 frames per
+    /// second.
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Duration::from_millis(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that tries operations only once.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            backoff: Duration::ZERO,
+        }
+    }
+
+    /// Run the operation, retrying it while it fails with a transient error and attempts
+    /// remain.  Return the last result.  Nothing is held while waiting between attempts, so
+    /// an operation that needs a lock should take it inside the closure.
+    pub fn run<T>(&self, mut operation: impl FnMut() -> Result<T, Error>) -> Result<T, Error> {
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            match operation() {
+                Err(e) if e.is_transient() && attempt < self.max_attempts => {
+                    sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::ZERO,
+        };
+        let mut calls = 0;
+        let result = policy.run(|| {
+            calls += 1;
+            match calls {
+                1 => Err(Error::Timeout),
+                _ => Ok(calls),
+            }
+        });
+        assert_eq!(result.unwrap(), 2);
+
+        calls = 0;
+        let result: Result<(), Error> = policy.run(|| {
+            calls += 1;
+            Err(Error::Timeout)
+        });
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert_eq!(calls, 3);
+
+        calls = 0;
+        let result: Result<(), Error> = policy.run(|| {
+            calls += 1;
+            Err(Error::NotOpen)
+        });
+        assert!(matches!(result, Err(Error::NotOpen)));
+        assert_eq!(calls, 1);
+
+        calls = 0;
+        let result: Result<(), Error> = RetryPolicy::none().run(|| {
+            calls += 1;
+            Err(Error::Timeout)
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,238 @@
+//! Saved configurations of the ports driving each universe.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fmt;
+
+use crate::reconnect::is_same_interface;
+use crate::{scan_ports, DmxController, DmxPort, Error, PortKind, PortScan};
+
+/// The ports driving each numbered universe of a lighting rig, including network ports.
+/// A rig serializes with any serde format, so it can be saved to a file and loaded back at
+/// startup; ports need to be opened again with `open_all` after loading.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Rig {
+    universes: BTreeMap<u16, Box<dyn DmxPort>>,
+}
+
+impl Rig {
+    /// Create a rig with no ports.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drive a universe with the provided port, returning the port that drove it before.
+    pub fn insert(&mut self, universe: u16, port: Box<dyn DmxPort>) -> Option<Box<dyn DmxPort>> {
+        self.universes.insert(universe, port)
+    }
+
+    /// Stop driving a universe, returning its port.
+    pub fn remove(&mut self, universe: u16) -> Option<Box<dyn DmxPort>> {
+        self.universes.remove(&universe)
+    }
+
+    /// Return the port driving a universe.
+    pub fn port(&self, universe: u16) -> Option<&dyn DmxPort> {
+        self.universes.get(&universe).map(|port| port.as_ref())
+    }
+
+    /// Return the port driving a universe.
+    pub fn port_mut(&mut self, universe: u16) -> Option<&mut (dyn DmxPort + 'static)> {
+        self.universes.get_mut(&universe).map(|port| port.as_mut())
+    }
+
+    /// Iterate over the numbers of the universes in the rig, in order.
+    pub fn universes(&self) -> impl Iterator<Item = u16> + '_ {
+        self.universes.keys().copied()
+    }
+
+    /// Check that the interface behind every hardware port is attached, without opening
+    /// anything.  Ports whose interface isn't listed are reported as disconnected, and
+    /// providers that couldn't list their ports are reported without a universe, in place of
+    /// the ports of the kinds they list.
+    pub fn validate(&self) -> Result<(), RigError> {
+        RigError::check(self.failures(scan_ports()))
+    }
+
+    fn failures(&self, scan: PortScan) -> Vec<RigFailure> {
+        let PortScan { ports, errors } = scan;
+        let unlisted: Vec<PortKind> = errors
+            .iter()
+            .flat_map(|failed| failed.kinds.iter().copied())
+            .collect();
+        let mut failures: Vec<_> = errors
+            .into_iter()
+            .map(|failed| RigFailure {
+                universe: None,
+                port: String::new(),
+                error: failed.error,
+            })
+            .collect();
+        failures.extend(
+            self.universes
+                .iter()
+                .filter(|(_, port)| port.kind().is_hardware())
+                .filter(|(_, port)| !unlisted.contains(&port.kind()))
+                .filter(|(_, port)| {
+                    !ports
+                        .iter()
+                        .any(|info| is_same_interface(port.as_ref(), info))
+                })
+                .map(|(universe, port)| RigFailure {
+                    universe: Some(*universe),
+                    port: port.name().to_string(),
+                    error: Error::DeviceDisconnected,
+                }),
+        );
+        failures
+    }
+
+    /// Open every port.  A port that fails to open doesn't stop the others being opened; the
+    /// error lists every failure, and the ports that did open are left open.
+    pub fn open_all(&mut self) -> Result<(), RigError> {
+        let failures: Vec<_> = self
+            .universes
+            .iter_mut()
+            .filter_map(|(universe, port)| {
+                port.open().err().map(|error| RigFailure {
+                    universe: Some(*universe),
+                    port: port.name().to_string(),
+                    error,
+                })
+            })
+            .collect();
+        RigError::check(failures)
+    }
+
+    /// Close every port.
+    pub fn close_all(&mut self) {
+        for port in self.universes.values_mut() {
+            port.close();
+        }
+    }
+
+    /// Write a frame to a universe.
+    pub fn write(&mut self, universe: u16, frame: &[u8]) -> Result<(), Error> {
+        self.universes
+            .get_mut(&universe)
+            .ok_or(Error::InvalidUniverse(universe))?
+            .write(frame)
+    }
+}
+
+impl From<Rig> for DmxController {
+    /// Drive the rig's universes with a controller.
+    fn from(rig: Rig) -> Self {
+        let mut controller = DmxController::new();
+        for (universe, port) in rig.universes {
+            controller.add_port(universe, port);
+        }
+        controller
+    }
+}
+
+/// A port of a rig that couldn't be used.
+#[derive(Debug)]
+pub struct RigFailure {
+    /// The universe the port drives, or `None` if the failure wasn't specific to one port,
+    /// such as the system's ports not being listable.
+    pub universe: Option<u16>,
+    /// The name of the port.
+    pub port: String,
+    pub error: Error,
+}
+
+/// Every port of a rig that couldn't be validated or opened.
+#[derive(Debug)]
+pub struct RigError {
+    pub failures: Vec<RigFailure>,
+}
+
+impl RigError {
+    fn check(failures: Vec<RigFailure>) -> Result<(), RigError> {
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(RigError { failures })
+        }
+    }
+
+    /// Iterate over the failures caused by an interface not being attached.
+    pub fn missing_devices(&self) -> impl Iterator<Item = &RigFailure> {
+        self.failures
+            .iter()
+            .filter(|failure| matches!(failure.error, Error::DeviceDisconnected))
+    }
+}
+
+impl fmt::Display for RigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, failure) in self.failures.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            match failure.universe {
+                Some(universe) => write!(
+                    f,
+                    "universe {} ({}): {}",
+                    universe, failure.port, failure.error
+                )?,
+                None => write!(f, "{}", failure.error)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl StdError for RigError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.failures
+            .first()
+            .map(|failure| &failure.error as &(dyn StdError + 'static))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{MockDmxPort, OfflineDmxPort, ProviderError};
+
+    #[test]
+    fn test_open_all() {
+        let failing = MockDmxPort::new();
+        failing.fail_next_open(Error::DeviceDisconnected);
+        let mut rig = Rig::new();
+        rig.insert(1, Box::new(OfflineDmxPort::new()));
+        rig.insert(2, Box::new(failing));
+        let error = rig.open_all().unwrap_err();
+        assert_eq!(error.failures.len(), 1);
+        assert_eq!(error.missing_devices().count(), 1);
+        assert_eq!(error.to_string(), "universe 2 (mock): device disconnected");
+        rig.write(1, &[1, 2, 3]).unwrap();
+        assert!(matches!(rig.write(3, &[1]), Err(Error::InvalidUniverse(3))));
+        let controller = DmxController::from(rig);
+        assert_eq!(controller.universes().collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_failed_provider() {
+        let mut rig = Rig::new();
+        rig.insert(1, Box::new(MockDmxPort::new().with_kind(PortKind::Udmx)));
+        rig.insert(2, Box::new(MockDmxPort::new().with_kind(PortKind::OpenDmx)));
+        let scan = PortScan {
+            ports: Vec::new(),
+            errors: vec![ProviderError {
+                provider: "udmx".to_string(),
+                kinds: vec![PortKind::Udmx],
+                error: Error::Timeout,
+            }],
+        };
+        let failures = rig.failures(scan);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].universe, None);
+        assert!(matches!(failures[0].error, Error::Timeout));
+        assert_eq!(failures[1].universe, Some(2));
+        assert!(matches!(failures[1].error, Error::DeviceDisconnected));
+    }
+}
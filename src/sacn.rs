@@ -0,0 +1,1759 @@
+//! Implementation of sACN (ANSI E1.31) output and input over UDP.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cmp::min;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::pcap::capture;
+use crate::target::deserialize_target;
+use crate::{
+    DmxFrame, DmxInputPort, DmxPort, Error, FrameShape, InputPortListing, NetworkTarget,
+    PcapWriter, PortKind, PortListing, PortProvider, PortStatus, ReceivedFrame, StatusTracker,
+};
+
+/// The UDP port sACN receivers listen on.
+pub const SACN_PORT: u16 = 5568;
+
+/// Valid range of sACN universes.
+pub const MIN_UNIVERSE: u16 = 1;
+pub const MAX_UNIVERSE: u16 = 63999;
+
+/// Priority of sources unless configured otherwise, and the highest priority allowed.
+pub const DEFAULT_PRIORITY: u8 = 100;
+pub const MAX_PRIORITY: u8 = 200;
+
+/// START code of packets carrying a priority for each address of a universe, an extension of
+/// E1.31 that ETC consoles and nodes and most other receivers support.
+pub const ADDRESS_PRIORITY_START_CODE: u8 = 0xDD;
+/// How often per-address priorities are sent while they don't change.
+pub const ADDRESS_PRIORITY_INTERVAL: Duration = Duration::from_secs(1);
+
+// Root layer constants.
+const PREAMBLE_SIZE: u16 = 0x0010;
+const POSTAMBLE_SIZE: u16 = 0x0000;
+const ACN_PACKET_IDENTIFIER: &[u8; 12] = b"ASC-E1.17\0\0\0";
+const VECTOR_ROOT_E131_DATA: u32 = 0x0000_0004;
+const VECTOR_ROOT_E131_EXTENDED: u32 = 0x0000_0008;
+
+// Framing layer constants.
+const VECTOR_E131_DATA_PACKET: u32 = 0x0000_0002;
+const VECTOR_E131_EXTENDED_SYNCHRONIZATION: u32 = 0x0000_0001;
+const VECTOR_E131_EXTENDED_DISCOVERY: u32 = 0x0000_0002;
+const SOURCE_NAME_LENGTH: usize = 64;
+const OPTION_PREVIEW_DATA: u8 = 0x80;
+const OPTION_STREAM_TERMINATED: u8 = 0x40;
+
+// DMP layer constants.
+const VECTOR_DMP_SET_PROPERTY: u8 = 0x02;
+const ADDRESS_AND_DATA_TYPE: u8 = 0xA1;
+
+/// Offsets of the start of each PDU, used to compute the flags and length fields.
+const ROOT_PDU_OFFSET: usize = 16;
+const FRAMING_PDU_OFFSET: usize = 38;
+const DMP_PDU_OFFSET: usize = 115;
+/// Offset of the options field of a data packet.
+const OPTIONS_OFFSET: usize = 112;
+/// How many packets with the stream terminated option a source sends when it stops.
+const TERMINATION_PACKETS: usize = 3;
+const DISCOVERY_PDU_OFFSET: usize = 112;
+
+// Universe discovery constants.
+const VECTOR_UNIVERSE_DISCOVERY_UNIVERSE_LIST: u32 = 0x0000_0001;
+/// The universe whose multicast group carries discovery packets.
+pub const DISCOVERY_UNIVERSE: u16 = 64214;
+/// How often sources announce their universes.
+pub const UNIVERSE_DISCOVERY_INTERVAL: Duration = Duration::from_secs(10);
+const UNIVERSES_PER_PAGE: usize = 512;
+
+const MAX_SLOTS: usize = 512;
+
+// Receiving constants.
+/// How long a source may go without sending before its data is considered lost.
+pub const NETWORK_DATA_LOSS_TIMEOUT: Duration = Duration::from_millis(2500);
+/// Packets up to this far behind the last sequence number seen are out of order.
+const SEQUENCE_WINDOW: i8 = -20;
+
+pub(crate) const DEFAULT_SOURCE_NAME: &str = "rust-dmx";
+
+/// Component identifier of an sACN source.
+pub type Cid = [u8; 16];
+
+/// Generate a random version 4 UUID for use as a CID.
+pub fn random_cid() -> Cid {
+    let mut cid = [0; 16];
+    for chunk in cid.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        // There are no processes on wasm32, where asking for the id panics.
+        #[cfg(not(target_arch = "wasm32"))]
+        hasher.write_u64(std::process::id() as u64);
+        chunk.copy_from_slice(&hasher.finish().to_be_bytes());
+    }
+    cid[6] = (cid[6] & 0x0F) | 0x40;
+    cid[8] = (cid[8] & 0x3F) | 0x80;
+    cid
+}
+
+/// Return the multicast group that carries the provided universe.
+pub fn multicast_address(universe: u16) -> Ipv4Addr {
+    let [hi, lo] = universe.to_be_bytes();
+    Ipv4Addr::new(239, 255, hi, lo)
+}
+
+/// Return the IPv6 multicast group that carries the provided universe.
+pub fn multicast_address_v6(universe: u16) -> Ipv6Addr {
+    Ipv6Addr::new(0xFF18, 0, 0, 0, 0, 0, 0x8300, universe)
+}
+
+/// Bind a socket to send from, on the provided local address or any address of the family
+/// of the addresses it will send to.  They must all be of the same family.  Multicast is sent
+/// from the interface with the local address, if one is provided.
+fn bind_sender(local_address: Option<IpAddr>, addresses: &[IpAddr]) -> Result<UdpSocket, Error> {
+    let ipv6 = match local_address {
+        Some(address) => address.is_ipv6(),
+        None => addresses.first().is_some_and(IpAddr::is_ipv6),
+    };
+    if addresses.iter().any(|address| address.is_ipv6() != ipv6) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "can't send to IPv4 and IPv6 addresses from one socket",
+        )
+        .into());
+    }
+    let socket = match local_address {
+        Some(local_address) => {
+            let socket = UdpSocket::bind((local_address, 0))?;
+            set_multicast_interface(&socket, local_address)?;
+            socket
+        }
+        None if ipv6 => UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?,
+        None => UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+    };
+    if !ipv6 {
+        socket.set_broadcast(true)?;
+    }
+    Ok(socket)
+}
+
+/// Send the socket's multicast from the interface with the provided address.  Binding to the
+/// address doesn't do this on its own, as the OS picks the interface for a multicast group
+/// from its routes.
+#[cfg(unix)]
+fn set_multicast_interface(socket: &UdpSocket, local_address: IpAddr) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    fn set_option<T>(
+        socket: &UdpSocket,
+        level: libc::c_int,
+        name: libc::c_int,
+        value: &T,
+    ) -> io::Result<()> {
+        // SAFETY: the option value is a live `T` of the size passed.
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                level,
+                name,
+                (value as *const T).cast(),
+                std::mem::size_of::<T>() as libc::socklen_t,
+            )
+        };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    if local_address.is_unspecified() {
+        return Ok(());
+    }
+    match local_address {
+        IpAddr::V4(address) => {
+            let interface = libc::in_addr {
+                s_addr: u32::from_ne_bytes(address.octets()),
+            };
+            set_option(socket, libc::IPPROTO_IP, libc::IP_MULTICAST_IF, &interface)
+        }
+        IpAddr::V6(address) => {
+            let interface = interface_index(address)?;
+            set_option(
+                socket,
+                libc::IPPROTO_IPV6,
+                libc::IPV6_MULTICAST_IF,
+                &interface,
+            )
+        }
+    }
+}
+
+/// Return the index of the network interface with the provided address.  IPv6 selects the
+/// interface to multicast from by index rather than by address.
+#[cfg(unix)]
+fn interface_index(address: Ipv6Addr) -> io::Result<libc::c_uint> {
+    let mut interfaces = std::ptr::null_mut();
+    // SAFETY: the list is only read until it is freed below.
+    unsafe {
+        if libc::getifaddrs(&mut interfaces) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut index = 0;
+        let mut interface = interfaces;
+        while let Some(entry) = interface.as_ref() {
+            let found = entry.ifa_addr.as_ref().is_some_and(|sockaddr| {
+                libc::c_int::from(sockaddr.sa_family) == libc::AF_INET6
+                    && (*entry.ifa_addr.cast::<libc::sockaddr_in6>())
+                        .sin6_addr
+                        .s6_addr
+                        == address.octets()
+            });
+            if found {
+                index = libc::if_nametoindex(entry.ifa_name);
+                break;
+            }
+            interface = entry.ifa_next;
+        }
+        libc::freeifaddrs(interfaces);
+        if index == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrNotAvailable,
+                format!("no network interface has the address {}", address),
+            ));
+        }
+        Ok(index)
+    }
+}
+
+/// Leave the choice of interface to the OS where it can't be set.
+#[cfg(not(unix))]
+fn set_multicast_interface(_socket: &UdpSocket, _local_address: IpAddr) -> io::Result<()> {
+    Ok(())
+}
+
+/// Return the address a received packet was most likely sent to, for captures.  A socket
+/// bound to every interface can't tell which address a packet arrived on, so data packets are
+/// recorded as sent to their universe's multicast group.
+fn received_destination(local: SocketAddr, packet: &[u8]) -> SocketAddr {
+    if !local.ip().is_unspecified() {
+        return local;
+    }
+    let universe = match parse_data_packet(packet) {
+        Some(packet) => packet.universe,
+        None => return local,
+    };
+    match local {
+        SocketAddr::V4(_) => (multicast_address(universe), local.port()).into(),
+        SocketAddr::V6(_) => (multicast_address_v6(universe), local.port()).into(),
+    }
+}
+
+/// Write an ACN flags and length field for a PDU that runs to the end of the buffer.
+fn write_flags_and_length(buf: &mut [u8], pdu_offset: usize) {
+    let length = (buf.len() - pdu_offset) as u16 | 0x7000;
+    buf[pdu_offset..pdu_offset + 2].copy_from_slice(&length.to_be_bytes());
+}
+
+/// Start a packet in the provided buffer with a root layer carrying the provided vector.
+fn write_root_layer(vector: u32, cid: &Cid, buf: &mut Vec<u8>) {
+    buf.clear();
+    buf.extend_from_slice(&PREAMBLE_SIZE.to_be_bytes());
+    buf.extend_from_slice(&POSTAMBLE_SIZE.to_be_bytes());
+    buf.extend_from_slice(ACN_PACKET_IDENTIFIER);
+    buf.extend_from_slice(&[0, 0]);
+    buf.extend_from_slice(&vector.to_be_bytes());
+    buf.extend_from_slice(cid);
+}
+
+/// Write a source name field, truncated to leave room for the null terminator.
+fn write_source_name(source_name: &str, buf: &mut Vec<u8>) {
+    let name = source_name.as_bytes();
+    let name = &name[0..min(name.len(), SOURCE_NAME_LENGTH - 1)];
+    buf.extend_from_slice(name);
+    buf.resize(buf.len() + SOURCE_NAME_LENGTH - name.len(), 0);
+}
+
+/// Per-packet fields of an E1.31 data packet.
+pub(crate) struct DataPacket<'a> {
+    pub cid: &'a Cid,
+    pub source_name: &'a str,
+    pub priority: u8,
+    pub sequence: u8,
+    pub universe: u16,
+    pub start_code: u8,
+    /// The universe whose sync packets release this data, or 0 to use it immediately.
+    pub sync_address: u16,
+    /// Mark the data as meant for visualizers and monitors only, not live output.
+    pub preview: bool,
+    pub data: &'a [u8],
+}
+
+impl DataPacket<'_> {
+    /// Format the packet into the provided buffer.
+    /// Data beyond 512 slots is ignored.
+    pub fn write_into(&self, buf: &mut Vec<u8>) {
+        let data = &self.data[0..min(self.data.len(), MAX_SLOTS)];
+        write_root_layer(VECTOR_ROOT_E131_DATA, self.cid, buf);
+        // Framing layer
+        buf.extend_from_slice(&[0, 0]);
+        buf.extend_from_slice(&VECTOR_E131_DATA_PACKET.to_be_bytes());
+        write_source_name(self.source_name, buf);
+        buf.push(self.priority);
+        buf.extend_from_slice(&self.sync_address.to_be_bytes());
+        buf.push(self.sequence);
+        buf.push(if self.preview { OPTION_PREVIEW_DATA } else { 0 });
+        buf.extend_from_slice(&self.universe.to_be_bytes());
+        // DMP layer
+        buf.extend_from_slice(&[0, 0]);
+        buf.push(VECTOR_DMP_SET_PROPERTY);
+        buf.push(ADDRESS_AND_DATA_TYPE);
+        buf.extend_from_slice(&0u16.to_be_bytes()); // first property address
+        buf.extend_from_slice(&1u16.to_be_bytes()); // address increment
+        buf.extend_from_slice(&(data.len() as u16 + 1).to_be_bytes());
+        buf.push(self.start_code);
+        buf.extend_from_slice(data);
+
+        write_flags_and_length(buf, ROOT_PDU_OFFSET);
+        write_flags_and_length(buf, FRAMING_PDU_OFFSET);
+        write_flags_and_length(buf, DMP_PDU_OFFSET);
+    }
+}
+
+fn default_priority() -> u8 {
+    DEFAULT_PRIORITY
+}
+
+/// Read a priority, clamped as `set_priority` clamps it.
+fn deserialize_priority<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    Ok(u8::deserialize(deserializer)?.min(MAX_PRIORITY))
+}
+
+/// Read per-address priorities, clamped and truncated as `set_address_priorities` does.
+fn deserialize_address_priorities<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<u8>>, D::Error> {
+    Ok(Option::<Vec<u8>>::deserialize(deserializer)?.map(normalize_address_priorities))
+}
+
+/// Drop priorities beyond the last address, and clamp the rest to the maximum.
+fn normalize_address_priorities(mut priorities: Vec<u8>) -> Vec<u8> {
+    priorities.truncate(MAX_SLOTS);
+    for priority in &mut priorities {
+        *priority = (*priority).min(MAX_PRIORITY);
+    }
+    priorities
+}
+
+/// A DMX port that transmits a single sACN universe, to its multicast group unless targeted
+/// otherwise.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SacnDmxPort {
+    name: String,
+    universe: u16,
+    /// Where packets are sent: the universe's multicast group, unicast receivers, or broadcast.
+    #[serde(alias = "destination", deserialize_with = "deserialize_target")]
+    target: NetworkTarget,
+    source_name: String,
+    cid: Cid,
+    /// The universe whose sync packets release this port's data, if it's synchronized.
+    #[serde(default)]
+    sync_address: Option<u16>,
+    /// Whether the data is marked as preview data, for visualizers only.
+    #[serde(default)]
+    preview: bool,
+    #[serde(
+        default = "default_priority",
+        deserialize_with = "deserialize_priority"
+    )]
+    priority: u8,
+    /// The local address to send from, selecting the network interface; if absent, the OS
+    /// picks one.
+    #[serde(default)]
+    local_address: Option<IpAddr>,
+    /// The priority of each address, sent in packets with the per-address priority START
+    /// code, if this source sends them.
+    #[serde(default, deserialize_with = "deserialize_address_priorities")]
+    address_priorities: Option<Vec<u8>>,
+    #[serde(default)]
+    shape: FrameShape,
+    #[serde(skip)]
+    socket: Option<Arc<UdpSocket>>,
+    /// The sequence number of the next packet, shared by clones since they send one stream.
+    #[serde(skip)]
+    sequence: Arc<AtomicU8>,
+    /// When the per-address priorities were last sent, if they have been since they changed.
+    #[serde(skip)]
+    address_priorities_sent: Option<Instant>,
+    #[serde(skip)]
+    buf: Vec<u8>,
+    #[serde(skip)]
+    capture: Option<PcapWriter>,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+impl SacnDmxPort {
+    /// Create an sACN port that multicasts the provided universe.
+    /// A random CID is generated for the source.
+    /// The port is not opened yet.
+    pub fn new(universe: u16) -> Self {
+        let mut port = Self {
+            name: String::new(),
+            universe,
+            target: NetworkTarget::Multicast,
+            source_name: DEFAULT_SOURCE_NAME.to_string(),
+            cid: random_cid(),
+            sync_address: None,
+            preview: false,
+            priority: DEFAULT_PRIORITY,
+            local_address: None,
+            address_priorities: None,
+            shape: FrameShape::default(),
+            socket: None,
+            sequence: Arc::new(AtomicU8::new(0)),
+            address_priorities_sent: None,
+            buf: Vec::new(),
+            capture: None,
+            last_frame: None,
+            status: StatusTracker::new(),
+        };
+        port.update_name();
+        port
+    }
+
+    /// Create an sACN port and open it.
+    pub fn opened(universe: u16) -> Result<Self, Error> {
+        let mut port = Self::new(universe);
+        port.open()?;
+        Ok(port)
+    }
+
+    /// Send to a unicast receiver instead of the universe's multicast group.
+    pub fn with_destination(self, destination: IpAddr) -> Self {
+        self.with_target(destination.into())
+    }
+
+    /// Send to the provided target instead of the universe's multicast group, such as a list
+    /// of unicast receivers on networks that block multicast.
+    pub fn with_target(mut self, target: NetworkTarget) -> Self {
+        self.target = target;
+        self.update_name();
+        self
+    }
+
+    /// Return where packets are sent.
+    pub fn target(&self) -> &NetworkTarget {
+        &self.target
+    }
+
+    /// Send from the provided local address, so packets leave through the network interface
+    /// that has it, rather than whichever the OS routes the universe's multicast group to.
+    pub fn with_local_address(mut self, local_address: IpAddr) -> Self {
+        self.local_address = Some(local_address);
+        self
+    }
+
+    /// Return the local address packets are sent from, if one was chosen.
+    pub fn local_address(&self) -> Option<IpAddr> {
+        self.local_address
+    }
+
+    /// Check the universe and target and bind a socket to send to them from.
+    fn bind(&self) -> Result<UdpSocket, Error> {
+        if !(MIN_UNIVERSE..=MAX_UNIVERSE).contains(&self.universe) {
+            return Err(Error::InvalidUniverse(self.universe));
+        }
+        self.target.check()?;
+        bind_sender(self.local_address, &self.addresses())
+    }
+
+    /// Record the packets sent to a capture.
+    pub fn with_capture(mut self, capture: PcapWriter) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Set how each frame is shaped before it is transmitted: how many of its slots are sent,
+    /// and how shorter frames are padded.  By default frames are transmitted at the length
+    /// they're written, padded with zeros only as far as the protocol requires.
+    pub fn with_shape(mut self, shape: FrameShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Return how each frame is shaped before it is transmitted.
+    pub fn shape(&self) -> &FrameShape {
+        &self.shape
+    }
+
+    /// Return the addresses packets are sent to.
+    fn addresses(&self) -> Vec<IpAddr> {
+        let groups = (
+            multicast_address(self.universe),
+            multicast_address_v6(self.universe),
+        );
+        self.target.addresses(Some(groups))
+    }
+
+    /// Set the source name displayed by receivers.  Names longer than 63 bytes are truncated.
+    pub fn with_source_name<S: Into<String>>(mut self, source_name: S) -> Self {
+        self.source_name = source_name.into();
+        self
+    }
+
+    /// Set the component identifier of this source.  Receivers use the CID to tell sources
+    /// apart, so it should be stable across restarts of the same application.
+    pub fn with_cid(mut self, cid: Cid) -> Self {
+        self.cid = cid;
+        self
+    }
+
+    /// Tag this port's data with a synchronization universe, so receivers hold it until a
+    /// sync packet for that universe arrives.  `SacnSyncGroup` creates ports tagged this way.
+    pub fn with_sync_address(mut self, sync_universe: u16) -> Self {
+        self.sync_address = Some(sync_universe);
+        self
+    }
+
+    /// Mark this port's data as preview data, which receivers driving fixtures ignore but
+    /// visualizers and monitors show, so a preview of a look can share the network with the
+    /// live show.
+    pub fn with_preview(mut self, preview: bool) -> Self {
+        self.preview = preview;
+        self
+    }
+
+    /// Return true if this port's data is marked as preview data.
+    pub fn is_preview(&self) -> bool {
+        self.preview
+    }
+
+    /// Set the priority receivers merge this source's data at, from 0 to 200.  Receivers use
+    /// the data of the highest priority sources sending a universe, so a higher priority
+    /// overrides a console on the same universe and a lower one yields to it.  Priorities
+    /// above 200 are clamped.
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.set_priority(priority);
+        self
+    }
+
+    /// Change the priority of this source, taking effect from the next write.  Priorities
+    /// above 200 are clamped.
+    pub fn set_priority(&mut self, priority: u8) {
+        self.priority = priority.min(MAX_PRIORITY);
+    }
+
+    /// Return the priority of this source.
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// Send a priority for each address, from the first, alongside the data, so receivers
+    /// can take some channels from this source and leave others to a console.  A priority of
+    /// 0 means this source doesn't drive the address; addresses past the end of the list
+    /// aren't driven either.  Priorities above 200 are clamped.
+    ///
+    /// Priorities are sent with the first frame written after they change, and then once a
+    /// second, in packets with the per-address priority START code.  Receivers that don't
+    /// support them use the source's priority for every address.
+    pub fn with_address_priorities(mut self, priorities: Vec<u8>) -> Self {
+        self.set_address_priorities(Some(priorities));
+        self
+    }
+
+    /// Change the per-address priorities, or stop sending them if `None`, taking effect from
+    /// the next write.  Receivers go back to the source's priority for every address once
+    /// they stop hearing per-address priorities, after a few seconds.
+    pub fn set_address_priorities(&mut self, priorities: Option<Vec<u8>>) {
+        self.address_priorities = priorities.map(normalize_address_priorities);
+        self.address_priorities_sent = None;
+    }
+
+    /// Return the priority of each address, if this source sends them.
+    pub fn address_priorities(&self) -> Option<&[u8]> {
+        self.address_priorities.as_deref()
+    }
+
+    /// Return the universe this port transmits.
+    pub fn universe(&self) -> u16 {
+        self.universe
+    }
+
+    /// Return the component identifier of this source.
+    pub fn cid(&self) -> &Cid {
+        &self.cid
+    }
+
+    fn update_name(&mut self) {
+        self.name = match self.target {
+            NetworkTarget::Multicast => format!("sacn:{}", self.universe),
+            _ => format!("sacn:{}/{}", self.target, self.universe),
+        };
+    }
+
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), Error> {
+        self.send_data(0, frame, false)?;
+        self.last_frame = Some(DmxFrame::from(frame));
+        self.send_address_priorities()
+    }
+
+    /// Send the per-address priorities, if they have changed or are due to be repeated.
+    fn send_address_priorities(&mut self) -> Result<(), Error> {
+        let due = self
+            .address_priorities_sent
+            .map_or(true, |sent| sent.elapsed() >= ADDRESS_PRIORITY_INTERVAL);
+        if let (true, Some(priorities)) = (due, self.address_priorities.clone()) {
+            self.send_data(ADDRESS_PRIORITY_START_CODE, &priorities, false)?;
+            self.address_priorities_sent = Some(Instant::now());
+        }
+        Ok(())
+    }
+
+    /// Send a data packet, with the stream terminated option if the source is stopping.  Only
+    /// DMX frames, with the null start code, are changed to the length transmitted.
+    fn send_data(
+        &mut self,
+        start_code: u8,
+        frame: &[u8],
+        stream_terminated: bool,
+    ) -> Result<(), Error> {
+        let socket = self.socket.as_ref().ok_or(Error::NotOpen)?;
+        let data = match start_code {
+            0 => self.shape.apply(frame, |slots| slots),
+            _ => frame,
+        };
+        DataPacket {
+            cid: &self.cid,
+            source_name: &self.source_name,
+            priority: self.priority,
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+            universe: self.universe,
+            start_code,
+            sync_address: self.sync_address.unwrap_or(0),
+            preview: self.preview,
+            data,
+        }
+        .write_into(&mut self.buf);
+        if stream_terminated {
+            self.buf[OPTIONS_OFFSET] |= OPTION_STREAM_TERMINATED;
+        }
+        for address in self.addresses() {
+            socket.send_to(&self.buf, (address, SACN_PORT))?;
+            if self.capture.is_some() {
+                let source = socket.local_addr()?;
+                let destination = (address, SACN_PORT).into();
+                capture(&self.capture, source, destination, &self.buf);
+            }
+        }
+        Ok(())
+    }
+
+    /// Tell receivers this source has stopped, as E1.31 requires, so they release the universe
+    /// straight away rather than holding the last look until the source times out.  Nothing
+    /// is sent if the port hasn't sent any data.  Failures are ignored, since the receivers
+    /// will time the source out anyway.
+    fn terminate_stream(&mut self) {
+        let frame = match (&self.socket, &self.last_frame) {
+            (Some(_), Some(frame)) => frame.clone(),
+            _ => return,
+        };
+        for _ in 0..TERMINATION_PACKETS {
+            let _ = self.send_data(0, &frame, true);
+        }
+    }
+}
+
+/// Dropping the last clone of an open port terminates its stream, as closing it does.
+impl Drop for SacnDmxPort {
+    fn drop(&mut self) {
+        if self
+            .socket
+            .as_ref()
+            .is_some_and(|socket| Arc::strong_count(socket) == 1)
+        {
+            self.terminate_stream();
+        }
+    }
+}
+
+/// Lists a multicast sACN port for the first universe.  Other universes must be constructed
+/// directly.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SacnProvider;
+
+impl PortProvider for SacnProvider {
+    fn name(&self) -> &str {
+        "sacn"
+    }
+
+    fn available_ports(&self) -> Result<PortListing, Error> {
+        Ok(vec![Box::new(SacnDmxPort::new(MIN_UNIVERSE))])
+    }
+
+    fn kinds(&self) -> &[PortKind] {
+        &[PortKind::Sacn]
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for SacnDmxPort {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Open the port.
+    fn open(&mut self) -> Result<(), Error> {
+        if self.socket.is_some() {
+            return Ok(());
+        }
+        let result = self.bind().map(|socket| {
+            self.socket = Some(Arc::new(socket));
+            self.sequence = Arc::new(AtomicU8::new(0));
+            self.address_priorities_sent = None;
+        });
+        self.status.record(result)
+    }
+
+    /// Close the port.  Clones share the stream, so closing the last open clone first sends
+    /// the packets that tell receivers the stream has ended, as dropping it does.
+    fn close(&mut self) {
+        if self
+            .socket
+            .as_ref()
+            .is_some_and(|socket| Arc::strong_count(socket) == 1)
+        {
+            self.terminate_stream();
+        }
+        self.status.clear();
+        self.socket = None;
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let result = self.send_frame(frame);
+        self.status.record(result)
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        let result = match start_code {
+            0 => self.send_frame(data),
+            _ => self.send_data(start_code, data, false),
+        };
+        self.status.record(result)
+    }
+
+    fn kind(&self) -> PortKind {
+        PortKind::Sacn
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(self.socket.is_some())
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+}
+
+impl fmt::Display for SacnDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target {
+            NetworkTarget::Multicast => write!(f, "sACN universe {}", self.universe),
+            _ => write!(f, "sACN universe {} to {}", self.universe, self.target),
+        }
+    }
+}
+
+/// Format a synchronization packet into the provided buffer.
+fn write_sync_packet(cid: &Cid, sequence: u8, sync_address: u16, buf: &mut Vec<u8>) {
+    write_root_layer(VECTOR_ROOT_E131_EXTENDED, cid, buf);
+    // Framing layer
+    buf.extend_from_slice(&[0, 0]);
+    buf.extend_from_slice(&VECTOR_E131_EXTENDED_SYNCHRONIZATION.to_be_bytes());
+    buf.push(sequence);
+    buf.extend_from_slice(&sync_address.to_be_bytes());
+    buf.extend_from_slice(&[0, 0]);
+
+    write_flags_and_length(buf, ROOT_PDU_OFFSET);
+    write_flags_and_length(buf, FRAMING_PDU_OFFSET);
+}
+
+/// Releases the data of several sACN ports at once.  The group's ports tag their data with
+/// its synchronization universe, so receivers hold each universe until `flush_sync` sends a
+/// sync packet, and pixel-mapped universes update together.
+pub struct SacnSyncGroup {
+    sync_universe: u16,
+    cid: Cid,
+    /// Where sync packets are sent, by default the sync universe's multicast group.
+    target: NetworkTarget,
+    local_address: Option<IpAddr>,
+    socket: UdpSocket,
+    sequence: u8,
+    buf: Vec<u8>,
+}
+
+impl SacnSyncGroup {
+    /// Create a group synchronized on the provided universe.  A random CID is generated for
+    /// the source; receivers only accept sync packets from the source that sent the data.
+    pub fn new(sync_universe: u16) -> Result<Self, Error> {
+        if !(MIN_UNIVERSE..=MAX_UNIVERSE).contains(&sync_universe) {
+            return Err(Error::InvalidUniverse(sync_universe));
+        }
+        Ok(Self {
+            sync_universe,
+            cid: random_cid(),
+            target: NetworkTarget::Multicast,
+            local_address: None,
+            socket: bind_sender(None, &[])?,
+            sequence: 0,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Set the component identifier of the source, which the group's ports share.
+    pub fn with_cid(mut self, cid: Cid) -> Self {
+        self.cid = cid;
+        self
+    }
+
+    /// Send sync packets to a unicast receiver instead of the sync universe's multicast group.
+    pub fn with_destination(self, destination: IpAddr) -> Self {
+        self.with_target(destination.into())
+    }
+
+    /// Send sync packets to the provided target instead of the sync universe's multicast
+    /// group, as the group's ports can be.
+    pub fn with_target(mut self, target: NetworkTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    /// Return where sync packets are sent.
+    pub fn target(&self) -> &NetworkTarget {
+        &self.target
+    }
+
+    /// Send sync packets, and the data of ports created afterwards, from the provided local
+    /// address.  If it's an IPv6 address, ports multicast to their IPv6 groups and sync
+    /// packets are multicast to the sync universe's.  Multicast is sent from the interface
+    /// with the address.  Fails if the address can't be bound.
+    pub fn with_local_address(mut self, local_address: IpAddr) -> Result<Self, Error> {
+        self.socket = bind_sender(Some(local_address), &[])?;
+        self.local_address = Some(local_address);
+        Ok(self)
+    }
+
+    /// Return the universe sync packets are sent on.
+    pub fn sync_universe(&self) -> u16 {
+        self.sync_universe
+    }
+
+    /// Create a port in this group that multicasts the provided universe.
+    /// The port is not opened yet.
+    pub fn port(&self, universe: u16) -> SacnDmxPort {
+        let mut port = SacnDmxPort::new(universe)
+            .with_cid(self.cid)
+            .with_sync_address(self.sync_universe);
+        port.local_address = self.local_address;
+        if self.local_address.is_some_and(|address| address.is_ipv6()) {
+            port = port.with_target(NetworkTarget::MulticastV6);
+        }
+        port
+    }
+
+    /// Send a sync packet, releasing the data written to the group's ports since the last one.
+    pub fn flush_sync(&mut self) -> Result<(), Error> {
+        self.target.check()?;
+        write_sync_packet(&self.cid, self.sequence, self.sync_universe, &mut self.buf);
+        let ipv6 = self.local_address.is_some_and(|address| address.is_ipv6());
+        let target = match &self.target {
+            NetworkTarget::Multicast if ipv6 => &NetworkTarget::MulticastV6,
+            target => target,
+        };
+        let groups = (
+            multicast_address(self.sync_universe),
+            multicast_address_v6(self.sync_universe),
+        );
+        for address in target.addresses(Some(groups)) {
+            self.socket.send_to(&self.buf, (address, SACN_PORT))?;
+        }
+        self.sequence = self.sequence.wrapping_add(1);
+        Ok(())
+    }
+}
+
+/// Format one page of a universe discovery packet into the provided buffer.
+fn write_discovery_packet(
+    cid: &Cid,
+    source_name: &str,
+    page: u8,
+    last_page: u8,
+    universes: &[u16],
+    buf: &mut Vec<u8>,
+) {
+    write_root_layer(VECTOR_ROOT_E131_EXTENDED, cid, buf);
+    // Framing layer
+    buf.extend_from_slice(&[0, 0]);
+    buf.extend_from_slice(&VECTOR_E131_EXTENDED_DISCOVERY.to_be_bytes());
+    write_source_name(source_name, buf);
+    buf.extend_from_slice(&[0; 4]);
+    // Universe discovery layer
+    buf.extend_from_slice(&[0, 0]);
+    buf.extend_from_slice(&VECTOR_UNIVERSE_DISCOVERY_UNIVERSE_LIST.to_be_bytes());
+    buf.push(page);
+    buf.push(last_page);
+    for universe in universes {
+        buf.extend_from_slice(&universe.to_be_bytes());
+    }
+
+    write_flags_and_length(buf, ROOT_PDU_OFFSET);
+    write_flags_and_length(buf, FRAMING_PDU_OFFSET);
+    write_flags_and_length(buf, DISCOVERY_PDU_OFFSET);
+}
+
+/// One page of a source's universe list.
+struct DiscoveryPage<'a> {
+    cid: Cid,
+    source_name: String,
+    universes: &'a [u8],
+}
+
+/// Parse a universe discovery packet, returning None for anything else.
+fn parse_discovery_packet(packet: &[u8]) -> Option<DiscoveryPage<'_>> {
+    let vector = |offset: usize| {
+        packet
+            .get(offset..offset + 4)
+            .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+    };
+    if packet.len() < DISCOVERY_PDU_OFFSET + 8
+        || &packet[4..16] != ACN_PACKET_IDENTIFIER
+        || vector(18)? != VECTOR_ROOT_E131_EXTENDED
+        || vector(40)? != VECTOR_E131_EXTENDED_DISCOVERY
+        || vector(114)? != VECTOR_UNIVERSE_DISCOVERY_UNIVERSE_LIST
+    {
+        return None;
+    }
+    let mut cid = [0; 16];
+    cid.copy_from_slice(&packet[22..38]);
+    let name = &packet[44..44 + SOURCE_NAME_LENGTH];
+    let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    Some(DiscoveryPage {
+        cid,
+        source_name: String::from_utf8_lossy(&name[..end]).into_owned(),
+        universes: &packet[120..],
+    })
+}
+
+/// An sACN source found by universe discovery.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SacnSource {
+    pub cid: Cid,
+    pub name: String,
+    /// The universes the source is transmitting, in ascending order.
+    pub universes: Vec<u16>,
+}
+
+/// Listen for universe discovery packets, returning the sources heard within the timeout.
+/// Sources announce every 10 seconds, so a shorter timeout may miss some of them.
+///
+/// This listens on the sACN port, so it fails if another application has that port bound
+/// exclusively.
+pub fn discover_sacn_universes(timeout: Duration) -> Result<Vec<SacnSource>, Error> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, SACN_PORT))?;
+    socket.join_multicast_v4(
+        &multicast_address(DISCOVERY_UNIVERSE),
+        &Ipv4Addr::UNSPECIFIED,
+    )?;
+    let deadline = Instant::now() + timeout;
+    let mut sources: Vec<SacnSource> = Vec::new();
+    let mut buf = [0; 1500];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(remaining))?;
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                break
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let page = match parse_discovery_packet(&buf[..len]) {
+            Some(page) => page,
+            None => continue,
+        };
+        let index = match sources.iter().position(|s| s.cid == page.cid) {
+            Some(index) => index,
+            None => {
+                sources.push(SacnSource {
+                    cid: page.cid,
+                    name: String::new(),
+                    universes: Vec::new(),
+                });
+                sources.len() - 1
+            }
+        };
+        let source = &mut sources[index];
+        source.name = page.source_name;
+        source.universes.extend(
+            page.universes
+                .chunks_exact(2)
+                .map(|u| u16::from_be_bytes([u[0], u[1]])),
+        );
+        source.universes.sort_unstable();
+        source.universes.dedup();
+    }
+    Ok(sources)
+}
+
+/// Announces the universes this application transmits, so other sources and receivers can
+/// see them with universe discovery.  Call `update` regularly, such as once per frame; it
+/// only sends every 10 seconds.
+pub struct SacnUniverseAnnouncer {
+    cid: Cid,
+    source_name: String,
+    universes: Vec<u16>,
+    socket: UdpSocket,
+    last_sent: Option<Instant>,
+    buf: Vec<u8>,
+}
+
+impl SacnUniverseAnnouncer {
+    /// Create an announcer for the source with the provided CID, which should be the CID its
+    /// ports send with.
+    pub fn new(cid: Cid) -> Result<Self, Error> {
+        Ok(Self {
+            cid,
+            source_name: DEFAULT_SOURCE_NAME.to_string(),
+            universes: Vec::new(),
+            socket: UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?,
+            last_sent: None,
+            buf: Vec::new(),
+        })
+    }
+
+    /// Set the source name displayed by receivers.  Names longer than 63 bytes are truncated.
+    pub fn with_source_name<S: Into<String>>(mut self, source_name: S) -> Self {
+        self.source_name = source_name.into();
+        self
+    }
+
+    /// Set the universes to announce.  The change is sent with the next announcement.
+    pub fn set_universes(&mut self, universes: &[u16]) {
+        self.universes = universes.to_vec();
+        self.universes.sort_unstable();
+        self.universes.dedup();
+    }
+
+    /// Return the universes being announced, in ascending order.
+    pub fn universes(&self) -> &[u16] {
+        &self.universes
+    }
+
+    /// Announce the universes if the discovery interval has passed since the last time.
+    pub fn update(&mut self) -> Result<(), Error> {
+        match self.last_sent {
+            Some(last_sent) if last_sent.elapsed() < UNIVERSE_DISCOVERY_INTERVAL => Ok(()),
+            _ => self.announce(),
+        }
+    }
+
+    /// Announce the universes now.
+    pub fn announce(&mut self) -> Result<(), Error> {
+        let destination = (multicast_address(DISCOVERY_UNIVERSE), SACN_PORT);
+        // An empty list is still announced as a single empty page.
+        let pages: Vec<&[u16]> = if self.universes.is_empty() {
+            vec![&[]]
+        } else {
+            self.universes.chunks(UNIVERSES_PER_PAGE).collect()
+        };
+        let last_page = (pages.len() - 1) as u8;
+        for (page, universes) in pages.into_iter().enumerate() {
+            write_discovery_packet(
+                &self.cid,
+                &self.source_name,
+                page as u8,
+                last_page,
+                universes,
+                &mut self.buf,
+            );
+            self.socket.send_to(&self.buf, destination)?;
+        }
+        self.last_sent = Some(Instant::now());
+        Ok(())
+    }
+}
+
+/// The fields of a received E1.31 data packet that a receiver uses.
+struct ReceivedData<'a> {
+    cid: Cid,
+    source_name: String,
+    priority: u8,
+    sequence: u8,
+    options: u8,
+    universe: u16,
+    start_code: u8,
+    data: &'a [u8],
+}
+
+/// Parse an E1.31 data packet, returning None for anything else.
+fn parse_data_packet(packet: &[u8]) -> Option<ReceivedData<'_>> {
+    let vector = |offset: usize| {
+        packet
+            .get(offset..offset + 4)
+            .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]))
+    };
+    if packet.len() < DMP_PDU_OFFSET + 11
+        || &packet[4..16] != ACN_PACKET_IDENTIFIER
+        || vector(18)? != VECTOR_ROOT_E131_DATA
+        || vector(40)? != VECTOR_E131_DATA_PACKET
+        || packet[117] != VECTOR_DMP_SET_PROPERTY
+        || packet[118] != ADDRESS_AND_DATA_TYPE
+    {
+        return None;
+    }
+    let mut cid = [0; 16];
+    cid.copy_from_slice(&packet[22..38]);
+    let name = &packet[44..44 + SOURCE_NAME_LENGTH];
+    let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    // The property count includes the start code.
+    let count = usize::from(u16::from_be_bytes([packet[123], packet[124]]));
+    let slots = count.checked_sub(1)?.min(MAX_SLOTS);
+    Some(ReceivedData {
+        cid,
+        source_name: String::from_utf8_lossy(&name[..end]).into_owned(),
+        priority: packet[108],
+        sequence: packet[111],
+        options: packet[112],
+        universe: u16::from_be_bytes([packet[113], packet[114]]),
+        start_code: packet[125],
+        data: packet.get(126..126 + slots)?,
+    })
+}
+
+/// A source heard sending to a receiver's universe.
+struct ReceivedSource {
+    cid: Cid,
+    name: String,
+    priority: u8,
+    sequence: u8,
+    last_seen: Instant,
+    data: Vec<u8>,
+}
+
+/// Tracks the sources sending to one universe and merges their data: the highest priority
+/// sources win, and sources sharing that priority are merged highest takes precedence.
+#[derive(Default)]
+struct SourceMerger {
+    sources: Vec<ReceivedSource>,
+}
+
+impl SourceMerger {
+    /// Take in a data packet, returning true if the merged data may have changed.
+    /// Out of order packets, preview data and alternate start codes are ignored.
+    fn receive(&mut self, packet: &ReceivedData<'_>, now: Instant) -> bool {
+        let index = self.sources.iter().position(|s| s.cid == packet.cid);
+        if let Some(index) = index {
+            let delta = packet.sequence.wrapping_sub(self.sources[index].sequence) as i8;
+            if delta <= 0 && delta > SEQUENCE_WINDOW {
+                return false;
+            }
+        }
+        if packet.options & OPTION_STREAM_TERMINATED != 0 {
+            return match index {
+                Some(index) => {
+                    self.sources.remove(index);
+                    true
+                }
+                None => false,
+            };
+        }
+        if packet.options & OPTION_PREVIEW_DATA != 0 || packet.start_code != 0 {
+            return false;
+        }
+        let source = match index {
+            Some(index) => &mut self.sources[index],
+            None => {
+                self.sources.push(ReceivedSource {
+                    cid: packet.cid,
+                    name: String::new(),
+                    priority: 0,
+                    sequence: 0,
+                    last_seen: now,
+                    data: Vec::new(),
+                });
+                self.sources.last_mut().unwrap()
+            }
+        };
+        source.name.clone_from(&packet.source_name);
+        source.priority = packet.priority;
+        source.sequence = packet.sequence;
+        source.last_seen = now;
+        source.data.clear();
+        source.data.extend_from_slice(packet.data);
+        true
+    }
+
+    /// Forget sources that have stopped sending, returning true if any were.
+    fn expire(&mut self, now: Instant) -> bool {
+        let before = self.sources.len();
+        self.sources
+            .retain(|source| now.duration_since(source.last_seen) < NETWORK_DATA_LOSS_TIMEOUT);
+        self.sources.len() != before
+    }
+
+    /// Return when the next source will be forgotten if it doesn't send again.
+    fn next_expiry(&self) -> Option<Instant> {
+        self.sources
+            .iter()
+            .map(|source| source.last_seen + NETWORK_DATA_LOSS_TIMEOUT)
+            .min()
+    }
+
+    /// Return the merged data, or None if no sources are sending.
+    fn merged(&self) -> Option<Vec<u8>> {
+        let priority = self.sources.iter().map(|source| source.priority).max()?;
+        let mut merged = Vec::new();
+        for source in self.sources.iter().filter(|s| s.priority == priority) {
+            if merged.len() < source.data.len() {
+                merged.resize(source.data.len(), 0);
+            }
+            for (level, &value) in merged.iter_mut().zip(&source.data) {
+                *level = (*level).max(value);
+            }
+        }
+        Some(merged)
+    }
+}
+
+/// Receives sACN universes, merging the data of every source sending to each.  The highest
+/// priority sources win, with sources at the same priority merged highest takes precedence.
+/// Sources that stop sending for 2.5 seconds, or that terminate their stream, drop out of
+/// the merge.
+pub struct SacnReceiver {
+    name: String,
+    bind_address: SocketAddr,
+    universes: Vec<(u16, SourceMerger)>,
+    socket: Option<UdpSocket>,
+    buf: Vec<u8>,
+    capture: Option<PcapWriter>,
+}
+
+impl SacnReceiver {
+    /// Create a receiver for the provided universes, listening to their multicast groups on
+    /// the sACN port of every interface.  A universe listed more than once is received once,
+    /// since its group can only be joined once.  The receiver is not opened yet.
+    pub fn new(universes: impl IntoIterator<Item = u16>) -> Self {
+        let mut merged: Vec<(u16, SourceMerger)> = Vec::new();
+        for universe in universes {
+            if merged.iter().all(|(u, _)| *u != universe) {
+                merged.push((universe, SourceMerger::default()));
+            }
+        }
+        let universes = merged;
+        let names: Vec<_> = universes.iter().map(|(u, _)| u.to_string()).collect();
+        Self {
+            name: format!("sacn-in:{}", names.join(",")),
+            bind_address: (Ipv4Addr::UNSPECIFIED, SACN_PORT).into(),
+            universes,
+            socket: None,
+            buf: vec![0; 1500],
+            capture: None,
+        }
+    }
+
+    /// Listen on the provided address instead.  Multicast is only joined when listening on
+    /// every interface, so a receiver bound to one address only hears unicast.
+    pub fn with_bind_address(mut self, bind_address: SocketAddr) -> Self {
+        self.bind_address = bind_address;
+        self
+    }
+
+    /// Record the packets received to a capture, including those for other universes.
+    pub fn with_capture(mut self, capture: PcapWriter) -> Self {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Iterate over the universes being received.
+    pub fn universes(&self) -> impl Iterator<Item = u16> + '_ {
+        self.universes.iter().map(|(universe, _)| *universe)
+    }
+
+    /// Return the address the receiver is listening on, once open.
+    pub fn local_addr(&self) -> Result<SocketAddr, Error> {
+        Ok(self.socket.as_ref().ok_or(Error::NotOpen)?.local_addr()?)
+    }
+
+    /// Return the names of the sources currently sending to a universe.
+    pub fn source_names(&self, universe: u16) -> Vec<&str> {
+        self.universes
+            .iter()
+            .filter(|(u, _)| *u == universe)
+            .flat_map(|(_, merger)| merger.sources.iter().map(|s| s.name.as_str()))
+            .collect()
+    }
+
+    /// Block until the merged data of one of the universes changes, and return the universe
+    /// with its data.  Fails with `Timeout` when the last source of a universe stops
+    /// sending; keep reading to wait for sources to return.
+    pub fn read_universe(&mut self) -> Result<(u16, ReceivedFrame), Error> {
+        let socket = self.socket.as_ref().ok_or(Error::NotOpen)?;
+        loop {
+            let now = Instant::now();
+            let expired = self
+                .universes
+                .iter_mut()
+                .position(|(_, merger)| merger.expire(now));
+            let changed = match expired {
+                Some(index) => Some(index),
+                None => {
+                    let timeout = self
+                        .universes
+                        .iter()
+                        .filter_map(|(_, merger)| merger.next_expiry())
+                        .min()
+                        .map(|expiry| {
+                            expiry
+                                .saturating_duration_since(now)
+                                .max(Duration::from_millis(1))
+                        });
+                    socket.set_read_timeout(timeout)?;
+                    let len = match socket.recv_from(&mut self.buf) {
+                        Ok((len, source)) => {
+                            if self.capture.is_some() {
+                                let packet = &self.buf[..len];
+                                let destination =
+                                    received_destination(socket.local_addr()?, packet);
+                                capture(&self.capture, source, destination, packet);
+                            }
+                            len
+                        }
+                        Err(e)
+                            if matches!(
+                                e.kind(),
+                                io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                            ) =>
+                        {
+                            continue
+                        }
+                        Err(e) => return Err(e.into()),
+                    };
+                    let universes = &mut self.universes;
+                    parse_data_packet(&self.buf[..len]).and_then(|packet| {
+                        let index = universes
+                            .iter()
+                            .position(|(universe, _)| *universe == packet.universe)?;
+                        let (_, merger) = &mut universes[index];
+                        merger.receive(&packet, Instant::now()).then_some(index)
+                    })
+                }
+            };
+            if let Some(index) = changed {
+                let (universe, merger) = &self.universes[index];
+                let data = merger.merged().ok_or(Error::Timeout)?;
+                let frame = ReceivedFrame {
+                    start_code: 0,
+                    data,
+                };
+                return Ok((*universe, frame));
+            }
+        }
+    }
+}
+
+impl DmxInputPort for SacnReceiver {
+    /// Receivers are created for whichever universes are wanted, so there are none to list.
+    fn available_ports() -> Result<InputPortListing, Error> {
+        Ok(Vec::new())
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        if self.socket.is_some() {
+            return Ok(());
+        }
+        if let Some(universe) = self
+            .universes()
+            .find(|universe| !(MIN_UNIVERSE..=MAX_UNIVERSE).contains(universe))
+        {
+            return Err(Error::InvalidUniverse(universe));
+        }
+        let socket = UdpSocket::bind(self.bind_address)?;
+        if self.bind_address.ip().is_unspecified() {
+            for universe in self.universes() {
+                match self.bind_address {
+                    SocketAddr::V4(_) => socket
+                        .join_multicast_v4(&multicast_address(universe), &Ipv4Addr::UNSPECIFIED)?,
+                    SocketAddr::V6(_) => {
+                        socket.join_multicast_v6(&multicast_address_v6(universe), 0)?
+                    }
+                }
+            }
+        }
+        self.socket = Some(socket);
+        for (_, merger) in &mut self.universes {
+            *merger = SourceMerger::default();
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.socket = None;
+    }
+
+    /// Block until the merged data of one of the universes changes.  Use `read_universe` to
+    /// find out which one.
+    fn read_frame(&mut self) -> Result<ReceivedFrame, Error> {
+        self.read_universe().map(|(_, frame)| frame)
+    }
+}
+
+impl fmt::Display for SacnReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sACN input universe")?;
+        for universe in self.universes() {
+            write!(f, " {}", universe)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::pcap::CaptureBuffer;
+
+    #[test]
+    fn test_data_packet() {
+        let cid = [0xAB; 16];
+        let mut buf = Vec::new();
+        DataPacket {
+            cid: &cid,
+            source_name: "test",
+            priority: 100,
+            sequence: 3,
+            universe: 0x0102,
+            start_code: 0,
+            sync_address: 0x0304,
+            preview: true,
+            data: &[1; 512],
+        }
+        .write_into(&mut buf);
+        assert_eq!(buf.len(), 638);
+        assert_eq!(&buf[0..4], &[0x00, 0x10, 0x00, 0x00]);
+        assert_eq!(&buf[4..16], b"ASC-E1.17\0\0\0");
+        assert_eq!(&buf[16..18], &[0x72, 0x6E]);
+        assert_eq!(&buf[22..38], &cid);
+        assert_eq!(&buf[38..40], &[0x72, 0x58]);
+        assert_eq!(&buf[44..49], b"test\0");
+        assert_eq!(&buf[108..115], &[100, 0x03, 0x04, 3, 0x80, 0x01, 0x02]);
+        assert_eq!(&buf[115..117], &[0x72, 0x0B]);
+        assert_eq!(&buf[117..126], &[0x02, 0xA1, 0, 0, 0, 1, 0x02, 0x01, 0]);
+
+        let any = SocketAddr::from((Ipv4Addr::UNSPECIFIED, SACN_PORT));
+        assert_eq!(
+            received_destination(any, &buf),
+            (multicast_address(0x0102), SACN_PORT).into()
+        );
+        assert_eq!(received_destination(any, &buf[..100]), any);
+        let local = SocketAddr::from((Ipv4Addr::LOCALHOST, SACN_PORT));
+        assert_eq!(received_destination(local, &buf), local);
+    }
+
+    #[test]
+    fn test_source_merger() {
+        let mut buf = Vec::new();
+        let mut send = |merger: &mut SourceMerger, cid: u8, priority, sequence, data| {
+            DataPacket {
+                cid: &[cid; 16],
+                source_name: "test",
+                priority,
+                sequence,
+                universe: 1,
+                start_code: 0,
+                sync_address: 0,
+                preview: false,
+                data,
+            }
+            .write_into(&mut buf);
+            let packet = parse_data_packet(&buf).unwrap();
+            assert_eq!(packet.data, data);
+            merger.receive(&packet, Instant::now())
+        };
+        let mut merger = SourceMerger::default();
+        assert!(send(&mut merger, 1, 100, 5, &[10, 0, 30]));
+        assert!(send(&mut merger, 2, 100, 0, &[0, 20]));
+        assert_eq!(merger.merged(), Some(vec![10, 20, 30]));
+        // Out of order packets are ignored.
+        assert!(!send(&mut merger, 1, 100, 4, &[0, 0, 0]));
+        // A higher priority source takes over.
+        assert!(send(&mut merger, 3, 150, 0, &[1]));
+        assert_eq!(merger.merged(), Some(vec![1]));
+
+        let later = Instant::now() + NETWORK_DATA_LOSS_TIMEOUT;
+        assert!(merger.expire(later));
+        assert_eq!(merger.merged(), None);
+    }
+
+    #[test]
+    fn test_receiver() {
+        let mut receiver =
+            SacnReceiver::new([1, 2, 1]).with_bind_address((Ipv4Addr::LOCALHOST, 0).into());
+        assert_eq!(receiver.universes().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(receiver.name(), "sacn-in:1,2");
+        receiver.open().unwrap();
+        let address = receiver.local_addr().unwrap();
+        let socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut buf = Vec::new();
+        for universe in [3, 2] {
+            DataPacket {
+                cid: &[1; 16],
+                source_name: "test",
+                priority: 100,
+                sequence: 0,
+                universe,
+                start_code: 0,
+                sync_address: 0,
+                preview: false,
+                data: &[1, 2, 3],
+            }
+            .write_into(&mut buf);
+            socket.send_to(&buf, address).unwrap();
+        }
+        let (universe, frame) = receiver.read_universe().unwrap();
+        assert_eq!(universe, 2);
+        assert_eq!(frame.data, vec![1, 2, 3]);
+        assert_eq!(receiver.source_names(2), vec!["test"]);
+    }
+
+    #[test]
+    fn test_priority() {
+        let mut port = SacnDmxPort::new(1);
+        assert_eq!(port.priority(), DEFAULT_PRIORITY);
+        port.set_priority(50);
+        assert_eq!(port.priority(), 50);
+        assert_eq!(
+            SacnDmxPort::new(1).with_priority(255).priority(),
+            MAX_PRIORITY
+        );
+        let saved = serde::de::value::U8Deserializer::<serde::de::value::Error>::new(255);
+        assert_eq!(deserialize_priority(saved).unwrap(), MAX_PRIORITY);
+    }
+
+    #[test]
+    fn test_stream_terminated() {
+        let mut buf = Vec::new();
+        DataPacket {
+            cid: &[1; 16],
+            source_name: "test",
+            priority: 100,
+            sequence: 0,
+            universe: 1,
+            start_code: 0,
+            sync_address: 0,
+            preview: false,
+            data: &[1],
+        }
+        .write_into(&mut buf);
+        let mut merger = SourceMerger::default();
+        assert!(merger.receive(&parse_data_packet(&buf).unwrap(), Instant::now()));
+        buf[111] = 1; // sequence
+        buf[112] = OPTION_STREAM_TERMINATED;
+        assert!(merger.receive(&parse_data_packet(&buf).unwrap(), Instant::now()));
+        assert_eq!(merger.merged(), None);
+    }
+
+    #[test]
+    fn test_terminate_stream() {
+        let out = CaptureBuffer::default();
+        let mut port = SacnDmxPort::new(1)
+            .with_destination(Ipv4Addr::LOCALHOST.into())
+            .with_capture(PcapWriter::new(out.clone()).unwrap());
+        port.open().unwrap();
+        port.write(&[1, 2]).unwrap();
+        drop(port.clone());
+        let mut clone = port.clone();
+        // The stream is only ended once no clone is left sending it.
+        port.close();
+        assert_eq!(out.payloads().len(), 1);
+        clone.close();
+        clone.close();
+        let packets = out.payloads();
+        let options: Vec<_> = packets.iter().map(|p| p[OPTIONS_OFFSET]).collect();
+        assert_eq!(options, vec![0, 0x40, 0x40, 0x40]);
+        let sequences: Vec<_> = packets.iter().map(|p| p[111]).collect();
+        assert_eq!(sequences, vec![0, 1, 2, 3]);
+        assert_eq!(&packets[3][126..128], &[1, 2]);
+    }
+
+    #[test]
+    fn test_clones_share_sequence() {
+        let out = CaptureBuffer::default();
+        let mut port = SacnDmxPort::new(1)
+            .with_destination(Ipv4Addr::LOCALHOST.into())
+            .with_capture(PcapWriter::new(out.clone()).unwrap());
+        port.open().unwrap();
+        let mut clone = port.clone();
+        port.write(&[1]).unwrap();
+        clone.write(&[2]).unwrap();
+        port.write(&[3]).unwrap();
+        let sequences: Vec<_> = out.payloads().iter().map(|p| p[111]).collect();
+        assert_eq!(sequences, vec![0, 1, 2]);
+        // Reopening starts a new stream.
+        port.close();
+        clone.close();
+        port.open().unwrap();
+        assert_eq!(port.sequence.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_address_priorities() {
+        let out = CaptureBuffer::default();
+        let mut port = SacnDmxPort::new(1)
+            .with_destination(Ipv4Addr::LOCALHOST.into())
+            .with_capture(PcapWriter::new(out.clone()).unwrap())
+            .with_address_priorities(vec![0, 150, 255]);
+        assert_eq!(port.address_priorities(), Some(&[0, 150, 200][..]));
+        assert_eq!(normalize_address_priorities(vec![255; 600]), vec![200; 512]);
+        port.open().unwrap();
+        port.write(&[1, 2, 3]).unwrap();
+        // Unchanged priorities aren't sent again until the interval is up.
+        port.write(&[1, 2, 3]).unwrap();
+        port.set_address_priorities(Some(vec![100]));
+        port.write(&[1, 2, 3]).unwrap();
+        port.set_address_priorities(None);
+        port.write(&[1, 2, 3]).unwrap();
+        let packets = out.payloads();
+        let start_codes: Vec<_> = packets.iter().map(|p| p[125]).collect();
+        assert_eq!(start_codes, vec![0, 0xDD, 0, 0, 0xDD, 0]);
+        assert_eq!(&packets[1][126..], &[0, 150, 200]);
+        assert_eq!(&packets[4][126..], &[100]);
+        let sequences: Vec<_> = packets.iter().map(|p| p[111]).collect();
+        assert_eq!(sequences, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(&port.last_frame().unwrap()[..3], &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_sync_packet() {
+        let cid = [0xAB; 16];
+        let mut buf = Vec::new();
+        write_sync_packet(&cid, 5, 0x0102, &mut buf);
+        assert_eq!(buf.len(), 49);
+        assert_eq!(&buf[16..22], &[0x70, 0x21, 0, 0, 0, 8]);
+        assert_eq!(&buf[22..38], &cid);
+        assert_eq!(&buf[38..49], &[0x70, 0x0B, 0, 0, 0, 1, 5, 1, 2, 0, 0]);
+
+        let group = SacnSyncGroup::new(7).unwrap();
+        let port = group.port(1);
+        assert_eq!(port.cid(), &group.cid);
+        assert_eq!(port.sync_address, Some(7));
+        assert!(matches!(
+            SacnSyncGroup::new(0),
+            Err(Error::InvalidUniverse(0))
+        ));
+    }
+
+    #[test]
+    fn test_open_error_status() {
+        let mut port = SacnDmxPort::new(0);
+        assert!(port.open().is_err());
+        assert_eq!(
+            port.status(),
+            PortStatus::Errored(Error::InvalidUniverse(0).to_string())
+        );
+        port.close();
+        assert_eq!(port.status(), PortStatus::Closed);
+    }
+
+    #[test]
+    fn test_sync_target() {
+        let receiver = UdpSocket::bind((Ipv4Addr::LOCALHOST, SACN_PORT));
+        let mut group = SacnSyncGroup::new(7)
+            .unwrap()
+            .with_target(NetworkTarget::Unicast(Vec::new()));
+        assert!(group.flush_sync().is_err());
+        // The sACN port may be taken on this machine; the target is still checked above.
+        if let Ok(receiver) = receiver {
+            let mut group = group.with_destination(Ipv4Addr::LOCALHOST.into());
+            group.flush_sync().unwrap();
+            let mut buf = [0; 64];
+            assert_eq!(receiver.recv(&mut buf).unwrap(), 49);
+        }
+    }
+
+    #[test]
+    fn test_discovery_packet() {
+        let cid = [0xAB; 16];
+        let mut buf = Vec::new();
+        write_discovery_packet(&cid, "test", 1, 2, &[1, 0x0203], &mut buf);
+        assert_eq!(buf.len(), 124);
+        assert_eq!(&buf[16..22], &[0x70, 0x6C, 0, 0, 0, 8]);
+        assert_eq!(&buf[38..44], &[0x70, 0x56, 0, 0, 0, 2]);
+        assert_eq!(&buf[112..124], &[0x70, 0x0C, 0, 0, 0, 1, 1, 2, 0, 1, 2, 3]);
+        let page = parse_discovery_packet(&buf).unwrap();
+        assert_eq!(page.cid, cid);
+        assert_eq!(page.source_name, "test");
+        assert_eq!(page.universes, &[0, 1, 2, 3]);
+        assert!(parse_discovery_packet(&buf[..100]).is_none());
+    }
+
+    #[test]
+    fn test_multicast_address() {
+        assert_eq!(multicast_address(1), Ipv4Addr::new(239, 255, 0, 1));
+        assert_eq!(multicast_address(63999), Ipv4Addr::new(239, 255, 249, 255));
+    }
+
+    #[test]
+    fn test_multicast_address_v6() {
+        assert_eq!(
+            multicast_address_v6(1),
+            "ff18::8300:1".parse::<Ipv6Addr>().unwrap()
+        );
+        assert_eq!(
+            multicast_address_v6(63999),
+            "ff18::8300:f9ff".parse::<Ipv6Addr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_bind_sender() {
+        let v4 = IpAddr::from(Ipv4Addr::LOCALHOST);
+        let v6 = IpAddr::from(Ipv6Addr::LOCALHOST);
+        assert!(bind_sender(None, &[v4, v6]).is_err());
+        // Loopback may have no IPv6 address where IPv6 is disabled.
+        if let Ok(socket) = bind_sender(None, &[v6]) {
+            assert!(socket.local_addr().unwrap().is_ipv6());
+        }
+        let socket = bind_sender(Some(v4), &[]).unwrap();
+        assert!(socket.broadcast().unwrap());
+        #[cfg(unix)]
+        assert!(interface_index("2001:db8::1".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_sync_group_broadcast() {
+        let group = SacnSyncGroup::new(7).unwrap();
+        assert!(group.socket.broadcast().unwrap());
+        let group = group
+            .with_local_address(Ipv4Addr::LOCALHOST.into())
+            .unwrap();
+        assert!(group.socket.broadcast().unwrap());
+    }
+}
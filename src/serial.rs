@@ -0,0 +1,305 @@
+//! Helpers shared by the ports built on plain serial devices.
+
+use serde::{Deserialize, Serialize};
+use serialport::{SerialPort, SerialPortInfo, SerialPortType, UsbPortInfo};
+use std::cmp::min;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+
+use crate::{BreakTiming, Error, HardwareInfo};
+
+/// DMX line rate.
+pub const DMX_BAUD_RATE: u32 = 250_000;
+
+// Universe size constraints.
+pub(crate) const MIN_UNIVERSE_SIZE: usize = 24;
+const MAX_UNIVERSE_SIZE: usize = 512;
+
+/// Transmit a DMX packet by holding the line in break, releasing it for the mark after break,
+/// then writing the start code and slots.  The frame is padded or truncated to a valid
+/// universe size, and the call returns once the data has left the serial port.
+pub fn write_with_break(
+    port: &mut dyn SerialPort,
+    timing: &BreakTiming,
+    start_code: u8,
+    frame: &[u8],
+) -> Result<(), Error> {
+    let frame = &frame[0..min(frame.len(), MAX_UNIVERSE_SIZE)];
+    port.set_break()?;
+    sleep(timing.break_time);
+    port.clear_break()?;
+    sleep(timing.mark_after_break_time);
+    port.write_all(&[start_code])?;
+    port.write_all(frame)?;
+    if frame.len() < MIN_UNIVERSE_SIZE {
+        port.write_all(&[0; MIN_UNIVERSE_SIZE][0..MIN_UNIVERSE_SIZE - frame.len()])?;
+    }
+    // Wait for the line to go idle so the next break doesn't cut this packet short.
+    port.flush()?;
+    Ok(())
+}
+
+/// Describe the hardware behind a serial port.
+pub fn hardware_info(info: &SerialPortInfo) -> HardwareInfo {
+    let mut hardware = HardwareInfo {
+        path: Some(info.port_name.clone()),
+        ..HardwareInfo::default()
+    };
+    if let SerialPortType::UsbPort(usb) = &info.port_type {
+        hardware.serial_number = usb.serial_number.clone();
+        hardware.vid = Some(usb.vid);
+        hardware.pid = Some(usb.pid);
+    }
+    hardware.description = friendly_name(info).map(str::to_string);
+    hardware
+}
+
+/// Return the name Windows gives the serial port, such as "USB Serial Port (COM7)", which
+/// serialport reports as the USB product there.  Elsewhere the product is the string the
+/// device itself reports, which doesn't tell ports apart, so there is no friendly name.
+pub fn friendly_name(info: &SerialPortInfo) -> Option<&str> {
+    match &info.port_type {
+        SerialPortType::UsbPort(usb) if cfg!(windows) => usb.product.as_deref(),
+        _ => None,
+    }
+}
+
+/// Identify a device by serial number if it has one, or by port name otherwise, followed by
+/// the port's friendly name where the system provides one.
+pub fn write_device_name(
+    product: &str,
+    info: &SerialPortInfo,
+    f: &mut fmt::Formatter<'_>,
+) -> fmt::Result {
+    match &info.port_type {
+        SerialPortType::UsbPort(UsbPortInfo {
+            serial_number: Some(sn),
+            ..
+        }) => write!(f, "{} {}", product, sn)?,
+        _ => write!(f, "{} {}", product, info.port_name)?,
+    }
+    if let Some(name) = friendly_name(info) {
+        write!(f, " on {}", name)?;
+    }
+    Ok(())
+}
+
+/// The links udev makes to a serial device on Linux, which keep naming the same device when
+/// `ttyUSB` numbers are handed out in a different order.  The `by-id` link is named from the
+/// device's USB vendor, product and serial number attributes, so it follows the device from
+/// one USB socket to another; the `by-path` link is named from the socket and also works for
+/// devices without a serial number.  There are no links on other systems.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceLinks {
+    pub by_id: Option<PathBuf>,
+    pub by_path: Option<PathBuf>,
+}
+
+impl DeviceLinks {
+    /// Find the links to the serial device at the provided path.
+    pub fn resolve(port_name: &str) -> Self {
+        Self {
+            by_id: find_link(Path::new("/dev/serial/by-id"), port_name),
+            by_path: find_link(Path::new("/dev/serial/by-path"), port_name),
+        }
+    }
+
+    /// Return true if no links to the device are known.
+    pub fn is_empty(&self) -> bool {
+        self.by_id.is_none() && self.by_path.is_none()
+    }
+}
+
+/// Return the link in the directory that points to the device at the provided path.
+#[cfg(target_os = "linux")]
+fn find_link(dir: &Path, port_name: &str) -> Option<PathBuf> {
+    let device = fs::canonicalize(port_name).ok()?;
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|link| fs::canonicalize(link).is_ok_and(|target| target == device))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn find_link(_dir: &Path, _port_name: &str) -> Option<PathBuf> {
+    None
+}
+
+/// Return the path of the device a link points to now, if it is attached.
+fn link_target(link: &Path) -> Option<String> {
+    fs::canonicalize(link)
+        .ok()?
+        .into_os_string()
+        .into_string()
+        .ok()
+}
+
+/// Find where a serial device is attached now and update its path if it has moved.  Its
+/// udev `by-id` link is followed first, then its USB serial number is looked up among the
+/// listed ports, then its `by-path` link is followed.  Paths change across reboots and
+/// operating systems but these don't, so saved ports can be reopened.  Ports that can't be
+/// found are left alone.  Return true if the path changed.
+pub fn relocate(
+    info: &mut SerialPortInfo,
+    links: &DeviceLinks,
+    list: impl FnOnce() -> Result<Vec<SerialPortInfo>, Error>,
+) -> bool {
+    if let Some(path) = links.by_id.as_deref().and_then(link_target) {
+        return move_to(info, path);
+    }
+    if let Some(found) = find_by_serial_number(info, list) {
+        if found.port_name == info.port_name {
+            return false;
+        }
+        *info = found;
+        return true;
+    }
+    match links.by_path.as_deref().and_then(link_target) {
+        Some(path) => move_to(info, path),
+        None => false,
+    }
+}
+
+/// Look the serial number of a USB serial port up among the listed ports.
+fn find_by_serial_number(
+    info: &SerialPortInfo,
+    list: impl FnOnce() -> Result<Vec<SerialPortInfo>, Error>,
+) -> Option<SerialPortInfo> {
+    let serial_number = match &info.port_type {
+        SerialPortType::UsbPort(UsbPortInfo {
+            serial_number: Some(sn),
+            ..
+        }) => sn,
+        _ => return None,
+    };
+    list()
+        .ok()?
+        .into_iter()
+        .find(|listed| match &listed.port_type {
+            SerialPortType::UsbPort(usb) => usb.serial_number.as_ref() == Some(serial_number),
+            _ => false,
+        })
+}
+
+/// Change the path of a port, returning true if it differs.
+fn move_to(info: &mut SerialPortInfo, path: String) -> bool {
+    if path == info.port_name {
+        return false;
+    }
+    info.port_name = path;
+    true
+}
+
+// Derive serde for serial port info.
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SerialPortInfo")]
+pub(crate) struct SerialPortInfoDef {
+    pub port_name: String,
+    #[serde(with = "SerialPortTypeDef")]
+    pub port_type: SerialPortType,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "SerialPortType")]
+pub(crate) enum SerialPortTypeDef {
+    #[serde(with = "UsbPortInfoDef")]
+    UsbPort(UsbPortInfo),
+    PciPort,
+    BluetoothPort,
+    Unknown,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "UsbPortInfo")]
+pub(crate) struct UsbPortInfoDef {
+    pub vid: u16,
+    pub pid: u16,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_friendly_name() {
+        let mut info = usb_port("COM7", Some("EN111111"));
+        assert_eq!(friendly_name(&info), None);
+        if let SerialPortType::UsbPort(usb) = &mut info.port_type {
+            usb.product = Some("USB Serial Port (COM7)".to_string());
+        }
+        let expected = cfg!(windows).then_some("USB Serial Port (COM7)");
+        assert_eq!(friendly_name(&info), expected);
+        assert_eq!(hardware_info(&info).description.as_deref(), expected);
+    }
+
+    fn usb_port(port_name: &str, serial_number: Option<&str>) -> SerialPortInfo {
+        SerialPortInfo {
+            port_name: port_name.to_string(),
+            port_type: SerialPortType::UsbPort(UsbPortInfo {
+                vid: 0x0403,
+                pid: 0x6001,
+                serial_number: serial_number.map(str::to_string),
+                manufacturer: None,
+                product: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn test_relocate() {
+        let listed = || {
+            Ok(vec![
+                usb_port("/dev/ttyUSB0", Some("EN111111")),
+                usb_port("/dev/ttyUSB1", Some("EN222222")),
+            ])
+        };
+        let none = DeviceLinks::default();
+        let mut moved = usb_port("COM3", Some("EN222222"));
+        assert!(relocate(&mut moved, &none, listed));
+        assert_eq!(moved.port_name, "/dev/ttyUSB1");
+        assert!(!relocate(&mut moved, &none, listed));
+
+        let mut missing = usb_port("COM3", Some("EN333333"));
+        assert!(!relocate(&mut missing, &none, listed));
+        assert_eq!(missing.port_name, "COM3");
+
+        let mut anonymous = usb_port("COM3", None);
+        assert!(!relocate(&mut anonymous, &none, listed));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_links() {
+        let dir = std::env::temp_dir().join(format!("rust_dmx_links_{}", std::process::id()));
+        let by_id = dir.join("by-id");
+        fs::create_dir_all(&by_id).unwrap();
+        let device = dir.join("ttyUSB1");
+        fs::write(&device, []).unwrap();
+        let link = by_id.join("usb-ENTTEC_DMX_USB_PRO_EN222222-if00-port0");
+        let _ = fs::remove_file(&link);
+        std::os::unix::fs::symlink(&device, &link).unwrap();
+
+        let device_name = device.to_str().unwrap();
+        assert_eq!(find_link(&by_id, device_name), Some(link.clone()));
+        assert_eq!(find_link(&by_id, "/nonexistent/ttyUSB9"), None);
+
+        let links = DeviceLinks {
+            by_id: Some(link),
+            by_path: None,
+        };
+        let mut renumbered = usb_port("/dev/ttyUSB0", None);
+        assert!(relocate(&mut renumbered, &links, || Ok(Vec::new())));
+        assert_eq!(
+            renumbered.port_name,
+            fs::canonicalize(&device).unwrap().to_str().unwrap()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
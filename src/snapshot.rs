@@ -0,0 +1,256 @@
+//! Saving the last frame written to a port, to restore after a restart.
+//!
+//! A snapshot is `DMXSNAP` followed by the format version, then the channel levels.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::{DmxPort, Error, HardwareInfo, PortKind, PortStats, PortStatus, UNIVERSE_SIZE};
+
+const MAGIC: &[u8; 8] = b"DMXSNAP\x01";
+
+fn default_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+/// Read the frame saved in a snapshot file, or None if there is no file.
+pub fn read_snapshot(path: impl AsRef<Path>) -> Result<Option<Vec<u8>>, Error> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    match bytes.strip_prefix(&MAGIC[..]) {
+        Some(frame) if frame.len() <= UNIVERSE_SIZE => Ok(Some(frame.to_vec())),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "not a DMX snapshot").into()),
+    }
+}
+
+/// Save a frame to a snapshot file.  The snapshot is written beside the file and renamed over
+/// it, so losing power part way through leaves the previous snapshot rather than a torn one.
+pub fn write_snapshot(path: impl AsRef<Path>, frame: &[u8]) -> Result<(), Error> {
+    let path = path.as_ref();
+    let mut temp = OsString::from(path.as_os_str());
+    temp.push(".tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(MAGIC)?;
+    file.write_all(&frame[..min(frame.len(), UNIVERSE_SIZE)])?;
+    file.sync_all()?;
+    fs::rename(&temp, path)?;
+    Ok(())
+}
+
+/// A port that saves the last frame written to it, and writes it back when opened, so an
+/// installation that restarts comes back up with the look it had rather than dark.
+///
+/// Saving every frame would wear out flash storage, so changes are saved at most once per
+/// interval, a second by default: by the first write once the interval is up, and when the
+/// port is closed.  There is no timer, so a change is only saved promptly while frames keep
+/// being written.  An application that writes a change and then stops writing should call
+/// `save`, or have a `RefreshingPort` keep writing to this one; otherwise a restart loses the
+/// changes since the last save.  Failing to save doesn't fail writing, which matters more
+/// than the snapshot.  A snapshot that can't be read is ignored when opening, so the port still
+/// starts, dark.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SnapshotPort {
+    port: Box<dyn DmxPort>,
+    path: PathBuf,
+    #[serde(default = "default_interval")]
+    interval: Duration,
+    #[serde(skip)]
+    frame: Vec<u8>,
+    #[serde(skip)]
+    unsaved: bool,
+    #[serde(skip)]
+    last_saved: Option<Instant>,
+}
+
+impl SnapshotPort {
+    /// Wrap a port, saving its frames to the provided file.
+    pub fn new(port: Box<dyn DmxPort>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            port,
+            path: path.into(),
+            interval: default_interval(),
+            frame: Vec::new(),
+            unsaved: false,
+            last_saved: None,
+        }
+    }
+
+    /// Save changes at most once per interval.  A zero interval saves every frame.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Return the path of the snapshot file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Save the last frame written now, if it hasn't been saved yet.
+    pub fn save(&mut self) -> Result<(), Error> {
+        if !self.unsaved {
+            return Ok(());
+        }
+        self.last_saved = Some(Instant::now());
+        write_snapshot(&self.path, &self.frame)?;
+        self.unsaved = false;
+        Ok(())
+    }
+
+    /// Return the wrapped port.  An unsaved frame is discarded.
+    pub fn into_inner(self) -> Box<dyn DmxPort> {
+        self.port
+    }
+
+    fn save_in_time(&mut self) {
+        let due = self
+            .last_saved
+            .map_or(true, |saved| saved.elapsed() >= self.interval);
+        if due {
+            let result = self.save();
+            crate::trace::snapshot_saved(self.port.name(), &result);
+        }
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for SnapshotPort {
+    fn name(&self) -> &str {
+        self.port.name()
+    }
+
+    /// Open the wrapped port and write the saved frame to it, if there is one and nothing has
+    /// been written yet.
+    fn open(&mut self) -> Result<(), Error> {
+        self.port.open()?;
+        if self.frame.is_empty() {
+            if let Ok(Some(frame)) = read_snapshot(&self.path) {
+                self.frame = frame;
+            }
+        }
+        if self.frame.is_empty() {
+            return Ok(());
+        }
+        self.port.write(&self.frame)
+    }
+
+    fn close(&mut self) {
+        let result = self.save();
+        crate::trace::snapshot_saved(self.port.name(), &result);
+        self.port.close();
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let frame = &frame[..min(frame.len(), UNIVERSE_SIZE)];
+        if frame != self.frame.as_slice() {
+            self.frame.clear();
+            self.frame.extend_from_slice(frame);
+            self.unsaved = true;
+        }
+        let result = self.port.write(frame);
+        self.save_in_time();
+        result
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        match start_code {
+            0 => self.write(data),
+            _ => self.port.write_with_start_code(start_code, data),
+        }
+    }
+
+    fn kind(&self) -> PortKind {
+        self.port.kind()
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        self.port.hardware()
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        self.port.stats()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.port.status()
+    }
+
+    fn health_check(&mut self) -> Result<(), Error> {
+        self.port.health_check()
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.port.last_frame()
+    }
+}
+
+impl fmt::Display for SnapshotPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (saving to {})", self.port, self.path.display())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+
+    #[test]
+    fn test_restore() {
+        let path = std::env::temp_dir().join(format!("rust_dmx_snapshot_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mock = MockDmxPort::new();
+        let mut port = SnapshotPort::new(Box::new(mock.clone()), &path)
+            .with_interval(Duration::from_secs(3600));
+        port.open().unwrap();
+        assert_eq!(mock.write_count(), 0);
+        port.write(&[1, 2, 3]).unwrap();
+        assert_eq!(read_snapshot(&path).unwrap(), Some(vec![1, 2, 3]));
+        // Saved on close rather than within the interval.
+        port.write(&[4, 5]).unwrap();
+        assert_eq!(read_snapshot(&path).unwrap(), Some(vec![1, 2, 3]));
+        port.close();
+        assert_eq!(read_snapshot(&path).unwrap(), Some(vec![4, 5]));
+
+        let mock = MockDmxPort::new();
+        let mut port = SnapshotPort::new(Box::new(mock.clone()), &path);
+        port.open().unwrap();
+        assert_eq!(mock.last_frame(), Some(vec![4, 5]));
+
+        fs::write(&path, b"garbage").unwrap();
+        assert!(read_snapshot(&path).is_err());
+        let mock = MockDmxPort::new();
+        SnapshotPort::new(Box::new(mock.clone()), &path)
+            .open()
+            .unwrap();
+        assert_eq!(mock.write_count(), 0);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_save_on_next_write() {
+        let path =
+            std::env::temp_dir().join(format!("rust_dmx_snapshot_next_{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut port = SnapshotPort::new(Box::new(MockDmxPort::new()), &path)
+            .with_interval(Duration::from_millis(10));
+        port.open().unwrap();
+        port.write(&[1]).unwrap();
+        port.write(&[2]).unwrap();
+        assert_eq!(read_snapshot(&path).unwrap(), Some(vec![1]));
+        std::thread::sleep(Duration::from_millis(20));
+        // Writing the same frame again saves the change once the interval is up.
+        port.write(&[2]).unwrap();
+        assert_eq!(read_snapshot(&path).unwrap(), Some(vec![2]));
+        fs::remove_file(&path).unwrap();
+    }
+}
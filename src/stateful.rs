@@ -0,0 +1,173 @@
+//! Setting channels one at a time.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{
+    check_channels, DmxFrame, DmxPort, Error, HardwareInfo, PortKind, PortStats, PortStatus,
+};
+
+/// A port that keeps the current universe, so channels can be set and read back one at a
+/// time, as a fixture tester or a simple installation wants, rather than written a whole
+/// frame at a time.  Changes are only sent by `flush`; writing a whole frame replaces the
+/// universe and sends it straight away.
+///
+/// The universe isn't serialized, and starts out at zero.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StatefulPort {
+    port: Box<dyn DmxPort>,
+    #[serde(skip)]
+    frame: DmxFrame,
+}
+
+impl StatefulPort {
+    /// Wrap a port, starting from a universe at zero.
+    pub fn new(port: Box<dyn DmxPort>) -> Self {
+        Self {
+            port,
+            frame: DmxFrame::new(),
+        }
+    }
+
+    /// Set the level of a channel, numbered from 1.  The change is sent by the next flush.
+    pub fn set_channel(&mut self, channel: u16, level: u8) -> Result<(), Error> {
+        check_channels(channel, 1)?;
+        self.frame.set(channel, level);
+        Ok(())
+    }
+
+    /// Return the level of a channel, or None if the channel number is outside 1 to 512.
+    pub fn get_channel(&self, channel: u16) -> Option<u8> {
+        self.frame.get(channel)
+    }
+
+    /// Set a 16-bit level across a pair of channels, the coarse byte in the provided channel,
+    /// numbered from 1, and the fine byte in the one after.  The change is sent by the next
+    /// flush.
+    pub fn set_channel_u16(&mut self, coarse_channel: u16, level: u16) -> Result<(), Error> {
+        check_channels(coarse_channel, 2)?;
+        self.frame.set_u16(coarse_channel, level);
+        Ok(())
+    }
+
+    /// Return the 16-bit level of a pair of channels, or None if either is outside 1 to 512.
+    pub fn get_channel_u16(&self, coarse_channel: u16) -> Option<u16> {
+        self.frame.get_u16(coarse_channel)
+    }
+
+    /// Return the current universe.
+    pub fn frame(&self) -> &DmxFrame {
+        &self.frame
+    }
+
+    /// Return the current universe for changing, to be sent by the next flush.
+    pub fn frame_mut(&mut self) -> &mut DmxFrame {
+        &mut self.frame
+    }
+
+    /// Send the current universe to the wrapped port.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.port.write(&self.frame)
+    }
+
+    /// Return the wrapped port.
+    pub fn inner(&self) -> &dyn DmxPort {
+        self.port.as_ref()
+    }
+
+    /// Return the wrapped port.
+    pub fn into_inner(self) -> Box<dyn DmxPort> {
+        self.port
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for StatefulPort {
+    fn name(&self) -> &str {
+        self.port.name()
+    }
+
+    fn open(&mut self) -> Result<(), Error> {
+        self.port.open()
+    }
+
+    fn close(&mut self) {
+        self.port.close()
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        self.frame = DmxFrame::from(frame);
+        self.flush()
+    }
+
+    fn write_with_start_code(&mut self, start_code: u8, data: &[u8]) -> Result<(), Error> {
+        match start_code {
+            0 => self.write(data),
+            _ => self.port.write_with_start_code(start_code, data),
+        }
+    }
+
+    /// Set the channels in the current universe and send it.
+    fn write_range(&mut self, start_channel: u16, levels: &[u8]) -> Result<(), Error> {
+        check_channels(start_channel, levels.len())?;
+        self.frame.set_range(start_channel, levels);
+        self.flush()
+    }
+
+    fn kind(&self) -> PortKind {
+        self.port.kind()
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        self.port.hardware()
+    }
+
+    fn stats(&self) -> Option<PortStats> {
+        self.port.stats()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.port.status()
+    }
+
+    fn health_check(&mut self) -> Result<(), Error> {
+        self.port.health_check()
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.port.last_frame()
+    }
+}
+
+impl fmt::Display for StatefulPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.port.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+
+    #[test]
+    fn test_stateful() {
+        let mock = MockDmxPort::new();
+        let mut port = StatefulPort::new(Box::new(mock.clone()));
+        port.open().unwrap();
+        port.set_channel(1, 10).unwrap();
+        port.set_channel(3, 30).unwrap();
+        assert!(port.set_channel(513, 1).is_err());
+        assert_eq!(port.get_channel(3), Some(30));
+        assert_eq!(mock.write_count(), 0);
+        port.flush().unwrap();
+        assert_eq!(&mock.last_frame().unwrap()[0..3], &[10, 0, 30]);
+        port.write_range(2, &[20]).unwrap();
+        assert_eq!(&mock.last_frame().unwrap()[0..3], &[10, 20, 30]);
+        port.set_channel_u16(4, 0xabcd).unwrap();
+        assert!(port.set_channel_u16(512, 1).is_err());
+        assert_eq!(port.get_channel_u16(4), Some(0xabcd));
+        port.flush().unwrap();
+        assert_eq!(&mock.last_frame().unwrap()[3..5], &[0xab, 0xcd]);
+    }
+}
@@ -0,0 +1,95 @@
+//! Counting what ports have written, for monitoring.
+
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::Error;
+
+/// How much weight each new frame interval has in the frame rate average.
+const RATE_SMOOTHING: f64 = 0.1;
+
+/// What a port has written since it was created.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PortStats {
+    /// How many frames have been written successfully.
+    pub frames_written: u64,
+    /// How many channel levels those frames held.
+    pub bytes_written: u64,
+    /// How many writes have failed.
+    pub errors: u64,
+    /// When the last successful write finished.
+    pub last_write: Option<SystemTime>,
+    /// The recent rate of successful writes, in frames per second.
+    pub frame_rate: f64,
+}
+
+/// Keeps a port's statistics up to date.  Ports implemented outside this crate can use one to
+/// implement `DmxPort::stats`.
+#[derive(Clone, Debug, Default)]
+pub struct StatsCounter {
+    stats: PortStats,
+    last_write: Option<Instant>,
+    /// Running average of the time between successful writes.
+    interval: Option<Duration>,
+}
+
+impl StatsCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the result of writing a frame, passing the result through.
+    pub fn record(&mut self, frame: &[u8], result: Result<(), Error>) -> Result<(), Error> {
+        if result.is_err() {
+            self.stats.errors += 1;
+            return result;
+        }
+        let now = Instant::now();
+        if let Some(last_write) = self.last_write {
+            let elapsed = now - last_write;
+            self.interval = Some(match self.interval {
+                Some(interval) => {
+                    interval.mul_f64(1.0 - RATE_SMOOTHING) + elapsed.mul_f64(RATE_SMOOTHING)
+                }
+                None => elapsed,
+            });
+        }
+        self.last_write = Some(now);
+        self.stats.frames_written += 1;
+        self.stats.bytes_written += frame.len() as u64;
+        self.stats.last_write = Some(SystemTime::now());
+        result
+    }
+
+    /// Return the statistics recorded so far.  The frame rate decays once writes stop.
+    pub fn stats(&self) -> PortStats {
+        let mut stats = self.stats.clone();
+        if let (Some(interval), Some(last_write)) = (self.interval, self.last_write) {
+            // Once writes stall, the time since the last one bounds the rate.
+            let interval = interval.max(last_write.elapsed());
+            if !interval.is_zero() {
+                stats.frame_rate = 1.0 / interval.as_secs_f64();
+            }
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let mut counter = StatsCounter::new();
+        assert_eq!(counter.stats(), PortStats::default());
+        counter.record(&[1, 2, 3], Ok(())).unwrap();
+        counter.record(&[1], Err(Error::NotOpen)).unwrap_err();
+        counter.record(&[1, 2], Ok(())).unwrap();
+        let stats = counter.stats();
+        assert_eq!(stats.frames_written, 2);
+        assert_eq!(stats.bytes_written, 5);
+        assert_eq!(stats.errors, 1);
+        assert!(stats.last_write.is_some());
+        assert!(stats.frame_rate > 0.0);
+    }
+}
@@ -0,0 +1,77 @@
+//! Whether ports are ready to write, for monitoring.
+
+use derive_more::Display;
+
+use crate::Error;
+
+/// Whether a port is ready to write.
+#[derive(Clone, Debug, Display, PartialEq, Eq)]
+pub enum PortStatus {
+    /// The port is open, and the last thing done with it succeeded.
+    #[display(fmt = "open")]
+    Open,
+    /// The port hasn't been opened, or has been closed.
+    #[display(fmt = "closed")]
+    Closed,
+    /// Opening, writing to or checking the port last failed, with this error.
+    #[display(fmt = "error: {}", _0)]
+    Errored(String),
+}
+
+/// Keeps track of the last error a port ran into.  Ports implemented outside this crate can
+/// use one to implement `DmxPort::status`.
+#[derive(Clone, Debug, Default)]
+pub struct StatusTracker {
+    error: Option<String>,
+}
+
+impl StatusTracker {
+    /// Create a tracker that hasn't seen an error.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the result of opening, writing to or checking the port, passing it through.
+    pub fn record<T>(&mut self, result: Result<T, Error>) -> Result<T, Error> {
+        self.error = result.as_ref().err().map(Error::to_string);
+        result
+    }
+
+    /// Forget the last error, as when the port is closed.
+    pub fn clear(&mut self) {
+        self.error = None;
+    }
+
+    /// Return the status of a port, given whether it is open now.  A failure since the port
+    /// was last opened or closed takes precedence.
+    pub fn status(&self, open: bool) -> PortStatus {
+        match &self.error {
+            Some(error) => PortStatus::Errored(error.clone()),
+            None if open => PortStatus::Open,
+            None => PortStatus::Closed,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        let mut tracker = StatusTracker::new();
+        assert_eq!(tracker.status(false), PortStatus::Closed);
+        tracker.record(Ok(())).unwrap();
+        assert_eq!(tracker.status(true), PortStatus::Open);
+        tracker.record::<()>(Err(Error::Timeout)).unwrap_err();
+        assert_eq!(
+            tracker.status(true),
+            PortStatus::Errored("timed out".to_string())
+        );
+        tracker.record(Ok(())).unwrap();
+        assert_eq!(tracker.status(true), PortStatus::Open);
+        tracker.record::<()>(Err(Error::NotOpen)).unwrap_err();
+        tracker.clear();
+        assert_eq!(tracker.status(false), PortStatus::Closed);
+    }
+}
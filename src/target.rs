@@ -0,0 +1,199 @@
+//! Where network ports send their packets.
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+use std::io;
+use std::net::{AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::Error;
+
+const NO_RECEIVERS: &str = "unicast target has no receivers";
+
+/// Where a network port sends its packets.  Many venue networks block multicast, or flood
+/// broadcast traffic to every device, so unicasting to the receivers is often the better
+/// choice.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NetworkTarget {
+    /// Broadcast to every device on the local network.
+    Broadcast,
+    /// Send to the protocol's multicast group for the universe.  Art-Net has no multicast
+    /// groups, so Art-Net ports fail to open with this target.
+    Multicast,
+    /// Send to the protocol's IPv6 multicast group for the universe, on IPv6-only networks.
+    MulticastV6,
+    /// Send a copy to each of these receivers.  Directed broadcast addresses work too.
+    Unicast(Vec<IpAddr>),
+}
+
+impl NetworkTarget {
+    /// Return the addresses to send to, given the IPv4 and IPv6 multicast groups of the
+    /// universe, if the protocol has them.
+    pub(crate) fn addresses(&self, multicast_groups: Option<(Ipv4Addr, Ipv6Addr)>) -> Vec<IpAddr> {
+        match self {
+            Self::Broadcast => vec![Ipv4Addr::BROADCAST.into()],
+            Self::Multicast => multicast_groups
+                .map(|(v4, _)| v4.into())
+                .into_iter()
+                .collect(),
+            Self::MulticastV6 => multicast_groups
+                .map(|(_, v6)| v6.into())
+                .into_iter()
+                .collect(),
+            Self::Unicast(addresses) => addresses.clone(),
+        }
+    }
+
+    /// Return true if the target has a multicast variant.
+    pub(crate) fn is_multicast(&self) -> bool {
+        matches!(self, Self::Multicast | Self::MulticastV6)
+    }
+
+    /// Fail for a unicast target without any receivers, which would send nothing.
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        match self {
+            Self::Unicast(addresses) if addresses.is_empty() => {
+                Err(io::Error::new(io::ErrorKind::InvalidInput, NO_RECEIVERS).into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<IpAddr> for NetworkTarget {
+    fn from(address: IpAddr) -> Self {
+        Self::Unicast(vec![address])
+    }
+}
+
+impl fmt::Display for NetworkTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Broadcast => write!(f, "broadcast"),
+            Self::Multicast => write!(f, "multicast"),
+            Self::MulticastV6 => write!(f, "multicast-v6"),
+            Self::Unicast(addresses) => {
+                for (i, address) in addresses.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", address)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for NetworkTarget {
+    type Err = AddrParseError;
+
+    /// Parse a target as it is displayed: `broadcast`, `multicast`, `multicast-v6`, or a
+    /// comma-separated list of receivers' addresses.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "broadcast" => Self::Broadcast,
+            "multicast" => Self::Multicast,
+            "multicast-v6" => Self::MulticastV6,
+            _ => Self::Unicast(s.split(',').map(str::parse).collect::<Result<_, _>>()?),
+        })
+    }
+}
+
+/// Read a target, also accepting the single address, or absent address meaning multicast,
+/// that ports were serialized with before targets could be configured.  Unicast targets
+/// without receivers are rejected.
+pub(crate) fn deserialize_target<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<NetworkTarget, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Stored {
+        Target(NetworkTarget),
+        Address(IpAddr),
+        Absent(()),
+    }
+    Ok(match Stored::deserialize(deserializer)? {
+        Stored::Target(NetworkTarget::Unicast(addresses)) if addresses.is_empty() => {
+            return Err(D::Error::custom(NO_RECEIVERS))
+        }
+        Stored::Target(target) => target,
+        Stored::Address(address) => address.into(),
+        Stored::Absent(()) => NetworkTarget::Multicast,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_addresses() {
+        let groups = (
+            Ipv4Addr::new(239, 255, 0, 1),
+            "ff18::8300:1".parse().unwrap(),
+        );
+        let group = IpAddr::from(groups.0);
+        let receivers = vec![IpAddr::from([10, 0, 0, 1]), IpAddr::from([10, 0, 0, 2])];
+        assert_eq!(
+            NetworkTarget::Broadcast.addresses(Some(groups)),
+            vec![IpAddr::from(Ipv4Addr::BROADCAST)]
+        );
+        assert_eq!(
+            NetworkTarget::Multicast.addresses(Some(groups)),
+            vec![group]
+        );
+        assert_eq!(
+            NetworkTarget::MulticastV6.addresses(Some(groups)),
+            vec![IpAddr::from(groups.1)]
+        );
+        assert_eq!(
+            NetworkTarget::Multicast.addresses(None),
+            Vec::<IpAddr>::new()
+        );
+        let unicast = NetworkTarget::Unicast(receivers.clone());
+        assert_eq!(unicast.addresses(Some(groups)), receivers);
+        assert_eq!(unicast.to_string(), "10.0.0.1,10.0.0.2");
+    }
+
+    #[test]
+    fn test_parse() {
+        for target in [
+            NetworkTarget::Broadcast,
+            NetworkTarget::Multicast,
+            NetworkTarget::MulticastV6,
+            NetworkTarget::Unicast(vec![[10, 0, 0, 1].into(), "::1".parse().unwrap()]),
+        ] {
+            assert_eq!(target.to_string().parse::<NetworkTarget>().unwrap(), target);
+        }
+        assert!("10.0.0.1,".parse::<NetworkTarget>().is_err());
+    }
+
+    #[test]
+    fn test_deserialize_target() {
+        use serde::de::value::{Error as ValueError, StrDeserializer, UnitDeserializer};
+
+        let read = |stored: &str| deserialize_target(StrDeserializer::<ValueError>::new(stored));
+        // Ports saved before targets stored an optional address, absent meaning multicast.
+        assert_eq!(
+            deserialize_target(UnitDeserializer::<ValueError>::new()).unwrap(),
+            NetworkTarget::Multicast
+        );
+        assert_eq!(
+            read("10.0.0.1").unwrap(),
+            NetworkTarget::from(IpAddr::from([10, 0, 0, 1]))
+        );
+        assert_eq!(read("Broadcast").unwrap(), NetworkTarget::Broadcast);
+        assert!(read("nowhere").is_err());
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(NetworkTarget::Unicast(Vec::new()).check().is_err());
+        assert!(NetworkTarget::Broadcast.check().is_ok());
+        assert!(NetworkTarget::from(IpAddr::from([10, 0, 0, 1]))
+            .check()
+            .is_ok());
+    }
+}
@@ -0,0 +1,188 @@
+//! Writing to a port from a dedicated thread.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+
+use crate::{DmxPort, Error};
+
+/// A port that is written from a background thread, so the thread rendering frames never
+/// waits on the interface.  Frames are queued up to a fixed capacity; once the queue is full
+/// the oldest queued frame is dropped to make room, so a slow interface always catches up to
+/// the latest frame rather than falling further behind.
+///
+/// The wrapped port must already be open.  Errors from the background thread are passed to
+/// the error handler, on that thread, as they happen.
+pub struct ThreadedPort {
+    name: String,
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<Box<dyn DmxPort>>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+struct State {
+    queue: VecDeque<Vec<u8>>,
+    capacity: usize,
+    /// Buffers of frames already written, reused to avoid allocating for every frame.
+    spare: Vec<Vec<u8>>,
+    dropped: u64,
+    stop: bool,
+}
+
+impl Shared {
+    /// Lock the shared state.  The wrapped port is never called with the lock held, so
+    /// poisoning is ignored.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl ThreadedPort {
+    /// Start writing to the port from a background thread, queuing up to `capacity` frames.
+    /// A capacity of one always writes the latest frame.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capacity is zero.
+    pub fn new<F>(port: Box<dyn DmxPort>, capacity: usize, on_error: F) -> Self
+    where
+        F: FnMut(Error) + Send + 'static,
+    {
+        assert!(capacity > 0, "threaded port capacity must not be zero");
+        let name = port.name().to_string();
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                queue: VecDeque::with_capacity(capacity),
+                capacity,
+                spare: Vec::new(),
+                dropped: 0,
+                stop: false,
+            }),
+            changed: Condvar::new(),
+        });
+        let thread = {
+            let shared = shared.clone();
+            thread::spawn(move || run(port, &shared, on_error))
+        };
+        Self {
+            name,
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Return the name of the wrapped port.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Queue a frame to be written, dropping the oldest queued frame if the queue is full.
+    /// Never waits for the interface.
+    pub fn write(&mut self, frame: &[u8]) {
+        let mut state = self.shared.lock();
+        let mut buffer = if state.queue.len() >= state.capacity {
+            state.dropped += 1;
+            state.queue.pop_front()
+        } else {
+            state.spare.pop()
+        }
+        .unwrap_or_default();
+        buffer.clear();
+        buffer.extend_from_slice(frame);
+        state.queue.push_back(buffer);
+        drop(state);
+        self.shared.changed.notify_one();
+    }
+
+    /// Return how many frames are waiting to be written.
+    pub fn queued(&self) -> usize {
+        self.shared.lock().queue.len()
+    }
+
+    /// Return how many frames were dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+
+    /// Write the frames still queued, stop the background thread and return the wrapped port.
+    pub fn into_inner(mut self) -> Box<dyn DmxPort> {
+        self.stop().expect("writer thread is only taken once")
+    }
+
+    fn stop(&mut self) -> Option<Box<dyn DmxPort>> {
+        let thread = self.thread.take()?;
+        self.shared.lock().stop = true;
+        self.shared.changed.notify_one();
+        // The thread only panics if the wrapped port or error handler does, so pass that on.
+        Some(
+            thread
+                .join()
+                .unwrap_or_else(|e| std::panic::resume_unwind(e)),
+        )
+    }
+}
+
+impl Drop for ThreadedPort {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            self.stop();
+        }
+    }
+}
+
+/// Write queued frames as they arrive, until stopped with the queue empty.
+fn run<F>(mut port: Box<dyn DmxPort>, shared: &Shared, mut on_error: F) -> Box<dyn DmxPort>
+where
+    F: FnMut(Error),
+{
+    let mut state = shared.lock();
+    loop {
+        if let Some(frame) = state.queue.pop_front() {
+            // Don't hold the lock while writing, so writes from the application never wait on
+            // the hardware.
+            drop(state);
+            if let Err(e) = port.write(&frame) {
+                on_error(e);
+            }
+            state = shared.lock();
+            state.spare.push(frame);
+        } else if state.stop {
+            return port;
+        } else {
+            state = shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockDmxPort;
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_threaded() {
+        let mock = MockDmxPort::new();
+        let mut inner = mock.clone();
+        inner.open().unwrap();
+        mock.fail_next_write(Error::Timeout);
+        let (errors, received) = mpsc::channel();
+        let mut port = ThreadedPort::new(Box::new(inner), 2, move |e| {
+            errors.send(e).unwrap();
+        });
+        for level in 0..10 {
+            port.write(&[level]);
+        }
+        assert!(port.queued() <= 2);
+        port.into_inner();
+        assert!(matches!(received.recv(), Ok(Error::Timeout)));
+        assert_eq!(mock.last_frame().unwrap()[0], 9);
+    }
+}
@@ -0,0 +1,81 @@
+//! Tracing events for the paths where problems with interfaces show up, so failures can be
+//! lined up with the rest of an application's logs.  Without the `tracing` feature these do
+//! nothing.
+#![cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+
+#[cfg(feature = "tracing")]
+use tracing::{debug, error, info, trace, warn};
+
+use crate::Error;
+
+/// Report the ports found by enumeration.
+pub(crate) fn listed(count: usize) {
+    #[cfg(feature = "tracing")]
+    debug!(count, "listed ports");
+}
+
+/// Report a provider that couldn't list its ports.
+pub(crate) fn provider_failed(provider: &str, error: &Error) {
+    #[cfg(feature = "tracing")]
+    warn!(provider, error = %error, "failed to list ports");
+}
+
+/// Report the outcome of opening a port.
+pub(crate) fn opened(port: &str, result: &Result<(), Error>) {
+    #[cfg(feature = "tracing")]
+    match result {
+        Ok(()) => debug!(port, "opened port"),
+        Err(e) => warn!(port, error = %e, "failed to open port"),
+    }
+}
+
+pub(crate) fn closed(port: &str) {
+    #[cfg(feature = "tracing")]
+    debug!(port, "closed port");
+}
+
+/// Report that no interface could be opened, so an offline port is used instead.
+pub(crate) fn fell_back_to_offline() {
+    #[cfg(feature = "tracing")]
+    warn!("no DMX interface could be opened, using an offline port");
+}
+
+/// Report the outcome of writing a frame.
+pub(crate) fn wrote(port: &str, len: usize, result: &Result<(), Error>) {
+    #[cfg(feature = "tracing")]
+    match result {
+        Ok(()) => trace!(port, len, "wrote frame"),
+        Err(e) => warn!(port, len, error = %e, "failed to write frame"),
+    }
+}
+
+/// Report an attempt to reconnect a port after the error that prompted it.
+pub(crate) fn reconnecting(port: &str, attempt: u32, error: &Error) {
+    #[cfg(feature = "tracing")]
+    warn!(port, attempt, error = %error, "reconnecting port");
+}
+
+/// Report the outcome of reconnecting a port.
+pub(crate) fn reconnected(port: &str, result: &Result<(), Error>) {
+    #[cfg(feature = "tracing")]
+    match result {
+        Ok(()) => info!(port, "reconnected port"),
+        Err(e) => error!(port, error = %e, "gave up reconnecting port"),
+    }
+}
+
+/// Report a failure to record a packet to a capture.
+pub(crate) fn captured(result: &Result<(), Error>) {
+    #[cfg(feature = "tracing")]
+    if let Err(e) = result {
+        warn!(error = %e, "failed to capture packet");
+    }
+}
+
+/// Report a failure to save the snapshot of a port's universe.
+pub(crate) fn snapshot_saved(port: &str, result: &Result<(), Error>) {
+    #[cfg(feature = "tracing")]
+    if let Err(e) = result {
+        warn!(port, error = %e, "failed to save snapshot");
+    }
+}
@@ -0,0 +1,143 @@
+//! An interactive terminal port picker.
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::style::{Attribute, Print, SetAttribute};
+use crossterm::terminal::{self, ClearType};
+use crossterm::{cursor, execute, queue};
+use std::io::{self, Write};
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::{available_ports, not_found, DmxPort, Error, PortInfo};
+
+/// How long channel 1 stays at each level while flashing.
+const FLASH_STEP: Duration = Duration::from_millis(300);
+
+/// Let the user pick a port with the arrow keys, showing the details of the highlighted port.
+/// Pressing `f` flashes channel 1 of the highlighted port, to check it's the one wanted before
+/// choosing it with Enter.  Pressing Escape cancels with an `Interrupted` IO error.
+pub fn select_port_tui() -> Result<Box<dyn DmxPort>, Error> {
+    let mut ports = available_ports()?;
+    if ports.is_empty() {
+        return Err(not_found("no DMX ports available"));
+    }
+    let index = {
+        let _terminal = Terminal::enter()?;
+        pick(&mut ports, &mut io::stdout())?
+    };
+    ports.swap_remove(index).open()
+}
+
+/// Puts the terminal into raw mode on an alternate screen until dropped.
+struct Terminal;
+
+impl Terminal {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), terminal::EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Run the picker until a port is chosen, returning its index.
+fn pick(ports: &mut [PortInfo], out: &mut impl Write) -> Result<usize, Error> {
+    let mut selected = 0;
+    let mut status = String::new();
+    loop {
+        draw(ports, selected, &status, out)?;
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+        status.clear();
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => selected = (selected + 1).min(ports.len() - 1),
+            KeyCode::Char('f') => {
+                status = match flash(ports[selected].port_mut()) {
+                    Ok(()) => "Flashed channel 1.".to_string(),
+                    Err(e) => format!("Flashing failed: {}", e),
+                };
+            }
+            KeyCode::Enter => return Ok(selected),
+            KeyCode::Esc | KeyCode::Char('q') => {
+                return Err(
+                    io::Error::new(io::ErrorKind::Interrupted, "port selection cancelled").into(),
+                )
+            }
+            _ => {}
+        }
+    }
+}
+
+fn draw(ports: &[PortInfo], selected: usize, status: &str, out: &mut impl Write) -> io::Result<()> {
+    queue!(out, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    line(
+        out,
+        "Select a DMX port: arrows to move, f to flash channel 1, Enter to choose, Esc to cancel",
+    )?;
+    line(out, "")?;
+    for (i, port) in ports.iter().enumerate() {
+        if i == selected {
+            queue!(out, SetAttribute(Attribute::Reverse))?;
+        }
+        match &port.alias {
+            Some(alias) => line(out, &format!("{:>3}: {} ({})", i, alias, port.name))?,
+            None => line(out, &format!("{:>3}: {}", i, port.name))?,
+        }
+        queue!(out, SetAttribute(Attribute::Reset))?;
+    }
+    line(out, "")?;
+    let port = &ports[selected];
+    let hardware = &port.hardware;
+    line(out, &port.to_string())?;
+    line(out, &format!("Kind: {}", port.kind))?;
+    if let Some(path) = &hardware.path {
+        line(out, &format!("Path: {}", path))?;
+    }
+    if let Some(description) = &hardware.description {
+        line(out, &format!("Description: {}", description))?;
+    }
+    if let Some(sn) = &hardware.serial_number {
+        line(out, &format!("Serial number: {}", sn))?;
+    }
+    if let (Some(vid), Some(pid)) = (hardware.vid, hardware.pid) {
+        line(out, &format!("USB ID: {:04x}:{:04x}", vid, pid))?;
+    }
+    if let Some(sn) = &hardware.device_serial_number {
+        line(out, &format!("Device serial number: {}", sn))?;
+    }
+    if let Some(version) = &hardware.firmware_version {
+        line(out, &format!("Firmware: {}", version))?;
+    }
+    line(out, "")?;
+    line(out, status)?;
+    out.flush()
+}
+
+/// Write a line of text.  Raw mode doesn't turn newlines into carriage returns.
+fn line(out: &mut impl Write, text: &str) -> io::Result<()> {
+    queue!(out, Print(text), Print("\r\n"))
+}
+
+/// Flash channel 1 of a port on and off.  The port is left closed.
+fn flash(port: &mut dyn DmxPort) -> Result<(), Error> {
+    port.open()?;
+    let mut result = Ok(());
+    for &level in &[255, 0, 255, 0] {
+        result = port.write(&[level]);
+        if result.is_err() {
+            break;
+        }
+        sleep(FLASH_STEP);
+    }
+    port.close();
+    result
+}
@@ -0,0 +1,239 @@
+//! Implementation of support for Anyma uDMX and compatible USB interfaces.
+
+use rusb::{Context, Device, DeviceHandle, UsbContext};
+use serde::{Deserialize, Serialize};
+use std::cmp::min;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::alias::write_aliased;
+use crate::trace;
+use crate::usb::{usb_context, UsbLocation};
+use crate::{
+    DmxFrame, DmxPort, Error, HardwareInfo, PortKind, PortListing, PortProvider, PortStatus,
+    StatusTracker,
+};
+
+// USB identifiers shared by every uDMX-compatible device.
+const UDMX_VID: u16 = 0x16C0;
+const UDMX_PID: u16 = 0x05DC;
+const UDMX_PRODUCT: &str = "uDMX";
+
+/// Vendor request that sets a run of consecutive channels.
+const SET_CHANNEL_RANGE: u8 = 0x02;
+
+const MAX_UNIVERSE_SIZE: usize = 512;
+
+const USB_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// A DMX port on a uDMX interface.  The interface generates DMX timing itself; frames are
+/// uploaded with USB control transfers.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UdmxDmxPort {
+    name: String,
+    location: UsbLocation,
+    /// Shared by clones.
+    #[serde(skip)]
+    handle: Option<Arc<DeviceHandle<Context>>>,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+/// Return true if the device is a uDMX.  The VID/PID pair is shared with other hobbyist
+/// devices, so the product string is checked too where it can be read.
+fn is_udmx<T: UsbContext>(device: &Device<T>) -> bool {
+    let descriptor = match device.device_descriptor() {
+        Ok(descriptor) => descriptor,
+        Err(_) => return false,
+    };
+    let product = || {
+        device
+            .open()
+            .ok()?
+            .read_product_string_ascii(&descriptor)
+            .ok()
+    };
+    identifies_udmx(descriptor.vendor_id(), descriptor.product_id(), product)
+}
+
+/// Return true if the IDs are the uDMX's and the product string, if it can be read, is too.
+/// The product string is only read for devices with the right IDs.
+fn identifies_udmx(vid: u16, pid: u16, product: impl FnOnce() -> Option<String>) -> bool {
+    vid == UDMX_VID && pid == UDMX_PID && product().map_or(true, |product| product == UDMX_PRODUCT)
+}
+
+/// Return the value and index of the control transfer that sets the channels of the frame,
+/// and the levels it carries.  The interface takes at most a universe.
+fn channel_range(frame: &[u8]) -> (u16, u16, &[u8]) {
+    let frame = &frame[0..min(frame.len(), MAX_UNIVERSE_SIZE)];
+    // The number of channels, and the first channel.
+    (frame.len() as u16, 0, frame)
+}
+
+impl UdmxDmxPort {
+    fn new(device: &Device<Context>) -> Self {
+        let location = UsbLocation::of(device);
+        Self {
+            name: format!("udmx:{}:{}", location.bus, location.address),
+            location,
+            handle: None,
+            last_frame: None,
+            status: StatusTracker::new(),
+        }
+    }
+
+    /// Find the device this port refers to among those currently attached.
+    fn find_device(&self) -> Result<Device<Context>, Error> {
+        self.location.find(&usb_context()?, is_udmx)
+    }
+
+    fn write_frame(&self, frame: &[u8]) -> Result<(), Error> {
+        let handle = self.handle.as_ref().ok_or(Error::NotOpen)?;
+        let (value, index, frame) = channel_range(frame);
+        let request_type = rusb::request_type(
+            rusb::Direction::Out,
+            rusb::RequestType::Vendor,
+            rusb::Recipient::Device,
+        );
+        handle.write_control(
+            request_type,
+            SET_CHANNEL_RANGE,
+            value,
+            index,
+            frame,
+            USB_TIMEOUT,
+        )?;
+        Ok(())
+    }
+}
+
+/// Lists the uDMX interfaces attached to this system.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UdmxProvider;
+
+impl PortProvider for UdmxProvider {
+    fn name(&self) -> &str {
+        "udmx"
+    }
+
+    fn available_ports(&self) -> Result<PortListing, Error> {
+        Ok(usb_context()?
+            .devices()?
+            .iter()
+            .filter(is_udmx)
+            .map(|device| Box::new(UdmxDmxPort::new(&device)) as Box<dyn DmxPort>)
+            .collect())
+    }
+
+    fn kinds(&self) -> &[PortKind] {
+        &[PortKind::Udmx]
+    }
+}
+
+#[typetag::serde]
+impl DmxPort for UdmxDmxPort {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Open the port.
+    fn open(&mut self) -> Result<(), Error> {
+        if self.handle.is_some() {
+            return Ok(());
+        }
+        let result = self
+            .find_device()
+            .and_then(|device| Ok(device.open()?))
+            .map(|handle| self.handle = Some(Arc::new(handle)));
+        trace::opened(&self.name, &result);
+        self.status.record(result)
+    }
+
+    fn close(&mut self) {
+        self.status.clear();
+        if self.handle.take().is_some() {
+            trace::closed(&self.name);
+        }
+    }
+
+    /// The interface keeps transmitting the last values it was sent, so frames are not padded.
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let result = self.write_frame(frame);
+        trace::wrote(&self.name, frame.len(), &result);
+        if result.is_ok() {
+            self.last_frame = Some(DmxFrame::from(frame));
+        }
+        self.status.record(result)
+    }
+
+    fn kind(&self) -> PortKind {
+        PortKind::Udmx
+    }
+
+    fn hardware(&self) -> HardwareInfo {
+        HardwareInfo {
+            path: Some(format!("{}:{}", self.location.bus, self.location.address)),
+            serial_number: self.location.serial_number.clone(),
+            vid: Some(UDMX_VID),
+            pid: Some(UDMX_PID),
+            ..HardwareInfo::default()
+        }
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+
+    fn status(&self) -> PortStatus {
+        self.status.status(self.handle.is_some())
+    }
+
+    /// Look for the device among those attached, since it can't be asked anything.
+    fn health_check(&mut self) -> Result<(), Error> {
+        let result = match self.handle {
+            Some(_) => self.find_device().map(|_| ()),
+            None => Err(Error::NotOpen),
+        };
+        self.status.record(result)
+    }
+}
+
+impl fmt::Display for UdmxDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_aliased(f, &self.hardware(), |f| {
+            match &self.location.serial_number {
+                Some(sn) => write!(f, "uDMX {}", sn),
+                None => write!(
+                    f,
+                    "uDMX on bus {} address {}",
+                    self.location.bus, self.location.address
+                ),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_identifies_udmx() {
+        let product = |name: &str| Some(name.to_string());
+        assert!(identifies_udmx(UDMX_VID, UDMX_PID, || product("uDMX")));
+        assert!(identifies_udmx(UDMX_VID, UDMX_PID, || None));
+        assert!(!identifies_udmx(UDMX_VID, UDMX_PID, || product("USBasp")));
+        assert!(!identifies_udmx(0x0403, UDMX_PID, || product("uDMX")));
+    }
+
+    #[test]
+    fn test_channel_range() {
+        assert_eq!(channel_range(&[1, 2, 3]), (3, 0, &[1, 2, 3][..]));
+        let (value, _, levels) = channel_range(&[0; 600]);
+        assert_eq!(value, 512);
+        assert_eq!(levels.len(), 512);
+    }
+}
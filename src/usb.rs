@@ -0,0 +1,65 @@
+//! Helpers shared by the ports that talk to USB devices directly through libusb.
+
+use rusb::{Context, Device, UsbContext};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Create a libusb context.  rusb's global context panics if libusb can't be initialized, on a
+/// system without USB support for example, so ports use a context of their own, which fails
+/// with an error instead.
+pub(crate) fn usb_context() -> Result<Context, Error> {
+    Context::new().map_err(Error::Usb)
+}
+
+/// Where a USB device is attached.  Devices with a serial number are found by it when
+/// opened; otherwise the bus and address the device had when it was listed are used.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct UsbLocation {
+    pub bus: u8,
+    pub address: u8,
+    pub serial_number: Option<String>,
+}
+
+impl UsbLocation {
+    /// Return where the device is attached now.
+    pub fn of<T: UsbContext>(device: &Device<T>) -> Self {
+        Self {
+            bus: device.bus_number(),
+            address: device.address(),
+            serial_number: serial_number(device),
+        }
+    }
+
+    /// Find the device at this location among those currently attached that match.
+    pub fn find<T: UsbContext>(
+        &self,
+        context: &T,
+        matches: impl Fn(&Device<T>) -> bool,
+    ) -> Result<Device<T>, Error> {
+        let devices: Vec<_> = context
+            .devices()?
+            .iter()
+            .filter(|device| matches(device))
+            .collect();
+        let found = match &self.serial_number {
+            Some(sn) => devices
+                .into_iter()
+                .find(|device| serial_number(device).as_ref() == Some(sn)),
+            None => devices
+                .into_iter()
+                .find(|device| device.bus_number() == self.bus && device.address() == self.address),
+        };
+        found.ok_or(Error::DeviceDisconnected)
+    }
+}
+
+/// Return the serial number of the device, if it has one and can be opened to read it.
+pub(crate) fn serial_number<T: UsbContext>(device: &Device<T>) -> Option<String> {
+    let descriptor = device.device_descriptor().ok()?;
+    device
+        .open()
+        .ok()?
+        .read_serial_number_string_ascii(&descriptor)
+        .ok()
+}
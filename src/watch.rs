@@ -0,0 +1,229 @@
+//! Watching for ports being attached to and removed from the system.
+
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::provider::providers;
+use crate::{Error, PortScan};
+
+/// How often the watcher re-enumerates the attached ports.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A change to the set of available ports, identified by port name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PortEvent {
+    PortAdded(String),
+    PortRemoved(String),
+}
+
+/// The receiving end of a port watcher, which derefs to the `Receiver` of its events.
+/// Dropping it stops the watcher thread at its next check.
+pub struct PortWatcher {
+    receiver: Receiver<PortEvent>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Deref for PortWatcher {
+    type Target = Receiver<PortEvent>;
+
+    fn deref(&self) -> &Receiver<PortEvent> {
+        &self.receiver
+    }
+}
+
+impl Drop for PortWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Spawn a background thread that watches for ports being attached and removed, and return the
+/// watcher it reports changes on.  Every port available when the watcher starts is reported as
+/// added.  The port itself can be found by name in `available_ports()`.  The watcher stops once
+/// it is dropped.
+///
+/// The ports of interfaces attached to this system are only listed again once the serial
+/// ports or USB devices attached change, so interfaces aren't asked what they are every time.
+pub fn watch_ports() -> PortWatcher {
+    let mut lister = Lister::default();
+    spawn_watcher(WATCH_INTERVAL, move || Ok(lister.list()))
+}
+
+/// Lists the names of the available ports for the watcher.  Listing the ports of interfaces
+/// attached to this system can mean talking to them, so they are only listed again once the
+/// devices attached change, which is quick to check.  Other ports are listed every time.
+#[derive(Default)]
+struct Lister {
+    /// The devices attached when the interfaces were last listed.
+    devices: Option<Vec<String>>,
+    /// The names of the interfaces' ports listed then.
+    hardware: Vec<String>,
+}
+
+impl Lister {
+    fn list(&mut self) -> Vec<String> {
+        let devices = attached_devices();
+        let relist = devices.is_none() || devices != self.devices;
+        let mut names = Vec::new();
+        let mut hardware = Vec::new();
+        for provider in providers() {
+            let lists_hardware = provider.kinds().iter().any(|kind| kind.is_hardware());
+            if lists_hardware && !relist {
+                continue;
+            }
+            let mut scan = PortScan::default();
+            scan.list(provider.as_ref());
+            let listed = scan.ports.into_iter().map(|port| port.name);
+            match lists_hardware {
+                true => hardware.extend(listed),
+                false => names.extend(listed),
+            }
+        }
+        if relist {
+            self.devices = devices;
+            self.hardware = hardware;
+        }
+        names.extend(self.hardware.iter().cloned());
+        names
+    }
+}
+
+/// Return the names of the serial ports and the locations of the USB devices attached, or
+/// nothing if they can't be enumerated.
+fn attached_devices() -> Option<Vec<String>> {
+    #[cfg_attr(
+        not(any(feature = "enttec", feature = "udmx", feature = "ftdi")),
+        allow(unused_mut)
+    )]
+    let mut devices = Vec::new();
+    #[cfg(feature = "enttec")]
+    devices.extend(
+        serialport::available_ports()
+            .ok()?
+            .into_iter()
+            .map(|info| info.port_name),
+    );
+    #[cfg(any(feature = "udmx", feature = "ftdi"))]
+    {
+        use rusb::UsbContext;
+
+        let context = crate::usb::usb_context().ok()?;
+        devices.extend(
+            context
+                .devices()
+                .ok()?
+                .iter()
+                .map(|device| format!("usb {}:{}", device.bus_number(), device.address())),
+        );
+    }
+    Some(devices)
+}
+
+fn spawn_watcher(
+    interval: Duration,
+    list: impl FnMut() -> Result<Vec<String>, Error> + Send + 'static,
+) -> PortWatcher {
+    let (sender, receiver) = channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    thread::spawn(move || watch(sender, &stopped, interval, list));
+    PortWatcher { receiver, stop }
+}
+
+fn watch(
+    sender: Sender<PortEvent>,
+    stop: &AtomicBool,
+    interval: Duration,
+    mut list: impl FnMut() -> Result<Vec<String>, Error>,
+) {
+    let mut known = Vec::new();
+    while !stop.load(Ordering::Relaxed) {
+        // A failed enumeration is treated like a transient glitch rather than every port
+        // disappearing at once.
+        if let Ok(current) = list() {
+            for event in changes(&known, &current) {
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+            known = current;
+        }
+        thread::sleep(interval);
+    }
+}
+
+/// Return the events that turn the known port names into the current ones.
+fn changes(known: &[String], current: &[String]) -> Vec<PortEvent> {
+    let removed = known
+        .iter()
+        .filter(|name| !current.contains(name))
+        .map(|name| PortEvent::PortRemoved(name.clone()));
+    let added = current
+        .iter()
+        .filter(|name| !known.contains(name))
+        .map(|name| PortEvent::PortAdded(name.clone()));
+    removed.chain(added).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_changes() {
+        let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            changes(&[], &names(&["offline", "COM3"])),
+            vec![
+                PortEvent::PortAdded("offline".to_string()),
+                PortEvent::PortAdded("COM3".to_string()),
+            ]
+        );
+        assert_eq!(
+            changes(&names(&["offline", "COM3"]), &names(&["offline", "COM4"])),
+            vec![
+                PortEvent::PortRemoved("COM3".to_string()),
+                PortEvent::PortAdded("COM4".to_string()),
+            ]
+        );
+        assert!(changes(&names(&["offline"]), &names(&["offline"])).is_empty());
+    }
+
+    #[test]
+    fn test_lister() {
+        let mut lister = Lister::default();
+        assert!(lister.list().contains(&"offline".to_string()));
+        // Interfaces aren't listed again until the devices attached change.
+        if lister.devices.is_some() {
+            lister.hardware = vec!["COM3".to_string()];
+            assert!(lister.list().contains(&"COM3".to_string()));
+        }
+        lister.devices = Some(vec!["COM4".to_string()]);
+        assert!(!lister.list().contains(&"COM3".to_string()));
+    }
+
+    #[test]
+    fn test_stop_on_drop() {
+        use std::sync::atomic::AtomicUsize;
+
+        let listings = Arc::new(AtomicUsize::new(0));
+        let counted = listings.clone();
+        let watcher = spawn_watcher(Duration::from_millis(1), move || {
+            counted.fetch_add(1, Ordering::Relaxed);
+            Ok(vec!["offline".to_string()])
+        });
+        assert_eq!(
+            watcher.recv().unwrap(),
+            PortEvent::PortAdded("offline".to_string())
+        );
+        drop(watcher);
+        thread::sleep(Duration::from_millis(20));
+        let stopped_at = listings.load(Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(listings.load(Ordering::Relaxed), stopped_at);
+    }
+}
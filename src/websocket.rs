@@ -0,0 +1,190 @@
+//! Output from the browser over a WebSocket, to a bridge server that relays the packets onto
+//! the lighting network.
+
+use send_wrapper::SendWrapper;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use web_sys::{BinaryType, WebSocket};
+
+use crate::artnet::write_art_dmx;
+use crate::sacn::{random_cid, Cid, DataPacket, DEFAULT_PRIORITY, DEFAULT_SOURCE_NAME};
+use crate::{DmxFrame, DmxPort, Error, PortKind, PortStatus, StatusTracker};
+
+/// The packet format frames are sent in; each frame is one binary WebSocket message holding
+/// the UDP payload the bridge should send.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebSocketEncoding {
+    /// ArtDmx packets for a 15-bit Art-Net port-address.
+    ArtNet { port_address: u16 },
+    /// E1.31 data packets for an sACN universe.
+    Sacn { universe: u16 },
+}
+
+/// A DMX port that sends Art-Net or sACN packets over a WebSocket, for browser-based tools
+/// that can't open UDP sockets themselves.
+///
+/// Browsers connect WebSockets in the background, so frames written before the connection
+/// is established are dropped rather than failing the write.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WebSocketDmxPort {
+    url: String,
+    encoding: WebSocketEncoding,
+    #[serde(default = "random_cid")]
+    cid: Cid,
+    /// JavaScript objects are tied to the thread that made them; the wrapper makes the port
+    /// `Send`, and panics if the socket is used from any other thread.
+    #[serde(skip)]
+    socket: Option<SendWrapper<WebSocket>>,
+    #[serde(skip)]
+    sequence: u8,
+    #[serde(skip)]
+    buf: Vec<u8>,
+    #[serde(skip)]
+    last_frame: Option<DmxFrame>,
+    #[serde(skip)]
+    status: StatusTracker,
+}
+
+impl WebSocketDmxPort {
+    /// Create a port sending to the bridge at the provided `ws://` or `wss://` URL.
+    /// The port is not opened yet.
+    pub fn new(url: impl Into<String>, encoding: WebSocketEncoding) -> Self {
+        Self {
+            url: url.into(),
+            encoding,
+            cid: random_cid(),
+            socket: None,
+            sequence: 0,
+            buf: Vec::new(),
+            last_frame: None,
+            status: StatusTracker::new(),
+        }
+    }
+
+    /// Return the packet format frames are sent in.
+    pub fn encoding(&self) -> WebSocketEncoding {
+        self.encoding
+    }
+
+    /// Advance the sequence number.  Art-Net reserves 0 to disable sequencing.
+    fn next_sequence(&mut self) -> u8 {
+        self.sequence = match self.encoding {
+            WebSocketEncoding::ArtNet { .. } => self.sequence.checked_add(1).unwrap_or(1),
+            WebSocketEncoding::Sacn { .. } => self.sequence.wrapping_add(1),
+        };
+        self.sequence
+    }
+
+    /// Open a new WebSocket to the bridge.
+    fn connect(&mut self) -> Result<(), Error> {
+        let socket = WebSocket::new(&self.url).map_err(js_error)?;
+        socket.set_binary_type(BinaryType::Arraybuffer);
+        self.socket = Some(SendWrapper::new(socket));
+        self.sequence = 0;
+        Ok(())
+    }
+
+    fn send_frame(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let state = self.socket.as_ref().ok_or(Error::NotOpen)?.ready_state();
+        match state {
+            WebSocket::CONNECTING => {
+                self.last_frame = Some(DmxFrame::from(frame));
+                return Ok(());
+            }
+            WebSocket::OPEN => {}
+            _ => return Err(Error::PortClosed),
+        }
+        let sequence = self.next_sequence();
+        match self.encoding {
+            WebSocketEncoding::ArtNet { port_address } => {
+                write_art_dmx(sequence, port_address, frame, &mut self.buf)
+            }
+            WebSocketEncoding::Sacn { universe } => DataPacket {
+                cid: &self.cid,
+                source_name: DEFAULT_SOURCE_NAME,
+                priority: DEFAULT_PRIORITY,
+                sequence,
+                universe,
+                start_code: 0,
+                sync_address: 0,
+                preview: false,
+                data: frame,
+            }
+            .write_into(&mut self.buf),
+        }
+        let socket = self.socket.as_ref().ok_or(Error::NotOpen)?;
+        socket.send_with_u8_array(&self.buf).map_err(js_error)?;
+        self.last_frame = Some(DmxFrame::from(frame));
+        Ok(())
+    }
+}
+
+/// WebSocket errors are JavaScript values; keep their description.
+fn js_error(e: wasm_bindgen::JsValue) -> Error {
+    let message = e.as_string().unwrap_or_else(|| format!("{:?}", e));
+    std::io::Error::other(message).into()
+}
+
+#[typetag::serde]
+impl DmxPort for WebSocketDmxPort {
+    fn name(&self) -> &str {
+        &self.url
+    }
+
+    /// Start connecting to the bridge.
+    fn open(&mut self) -> Result<(), Error> {
+        if let Some(socket) = &self.socket {
+            if socket.ready_state() <= WebSocket::OPEN {
+                return Ok(());
+            }
+        }
+        let result = self.connect();
+        self.status.record(result)
+    }
+
+    fn close(&mut self) {
+        self.status.clear();
+        if let Some(socket) = self.socket.take() {
+            let _ = socket.close();
+        }
+    }
+
+    fn write(&mut self, frame: &[u8]) -> Result<(), Error> {
+        let result = self.send_frame(frame);
+        self.status.record(result)
+    }
+
+    /// Report the port open while the WebSocket is connecting or connected, and closed once
+    /// the browser has closed it.
+    fn status(&self) -> PortStatus {
+        let open = self
+            .socket
+            .as_ref()
+            .is_some_and(|socket| socket.ready_state() <= WebSocket::OPEN);
+        self.status.status(open)
+    }
+
+    fn last_frame(&self) -> Option<&[u8]> {
+        self.last_frame.as_deref()
+    }
+
+    fn kind(&self) -> PortKind {
+        match self.encoding {
+            WebSocketEncoding::ArtNet { .. } => PortKind::ArtNet,
+            WebSocketEncoding::Sacn { .. } => PortKind::Sacn,
+        }
+    }
+}
+
+impl fmt::Display for WebSocketDmxPort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.encoding {
+            WebSocketEncoding::ArtNet { port_address } => {
+                write!(f, "Art-Net port-address {} over {}", port_address, self.url)
+            }
+            WebSocketEncoding::Sacn { universe } => {
+                write!(f, "sACN universe {} over {}", universe, self.url)
+            }
+        }
+    }
+}